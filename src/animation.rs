@@ -0,0 +1,89 @@
+//!  A module for animated images.
+
+// from rust
+
+// from external crate
+use gif;
+
+// from local crate
+use Image;
+
+/// A struct for representing an animated GIF or PNG.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// Width of the animation canvas in pixels.
+    pub width: i32,
+
+    /// Height of the animation canvas in pixels.
+    pub height: i32,
+
+    /// The frames of the animation in display order.
+    pub frames: Vec<Frame>,
+
+    /// How many times the animation plays. Pass it to `GifEncoder::repeat` or
+    /// `ApngEncoder::repeat` to keep it when re-encoding.
+    pub repeat: LoopCount,
+}
+
+/// The former name of `Animation`, from before it held APNGs too.
+#[deprecated(note = "renamed to `Animation`")]
+pub type GifAnimation = Animation;
+
+/// A single frame of an animation.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The fully composited frame. Always the size of the animation canvas.
+    pub image: Image,
+
+    /// How long the frame is displayed in units of 10 ms.
+    pub delay: u16,
+
+    /// What happens to the canvas after the frame is displayed.
+    pub disposal: DisposalMethod,
+
+    /// How the frame is drawn onto the canvas. Decoded frames are already composited and use
+    /// `Source`.
+    pub blend: FrameBlend,
+}
+
+/// Enumeration of frame disposal methods.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisposalMethod {
+    /// No disposal specified. Treated the same as `Keep`.
+    Any,
+    /// Leave the frame on the canvas.
+    Keep,
+    /// Clear the frame area to transparent.
+    Background,
+    /// Restore the canvas to what it was before the frame was drawn.
+    Previous,
+}
+
+impl From<gif::DisposalMethod> for DisposalMethod {
+    fn from(method: gif::DisposalMethod) -> DisposalMethod {
+        match method {
+            gif::DisposalMethod::Any => DisposalMethod::Any,
+            gif::DisposalMethod::Keep => DisposalMethod::Keep,
+            gif::DisposalMethod::Background => DisposalMethod::Background,
+            gif::DisposalMethod::Previous => DisposalMethod::Previous,
+        }
+    }
+}
+
+/// Enumeration of ways to draw a frame onto the canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameBlend {
+    /// Replace the canvas pixels, including their transparency.
+    Source,
+    /// Alpha blend the frame over the canvas.
+    Over,
+}
+
+/// Enumeration for how many times an animation plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopCount {
+    /// Loop forever.
+    Infinite,
+    /// Repeat the animation this many times after the first play.
+    Finite(u16),
+}
@@ -0,0 +1,72 @@
+//!  A module for converting images to and from `ndarray` arrays.
+
+// from rust
+use std::cmp;
+
+// from external crate
+use ndarray::ArrayView3;
+
+// from local crate
+use error::{RasterError, RasterResult};
+use Image;
+
+impl Image {
+    /// Create an image from an array of height by width by 4 RGBA bytes, like the one from
+    /// `to_ndarray`. Arrays of any memory layout are read in their logical order.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the width and height of the array if the
+    /// last axis doesn't have 4 elements or the image is too big.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// // Invert the colors with array math
+    /// let mut array = image.to_ndarray().unwrap().to_owned();
+    /// array.mapv_inplace(|value| 255 - value);
+    /// let inverted = Image::from_ndarray(array.view()).unwrap();
+    ///
+    /// assert_eq!(255 - image.get_pixel(5, 5).unwrap().r, inverted.get_pixel(5, 5).unwrap().r);
+    /// ```
+    pub fn from_ndarray(array: ArrayView3<'_, u8>) -> RasterResult<Image> {
+        let (height, width, channels) = array.dim();
+        let size = |len: usize| cmp::min(len, i32::MAX as usize) as i32;
+        if channels != 4 || width > i32::MAX as usize || height > i32::MAX as usize {
+            return Err(RasterError::SizeMismatch(size(width), size(height)));
+        }
+        Ok(Image {
+            width: width as i32,
+            height: height as i32,
+            bytes: array.iter().cloned().collect(),
+        })
+    }
+
+    /// Borrow the RGBA bytes of an image as an array of height by width by 4 without copying
+    /// them. Index it with `[[y, x, channel]]`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the size of the image if the bytes don't
+    /// match it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let array = image.to_ndarray().unwrap();
+    ///
+    /// assert_eq!(image.get_pixel(7, 3).unwrap().g, array[[3, 7, 1]]);
+    /// ```
+    pub fn to_ndarray(&self) -> RasterResult<ArrayView3<'_, u8>> {
+        let mismatch = RasterError::SizeMismatch(self.width, self.height);
+        if self.width < 0 || self.height < 0 {
+            return Err(mismatch);
+        }
+        let shape = (self.height as usize, self.width as usize, 4);
+        ArrayView3::from_shape(shape, &self.bytes).map_err(|_| mismatch)
+    }
+}
@@ -18,9 +18,29 @@ pub enum BlendMode {
     Multiply,
     Overlay,
     Screen,
+    /// Darken or lighten the base softly by the top, like a diffuse spotlight.
+    SoftLight,
+    /// Multiply or screen by the top, like a harsh spotlight.
+    HardLight,
+    /// Brighten the base to reflect the top.
+    ColorDodge,
+    /// Darken the base to reflect the top.
+    ColorBurn,
+    /// Like `Difference` with less contrast.
+    Exclusion,
+    /// Hue of the top with the saturation and luminosity of the base.
+    Hue,
+    /// Saturation of the top with the hue and luminosity of the base.
+    Saturation,
+    /// Hue and saturation of the top with the luminosity of the base.
+    Color,
+    /// Luminosity of the top with the hue and saturation of the base.
+    Luminosity,
 }
 
-pub fn difference(
+// Blend with alpha compositing as in the W3C Compositing and Blending spec. The result keeps the
+// alpha of both images, so transparent bases stay transparent.
+pub fn composite(
     image1: &Image,
     image2: &Image,
     loop_start_y: i32,
@@ -30,6 +50,7 @@ pub fn difference(
     offset_x: i32,
     offset_y: i32,
     opacity: f32,
+    mode: &BlendMode,
 ) -> RasterResult<Image> {
     let mut canvas = image1.clone();
 
@@ -37,27 +58,32 @@ pub fn difference(
         for x in loop_start_x..loop_end_x {
             let canvas_x = x + offset_x;
             let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
-
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
+            let color1 = image1.get_pixel(canvas_x, canvas_y)?;
+            let color2 = image2.get_pixel(x, y)?;
 
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Difference, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Difference, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Difference, a2);
-            let a3 = 255;
+            let base = [channel(color1.r), channel(color1.g), channel(color1.b)];
+            let top = [channel(color2.r), channel(color2.g), channel(color2.b)];
+            let a1 = channel(color1.a);
+            let a2 = channel(color2.a) * opacity;
+
+            let blended = blend_colors(base, top, mode);
+            let a3 = a2 + a1 * (1.0 - a2);
+            let mut rgb = [0; 3];
+            for (i, value) in rgb.iter_mut().enumerate() {
+                // The top is mixed with the blend where it overlaps the base
+                let top = (1.0 - a1) * top[i] + a1 * blended[i];
+                let premultiplied = a2 * top + a1 * (1.0 - a2) * base[i];
+                *value = if a3 > 0.0 {
+                    to_byte(premultiplied / a3)
+                } else {
+                    0
+                };
+            }
 
             canvas.set_pixel(
                 canvas_x,
                 canvas_y,
-                Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
+                Color::rgba(rgb[0], rgb[1], rgb[2], to_byte(a3)),
             )?;
         }
     }
@@ -65,232 +91,122 @@ pub fn difference(
     Ok(canvas)
 }
 
-pub fn multiply(
-    image1: &Image,
-    image2: &Image,
-    loop_start_y: i32,
-    loop_end_y: i32,
-    loop_start_x: i32,
-    loop_end_x: i32,
-    offset_x: i32,
-    offset_y: i32,
-    opacity: f32,
-) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
-
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
-
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Multiply, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Multiply, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Multiply, a2);
-            let a3 = 255;
+// PRIVATE FNs
 
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
-        }
+// Blend functions of `composite`, with channels from 0.0 to 1.0
+
+fn blend_colors(base: [f32; 3], top: [f32; 3], mode: &BlendMode) -> [f32; 3] {
+    let separable =
+        |f: fn(f32, f32) -> f32| [f(base[0], top[0]), f(base[1], top[1]), f(base[2], top[2])];
+    match *mode {
+        BlendMode::Normal => top,
+        BlendMode::Difference => separable(|b, t| (b - t).abs()),
+        BlendMode::Multiply => separable(|b, t| b * t),
+        BlendMode::Overlay => separable(|b, t| hard_light(t, b)),
+        BlendMode::Screen => separable(screen_f),
+        BlendMode::SoftLight => separable(soft_light),
+        BlendMode::HardLight => separable(hard_light),
+        BlendMode::ColorDodge => separable(color_dodge),
+        BlendMode::ColorBurn => separable(color_burn),
+        BlendMode::Exclusion => separable(|b, t| b + t - 2.0 * b * t),
+        BlendMode::Hue => set_lum(set_sat(top, sat(base)), lum(base)),
+        BlendMode::Saturation => set_lum(set_sat(base, sat(top)), lum(base)),
+        BlendMode::Color => set_lum(top, lum(base)),
+        BlendMode::Luminosity => set_lum(base, lum(top)),
     }
-
-    Ok(canvas)
 }
 
-pub fn normal(
-    image1: &Image,
-    image2: &Image,
-    loop_start_y: i32,
-    loop_end_y: i32,
-    loop_start_x: i32,
-    loop_end_x: i32,
-    offset_x: i32,
-    offset_y: i32,
-    opacity: f32,
-) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let color1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = color1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = color1.r as f32 * a1;
-            let g1 = color1.g as f32 * a1;
-            let b1 = color1.b as f32 * a1;
-
-            let color2 = image2.get_pixel(x, y)?;
-            let a2 = color2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = color2.r as f32;
-            let g2 = color2.g as f32;
-            let b2 = color2.b as f32;
-
-            let r3 = (a2 * r2) + ((1.0 - a2) * r1);
-            let g3 = (a2 * g2) + ((1.0 - a2) * g1);
-            let b3 = (a2 * b2) + ((1.0 - a2) * b1);
-            let a3 = 255;
+fn channel(value: u8) -> f32 {
+    value as f32 / 255.0
+}
 
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
+fn clip_color(color: [f32; 3]) -> [f32; 3] {
+    let l = lum(color);
+    let min = color[0].min(color[1]).min(color[2]);
+    let max = color[0].max(color[1]).max(color[2]);
+    let mut clipped = color;
+    for value in clipped.iter_mut() {
+        if min < 0.0 {
+            *value = l + (*value - l) * l / (l - min);
+        }
+        if max > 1.0 {
+            *value = l + (*value - l) * (1.0 - l) / (max - l);
         }
     }
-
-    Ok(canvas)
+    clipped
 }
 
-pub fn overlay(
-    image1: &Image,
-    image2: &Image,
-    loop_start_y: i32,
-    loop_end_y: i32,
-    loop_start_x: i32,
-    loop_end_x: i32,
-    offset_x: i32,
-    offset_y: i32,
-    opacity: f32,
-) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
-
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
-
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Overlay, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Overlay, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Overlay, a2);
-            let a3 = 255;
-
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
-        }
+fn color_burn(base: f32, top: f32) -> f32 {
+    if base >= 1.0 {
+        1.0
+    } else if top <= 0.0 {
+        0.0
+    } else {
+        1.0 - ((1.0 - base) / top).min(1.0)
     }
-
-    Ok(canvas)
 }
 
-pub fn screen(
-    image1: &Image,
-    image2: &Image,
-    loop_start_y: i32,
-    loop_end_y: i32,
-    loop_start_x: i32,
-    loop_end_x: i32,
-    offset_x: i32,
-    offset_y: i32,
-    opacity: f32,
-) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
-
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
-
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Screen, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Screen, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Screen, a2);
-            let a3 = 255;
-
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
-        }
+fn color_dodge(base: f32, top: f32) -> f32 {
+    if base <= 0.0 {
+        0.0
+    } else if top >= 1.0 {
+        1.0
+    } else {
+        (base / (1.0 - top)).min(1.0)
     }
-
-    Ok(canvas)
 }
 
-// PRIVATE FNs
-// base, top 0.0 - 255.0
-// opacity 0.0 - 1.0
+fn hard_light(base: f32, top: f32) -> f32 {
+    if top <= 0.5 {
+        base * 2.0 * top
+    } else {
+        screen_f(base, 2.0 * top - 1.0)
+    }
+}
 
-/*
-This is the private BlendFunction enum, not to be confused with BlendMode, which is for public
-consumption! BlendFunction differs only in lacking a Normal variant, as ch_alpha_f has no need for
-such things.
-*/
-#[derive(Debug)]
-enum BlendFunction {
-    Difference,
-    Multiply,
-    Overlay,
-    Screen,
+fn lum(color: [f32; 3]) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
 }
 
-fn ch_alpha_f(base: f32, top: f32, f: BlendFunction, opacity: f32) -> f32 {
-    match f {
-        BlendFunction::Difference => ch_alpha(base, ch_difference(base, top), opacity),
-        BlendFunction::Multiply => ch_alpha(base, ch_multiply(base, top), opacity),
-        BlendFunction::Overlay => ch_alpha(base, ch_overlay(base, top), opacity),
-        BlendFunction::Screen => ch_alpha(base, ch_screen(base, top), opacity),
-    }
+fn sat(color: [f32; 3]) -> f32 {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
 }
 
-fn ch_alpha(base: f32, top: f32, opacity: f32) -> f32 {
-    (opacity * top) + ((1.0 - opacity) * base)
+fn screen_f(base: f32, top: f32) -> f32 {
+    base + top - base * top
 }
 
-fn ch_difference(base: f32, top: f32) -> f32 {
-    (base - top).abs()
+fn set_lum(color: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(color);
+    clip_color([color[0] + d, color[1] + d, color[2] + d])
 }
 
-fn ch_multiply(base: f32, top: f32) -> f32 {
-    (base * top) / 255.0
+// Scale the channels to the saturation, keeping their order.
+fn set_sat(color: [f32; 3], s: f32) -> [f32; 3] {
+    let mut order = [0, 1, 2];
+    order.sort_by(|&a, &b| color[a].total_cmp(&color[b]));
+    let (min, mid, max) = (order[0], order[1], order[2]);
+    let mut result = [0.0; 3];
+    if color[max] > color[min] {
+        result[mid] = (color[mid] - color[min]) * s / (color[max] - color[min]);
+        result[max] = s;
+    }
+    result
 }
 
-fn ch_overlay(base: f32, top: f32) -> f32 {
-    if base < 128.0 {
-        2.0 * base * top / 255.0
+fn soft_light(base: f32, top: f32) -> f32 {
+    if top <= 0.5 {
+        base - (1.0 - 2.0 * top) * base * (1.0 - base)
     } else {
-        255.0 - ((2.0 * (255.0 - base)) * (255.0 - top) / 255.0)
+        let d = if base <= 0.25 {
+            ((16.0 * base - 12.0) * base + 4.0) * base
+        } else {
+            base.sqrt()
+        };
+        base + (2.0 * top - 1.0) * (d - base)
     }
 }
 
-fn ch_screen(base: f32, top: f32) -> f32 {
-    255.0 - (((255.0 - base) * (255.0 - top)) / 255.0)
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
 }
@@ -0,0 +1,296 @@
+//!  A module for images with other pixel formats than 8-bit RGBA.
+//!
+//! An `ImageBuffer` holds its samples in the pixel format it is typed with, like `Gray8` for
+//! grayscale scans that don't need four bytes per pixel or `Rgba16` for 16-bit data. Buffers
+//! convert between formats with `convert` and to and from `Image` with `to_image` and
+//! `from_image`.
+//!
+//! # Examples
+//!
+//! ```
+//! use raster::buffer::{Gray8, Rgba16};
+//! use raster::ImageBuffer;
+//!
+//! let image = raster::open("tests/in/sample.png").unwrap();
+//!
+//! let gray = ImageBuffer::<Gray8>::from_image(&image);
+//! assert_eq!(image.bytes.len() / 4, gray.samples.len());
+//!
+//! let deep = gray.convert::<Rgba16>();
+//! assert_eq!(65535, deep.samples[3]);
+//! ```
+
+// from rust
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use hdr;
+use Image;
+
+/// A trait for the pixel formats of an `ImageBuffer`. Pixels convert through RGBA floats from 0.0
+/// to 1.0, in the same color space as the samples.
+pub trait PixelFormat {
+    /// Type of a single sample.
+    type Sample: Copy + Debug + Default + PartialEq;
+
+    /// Number of samples per pixel.
+    const CHANNELS: usize;
+
+    /// Convert the samples of a pixel to RGBA.
+    fn to_rgba(pixel: &[Self::Sample]) -> [f32; 4];
+
+    /// Convert RGBA to the samples of a pixel.
+    fn from_rgba(rgba: [f32; 4], pixel: &mut [Self::Sample]);
+}
+
+/// 8-bit grayscale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gray8;
+
+/// 8-bit grayscale with alpha.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrayA8;
+
+/// 8-bit RGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgb8;
+
+/// 8-bit RGBA, the format of `Image`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba8;
+
+/// 16-bit RGBA.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba16;
+
+/// 32-bit float RGBA in linear light, the pixels of `HdrImage`. Values are not limited to the 0.0
+/// to 1.0 range, and are clamped when converting to other formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RgbaF32;
+
+impl PixelFormat for Gray8 {
+    type Sample = u8;
+    const CHANNELS: usize = 1;
+
+    fn to_rgba(pixel: &[u8]) -> [f32; 4] {
+        let gray = from_u8(pixel[0]);
+        [gray, gray, gray, 1.0]
+    }
+
+    fn from_rgba(rgba: [f32; 4], pixel: &mut [u8]) {
+        pixel[0] = to_u8(luma(rgba));
+    }
+}
+
+impl PixelFormat for GrayA8 {
+    type Sample = u8;
+    const CHANNELS: usize = 2;
+
+    fn to_rgba(pixel: &[u8]) -> [f32; 4] {
+        let gray = from_u8(pixel[0]);
+        [gray, gray, gray, from_u8(pixel[1])]
+    }
+
+    fn from_rgba(rgba: [f32; 4], pixel: &mut [u8]) {
+        pixel[0] = to_u8(luma(rgba));
+        pixel[1] = to_u8(rgba[3]);
+    }
+}
+
+impl PixelFormat for Rgb8 {
+    type Sample = u8;
+    const CHANNELS: usize = 3;
+
+    fn to_rgba(pixel: &[u8]) -> [f32; 4] {
+        [from_u8(pixel[0]), from_u8(pixel[1]), from_u8(pixel[2]), 1.0]
+    }
+
+    fn from_rgba(rgba: [f32; 4], pixel: &mut [u8]) {
+        for (sample, &value) in pixel.iter_mut().zip(&rgba) {
+            *sample = to_u8(value);
+        }
+    }
+}
+
+impl PixelFormat for Rgba8 {
+    type Sample = u8;
+    const CHANNELS: usize = 4;
+
+    fn to_rgba(pixel: &[u8]) -> [f32; 4] {
+        [
+            from_u8(pixel[0]),
+            from_u8(pixel[1]),
+            from_u8(pixel[2]),
+            from_u8(pixel[3]),
+        ]
+    }
+
+    fn from_rgba(rgba: [f32; 4], pixel: &mut [u8]) {
+        for (sample, &value) in pixel.iter_mut().zip(&rgba) {
+            *sample = to_u8(value);
+        }
+    }
+}
+
+impl PixelFormat for Rgba16 {
+    type Sample = u16;
+    const CHANNELS: usize = 4;
+
+    fn to_rgba(pixel: &[u16]) -> [f32; 4] {
+        let value = |i: usize| pixel[i] as f32 / 65535.0;
+        [value(0), value(1), value(2), value(3)]
+    }
+
+    fn from_rgba(rgba: [f32; 4], pixel: &mut [u16]) {
+        for (sample, &value) in pixel.iter_mut().zip(&rgba) {
+            *sample = (value.clamp(0.0, 1.0) * 65535.0).round() as u16;
+        }
+    }
+}
+
+impl PixelFormat for RgbaF32 {
+    type Sample = f32;
+    const CHANNELS: usize = 4;
+
+    fn to_rgba(pixel: &[f32]) -> [f32; 4] {
+        [
+            hdr::encode_srgb(pixel[0]),
+            hdr::encode_srgb(pixel[1]),
+            hdr::encode_srgb(pixel[2]),
+            pixel[3],
+        ]
+    }
+
+    fn from_rgba(rgba: [f32; 4], pixel: &mut [f32]) {
+        for (sample, &value) in pixel[..3].iter_mut().zip(&rgba) {
+            *sample = hdr::decode_srgb(value);
+        }
+        pixel[3] = rgba[3];
+    }
+}
+
+/// A struct for an image with samples in the pixel format `F`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBuffer<F: PixelFormat> {
+    /// Width of image in pixels.
+    pub width: i32,
+
+    /// Height of image in pixels.
+    pub height: i32,
+
+    /// Vector containing the samples of each pixel, row by row.
+    pub samples: Vec<F::Sample>,
+
+    format: PhantomData<F>,
+}
+
+impl<F: PixelFormat> ImageBuffer<F> {
+    /// Create a buffer from its samples.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the width and height if the number of
+    /// samples doesn't match them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::buffer::Gray8;
+    /// use raster::ImageBuffer;
+    ///
+    /// let gray = ImageBuffer::<Gray8>::new(2, 1, vec![0, 255]).unwrap();
+    /// assert_eq!(vec![255, 255, 255, 255], gray.to_image().bytes[4..].to_vec());
+    ///
+    /// assert!(ImageBuffer::<Gray8>::new(2, 2, vec![0, 255]).is_err());
+    /// ```
+    pub fn new(width: i32, height: i32, samples: Vec<F::Sample>) -> RasterResult<ImageBuffer<F>> {
+        let len = (width.max(0) as usize)
+            .checked_mul(height.max(0) as usize)
+            .and_then(|pixels| pixels.checked_mul(F::CHANNELS));
+        if width < 0 || height < 0 || len != Some(samples.len()) {
+            return Err(RasterError::SizeMismatch(width, height));
+        }
+        Ok(ImageBuffer {
+            width,
+            height,
+            samples,
+            format: PhantomData,
+        })
+    }
+
+    /// Create a buffer with every sample set to zero.
+    pub fn blank(width: i32, height: i32) -> ImageBuffer<F> {
+        let len = width.max(0) as usize * height.max(0) as usize * F::CHANNELS;
+        ImageBuffer {
+            width,
+            height,
+            samples: vec![F::Sample::default(); len],
+            format: PhantomData,
+        }
+    }
+
+    /// Convert to another pixel format. Colors turn gray with the weights of
+    /// `filter::grayscale`, and formats without alpha drop it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::buffer::{GrayA8, Rgb8};
+    /// use raster::ImageBuffer;
+    ///
+    /// let rgb = ImageBuffer::<Rgb8>::new(1, 1, vec![255, 0, 0]).unwrap();
+    ///
+    /// let gray = rgb.convert::<GrayA8>();
+    /// assert_eq!(vec![77, 255], gray.samples);
+    /// ```
+    pub fn convert<G: PixelFormat>(&self) -> ImageBuffer<G> {
+        let mut out = ImageBuffer::<G>::blank(self.width, self.height);
+        for (pixel, converted) in self
+            .samples
+            .chunks_exact(F::CHANNELS)
+            .zip(out.samples.chunks_exact_mut(G::CHANNELS))
+        {
+            G::from_rgba(F::to_rgba(pixel), converted);
+        }
+        out
+    }
+
+    /// Convert an image to the pixel format.
+    pub fn from_image(image: &Image) -> ImageBuffer<F> {
+        ImageBuffer::<Rgba8> {
+            width: image.width,
+            height: image.height,
+            samples: image.bytes.clone(),
+            format: PhantomData,
+        }
+        .convert()
+    }
+
+    /// Convert to an 8-bit RGBA image. Float values are clamped to the 0.0 to 1.0 range.
+    pub fn to_image(&self) -> Image {
+        let rgba = self.convert::<Rgba8>();
+        Image {
+            width: rgba.width,
+            height: rgba.height,
+            bytes: rgba.samples,
+        }
+    }
+}
+
+// Private functions
+
+fn from_u8(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+fn luma(rgba: [f32; 4]) -> f32 {
+    rgba[0] * 0.3 + rgba[1] * 0.59 + rgba[2] * 0.11
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
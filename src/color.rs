@@ -45,6 +45,36 @@ impl<'a> Color {
         }
     }
 
+    /// Convert HSL (Hue, Saturation, Lightness) to RGB. Hue is in degrees and wraps around,
+    /// saturation and lightness go from 0.0 to 100.0.
+    ///
+    /// ```
+    /// use raster::Color;
+    ///
+    /// assert_eq!((255, 0, 0), Color::from_hsl(360.0, 100.0, 50.0));
+    /// assert_eq!((128, 191, 64), Color::from_hsl(90.0, 50.0, 50.0));
+    /// ```
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> (u8, u8, u8) {
+        let s = s.clamp(0.0, 100.0) / 100.0;
+        let l = l.clamp(0.0, 100.0) / 100.0;
+        let h = h.rem_euclid(360.0) / 60.0;
+
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let x = chroma * (1.0 - ((h % 2.0) - 1.0).abs());
+        let (r, g, b) = match h as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        let m = l - chroma / 2.0;
+        let to_u8 = |value: f32| ((value + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+        (to_u8(r), to_u8(g), to_u8(b))
+    }
+
     /// Returns a green Color.
     pub fn green() -> Color {
         Color {
@@ -168,6 +198,44 @@ impl<'a> Color {
         Color { r, g, b, a }
     }
 
+    /// Convert RGB to HSL (Hue, Saturation, Lightness). Unlike `to_hsv`, the hue is not rounded,
+    /// so converting back with `from_hsl` gives the same color.
+    ///
+    /// ```
+    /// use raster::Color;
+    ///
+    /// let hsl = Color::to_hsl(64, 191, 128);
+    ///
+    /// assert_eq!(150.0, (hsl.0).round());
+    /// assert_eq!(50.0, (hsl.1).round());
+    /// assert_eq!(50.0, (hsl.2).round());
+    /// assert_eq!((64, 191, 128), Color::from_hsl(hsl.0, hsl.1, hsl.2));
+    /// ```
+    pub fn to_hsl(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+        let r = r as f32 / 255.0;
+        let g = g as f32 / 255.0;
+        let b = b as f32 / 255.0;
+
+        let min = rgb_min(r, g, b);
+        let max = rgb_max(r, g, b);
+        let chroma = max - min;
+        let l = (max + min) / 2.0;
+        if chroma == 0.0 {
+            return (0.0, 0.0, l * 100.0);
+        }
+
+        let h = if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / chroma + 2.0
+        } else {
+            (r - g) / chroma + 4.0
+        };
+        let s = chroma / (1.0 - (2.0 * l - 1.0).abs());
+
+        (h * 60.0, s * 100.0, l * 100.0)
+    }
+
     /// Convert RGB to HSV/HSB (Hue, Saturation, Brightness).
     ///
     /// ```
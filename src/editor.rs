@@ -8,15 +8,56 @@ use std::cmp;
 // from local crate
 use blend::{self, BlendMode};
 use error::{RasterError, RasterResult};
+use filter;
+use interpolate::InterpolationMode;
 use position::{Position, PositionMode};
 use transform;
 use Color;
 use Image;
 
+/// Mask an image, turning the luminance of the mask into alpha: black is transparent and white
+/// keeps the pixel. Unlike `Image::set_alpha_from`, the mask scales the alpha the image already
+/// has, so transparent pixels stay transparent.
+///
+/// # Errors
+///
+/// If the mask has another size than the image, this will fail with
+/// `RasterError::SizeMismatch`.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// // A round avatar from a square photo
+/// let mut photo = raster::open("tests/in/sample.jpg").unwrap();
+/// let (w, h) = (photo.width, photo.height);
+/// let r = w.min(h) as f32 / 2.0;
+/// let mask = Image::from_fn(w, h, |x, y| {
+///     let (dx, dy) = (x as f32 + 0.5 - w as f32 / 2.0, y as f32 + 0.5 - h as f32 / 2.0);
+///     if dx * dx + dy * dy < r * r { Color::white() } else { Color::black() }
+/// });
+///
+/// editor::apply_mask(&mut photo, &mask).unwrap();
+/// assert_eq!(0, photo.get_pixel(0, 0).unwrap().a);
+/// ```
+pub fn apply_mask(src: &mut Image, mask: &Image) -> RasterResult<()> {
+    if (mask.width, mask.height) != (src.width, src.height) {
+        return Err(RasterError::SizeMismatch(mask.width, mask.height));
+    }
+    for (pixel, masked) in src.pixels_mut().zip(mask.pixels()) {
+        let gray = masked[0] as f32 * 0.3 + masked[1] as f32 * 0.59 + masked[2] as f32 * 0.11;
+        pixel[3] = (pixel[3] as f32 * gray / 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    Ok(())
+}
+
 /// Blend 2 images into one. The image1 is the base and image2 is the top.
 ///
 /// Opacity is any value from 0.0 - 1.0
 ///
+/// All modes composite the alpha of both images as in the W3C Compositing and Blending spec, so
+/// the result is only transparent where both images are.
+///
 /// The `offset_x` and `offset_y` are added to the final position. Can also be negative offsets.
 ///
 /// # Errors
@@ -41,6 +82,9 @@ use Image;
 /// let multiply = editor::blend(&image1, &image2, BlendMode::Multiply, 1.0, PositionMode::Center, 0, 0).unwrap();
 /// let overlay = editor::blend(&image1, &image2, BlendMode::Overlay, 1.0, PositionMode::Center, 0, 0).unwrap();
 /// let screen = editor::blend(&image1, &image2, BlendMode::Screen, 1.0, PositionMode::Center, 0, 0).unwrap();
+/// let soft_light = editor::blend(&image1, &image2, BlendMode::SoftLight, 1.0, PositionMode::Center, 0, 0).unwrap();
+/// let color_dodge = editor::blend(&image1, &image2, BlendMode::ColorDodge, 1.0, PositionMode::Center, 0, 0).unwrap();
+/// let hue = editor::blend(&image1, &image2, BlendMode::Hue, 1.0, PositionMode::Center, 0, 0).unwrap();
 ///
 /// // Save it
 /// raster::save(&normal, "tests/out/test_blend_normal.png").unwrap();
@@ -148,63 +192,58 @@ pub fn blend(
         loop_end_y -= diff;
     }
 
-    match blend_mode {
-        BlendMode::Normal => blend::normal(
-            image1,
-            image2,
-            loop_start_y,
-            loop_end_y,
-            loop_start_x,
-            loop_end_x,
-            offset_x,
-            offset_y,
-            opacity,
-        ),
-        BlendMode::Difference => blend::difference(
-            image1,
-            image2,
-            loop_start_y,
-            loop_end_y,
-            loop_start_x,
-            loop_end_x,
-            offset_x,
-            offset_y,
-            opacity,
-        ),
-        BlendMode::Multiply => blend::multiply(
-            image1,
-            image2,
-            loop_start_y,
-            loop_end_y,
-            loop_start_x,
-            loop_end_x,
-            offset_x,
-            offset_y,
-            opacity,
-        ),
-        BlendMode::Overlay => blend::overlay(
-            image1,
-            image2,
-            loop_start_y,
-            loop_end_y,
-            loop_start_x,
-            loop_end_x,
-            offset_x,
-            offset_y,
-            opacity,
-        ),
-        BlendMode::Screen => blend::screen(
-            image1,
-            image2,
-            loop_start_y,
-            loop_end_y,
-            loop_start_x,
-            loop_end_x,
-            offset_x,
-            offset_y,
-            opacity,
-        ),
-    }
+    blend::composite(
+        image1,
+        image2,
+        loop_start_y,
+        loop_end_y,
+        loop_start_x,
+        loop_end_x,
+        offset_x,
+        offset_y,
+        opacity,
+        &blend_mode,
+    )
+}
+
+/// Blend 2 images with premultiplied alpha into one, like surfaces from Skia or a GPU. Works the
+/// same as `blend` otherwise. The result is premultiplied too.
+///
+/// # Errors
+///
+/// See `blend`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{editor, BlendMode, Color, Image, PositionMode};
+///
+/// let base = Image::filled(2, 2, Color::white());
+///
+/// // Half transparent red, premultiplied
+/// let top = Image::filled(2, 2, Color::rgba(128, 0, 0, 128));
+///
+/// let image = editor::blend_premultiplied(&base, &top, BlendMode::Normal, 1.0, PositionMode::TopLeft, 0, 0).unwrap();
+/// assert_eq!(127, image.get_pixel(0, 0).unwrap().g);
+/// ```
+pub fn blend_premultiplied(
+    image1: &Image,
+    image2: &Image,
+    blend_mode: BlendMode,
+    opacity: f32,
+    position: PositionMode,
+    offset_x: i32,
+    offset_y: i32,
+) -> RasterResult<Image> {
+    let mut image1 = image1.clone();
+    image1.unpremultiply_alpha();
+    let mut image2 = image2.clone();
+    image2.unpremultiply_alpha();
+    let mut image = blend(
+        &image1, &image2, blend_mode, opacity, position, offset_x, offset_y,
+    )?;
+    image.premultiply_alpha();
+    Ok(image)
 }
 
 /// Crop the image to the given dimension and position.
@@ -300,6 +339,93 @@ pub fn crop(
     Ok(())
 }
 
+/// Add a drop shadow behind an image, a copy of its shape in `color` moved by `dx` and `dy`
+/// pixels and blurred with a Gaussian of `blur` sigma. The canvas grows to fit the shadow, with
+/// the new area transparent, so the image ends up `dx` and `dy` pixels away from its shadow.
+/// The alpha of the color sets how dark the shadow is.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::filled(10, 10, Color::red());
+/// editor::drop_shadow(&mut image, 4, 4, 0.0, Color::rgba(0, 0, 0, 128)).unwrap();
+///
+/// assert_eq!((14, 14), (image.width, image.height));
+/// assert_eq!(255, image.get_pixel(0, 0).unwrap().r);
+/// assert_eq!(128, image.get_pixel(13, 13).unwrap().a);
+/// assert_eq!(0, image.get_pixel(13, 0).unwrap().a);
+/// ```
+pub fn drop_shadow(src: &mut Image, dx: i32, dy: i32, blur: f32, color: Color) -> RasterResult<()> {
+    // Room for the blur to spread past the shape
+    let spread = if blur > 0.0 {
+        (blur * 3.0).ceil() as i32
+    } else {
+        0
+    };
+    let left = cmp::max(spread - dx, 0);
+    let top = cmp::max(spread - dy, 0);
+    let width = left + src.width + cmp::max(dx + spread, 0);
+    let height = top + src.height + cmp::max(dy + spread, 0);
+
+    let mut dest = Image::filled(width, height, Color::rgba(color.r, color.g, color.b, 0));
+    let (shadow_x, shadow_y) = ((left + dx) as usize, (top + dy) as usize);
+    for (x, y, pixel) in src.enumerate_pixels() {
+        let i = ((shadow_y + y as usize) * width as usize + shadow_x + x as usize) * 4;
+        dest.bytes[i + 3] = ((pixel[3] as u32 * color.a as u32 + 127) / 255) as u8;
+    }
+    filter::gaussian_blur(&mut dest, blur)?;
+    paste(&mut dest, src, left, top, 1.0)?;
+
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
+
+/// Extend the canvas of an image by the given number of pixels on each side, filling the new
+/// area with a color. Negative amounts are treated as 0.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::filled(4, 3, Color::red());
+///
+/// // Letterbox to a square
+/// editor::extend(&mut image, 1, 0, 0, 0, Color::black()).unwrap();
+///
+/// assert_eq!((4, 4), (image.width, image.height));
+/// assert_eq!(0, image.get_pixel(0, 0).unwrap().r);
+/// assert_eq!(255, image.get_pixel(0, 1).unwrap().r);
+/// ```
+pub fn extend(
+    src: &mut Image,
+    top: i32,
+    right: i32,
+    bottom: i32,
+    left: i32,
+    color: Color,
+) -> RasterResult<()> {
+    let (top, right, bottom, left) = (
+        cmp::max(top, 0),
+        cmp::max(right, 0),
+        cmp::max(bottom, 0),
+        cmp::max(left, 0),
+    );
+    let width = src.width + left + right;
+    let height = src.height + top + bottom;
+    let mut dest = Image::filled(width, height, color);
+
+    copy_into(&mut dest, src, left, top);
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
+
 /// Fill an image with color.
 ///
 /// # Examples
@@ -328,6 +454,274 @@ pub fn fill(src: &mut Image, color: Color) -> RasterResult<()> {
     Ok(())
 }
 
+/// Join images side by side, left to right. Images shorter than the tallest are aligned to the
+/// top, with transparent pixels below them.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let strip = editor::hconcat(&[
+///     Image::filled(2, 3, Color::red()),
+///     Image::filled(4, 1, Color::blue()),
+/// ]);
+///
+/// assert_eq!((6, 3), (strip.width, strip.height));
+/// assert_eq!(255, strip.get_pixel(5, 0).unwrap().b);
+/// assert_eq!(0, strip.get_pixel(5, 1).unwrap().a);
+/// ```
+pub fn hconcat(images: &[Image]) -> Image {
+    let width = images.iter().map(|image| image.width).sum();
+    let height = images.iter().map(|image| image.height).max().unwrap_or(0);
+    let mut dest = Image::filled(width, height, Color::rgba(0, 0, 0, 0));
+    let mut x = 0;
+    for image in images {
+        copy_into(&mut dest, image, x, 0);
+        x += image.width;
+    }
+    dest
+}
+
+/// Arrange images in a grid, like a contact sheet, with `columns` images per row and `gap`
+/// pixels between them and around the edges. Works the same as `Montage` with those settings.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let images = vec![Image::filled(10, 10, Color::red()); 5];
+/// let sheet = editor::montage(&images, 3, 2, Color::white()).unwrap();
+///
+/// assert_eq!((38, 26), (sheet.width, sheet.height));
+/// ```
+pub fn montage(
+    images: &[Image],
+    columns: usize,
+    gap: i32,
+    background: Color,
+) -> RasterResult<Image> {
+    Montage::new(columns)
+        .gap(gap)
+        .background(background)
+        .render(images)
+}
+
+/// A builder for grids of images. Each cell is the size of the largest image, and smaller images
+/// are centered in their cell. Transparent pixels show the background.
+///
+/// # Examples
+/// ```
+/// use raster::editor::Montage;
+/// use raster::{Color, Image};
+///
+/// let images = vec![Image::filled(4, 4, Color::red()), Image::filled(2, 2, Color::blue())];
+///
+/// // A comparison strip with a black background
+/// let strip = Montage::new(2)
+///     .gap(1)
+///     .background(Color::black())
+///     .render(&images)
+///     .unwrap();
+///
+/// assert_eq!((11, 6), (strip.width, strip.height));
+/// assert_eq!(255, strip.get_pixel(7, 2).unwrap().b);
+/// assert_eq!(0, strip.get_pixel(6, 1).unwrap().b);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Montage {
+    columns: usize,
+    gap: i32,
+    background: Color,
+}
+
+impl Montage {
+    /// Create a montage with the number of columns, no gaps and a transparent background.
+    pub fn new(columns: usize) -> Montage {
+        Montage {
+            columns: cmp::max(columns, 1),
+            gap: 0,
+            background: Color::rgba(0, 0, 0, 0),
+        }
+    }
+
+    /// Set the pixels between cells and around the edges. Defaults to 0.
+    pub fn gap(mut self, gap: i32) -> Montage {
+        self.gap = cmp::max(gap, 0);
+        self
+    }
+
+    /// Set the color behind the images.
+    pub fn background(mut self, background: Color) -> Montage {
+        self.background = background;
+        self
+    }
+
+    /// Arrange the images into one, in rows from the top left.
+    pub fn render(&self, images: &[Image]) -> RasterResult<Image> {
+        let cell_width = images.iter().map(|image| image.width).max().unwrap_or(0);
+        let cell_height = images.iter().map(|image| image.height).max().unwrap_or(0);
+        let columns = cmp::min(self.columns, cmp::max(images.len(), 1));
+        let rows = images.len().div_ceil(columns);
+
+        let width = columns as i32 * (cell_width + self.gap) + self.gap;
+        let height = rows as i32 * (cell_height + self.gap) + self.gap;
+        let mut dest = Image::filled(width, height, self.background.clone());
+        for (i, image) in images.iter().enumerate() {
+            let column = (i % columns) as i32;
+            let row = (i / columns) as i32;
+            let x = self.gap + column * (cell_width + self.gap) + (cell_width - image.width) / 2;
+            let y = self.gap + row * (cell_height + self.gap) + (cell_height - image.height) / 2;
+            paste(&mut dest, image, x, y, 1.0)?;
+        }
+        Ok(dest)
+    }
+}
+
+/// Scale an image to a width and height with nine-slice scaling, like skins for UI buttons and
+/// panels. `insets` are the top, right, bottom and left borders in pixels. The corners keep their
+/// size, the edges stretch along their length and the center stretches both ways.
+///
+/// Insets are clamped to the image, and scaled down when the corners don't fit the new size.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// // A 9x9 button skin: red 3 pixel corners, a white middle
+/// let mut skin = Image::from_fn(9, 9, |x, y| {
+///     if (x < 3 || x > 5) && (y < 3 || y > 5) {
+///         Color::red()
+///     } else {
+///         Color::white()
+///     }
+/// });
+///
+/// editor::nine_slice(&mut skin, (3, 3, 3, 3), 40, 20).unwrap();
+///
+/// assert_eq!((40, 20), (skin.width, skin.height));
+/// assert_eq!(0, skin.get_pixel(39, 19).unwrap().g);
+/// assert_eq!(255, skin.get_pixel(20, 10).unwrap().g);
+/// ```
+pub fn nine_slice(
+    src: &mut Image,
+    insets: (i32, i32, i32, i32),
+    w: i32,
+    h: i32,
+) -> RasterResult<()> {
+    let (top, right, bottom, left) = insets;
+    let w = cmp::max(w, 0);
+    let h = cmp::max(h, 0);
+    let src_x = slice_edges(src.width, left, right);
+    let src_y = slice_edges(src.height, top, bottom);
+    let dest_x = slice_edges(w, src_x[1], src.width - src_x[2]);
+    let dest_y = slice_edges(h, src_y[1], src.height - src_y[2]);
+
+    let mut dest = Image::blank(w, h);
+    for row in 0..3 {
+        for column in 0..3 {
+            let (x1, x2) = (src_x[column], src_x[column + 1]);
+            let (y1, y2) = (src_y[row], src_y[row + 1]);
+            let cell_width = dest_x[column + 1] - dest_x[column];
+            let cell_height = dest_y[row + 1] - dest_y[row];
+            if x2 <= x1 || y2 <= y1 || cell_width <= 0 || cell_height <= 0 {
+                continue;
+            }
+            let mut cell = src.clone();
+            crop(&mut cell, x2 - x1, y2 - y1, PositionMode::TopLeft, x1, y1)?;
+            if (cell.width, cell.height) != (cell_width, cell_height) {
+                transform::resize_exact(&mut cell, cell_width, cell_height)?;
+            }
+            copy_into(&mut dest, &cell, dest_x[column], dest_y[row]);
+        }
+    }
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
+
+/// Pad an image to a width and height, placing it at a position and filling the rest with a
+/// color. Sizes smaller than the image leave that side as it is, so nothing is cropped.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image, PositionMode};
+///
+/// let mut image = Image::filled(16, 9, Color::red());
+///
+/// editor::pad_to(&mut image, 16, 16, PositionMode::Center, Color::black()).unwrap();
+///
+/// assert_eq!((16, 16), (image.width, image.height));
+/// assert_eq!(0, image.get_pixel(0, 2).unwrap().r);
+/// assert_eq!(255, image.get_pixel(0, 4).unwrap().r);
+/// ```
+pub fn pad_to(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    position: PositionMode,
+    color: Color,
+) -> RasterResult<()> {
+    let w = cmp::max(w, src.width);
+    let h = cmp::max(h, src.height);
+    let positioner = Position::new(position, 0, 0);
+    let (x, y) = positioner.get_x_y(w, h, src.width, src.height)?;
+    extend(src, y, w - src.width - x, h - src.height - y, x, color)
+}
+
+/// Paste an overlay onto the image with its top left corner at `x` and `y`, with opacity from
+/// 0.0 to 1.0.
+///
+/// Parts of the overlay falling outside the image are clipped, so `x` and `y` can be negative and
+/// the overlay can be larger than the image. Pasting entirely outside does nothing. The overlay
+/// is alpha composited over the image, like `BlendMode::Normal` but keeping the image's
+/// transparency.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::filled(4, 4, Color::white());
+/// let watermark = Image::filled(3, 3, Color::black());
+///
+/// // Only the bottom right 2x2 pixels of the watermark land on the image
+/// editor::paste(&mut image, &watermark, -1, -1, 0.5).unwrap();
+///
+/// assert_eq!(128, image.get_pixel(1, 1).unwrap().r);
+/// assert_eq!(255, image.get_pixel(2, 2).unwrap().r);
+/// ```
+pub fn paste(src: &mut Image, overlay: &Image, x: i32, y: i32, opacity: f32) -> RasterResult<()> {
+    let opacity = opacity.clamp(0.0, 1.0);
+    let start_x = cmp::max(x, 0);
+    let start_y = cmp::max(y, 0);
+    let end_x = cmp::min(x.saturating_add(overlay.width), src.width);
+    let end_y = cmp::min(y.saturating_add(overlay.height), src.height);
+
+    for canvas_y in start_y..end_y {
+        for canvas_x in start_x..end_x {
+            let i = (canvas_y as usize * src.width as usize + canvas_x as usize) * 4;
+            let j =
+                ((canvas_y - y) as usize * overlay.width as usize + (canvas_x - x) as usize) * 4;
+            let top = &overlay.bytes[j..j + 4];
+            let base = &mut src.bytes[i..i + 4];
+
+            let a2 = top[3] as f32 / 255.0 * opacity;
+            let a1 = base[3] as f32 / 255.0;
+            let a3 = a2 + a1 * (1.0 - a2);
+            if a3 <= 0.0 {
+                continue;
+            }
+            for c in 0..3 {
+                let value = (top[c] as f32 * a2 + base[c] as f32 * a1 * (1.0 - a2)) / a3;
+                base[c] = value.round().min(255.0) as u8;
+            }
+            base[3] = (a3 * 255.0).round() as u8;
+        }
+    }
+    Ok(())
+}
+
 /// An enum for the various modes that can be used for resizing.
 #[derive(Debug)]
 pub enum ResizeMode {
@@ -456,11 +850,644 @@ pub enum ResizeMode {
 /// ![](https://kosinix.github.io/raster/out/test_resize_exact_1.jpg) ![](https://kosinix.github.io/raster/out/test_resize_exact_2.jpg)
 ///
 pub fn resize(src: &mut Image, w: i32, h: i32, mode: ResizeMode) -> RasterResult<()> {
+    resize_with(src, w, h, mode, InterpolationMode::Bicubic)
+}
+
+/// Resize an image to a given width, height and mode with an interpolation mode. `resize` uses
+/// `InterpolationMode::Bicubic`; `Lanczos3` keeps more detail when downscaling photos, and
+/// `Nearest` keeps the hard edges of pixel art.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{editor, InterpolationMode, ResizeMode};
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// editor::resize_with(&mut image, 200, 200, ResizeMode::Fit, InterpolationMode::Lanczos3).unwrap();
+///
+/// assert_eq!(200, image.width);
+/// raster::save(&image, "tests/out/test_resize_lanczos.jpg").unwrap();
+/// ```
+pub fn resize_with(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    mode: ResizeMode,
+    interpolation: InterpolationMode,
+) -> RasterResult<()> {
+    resize_in(src, w, h, mode, interpolation, false)
+}
+
+/// Resize an image like `resize_with`, blending the pixels in linear light. Resizing in sRGB
+/// darkens fine bright detail, like white text on black, and leaves dark halos around
+/// high-contrast edges. Converting to linear light and back avoids that, at the cost of some
+/// speed. `InterpolationMode::Nearest` doesn't blend, so it's the same either way.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{editor, Color, Image, InterpolationMode, ResizeMode};
+///
+/// // Black and white stripes average to a mid gray, which is 188 in sRGB
+/// let mut image = Image::from_fn(2, 2, |x, _| if x == 0 { Color::black() } else { Color::white() });
+/// editor::resize_linear(&mut image, 1, 1, ResizeMode::Exact, InterpolationMode::Bilinear).unwrap();
+///
+/// assert_eq!(188, image.get_pixel(0, 0).unwrap().r);
+/// ```
+pub fn resize_linear(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    mode: ResizeMode,
+    interpolation: InterpolationMode,
+) -> RasterResult<()> {
+    resize_in(src, w, h, mode, interpolation, true)
+}
+
+/// Resize an image with seam carving, which removes or duplicates the paths of least detail
+/// through it instead of scaling everything. Subjects keep their proportions when the aspect ratio
+/// changes, while plain areas like sky or walls give way. The width is carved first, then the
+/// height. It is much slower than `resize`, so downscale large images first.
+///
+/// # Examples
+///
+/// ```
+/// use raster::editor;
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+/// editor::resize_liquid(&mut image, 120, 80).unwrap();
+///
+/// assert_eq!((120, 80), (image.width, image.height));
+/// ```
+pub fn resize_liquid(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
+    let carved = carve_width(src, cmp::max(w, 1));
+    let carved = transpose(&carve_width(&transpose(&carved), cmp::max(h, 1)));
+    src.width = carved.width;
+    src.height = carved.height;
+    src.bytes = carved.bytes;
+    Ok(())
+}
+
+/// Round the corners of an image with the given radius, making them transparent. The edges of the
+/// curves are antialiased. The radius is limited to half the shorter side, which makes a circle or
+/// a pill shape.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut card = Image::filled(40, 20, Color::red());
+///
+/// editor::round_corners(&mut card, 8).unwrap();
+///
+/// assert_eq!(0, card.get_pixel(0, 0).unwrap().a);
+/// assert_eq!(255, card.get_pixel(8, 8).unwrap().a);
+/// assert_eq!(255, card.get_pixel(20, 0).unwrap().a);
+/// ```
+pub fn round_corners(src: &mut Image, radius: i32) -> RasterResult<()> {
+    let radius = cmp::max(cmp::min(radius, cmp::min(src.width, src.height) / 2), 0) as f32;
+    let (w, h) = (src.width as f32, src.height as f32);
+    let width = cmp::max(src.width, 1) as usize;
+    for (i, pixel) in src.pixels_mut().enumerate() {
+        // Distance into the corner square from the center of its circle, if in one
+        let x = (i % width) as f32 + 0.5;
+        let y = (i / width) as f32 + 0.5;
+        let dx = (radius - x).max(x - (w - radius)).max(0.0);
+        let dy = (radius - y).max(y - (h - radius)).max(0.0);
+        if dx == 0.0 || dy == 0.0 {
+            continue;
+        }
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+    Ok(())
+}
+
+/// Crop the image to the given dimension around its most interesting part, like the subject of a
+/// photo for a thumbnail or a social media card. Windows are scored by their edges and saturation,
+/// favoring the center when they score alike. Sizes larger than the image are limited to it.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// // A red square on the right of a gray image
+/// let mut image = Image::from_fn(100, 40, |x, y| {
+///     if x >= 70 && x < 90 && y >= 10 && y < 30 {
+///         Color::red()
+///     } else {
+///         Color::rgb(128, 128, 128)
+///     }
+/// });
+/// editor::smart_crop(&mut image, 40, 40).unwrap();
+///
+/// assert_eq!(40, image.width);
+/// assert_eq!(255, image.get_pixel(30, 20).unwrap().r);
+/// ```
+pub fn smart_crop(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
+    let (w, h) = (cmp::min(w, src.width), cmp::min(h, src.height));
+    if w <= 0 || h <= 0 {
+        return crop(src, w, h, PositionMode::TopLeft, 0, 0);
+    }
+
+    // Summed area table of the scores, with a zero row and column in front
+    let (width, height) = (src.width as usize, src.height as usize);
+    let luma: Vec<f32> = src
+        .pixels()
+        .map(|pixel| pixel[0] as f32 * 0.3 + pixel[1] as f32 * 0.59 + pixel[2] as f32 * 0.11)
+        .collect();
+    let mut table = vec![0.0f64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = 0.0;
+        for x in 0..width {
+            let i = y * width + x;
+            let right = luma[y * width + cmp::min(x + 1, width - 1)];
+            let below = luma[cmp::min(y + 1, height - 1) * width + x];
+            let edge = (right - luma[i]).abs() + (below - luma[i]).abs();
+
+            let pixel = &src.bytes[i * 4..i * 4 + 3];
+            let max = *pixel.iter().max().unwrap_or(&0) as f32;
+            let min = *pixel.iter().min().unwrap_or(&0) as f32;
+            let saturation = max - min;
+
+            let alpha = src.bytes[i * 4 + 3] as f32 / 255.0;
+            row_sum += ((edge + saturation * 0.5) * alpha) as f64;
+            table[(y + 1) * (width + 1) + x + 1] = table[y * (width + 1) + x + 1] + row_sum;
+        }
+    }
+    let sum = |x: usize, y: usize, w: usize, h: usize| {
+        let stride = width + 1;
+        table[(y + h) * stride + x + w] - table[y * stride + x + w] - table[(y + h) * stride + x]
+            + table[y * stride + x]
+    };
+
+    let (w, h) = (w as usize, h as usize);
+    let (max_x, max_y) = (width - w, height - h);
+    let (mut best, mut best_score, mut best_distance) = ((max_x / 2, max_y / 2), f64::MIN, 1.0);
+    for y in 0..=max_y {
+        for x in 0..=max_x {
+            // Up to 10% less for the windows furthest from the center
+            let dx = if max_x > 0 {
+                (x as f64 / max_x as f64 - 0.5).abs()
+            } else {
+                0.0
+            };
+            let dy = if max_y > 0 {
+                (y as f64 / max_y as f64 - 0.5).abs()
+            } else {
+                0.0
+            };
+            let score = sum(x, y, w, h) * (1.0 - 0.1 * (dx + dy));
+            // Windows that score alike, like those of flat images, go to the one nearest the center
+            if score > best_score || (score == best_score && dx + dy < best_distance) {
+                best = (x, y);
+                best_score = score;
+                best_distance = dx + dy;
+            }
+        }
+    }
+    crop(
+        src,
+        w as i32,
+        h as i32,
+        PositionMode::TopLeft,
+        best.0 as i32,
+        best.1 as i32,
+    )
+}
+
+/// Fill a canvas of the given size by repeating the image from the top left, like a background
+/// pattern. An empty image gives a transparent canvas.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::from_fn(2, 1, |x, _| if x == 0 { Color::red() } else { Color::blue() });
+///
+/// editor::tile(&mut image, 5, 3).unwrap();
+///
+/// assert_eq!((5, 3), (image.width, image.height));
+/// assert_eq!(255, image.get_pixel(4, 2).unwrap().r);
+/// ```
+pub fn tile(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
+    tile_with(src, w, h, false)
+}
+
+/// Fill a canvas like `tile`, mirroring every other copy of the image so the seams between them
+/// match up.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::from_fn(2, 1, |x, _| if x == 0 { Color::red() } else { Color::blue() });
+///
+/// editor::tile_mirrored(&mut image, 5, 3).unwrap();
+///
+/// // Red, blue, blue, red, red
+/// assert_eq!(255, image.get_pixel(2, 0).unwrap().b);
+/// assert_eq!(255, image.get_pixel(4, 2).unwrap().r);
+/// ```
+pub fn tile_mirrored(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
+    tile_with(src, w, h, true)
+}
+
+/// Trim uniform borders off an image, like the margins of a scan or a screenshot.
+///
+/// The color of the top left pixel is the border color. Rows and columns along the edges are
+/// removed while every channel of their pixels, alpha included, is within `tolerance` of it. An
+/// image that is all border is left as it is.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::filled(10, 10, Color::white());
+/// image.set_pixel(3, 4, Color::black()).unwrap();
+/// image.set_pixel(5, 6, Color::black()).unwrap();
+/// image.set_pixel(8, 8, Color::rgb(250, 250, 250)).unwrap();
+/// let mut tolerant = image.clone();
+///
+/// editor::trim(&mut image, 0).unwrap();
+/// assert_eq!((6, 5), (image.width, image.height));
+///
+/// // A tolerance trims the near white pixel too
+/// editor::trim(&mut tolerant, 8).unwrap();
+/// assert_eq!((3, 3), (tolerant.width, tolerant.height));
+/// ```
+pub fn trim(src: &mut Image, tolerance: u8) -> RasterResult<()> {
+    let border = match src.bytes.get(0..4) {
+        Some(pixel) => [pixel[0], pixel[1], pixel[2], pixel[3]],
+        None => return Ok(()),
+    };
+    trim_by(src, |pixel| {
+        pixel
+            .iter()
+            .zip(border.iter())
+            .all(|(&a, &b)| (a as i16 - b as i16).abs() <= tolerance as i16)
+    })
+}
+
+/// Trim transparent borders off an image, removing rows and columns along the edges while the
+/// alpha of their pixels is at most `tolerance`. An image that is all transparent is left as it
+/// is.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::blank(8, 8);
+/// editor::fill(&mut image, Color::rgba(255, 255, 255, 0)).unwrap();
+/// image.set_pixel(2, 2, Color::red()).unwrap();
+/// image.set_pixel(4, 3, Color::red()).unwrap();
+///
+/// editor::trim_transparent(&mut image, 0).unwrap();
+/// assert_eq!((3, 2), (image.width, image.height));
+/// ```
+pub fn trim_transparent(src: &mut Image, tolerance: u8) -> RasterResult<()> {
+    trim_by(src, |pixel| pixel[3] <= tolerance)
+}
+
+/// Join images from top to bottom. Images narrower than the widest are aligned to the left, with
+/// transparent pixels beside them.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let column = editor::vconcat(&[
+///     Image::filled(2, 3, Color::red()),
+///     Image::filled(4, 1, Color::blue()),
+/// ]);
+///
+/// assert_eq!((4, 4), (column.width, column.height));
+/// assert_eq!(255, column.get_pixel(3, 3).unwrap().b);
+/// ```
+pub fn vconcat(images: &[Image]) -> Image {
+    let width = images.iter().map(|image| image.width).max().unwrap_or(0);
+    let height = images.iter().map(|image| image.height).sum();
+    let mut dest = Image::filled(width, height, Color::rgba(0, 0, 0, 0));
+    let mut y = 0;
+    for image in images {
+        copy_into(&mut dest, image, 0, y);
+        y += image.height;
+    }
+    dest
+}
+
+/// Watermark an image, placing the mark at a position `margin` pixels in from the edges.
+///
+/// The mark is pasted with `paste`, so it is clipped if it doesn't fit.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, PositionMode};
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// let mark = raster::open("tests/in/watermark.png").unwrap();
+///
+/// editor::watermark(&mut image, &mark, PositionMode::BottomRight, 10, 0.5).unwrap();
+///
+/// raster::save(&image, "tests/out/test_watermark.png").unwrap();
+/// ```
+pub fn watermark(
+    src: &mut Image,
+    mark: &Image,
+    position: PositionMode,
+    margin: i32,
+    opacity: f32,
+) -> RasterResult<()> {
+    // Offsets pointing inward from the edges the position sits against
+    let (offset_x, offset_y) = match position {
+        PositionMode::TopLeft => (margin, margin),
+        PositionMode::TopCenter => (0, margin),
+        PositionMode::TopRight => (-margin, margin),
+        PositionMode::CenterLeft => (margin, 0),
+        PositionMode::Center => (0, 0),
+        PositionMode::CenterRight => (-margin, 0),
+        PositionMode::BottomLeft => (margin, -margin),
+        PositionMode::BottomCenter => (0, -margin),
+        PositionMode::BottomRight => (-margin, -margin),
+    };
+    let positioner = Position::new(position, offset_x, offset_y);
+    let (x, y) = positioner.get_x_y(src.width, src.height, mark.width, mark.height)?;
+    paste(src, mark, x, y, opacity)
+}
+
+/// Watermark the whole image by tiling the mark across it, with `gap` pixels between the tiles.
+///
+/// # Examples
+/// ```
+/// use raster::{editor, Color, Image};
+///
+/// let mut image = Image::filled(10, 10, Color::white());
+/// let mark = Image::filled(2, 2, Color::black());
+///
+/// editor::watermark_tiled(&mut image, &mark, 3, 1.0).unwrap();
+///
+/// assert_eq!(0, image.get_pixel(5, 6).unwrap().r);
+/// assert_eq!(255, image.get_pixel(3, 3).unwrap().r);
+/// ```
+pub fn watermark_tiled(src: &mut Image, mark: &Image, gap: i32, opacity: f32) -> RasterResult<()> {
+    if mark.width <= 0 || mark.height <= 0 {
+        return Ok(());
+    }
+    let gap = cmp::max(gap, 0);
+    let mut y = 0;
+    while y < src.height {
+        let mut x = 0;
+        while x < src.width {
+            paste(src, mark, x, y, opacity)?;
+            x += mark.width + gap;
+        }
+        y += mark.height + gap;
+    }
+    Ok(())
+}
+
+// Private functions
+
+// Seam carve the width of an image. Seams to duplicate are found by removing them from a copy, so
+// widening by more than half the width is done in steps.
+fn carve_width(src: &Image, w: i32) -> Image {
+    let height = src.height.max(0) as usize;
+    let mut rows: Vec<Vec<[u8; 4]>> = src
+        .rows()
+        .map(|row| {
+            row.chunks_exact(4)
+                .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+                .collect()
+        })
+        .collect();
+    let mut width = src.width.max(0) as usize;
+    let target = w as usize;
+    if height == 0 || width == 0 {
+        return src.clone();
+    }
+
+    while width > target {
+        let seam = find_seam(&rows, width);
+        for (row, &x) in rows.iter_mut().zip(&seam) {
+            row.remove(x);
+        }
+        width -= 1;
+    }
+
+    while width < target {
+        let count = cmp::min(target - width, cmp::max(width / 2, 1));
+
+        // Columns of the copy mapped back to the columns of the image
+        let mut copy = rows.clone();
+        let mut columns: Vec<Vec<usize>> = vec![(0..width).collect(); height];
+        let mut duplicates = vec![Vec::with_capacity(count); height];
+        for removed in 0..count {
+            if width - removed < 2 {
+                // Too narrow to find more seams, duplicate the last column
+                for row in duplicates.iter_mut() {
+                    row.push(width - 1);
+                }
+                continue;
+            }
+            let seam = find_seam(&copy, width - removed);
+            for y in 0..height {
+                let x = seam[y];
+                duplicates[y].push(columns[y][x]);
+                copy[y].remove(x);
+                columns[y].remove(x);
+            }
+        }
+
+        for (row, duplicates) in rows.iter_mut().zip(duplicates.iter_mut()) {
+            duplicates.sort_unstable();
+            let mut widened = Vec::with_capacity(width + count);
+            let mut pending = duplicates.iter().peekable();
+            for x in 0..width {
+                widened.push(row[x]);
+                while pending.peek() == Some(&&x) {
+                    pending.next();
+                    let next = row[cmp::min(x + 1, width - 1)];
+                    let mut average = [0; 4];
+                    for (channel, value) in average.iter_mut().enumerate() {
+                        *value = ((row[x][channel] as u16 + next[channel] as u16) / 2) as u8;
+                    }
+                    widened.push(average);
+                }
+            }
+            *row = widened;
+        }
+        width += count;
+    }
+
+    let mut dest = Image::blank(width as i32, height as i32);
+    for (pixel, value) in dest
+        .bytes
+        .chunks_exact_mut(4)
+        .zip(rows.iter().flat_map(|row| row.iter()))
+    {
+        pixel.copy_from_slice(value);
+    }
+    dest
+}
+
+// Copy the pixels of an image that fits inside dest at x and y, leaving them as they are.
+fn copy_into(dest: &mut Image, src: &Image, x: i32, y: i32) {
+    let row_len = src.width as usize * 4;
+    for (row_y, row) in src.bytes.chunks(cmp::max(row_len, 1)).enumerate() {
+        let start = ((row_y + y as usize) * dest.width as usize + x as usize) * 4;
+        dest.bytes[start..start + row.len()].copy_from_slice(row);
+    }
+}
+
+// The column of each row on the vertical path with the least energy, found by dynamic programming
+// over the luma gradients.
+fn find_seam(rows: &[Vec<[u8; 4]>], width: usize) -> Vec<usize> {
+    let height = rows.len();
+    let luma = |pixel: [u8; 4]| {
+        (pixel[0] as f32 * 0.3 + pixel[1] as f32 * 0.59 + pixel[2] as f32 * 0.11)
+            * (pixel[3] as f32 / 255.0)
+    };
+    let mut cost = vec![0.0f32; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let left = luma(rows[y][x.saturating_sub(1)]);
+            let right = luma(rows[y][cmp::min(x + 1, width - 1)]);
+            let up = luma(rows[y.saturating_sub(1)][x]);
+            let down = luma(rows[cmp::min(y + 1, height - 1)][x]);
+            let energy = (right - left).abs() + (down - up).abs();
+
+            cost[y * width + x] = if y == 0 {
+                energy
+            } else {
+                let above = &cost[(y - 1) * width..y * width];
+                let from = x.saturating_sub(1);
+                let to = cmp::min(x + 1, width - 1);
+                energy + above[from..=to].iter().cloned().fold(f32::MAX, f32::min)
+            };
+        }
+    }
+
+    let mut seam = vec![0; height];
+    let last = &cost[(height - 1) * width..];
+    seam[height - 1] = (0..width)
+        .min_by(|&a, &b| last[a].total_cmp(&last[b]))
+        .unwrap_or(0);
+    for y in (0..height - 1).rev() {
+        let below = seam[y + 1];
+        let from = below.saturating_sub(1);
+        let to = cmp::min(below + 1, width - 1);
+        let row = &cost[y * width..(y + 1) * width];
+        seam[y] = (from..=to)
+            .min_by(|&a, &b| row[a].total_cmp(&row[b]))
+            .unwrap_or(below);
+    }
+    seam
+}
+
+fn resize_in(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    mode: ResizeMode,
+    interpolation: InterpolationMode,
+    linear: bool,
+) -> RasterResult<()> {
     match mode {
-        ResizeMode::Exact => transform::resize_exact(src, w, h),
-        ResizeMode::ExactWidth => transform::resize_exact_width(src, w),
-        ResizeMode::ExactHeight => transform::resize_exact_height(src, h),
-        ResizeMode::Fit => transform::resize_fit(src, w, h),
-        ResizeMode::Fill => transform::resize_fill(src, w, h),
+        ResizeMode::Exact => transform::resize_exact_with(src, w, h, interpolation, linear),
+        ResizeMode::ExactWidth => transform::resize_exact_width_with(src, w, interpolation, linear),
+        ResizeMode::ExactHeight => {
+            transform::resize_exact_height_with(src, h, interpolation, linear)
+        }
+        ResizeMode::Fit => transform::resize_fit_with(src, w, h, interpolation, linear),
+        ResizeMode::Fill => transform::resize_fill_with(src, w, h, interpolation, linear),
+    }
+}
+
+// Where a length splits into 3 slices with the given start and end borders. Borders that don't
+// fit the length are scaled down together.
+fn slice_edges(len: i32, start: i32, end: i32) -> [i32; 4] {
+    let (start, end) = (cmp::max(start, 0), cmp::max(end, 0));
+    let (start, end) = if start + end > len && start + end > 0 {
+        let start = (start as i64 * len as i64 / (start + end) as i64) as i32;
+        (start, len - start)
+    } else {
+        (start, end)
+    };
+    [0, start, len - end, len]
+}
+
+fn tile_with(src: &mut Image, w: i32, h: i32, mirror: bool) -> RasterResult<()> {
+    let (w, h) = (cmp::max(w, 0), cmp::max(h, 0));
+    if src.width <= 0 || src.height <= 0 {
+        src.width = w;
+        src.height = h;
+        src.bytes = vec![0; w as usize * h as usize * 4];
+        return Ok(());
+    }
+    let wrap = |i: i32, len: i32| {
+        if mirror {
+            let i = i % (len * 2);
+            if i < len {
+                i
+            } else {
+                len * 2 - 1 - i
+            }
+        } else {
+            i % len
+        }
+    };
+
+    let mut dest = Image::blank(w, h);
+    let columns: Vec<usize> = (0..w).map(|x| wrap(x, src.width) as usize * 4).collect();
+    let row_len = cmp::max(w as usize * 4, 1);
+    for (y, row) in dest.bytes.chunks_mut(row_len).enumerate() {
+        let src_row = wrap(y as i32, src.height) as usize * src.width as usize * 4;
+        for (pixel, &column) in row.chunks_exact_mut(4).zip(columns.iter()) {
+            let start = src_row + column;
+            pixel.copy_from_slice(&src.bytes[start..start + 4]);
+        }
+    }
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
+
+fn transpose(src: &Image) -> Image {
+    let mut dest = Image::blank(src.height, src.width);
+    for (x, y, pixel) in src.enumerate_pixels() {
+        let i = ((x * src.height + y) * 4) as usize;
+        dest.bytes[i..i + 4].copy_from_slice(pixel);
+    }
+    dest
+}
+
+// Crop to the bounds of the pixels that aren't border.
+fn trim_by<F>(src: &mut Image, is_border: F) -> RasterResult<()>
+where
+    F: Fn(&[u8]) -> bool,
+{
+    let (mut left, mut top) = (src.width, src.height);
+    let (mut right, mut bottom) = (-1, -1);
+    for (x, y, pixel) in src.enumerate_pixels() {
+        if !is_border(pixel) {
+            left = cmp::min(left, x);
+            right = cmp::max(right, x);
+            top = cmp::min(top, y);
+            bottom = cmp::max(bottom, y);
+        }
+    }
+    if right < 0 {
+        return Ok(());
     }
+    crop(
+        src,
+        right - left + 1,
+        bottom - top + 1,
+        PositionMode::TopLeft,
+        left,
+        top,
+    )
 }
@@ -1,69 +1,4646 @@
 //!  A module for encoding/decoding.
+//!
+//! The functions here are what `raster::open` and `raster::save` use under the hood. Use them
+//! directly when you need more than a single still image, like all the frames of an animated GIF.
+//! Every decoder also has a `_from_reader` variant for data that does not come from a file and
+//! every encoder a `_to_writer` variant for output that does not go to a file.
+//!
+//! Decoders refuse images larger than their `DecodeLimits`. The plain decoders use the default
+//! limits, the `_from_reader` variants take them as an argument.
 
 // from rust
+use std::cmp;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
 // from external crate
+#[cfg(feature = "avif")]
+use avif_decode;
+#[cfg(feature = "exr")]
+use exr;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use gif;
+use jpeg_decoder;
+use piston_image;
+use piston_image::ImageDecoder;
 use png;
+use qoi;
+#[cfg(feature = "svg")]
+use resvg;
+use tiff;
+use webp;
 
 // from local crate
+use animation::{Animation, Frame, FrameBlend, LoopCount};
+use buffer::{Gray8, ImageBuffer, Rgba16};
+use editor;
 use error::{RasterError, RasterResult};
+use hdr::HdrImage;
+#[cfg(feature = "heif")]
+use metadata;
+use open;
+use Color;
+use DisposalMethod;
 use Image;
 use ImageFormat;
+use ResizeMode;
+
+/// Detect the format of an image from the magic number at the start of its data. TGA has no
+/// magic number and is never detected. Reads at most 512 bytes.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::UnsupportedFormat` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::{endec, ImageFormat};
+///
+/// let mut file = File::open("tests/in/sample.png").unwrap();
+/// assert_eq!(ImageFormat::Png, endec::detect_format(&mut file).unwrap());
+/// ```
+pub fn detect_format(reader: &mut impl Read) -> RasterResult<ImageFormat> {
+    let mut data = Vec::new();
+    reader.take(512).read_to_end(&mut data)?;
+
+    let format = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        ImageFormat::Png
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        ImageFormat::Jpeg
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        ImageFormat::Gif
+    } else if data.starts_with(b"BM") {
+        ImageFormat::Bmp
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        ImageFormat::Tiff
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        ImageFormat::WebP
+    } else if data.starts_with(b"qoif") {
+        ImageFormat::Qoi
+    } else if data.starts_with(b"farbfeld") {
+        ImageFormat::Farbfeld
+    } else if data.starts_with(b"DDS ") {
+        ImageFormat::Dds
+    } else if data.starts_with(&[0x76, 0x2f, 0x31, 0x01]) {
+        ImageFormat::Exr
+    } else if data.starts_with(b"#?RADIANCE") || data.starts_with(b"#?RGBE") {
+        ImageFormat::Hdr
+    } else if (data.starts_with(&[0, 0, 1, 0]) || data.starts_with(&[0, 0, 2, 0]))
+        && data.len() > 6
+        && (data[4] != 0 || data[5] != 0)
+    {
+        // Uncompressed TGAs start the same as cursors but have no icon count
+        ImageFormat::Ico
+    } else if data.len() > 2
+        && data[0] == b'P'
+        && (b'1'..=b'7').contains(&data[1])
+        && data[2].is_ascii_whitespace()
+    {
+        ImageFormat::Pnm
+    } else if data.get(4..8) == Some(b"ftyp") {
+        // The major brand followed by the minor version and the compatible brands
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let brands: Vec<&[u8]> = data[8..cmp::min(size, data.len())]
+            .chunks(4)
+            .enumerate()
+            .filter(|&(i, _)| i != 1)
+            .map(|(_, brand)| brand)
+            .collect();
+        if brands.iter().any(|&b| b == b"avif" || b == b"avis") {
+            ImageFormat::Avif
+        } else if brands
+            .iter()
+            .any(|&b| b == b"heic" || b == b"heix" || b == b"hevc" || b == b"hevx" || b == b"mif1")
+        {
+            ImageFormat::Heif
+        } else {
+            return Err(RasterError::UnsupportedFormat("unknown".to_string()));
+        }
+    } else if String::from_utf8_lossy(&data).contains("<svg") {
+        ImageFormat::Svg
+    } else {
+        return Err(RasterError::UnsupportedFormat("unknown".to_string()));
+    };
+
+    Ok(format)
+}
+
+/// Size limits for decoding. Image headers are checked against them before any pixels are
+/// allocated, so that a small file claiming to be huge fails instead of exhausting memory.
+/// AVIFs are the exception and can only be checked once decoded.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+/// use raster::error::RasterError;
+///
+/// let limits = DecodeLimits {
+///     max_width: 100,
+///     ..DecodeLimits::default()
+/// };
+/// let data = std::fs::read("tests/in/sample.png").unwrap();
+/// match endec::decode_png_from_reader(Cursor::new(data), &limits) {
+///     Err(RasterError::LimitExceeded(_)) => {}
+///     _ => panic!("sample.png is wider than 100 pixels"),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeLimits {
+    /// Widest image allowed in pixels. Defaults to 65536.
+    pub max_width: u32,
+
+    /// Tallest image allowed in pixels. Defaults to 65536.
+    pub max_height: u32,
+
+    /// Most memory the decoded pixels may take up in bytes. Counts all frames of an animation
+    /// and all pages of a TIFF. Defaults to 1 GiB.
+    pub max_bytes: usize,
+}
+
+impl DecodeLimits {
+    // Fail if an image of this size, with pixels of this many bytes, is over the limits.
+    fn check(
+        &self,
+        format: ImageFormat,
+        width: u64,
+        height: u64,
+        pixel_size: u64,
+    ) -> RasterResult<()> {
+        if width > self.max_width as u64 || height > self.max_height as u64 {
+            return Err(RasterError::LimitExceeded(format));
+        }
+        self.check_bytes(
+            format,
+            width.saturating_mul(height).saturating_mul(pixel_size),
+        )
+    }
+
+    // Fail if decoded pixels taking up this many bytes are over the limits.
+    fn check_bytes(&self, format: ImageFormat, bytes: u64) -> RasterResult<()> {
+        if bytes > self.max_bytes as u64 {
+            return Err(RasterError::LimitExceeded(format));
+        }
+        Ok(())
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> DecodeLimits {
+        DecodeLimits {
+            max_width: 1 << 16,
+            max_height: 1 << 16,
+            max_bytes: 1 << 30,
+        }
+    }
+}
+
+/// Decoder settings for `raster::open_with`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecodeOptions {
+    /// Return as much as can be decoded of truncated or corrupt JPEGs and PNGs instead of
+    /// failing. Rows that could not be decoded are filled in, black or transparent for PNGs and
+    /// with whatever the JPEG decoder makes of a premature end for JPEGs. Defaults to `false`.
+    pub tolerant: bool,
+
+    /// Size limits. Defaults to `DecodeLimits::default()`.
+    pub limits: DecodeLimits,
+}
+
+/// Decode an image at a reduced size, for thumbnails. The result fits within `max_width` by
+/// `max_height` pixels and keeps the aspect ratio of the image. Images that already fit are
+/// returned at their own size.
+///
+/// JPEGs are scaled down by up to 8 times while decoding and PNGs that aren't interlaced are
+/// reduced a row at a time, so that large photos are never held in memory at full size. Other
+/// formats are decoded like `raster::open` does and resized afterwards.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::LimitExceeded` or `RasterError::UnsupportedFormat` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let thumbnail = endec::decode_scaled(Path::new("tests/in/sample.jpg"), 100, 100).unwrap();
+///
+/// assert_eq!(100, thumbnail.width);
+/// assert!(thumbnail.height <= 100);
+/// ```
+pub fn decode_scaled(path: &Path, max_width: i32, max_height: i32) -> RasterResult<Image> {
+    let limits = DecodeLimits::default();
+    let mut file = File::open(path)?;
+    let format = detect_format(&mut file);
+    file.seek(SeekFrom::Start(0))?;
+
+    let (mut image, (width, height)) = match format {
+        Ok(ImageFormat::Jpeg) => {
+            let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(file));
+            decoder.read_info()?;
+            let info = decoder.info().ok_or(RasterError::Unexpected)?;
+            let (src_w, src_h) = (info.width as i32, info.height as i32);
+            limits.check(ImageFormat::Jpeg, src_w as u64, src_h as u64, 4)?;
+            let size = fit_size(src_w, src_h, max_width, max_height);
+
+            decoder.scale(size.0 as u16, size.1 as u16)?;
+            let pixels = decoder.decode()?;
+            let info = decoder.info().ok_or(RasterError::Unexpected)?;
+            let bytes = match info.pixel_format {
+                jpeg_decoder::PixelFormat::L8 => {
+                    pixels.iter().flat_map(|&l| vec![l, l, l, 255]).collect()
+                }
+                jpeg_decoder::PixelFormat::RGB24 => pixels
+                    .chunks(3)
+                    .flat_map(|p| vec![p[0], p[1], p[2], 255])
+                    .collect(),
+                jpeg_decoder::PixelFormat::CMYK32 => pixels
+                    .chunks(4)
+                    .flat_map(|p| {
+                        // Same conversion as the image crate uses for decode_jpeg
+                        let k = p[3] as f32 / 255.0;
+                        let rgb =
+                            |c: u8| ((1.0 - (c as f32 / 255.0 * (1.0 - k) + k)) * 255.0) as u8;
+                        vec![rgb(p[0]), rgb(p[1]), rgb(p[2]), 255]
+                    })
+                    .collect(),
+            };
+            let image = Image {
+                width: info.width as i32,
+                height: info.height as i32,
+                bytes,
+            };
+            (image, size)
+        }
+        Ok(ImageFormat::Png) => {
+            let mut decoder = png::Decoder::new_with_limits(
+                BufReader::new(file),
+                png::Limits {
+                    bytes: limits.max_bytes,
+                },
+            );
+            decoder
+                .set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+            let mut reader = decoder.read_info()?;
+            let (src_w, src_h) = reader.info().size();
+            limits.check(ImageFormat::Png, src_w as u64, src_h as u64, 4)?;
+            let size = fit_size(src_w as i32, src_h as i32, max_width, max_height);
+            (png_scaled(&mut reader, size)?, size)
+        }
+        _ => {
+            let image = open(&path.to_string_lossy())?;
+            let size = fit_size(image.width, image.height, max_width, max_height);
+            (image, size)
+        }
+    };
+
+    if (image.width, image.height) != (width, height) {
+        editor::resize(&mut image, width, height, ResizeMode::Exact)?;
+    }
+    Ok(image)
+}
+
+/// Decode only a rectangle of a PNG or TIFF, like a single tile of a large map. The rectangle is
+/// clipped to the image.
+///
+/// Only the strips or tiles of a TIFF that overlap the rectangle are decoded. PNGs are decoded a
+/// row at a time down to the bottom of the rectangle, keeping only the pixels inside it.
+/// Interlaced PNGs are decoded whole first.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Decode` upon failure. Returns
+/// `RasterError::PixelOutOfBounds` if the rectangle is outside of the image. Other formats and
+/// TIFFs with separate color planes return `RasterError::UnsupportedFormat`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+/// use raster::ImageFormat;
+///
+/// let file = File::open("tests/in/sample.png").unwrap();
+/// let tile = endec::decode_region(ImageFormat::Png, file, 100, 50, 64, 64).unwrap();
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// assert_eq!((64, 64), (tile.width, tile.height));
+/// assert_eq!(image.get_pixel(100, 50).unwrap().r, tile.get_pixel(0, 0).unwrap().r);
+/// ```
+pub fn decode_region<R: Read + Seek>(
+    format: ImageFormat,
+    reader: R,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+) -> RasterResult<Image> {
+    match format {
+        ImageFormat::Png => {
+            let mut decoder = png::Decoder::new(reader);
+            decoder
+                .set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+            let mut reader = decoder.read_info()?;
+            let (image_w, image_h) = reader.info().size();
+            let (left, top, right, bottom) = clip_region(x, y, width, height, image_w, image_h)?;
+            let color_type = reader.output_color_type().0;
+
+            let mut bytes = Vec::with_capacity((right - left) * (bottom - top) * 4);
+            if reader.info().interlaced {
+                let mut buffer = vec![0; reader.output_buffer_size()];
+                reader.next_frame(&mut buffer)?;
+                let rgba = png_rgba(color_type, &buffer);
+                for row in rgba.chunks(image_w as usize * 4).take(bottom).skip(top) {
+                    bytes.extend_from_slice(&row[left * 4..right * 4]);
+                }
+            } else {
+                let samples = color_type.samples();
+                for row_y in 0..bottom {
+                    let row = reader.next_row()?.ok_or_else(|| {
+                        RasterError::Decode(ImageFormat::Png, "Unexpected end of image".to_string())
+                    })?;
+                    if row_y >= top {
+                        let pixels = &row.data()[left * samples..right * samples];
+                        bytes.extend_from_slice(&png_rgba(color_type, pixels));
+                    }
+                }
+            }
+
+            Ok(Image {
+                width: (right - left) as i32,
+                height: (bottom - top) as i32,
+                bytes,
+            })
+        }
+        ImageFormat::Tiff => {
+            let mut decoder = tiff::decoder::Decoder::new(BufReader::new(reader))?;
+            let (image_w, image_h) = decoder.dimensions()?;
+            let (left, top, right, bottom) = clip_region(x, y, width, height, image_w, image_h)?;
+            let color_type = decoder.colortype()?;
+
+            // Strips are chunks as wide as the image
+            let (chunk_w, chunk_h) = decoder.chunk_dimensions();
+            let (chunk_w, chunk_h) = (chunk_w.max(1) as usize, chunk_h.max(1) as usize);
+            let across = (image_w as usize).div_ceil(chunk_w);
+            let chunk_count = match decoder.get_chunk_type() {
+                tiff::decoder::ChunkType::Strip => decoder.strip_count()?,
+                tiff::decoder::ChunkType::Tile => decoder.tile_count()?,
+            } as usize;
+            // Separate color planes have a set of chunks per plane
+            if chunk_count != across * (image_h as usize).div_ceil(chunk_h) {
+                return Err(RasterError::UnsupportedFormat("planar tiff".to_string()));
+            }
+
+            let out_w = right - left;
+            let mut bytes = vec![0; out_w * (bottom - top) * 4];
+            for chunk_y in top / chunk_h..=(bottom - 1) / chunk_h {
+                for chunk_x in left / chunk_w..=(right - 1) / chunk_w {
+                    let index = (chunk_y * across + chunk_x) as u32;
+                    let data_w = decoder.chunk_data_dimensions(index).0 as usize;
+                    let rgba = tiff_rgba(color_type, tiff_samples(decoder.read_chunk(index)?)?)?;
+
+                    let (chunk_left, chunk_top) = (chunk_x * chunk_w, chunk_y * chunk_h);
+                    let start = cmp::max(left, chunk_left);
+                    let end = cmp::min(right, chunk_left + data_w);
+                    for (i, row) in rgba.chunks(data_w * 4).enumerate() {
+                        let row_y = chunk_top + i;
+                        if row_y < top || row_y >= bottom {
+                            continue;
+                        }
+                        let src = &row[(start - chunk_left) * 4..(end - chunk_left) * 4];
+                        let dest = ((row_y - top) * out_w + start - left) * 4;
+                        bytes[dest..dest + src.len()].copy_from_slice(src);
+                    }
+                }
+            }
+
+            Ok(Image {
+                width: out_w as i32,
+                height: (bottom - top) as i32,
+                bytes,
+            })
+        }
+        _ => Err(RasterError::UnsupportedFormat(
+            format!("{:?}", format).to_lowercase(),
+        )),
+    }
+}
+
+/// Encoder settings for `Image::to_bytes`. Settings that don't apply to the chosen format are
+/// ignored. The defaults are the ones `raster::save` uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodeOptions {
+    /// WebP quality from 0.0 to 100.0. Defaults to 75.0.
+    pub quality: f32,
+
+    /// Save WebPs as lossless and ignore `quality`. Defaults to `false`.
+    pub lossless: bool,
+
+    /// PNM format. Defaults to `PnmFormat::Ppm`.
+    pub pnm_format: PnmFormat,
+
+    /// PNM sample encoding. Defaults to `PnmEncoding::Binary`.
+    pub pnm_encoding: PnmEncoding,
+
+    /// TGA compression. Defaults to `TgaCompression::Rle`.
+    pub tga_compression: TgaCompression,
+
+    /// TIFF compression. Defaults to `TiffCompression::Lzw`.
+    pub tiff_compression: TiffCompression,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> EncodeOptions {
+        EncodeOptions {
+            quality: 75.0,
+            lossless: false,
+            pnm_format: PnmFormat::Ppm,
+            pnm_encoding: PnmEncoding::Binary,
+            tga_compression: TgaCompression::Rle,
+            tiff_compression: TiffCompression::Lzw,
+        }
+    }
+}
+
+/// A decoder that produces an image one band of rows at a time, so that huge images can be
+/// processed without holding all their pixels in memory.
+///
+/// Bands are `Image`s as wide as the whole image and come in order from top to bottom. PNGs are
+/// decoded one row at a time, TIFFs one strip at a time. Interlaced PNGs can't be decoded row by
+/// row and are decoded completely before the first row is returned.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec::StreamingDecoder;
+/// use raster::ImageFormat;
+///
+/// let file = File::open("tests/in/sample.png").unwrap();
+/// let decoder = StreamingDecoder::new(ImageFormat::Png, file).unwrap();
+/// let height = decoder.height();
+///
+/// let mut rows = 0;
+/// for band in decoder {
+///     rows += band.unwrap().height;
+/// }
+/// assert_eq!(height, rows);
+/// ```
+pub struct StreamingDecoder<R: Read + Seek> {
+    width: i32,
+    height: i32,
+    source: StreamSource<R>,
+}
+
+// Where the bands of a streaming decoder come from.
+enum StreamSource<R: Read + Seek> {
+    Png(Box<png::Reader<R>>),
+    // Interlaced PNGs and the rows left to hand out
+    PngBuffered(Image, i32),
+    // TIFF decoder and the next strip
+    Tiff(Box<tiff::decoder::Decoder<R>>, u32),
+    Done,
+}
+
+impl<R: Read + Seek> StreamingDecoder<R> {
+    /// Create a streaming decoder for a PNG or a TIFF. Only the header is read here.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` or `RasterError::Decode` upon failure. Other
+    /// formats, tiled TIFFs and TIFFs with separate color planes return
+    /// `RasterError::UnsupportedFormat`.
+    pub fn new(format: ImageFormat, reader: R) -> RasterResult<StreamingDecoder<R>> {
+        match format {
+            ImageFormat::Png => {
+                let mut decoder = png::Decoder::new(reader);
+                decoder.set_transformations(
+                    png::Transformations::EXPAND | png::Transformations::STRIP_16,
+                );
+                let mut reader = decoder.read_info()?;
+                let (width, height) = (reader.info().width as i32, reader.info().height as i32);
+                let source = if reader.info().interlaced {
+                    let mut buffer = vec![0; reader.output_buffer_size()];
+                    reader.next_frame(&mut buffer)?;
+                    let image = Image {
+                        width,
+                        height,
+                        bytes: png_rgba(reader.output_color_type().0, &buffer),
+                    };
+                    StreamSource::PngBuffered(image, height)
+                } else {
+                    StreamSource::Png(Box::new(reader))
+                };
+                Ok(StreamingDecoder {
+                    width,
+                    height,
+                    source,
+                })
+            }
+            ImageFormat::Tiff => {
+                let mut decoder = tiff::decoder::Decoder::new(reader)?;
+                let (width, height) = decoder.dimensions()?;
+                if decoder.get_chunk_type() != tiff::decoder::ChunkType::Strip {
+                    return Err(RasterError::UnsupportedFormat("tiled tiff".to_string()));
+                }
+                // Separate color planes have a set of strips per plane
+                let rows_per_strip = decoder.chunk_dimensions().1.max(1);
+                if decoder.strip_count()? != height.div_ceil(rows_per_strip) {
+                    return Err(RasterError::UnsupportedFormat("planar tiff".to_string()));
+                }
+                Ok(StreamingDecoder {
+                    width: width as i32,
+                    height: height as i32,
+                    source: StreamSource::Tiff(Box::new(decoder), 0),
+                })
+            }
+            format => Err(RasterError::UnsupportedFormat(
+                format!("{:?}", format).to_lowercase(),
+            )),
+        }
+    }
+
+    /// Width of the image in pixels.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    // Decode the next band, or None after the last one.
+    fn next_band(&mut self) -> RasterResult<Option<Image>> {
+        let width = self.width;
+        match self.source {
+            StreamSource::Png(ref mut reader) => {
+                let color_type = reader.output_color_type().0;
+                match reader.next_row()? {
+                    Some(row) => Ok(Some(Image {
+                        width,
+                        height: 1,
+                        bytes: png_rgba(color_type, row.data()),
+                    })),
+                    None => Ok(None),
+                }
+            }
+            StreamSource::PngBuffered(ref image, ref mut rows_left) => {
+                if *rows_left == 0 {
+                    return Ok(None);
+                }
+                let start = ((image.height - *rows_left) * width * 4) as usize;
+                *rows_left -= 1;
+                Ok(Some(Image {
+                    width,
+                    height: 1,
+                    bytes: image.bytes[start..start + (width * 4) as usize].to_vec(),
+                }))
+            }
+            StreamSource::Tiff(ref mut decoder, ref mut strip) => {
+                if *strip >= decoder.strip_count()? {
+                    return Ok(None);
+                }
+                let color_type = decoder.colortype()?;
+                let rows = decoder.chunk_data_dimensions(*strip).1;
+                let samples = tiff_samples(decoder.read_chunk(*strip)?)?;
+                *strip += 1;
+                Ok(Some(Image {
+                    width,
+                    height: rows as i32,
+                    bytes: tiff_rgba(color_type, samples)?,
+                }))
+            }
+            StreamSource::Done => Ok(None),
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for StreamingDecoder<R> {
+    type Item = RasterResult<Image>;
+
+    fn next(&mut self) -> Option<RasterResult<Image>> {
+        let band = self.next_band();
+        // Stop after the last band or the first error
+        if let Ok(None) | Err(_) = band {
+            self.source = StreamSource::Done;
+        }
+        band.transpose()
+    }
+}
+
+/// Decode an AVIF. Requires the `avif` feature. 10 and 12-bit images are reduced to 8 bits.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.avif").unwrap();
+/// let image = endec::decode_avif(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+#[cfg(feature = "avif")]
+pub fn decode_avif(image_file: &File) -> RasterResult<Image> {
+    decode_avif_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode an AVIF from a reader with the given size limits. Otherwise works the same as
+/// `decode_avif`. Requires the `avif` feature.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.avif").unwrap();
+/// let image = endec::decode_avif_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+#[cfg(feature = "avif")]
+pub fn decode_avif_from_reader<R: Read>(reader: R, limits: &DecodeLimits) -> RasterResult<Image> {
+    let decoder = avif_decode::Decoder::from_reader(&mut BufReader::new(reader))?;
+
+    let image = decoder.to_image()?;
+    let (width, height) = match image {
+        avif_decode::Image::Rgb8(ref img) => (img.width(), img.height()),
+        avif_decode::Image::Rgb16(ref img) => (img.width(), img.height()),
+        avif_decode::Image::Rgba8(ref img) => (img.width(), img.height()),
+        avif_decode::Image::Rgba16(ref img) => (img.width(), img.height()),
+        avif_decode::Image::Gray8(ref img) => (img.width(), img.height()),
+        avif_decode::Image::Gray16(ref img) => (img.width(), img.height()),
+    };
+    limits.check(ImageFormat::Avif, width as u64, height as u64, 4)?;
+
+    let (width, height, bytes) = match image {
+        avif_decode::Image::Rgb8(img) => (
+            img.width(),
+            img.height(),
+            img.pixels()
+                .flat_map(|p| vec![p.r, p.g, p.b, 255])
+                .collect(),
+        ),
+        avif_decode::Image::Rgb16(img) => (
+            img.width(),
+            img.height(),
+            img.pixels()
+                .flat_map(|p| vec![(p.r >> 8) as u8, (p.g >> 8) as u8, (p.b >> 8) as u8, 255])
+                .collect(),
+        ),
+        avif_decode::Image::Rgba8(img) => (
+            img.width(),
+            img.height(),
+            img.pixels()
+                .flat_map(|p| vec![p.r, p.g, p.b, p.a])
+                .collect(),
+        ),
+        avif_decode::Image::Rgba16(img) => (
+            img.width(),
+            img.height(),
+            img.pixels()
+                .flat_map(|p| {
+                    vec![
+                        (p.r >> 8) as u8,
+                        (p.g >> 8) as u8,
+                        (p.b >> 8) as u8,
+                        (p.a >> 8) as u8,
+                    ]
+                })
+                .collect(),
+        ),
+        avif_decode::Image::Gray8(img) => (
+            img.width(),
+            img.height(),
+            img.pixels()
+                .flat_map(|p| vec![p.value(), p.value(), p.value(), 255])
+                .collect(),
+        ),
+        avif_decode::Image::Gray16(img) => (
+            img.width(),
+            img.height(),
+            img.pixels()
+                .flat_map(|p| {
+                    let gray = (p.value() >> 8) as u8;
+                    vec![gray, gray, gray, 255]
+                })
+                .collect(),
+        ),
+    };
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+/// Decode a BMP. Supports 1, 4, 8, 16, 24 and 32-bit BMPs including RLE compressed 4 and 8-bit
+/// ones.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.bmp").unwrap();
+/// let image = endec::decode_bmp(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_bmp(image_file: &File) -> RasterResult<Image> {
+    decode_bmp_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a BMP from a reader with the given size limits. Otherwise works the same as `decode_bmp`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.bmp").unwrap();
+/// let image = endec::decode_bmp_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_bmp_from_reader<R: Read + Seek>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let start = reader.stream_position()?;
+    let (width, height) = piston_image::bmp::BMPDecoder::new(&mut reader)
+        .dimensions()
+        .map_err(|e| RasterError::from_image_error(ImageFormat::Bmp, e))?;
+    limits.check(ImageFormat::Bmp, width as u64, height as u64, 4)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    let src = piston_image::load(BufReader::new(reader), piston_image::ImageFormat::BMP)
+        .map_err(|e| RasterError::from_image_error(ImageFormat::Bmp, e))?;
+    let src = src.to_rgba();
+    let (w, h) = src.dimensions();
+
+    Ok(Image {
+        width: w as i32,
+        height: h as i32,
+        bytes: src.into_raw(),
+    })
+}
+
+/// Encode a BMP. Opaque images are saved as 24-bit BMPs. Images with transparency are saved as
+/// 32-bit BMPs with an alpha channel.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// endec::encode_bmp(&image, Path::new("tests/out/test_encode_bmp.bmp")).unwrap();
+/// ```
+pub fn encode_bmp(image: &Image, path: &Path) -> RasterResult<()> {
+    encode_bmp_to_writer(image, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a BMP to a writer. Works the same as `encode_bmp`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_bmp_to_writer(&image, &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_bmp_to_writer<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    let width = image.width as u32;
+    let height = image.height as u32;
+    let has_alpha = image.bytes.chunks(4).any(|p| p[3] != 255);
+
+    // 24-bit rows are padded to a multiple of 4 bytes. 32-bit rows always are.
+    let (bits_per_pixel, header_size, compression): (u16, u32, u32) = if has_alpha {
+        (32, 108, 3) // BITMAPV4HEADER, BI_BITFIELDS
+    } else {
+        (24, 40, 0) // BITMAPINFOHEADER, BI_RGB
+    };
+    let row_size = (width * bits_per_pixel as u32).div_ceil(32) * 4;
+    let image_size = row_size * height;
+    let data_offset = 14 + header_size;
+
+    // File header
+    let mut header = Vec::with_capacity(data_offset as usize);
+    header.extend_from_slice(b"BM");
+    header.extend_from_slice(&(data_offset + image_size).to_le_bytes());
+    header.extend_from_slice(&[0; 4]); // reserved
+    header.extend_from_slice(&data_offset.to_le_bytes());
+
+    // DIB header
+    header.extend_from_slice(&header_size.to_le_bytes());
+    header.extend_from_slice(&(width as i32).to_le_bytes());
+    header.extend_from_slice(&(height as i32).to_le_bytes()); // positive height is bottom-up
+    header.extend_from_slice(&1u16.to_le_bytes()); // color planes
+    header.extend_from_slice(&bits_per_pixel.to_le_bytes());
+    header.extend_from_slice(&compression.to_le_bytes());
+    header.extend_from_slice(&image_size.to_le_bytes());
+    header.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI horizontal
+    header.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI vertical
+    header.extend_from_slice(&[0; 8]); // palette size and important colors
+    if has_alpha {
+        // Channel masks for BGRA byte order
+        for mask in &[0x00ff_0000u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+            header.extend_from_slice(&mask.to_le_bytes());
+        }
+        header.extend_from_slice(b"BGRs"); // sRGB color space
+        header.extend_from_slice(&[0; 48]); // endpoints and gamma, unused for sRGB
+    }
+    writer.write_all(&header)?;
+
+    let mut row = Vec::with_capacity(row_size as usize);
+    for y in (0..image.height).rev() {
+        row.clear();
+        let start = (y * image.width * 4) as usize;
+        for p in image.bytes[start..start + (width * 4) as usize].chunks(4) {
+            row.extend_from_slice(&[p[2], p[1], p[0]]);
+            if has_alpha {
+                row.push(p[3]);
+            }
+        }
+        row.resize(row_size as usize, 0);
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Decode a DDS texture. Supports BC1 (DXT1), BC2 (DXT3) and BC3 (DXT5) compressed and
+/// uncompressed 16, 24 and 32-bit RGB(A) textures, including ones with a DX10 header. Only the
+/// main image is decoded, mipmaps and other faces of cube maps are ignored.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/bc1.dds").unwrap();
+/// let image = endec::decode_dds(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_dds(image_file: &File) -> RasterResult<Image> {
+    decode_dds_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a DDS from a reader with the given size limits. Otherwise works the same as `decode_dds`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/bc1.dds").unwrap();
+/// let image = endec::decode_dds_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_dds_from_reader<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let fail = |message: &str| RasterError::Decode(ImageFormat::Dds, message.to_string());
+    if data.len() < 128 || &data[..4] != b"DDS " {
+        return Err(fail("Not a DDS file"));
+    }
+    let read_u32 =
+        |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+
+    let height = read_u32(12);
+    let width = read_u32(16);
+    if width == 0 || height == 0 || width > 65536 || height > 65536 {
+        return Err(fail("Invalid image dimensions"));
+    }
+    limits.check(ImageFormat::Dds, width as u64, height as u64, 4)?;
+    // Pixel format
+    let flags = read_u32(80);
+    let four_cc = &data[84..88];
+    let bit_count = read_u32(88);
+    let masks = [read_u32(92), read_u32(96), read_u32(100), read_u32(104)];
+
+    let (format, start) = if flags & 0x4 != 0 {
+        match four_cc {
+            b"DXT1" => (DdsFormat::Bc1, 128),
+            b"DXT2" | b"DXT3" => (DdsFormat::Bc2, 128),
+            b"DXT4" | b"DXT5" => (DdsFormat::Bc3, 128),
+            b"DX10" => {
+                if data.len() < 148 {
+                    return Err(fail("Unexpected end of file"));
+                }
+                match read_u32(128) {
+                    70..=72 => (DdsFormat::Bc1, 148),
+                    73..=75 => (DdsFormat::Bc2, 148),
+                    76..=78 => (DdsFormat::Bc3, 148),
+                    27..=29 => (
+                        DdsFormat::Masked(32, [0xff, 0xff << 8, 0xff << 16, 0xff << 24]),
+                        148,
+                    ),
+                    87 | 91 => (
+                        DdsFormat::Masked(32, [0xff << 16, 0xff << 8, 0xff, 0xff << 24]),
+                        148,
+                    ),
+                    dxgi => return Err(fail(&format!("Unsupported DXGI format {}", dxgi))),
+                }
+            }
+            _ => {
+                return Err(fail(&format!(
+                    "Unsupported compression {}",
+                    String::from_utf8_lossy(four_cc)
+                )))
+            }
+        }
+    } else if flags & 0x40 != 0 && (bit_count == 16 || bit_count == 24 || bit_count == 32) {
+        // Alpha mask is only valid with the alpha pixels flag
+        let alpha = if flags & 0x1 != 0 { masks[3] } else { 0 };
+        (
+            DdsFormat::Masked(bit_count, [masks[0], masks[1], masks[2], alpha]),
+            128,
+        )
+    } else {
+        return Err(fail("Unsupported pixel format"));
+    };
+
+    // Check that the payload holds every pixel the header claims before allocating for them
+    let (width, height) = (width as usize, height as usize);
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let payload = match format {
+        DdsFormat::Masked(bit_count, _) => width * height * (bit_count as usize / 8),
+        DdsFormat::Bc1 => blocks_x * blocks_y * 8,
+        _ => blocks_x * blocks_y * 16,
+    };
+    if data.len() < start + payload {
+        return Err(fail("Unexpected end of file"));
+    }
+
+    let mut bytes = vec![0; width * height * 4];
+    match format {
+        DdsFormat::Masked(bit_count, masks) => {
+            let pixel_size = bit_count as usize / 8;
+            let pixels = data[start..start + payload].chunks(pixel_size);
+            for (pixel, dest) in pixels.zip(bytes.chunks_mut(4)) {
+                let value = pixel
+                    .iter()
+                    .rev()
+                    .fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+                for (channel, &mask) in dest.iter_mut().zip(&masks) {
+                    *channel = if mask == 0 {
+                        255
+                    } else {
+                        // In 64 bits, as masks can be up to 32 bits wide
+                        let max = (mask >> mask.trailing_zeros()) as u64;
+                        let bits = ((value & mask) >> mask.trailing_zeros()) as u64;
+                        (bits * 255 / max) as u8
+                    };
+                }
+            }
+        }
+        _ => {
+            let block_size = if format == DdsFormat::Bc1 { 8 } else { 16 };
+            let blocks = data[start..].chunks(block_size).take(blocks_x * blocks_y);
+            for (i, block) in blocks.enumerate() {
+                let pixels = dds_block(format, block);
+                let (block_x, block_y) = (i % blocks_x * 4, i / blocks_x * 4);
+                // Blocks on the right and bottom edges can stick out of the image.
+                for y in 0..cmp::min(4, height - block_y) {
+                    for x in 0..cmp::min(4, width - block_x) {
+                        let dest = ((block_y + y) * width + block_x + x) * 4;
+                        bytes[dest..dest + 4].copy_from_slice(&pixels[y * 4 + x]);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+/// Decode the first RGB or RGBA layer of an OpenEXR into linear light floats. Requires the `exr`
+/// feature. Half, float and integer channels are all converted to `f32`, missing alpha becomes
+/// 1.0. Use `HdrImage::tone_map` to tone map it into a regular image.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::{endec, ToneMap};
+///
+/// let file = File::open("tests/in/sample.exr").unwrap();
+/// let hdr = endec::decode_exr(&file).unwrap();
+/// let image = hdr.tone_map(ToneMap::Aces);
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+#[cfg(feature = "exr")]
+pub fn decode_exr(image_file: &File) -> RasterResult<HdrImage> {
+    decode_exr_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode an OpenEXR from a reader with the given size limits. Otherwise works the same as
+/// `decode_exr`. Requires the `exr` feature.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.exr").unwrap();
+/// let hdr = endec::decode_exr_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((hdr.width * hdr.height * 4) as usize, hdr.samples.len());
+/// ```
+#[cfg(feature = "exr")]
+pub fn decode_exr_from_reader<R: Read + Seek>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<HdrImage> {
+    use exr::image::read::image::ReadLayers;
+    use exr::image::read::layers::ReadChannels;
+
+    let start = reader.stream_position()?;
+    let meta = exr::meta::MetaData::read_from_buffered(BufReader::new(&mut reader), false)?;
+    for header in meta.headers.iter() {
+        let (width, height) = (header.layer_size.width(), header.layer_size.height());
+        limits.check(ImageFormat::Exr, width as u64, height as u64, 16)?;
+    }
+    reader.seek(SeekFrom::Start(start))?;
+
+    let image = exr::image::read::read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .rgba_channels(
+            |resolution, _| HdrImage::blank(resolution.width() as i32, resolution.height() as i32),
+            |hdr: &mut HdrImage, position, (r, g, b, a): (f32, f32, f32, f32)| {
+                let start = (position.y() * hdr.width as usize + position.x()) * 4;
+                hdr.samples[start..start + 4].copy_from_slice(&[r, g, b, a]);
+            },
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(BufReader::new(reader))?;
+
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+/// Decode a farbfeld. Its 16-bit channels are reduced to 8 bits.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.ff").unwrap();
+/// let image = endec::decode_farbfeld(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_farbfeld(image_file: &File) -> RasterResult<Image> {
+    decode_farbfeld_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a farbfeld from a reader with the given size limits. Otherwise works the same as
+/// `decode_farbfeld`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.ff").unwrap();
+/// let image = endec::decode_farbfeld_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_farbfeld_from_reader<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let fail = |message: &str| RasterError::Decode(ImageFormat::Farbfeld, message.to_string());
+    if data.len() < 16 || &data[..8] != b"farbfeld" {
+        return Err(fail("Not a farbfeld file"));
+    }
+    let width = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+    let height = u32::from_be_bytes([data[12], data[13], data[14], data[15]]);
+    if width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(fail("Invalid image dimensions"));
+    }
+    limits.check(ImageFormat::Farbfeld, width as u64, height as u64, 4)?;
+    let length = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(8))
+        .ok_or_else(|| fail("Invalid image dimensions"))?;
+    if data.len() < 16 + length {
+        return Err(fail("Unexpected end of file"));
+    }
+
+    // Big endian 16-bit RGBA. Keep the most significant byte.
+    let bytes = data[16..16 + length].iter().step_by(2).cloned().collect();
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+/// Encode a farbfeld.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// endec::encode_farbfeld(&image, Path::new("tests/out/test_encode_farbfeld.ff")).unwrap();
+/// ```
+pub fn encode_farbfeld(image: &Image, path: &Path) -> RasterResult<()> {
+    encode_farbfeld_to_writer(image, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a farbfeld to a writer. Works the same as `encode_farbfeld`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_farbfeld_to_writer(&image, &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_farbfeld_to_writer<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    let mut data = Vec::with_capacity(16 + image.bytes.len() * 2);
+    data.extend_from_slice(b"farbfeld");
+    data.extend_from_slice(&(image.width as u32).to_be_bytes());
+    data.extend_from_slice(&(image.height as u32).to_be_bytes());
+    for &byte in &image.bytes {
+        // Scale 8 bits to 16 so that 255 becomes 65535
+        data.extend_from_slice(&[byte, byte]);
+    }
+    writer.write_all(&data)?;
+    Ok(())
+}
 
 // Decode GIF
 pub fn decode_gif(image_file: &File) -> RasterResult<Image> {
-    let mut decoder = gif::Decoder::new(image_file);
+    decode_gif_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a GIF from a reader with the given size limits. Otherwise works the same as `decode_gif`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.gif").unwrap();
+/// let image = endec::decode_gif_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_gif_from_reader<R: Read>(reader: R, limits: &DecodeLimits) -> RasterResult<Image> {
+    let mut decoder = gif::Decoder::new(reader);
+
+    // Configure the decoder such that it will expand the image to RGBA.
+    gif::SetParameter::set(&mut decoder, gif::ColorOutput::RGBA);
+
+    // Read the file header
+    let mut reader = decoder.read_info()?;
+
+    // Read frame 1. Use decode_gif_animation to get all frames.
+    if let Some(info) = reader.next_frame_info()? {
+        limits.check(ImageFormat::Gif, info.width as u64, info.height as u64, 4)?;
+        let mut bytes = vec![0; reader.buffer_size()];
+        reader.read_into_buffer(&mut bytes)?;
+        Ok(Image {
+            width: reader.width() as i32,
+            height: reader.height() as i32,
+            bytes,
+        })
+    } else {
+        Err(RasterError::Decode(
+            ImageFormat::Gif,
+            "Error getting frame info".to_string(),
+        ))
+    }
+}
+
+/// Decode all frames of a GIF.
+///
+/// Each frame is composited onto the canvas the way a viewer would show it, taking the previous
+/// frames and their disposal methods into account. Areas not yet drawn on are transparent.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/animated.gif").unwrap();
+/// let animation = endec::decode_gif_animation(&file).unwrap();
+///
+/// for (i, frame) in animation.frames.iter().enumerate() {
+///     println!("frame {} shows for {}0 ms", i, frame.delay);
+///     assert_eq!(animation.width, frame.image.width);
+/// }
+///
+/// // Re-encode with the same timing
+/// let images: Vec<_> = animation.frames.iter().map(|f| f.image.clone()).collect();
+/// let delays: Vec<_> = animation.frames.iter().map(|f| f.delay).collect();
+/// endec::GifEncoder::new()
+///     .repeat(animation.repeat)
+///     .encode(&images, &delays, std::path::Path::new("tests/out/test_gif_timing.gif"))
+///     .unwrap();
+/// ```
+pub fn decode_gif_animation(image_file: &File) -> RasterResult<Animation> {
+    decode_gif_animation_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode all frames of a GIF from a reader with the given size limits. Otherwise works the same as
+/// `decode_gif_animation`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/animated.gif").unwrap();
+/// let animation = endec::decode_gif_animation_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert!(animation.frames.len() > 1);
+/// ```
+pub fn decode_gif_animation_from_reader<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Animation> {
+    // The decoder skips the loop count, so it is read from the data
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let repeat = gif_loop_count(&data);
+
+    let mut decoder = gif::Decoder::new(&data[..]);
+    gif::SetParameter::set(&mut decoder, gif::ColorOutput::RGBA);
+    let mut reader = decoder.read_info()?;
+
+    let width = reader.width() as i32;
+    let height = reader.height() as i32;
+    limits.check(ImageFormat::Gif, width as u64, height as u64, 4)?;
+    let mut canvas = Image {
+        width,
+        height,
+        bytes: vec![0; (width * height) as usize * 4],
+    };
+    let mut frames = Vec::new();
+
+    while let Some(info) = reader.next_frame_info()? {
+        let (left, top, frame_w, frame_h, delay, disposal) = (
+            info.left as i32,
+            info.top as i32,
+            info.width as i32,
+            info.height as i32,
+            info.delay,
+            DisposalMethod::from(info.dispose),
+        );
+        // Every frame is a copy of the canvas
+        let canvas_size = canvas.bytes.len() as u64;
+        limits.check(ImageFormat::Gif, frame_w as u64, frame_h as u64, 4)?;
+        limits.check_bytes(ImageFormat::Gif, canvas_size * (frames.len() as u64 + 2))?;
+        let mut bytes = vec![0; reader.buffer_size()];
+        reader.read_into_buffer(&mut bytes)?;
+
+        let previous = if disposal == DisposalMethod::Previous {
+            Some(canvas.bytes.clone())
+        } else {
+            None
+        };
+
+        // Draw the frame on the canvas. Transparent pixels let the canvas show through.
+        for y in 0..frame_h {
+            for x in 0..frame_w {
+                let (canvas_x, canvas_y) = (left + x, top + y);
+                if canvas_x >= width || canvas_y >= height {
+                    continue;
+                }
+                let src = ((y * frame_w + x) * 4) as usize;
+                if bytes[src + 3] == 0 {
+                    continue;
+                }
+                let dest = ((canvas_y * width + canvas_x) * 4) as usize;
+                canvas.bytes[dest..dest + 4].copy_from_slice(&bytes[src..src + 4]);
+            }
+        }
+
+        frames.push(Frame {
+            image: canvas.clone(),
+            delay,
+            disposal,
+            blend: FrameBlend::Source,
+        });
+
+        // Prepare the canvas for the next frame.
+        match disposal {
+            DisposalMethod::Background => {
+                for y in top..cmp::min(top + frame_h, height) {
+                    for x in left..cmp::min(left + frame_w, width) {
+                        let dest = ((y * width + x) * 4) as usize;
+                        canvas.bytes[dest..dest + 4].copy_from_slice(&[0, 0, 0, 0]);
+                    }
+                }
+            }
+            DisposalMethod::Previous => {
+                if let Some(bytes) = previous {
+                    canvas.bytes = bytes;
+                }
+            }
+            DisposalMethod::Any | DisposalMethod::Keep => {}
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(RasterError::Decode(
+            ImageFormat::Gif,
+            "Error getting frame info".to_string(),
+        ));
+    }
+
+    Ok(Animation {
+        width,
+        height,
+        frames,
+        repeat,
+    })
+}
+
+// Encode GIF
+pub fn encode_gif(image: &Image, path: &Path) -> RasterResult<()> {
+    encode_gif_to_writer(image, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a GIF to a writer. Works the same as `encode_gif`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_gif_to_writer(&image, &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_gif_to_writer<W: Write>(image: &Image, writer: W) -> RasterResult<()> {
+    let frame = gif::Frame::from_rgba(
+        image.width as u16,
+        image.height as u16,
+        &mut image.bytes.clone(),
+    ); // TODO: Perf issue?
+    let mut encoder = gif::Encoder::new(writer, frame.width, frame.height, &[])?;
+    encoder.write_frame(&frame).map_err(RasterError::Io)?;
+    Ok(())
+}
+
+/// Encode a sequence of images as an animated GIF that loops forever.
+///
+/// The delay of each frame is given in units of 10 ms. Use `GifEncoder` for control over the
+/// loop count and palette.
+///
+/// # Errors
+///
+/// This fails with `RasterError::Encode` if there are no frames, if the number of delays does not
+/// match the number of frames, or if the frames are not all the same size. It can also return
+/// `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::{editor, endec, Color, Image};
+///
+/// let mut red = Image::blank(16, 16);
+/// editor::fill(&mut red, Color::red()).unwrap();
+/// let mut blue = Image::blank(16, 16);
+/// editor::fill(&mut blue, Color::blue()).unwrap();
+///
+/// // Alternate between red and blue every half a second.
+/// let path = Path::new("tests/out/test_encode_gif_animation.gif");
+/// endec::encode_gif_animation(&[red, blue], &[50, 50], path).unwrap();
+/// ```
+pub fn encode_gif_animation(frames: &[Image], delays: &[u16], path: &Path) -> RasterResult<()> {
+    GifEncoder::new().encode(frames, delays, path)
+}
+
+/// Encode a sequence of images as an animated GIF to a writer. Works the same as
+/// `encode_gif_animation`.
+///
+/// # Errors
+///
+/// See `encode_gif_animation`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{endec, Image};
+///
+/// let frames = [Image::blank(4, 4), Image::blank(4, 4)];
+/// let mut data = Vec::new();
+/// endec::encode_gif_animation_to_writer(&frames, &[10, 10], &mut data).unwrap();
+///
+/// assert!(data.starts_with(b"GIF89a"));
+/// ```
+pub fn encode_gif_animation_to_writer<W: Write>(
+    frames: &[Image],
+    delays: &[u16],
+    writer: W,
+) -> RasterResult<()> {
+    GifEncoder::new().encode_to_writer(frames, delays, writer)
+}
+
+/// A builder for encoding animated GIFs.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::{editor, endec, Color, Image, LoopCount};
+///
+/// let mut black = Image::blank(16, 16);
+/// let mut white = Image::blank(16, 16);
+/// editor::fill(&mut white, Color::white()).unwrap();
+///
+/// // Blink 3 times using a fixed 2 color palette.
+/// endec::GifEncoder::new()
+///     .repeat(LoopCount::Finite(2))
+///     .palette(vec![Color::black(), Color::white()])
+///     .encode(&[black, white], &[20, 20], Path::new("tests/out/test_gif_encoder.gif"))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct GifEncoder {
+    repeat: LoopCount,
+    palette: Option<Vec<Color>>,
+    speed: i32,
+}
+
+impl GifEncoder {
+    /// Create an encoder that loops forever and computes a palette for each frame.
+    pub fn new() -> GifEncoder {
+        GifEncoder {
+            repeat: LoopCount::Infinite,
+            palette: None,
+            speed: 10,
+        }
+    }
+
+    /// Set how many times the animation plays.
+    pub fn repeat(mut self, repeat: LoopCount) -> GifEncoder {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Use a fixed global palette of up to 256 colors instead of computing one per frame. Each
+    /// pixel is mapped to the closest palette color.
+    pub fn palette(mut self, palette: Vec<Color>) -> GifEncoder {
+        self.palette = Some(palette);
+        self
+    }
+
+    /// Set the speed of the per frame color quantization. Speed can be a value from 1 - 30. A
+    /// higher value runs faster at the cost of quality. Defaults to 10.
+    pub fn speed(mut self, speed: i32) -> GifEncoder {
+        self.speed = speed.clamp(1, 30);
+        self
+    }
+
+    /// Encode the frames to a file. The delay of each frame is given in units of 10 ms.
+    ///
+    /// # Errors
+    ///
+    /// See `encode_gif_animation`. This also fails with `RasterError::Encode` if the palette has
+    /// no colors or more than 256, or if the frames are wider or taller than 65535 pixels.
+    pub fn encode(&self, frames: &[Image], delays: &[u16], path: &Path) -> RasterResult<()> {
+        self.encode_to_writer(frames, delays, BufWriter::new(File::create(path)?))
+    }
+
+    /// Encode the frames to a writer. Works the same as `encode`.
+    ///
+    /// # Errors
+    ///
+    /// See `encode`.
+    pub fn encode_to_writer<W: Write>(
+        &self,
+        frames: &[Image],
+        delays: &[u16],
+        writer: W,
+    ) -> RasterResult<()> {
+        let fail = |msg: &str| Err(RasterError::Encode(ImageFormat::Gif, msg.to_string()));
+
+        let (width, height) = match frames.first() {
+            Some(first) => (first.width, first.height),
+            None => return fail("No frames to encode"),
+        };
+        if frames.len() != delays.len() {
+            return fail("Number of delays does not match number of frames");
+        }
+        if frames
+            .iter()
+            .any(|f| f.width != width || f.height != height)
+        {
+            return fail("Frames are not all the same size");
+        }
+        if width > 65535 || height > 65535 {
+            return fail("Frames are too big for a GIF");
+        }
+
+        // Reserve one palette entry after the given colors for transparent pixels.
+        let (global_palette, transparent) = match self.palette {
+            Some(ref palette) if palette.is_empty() => return fail("Palette has no colors"),
+            Some(ref palette) if palette.len() > 256 => {
+                return fail("Palette has more than 256 colors")
+            }
+            Some(ref palette) => {
+                let mut bytes = Vec::with_capacity((palette.len() + 1) * 3);
+                for color in palette {
+                    bytes.extend_from_slice(&[color.r, color.g, color.b]);
+                }
+                let transparent = if palette.len() < 256 {
+                    bytes.extend_from_slice(&[0, 0, 0]);
+                    Some(palette.len() as u8)
+                } else {
+                    None
+                };
+                (bytes, transparent)
+            }
+            None => (Vec::new(), None),
+        };
+
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &global_palette)?;
+        let repeat = match self.repeat {
+            LoopCount::Infinite => gif::Repeat::Infinite,
+            LoopCount::Finite(count) => gif::Repeat::Finite(count),
+        };
+        gif::SetParameter::set(&mut encoder, repeat)?;
+
+        for (image, delay) in frames.iter().zip(delays) {
+            let mut frame = match self.palette {
+                Some(ref palette) => {
+                    let indices: Vec<u8> = image
+                        .bytes
+                        .chunks(4)
+                        .map(|p| match transparent {
+                            Some(index) if p[3] == 0 => index,
+                            _ => closest_color(palette, p),
+                        })
+                        .collect();
+                    gif::Frame::from_indexed_pixels(
+                        width as u16,
+                        height as u16,
+                        &indices,
+                        transparent,
+                    )
+                }
+                None => gif::Frame::from_rgba_speed(
+                    width as u16,
+                    height as u16,
+                    &mut image.bytes.clone(),
+                    self.speed,
+                ),
+            };
+            frame.delay = *delay;
+            frame.dispose = gif::DisposalMethod::Background;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for GifEncoder {
+    fn default() -> GifEncoder {
+        GifEncoder::new()
+    }
+}
+
+/// Decode a Radiance HDR (.hdr) into linear light floats. Use `HdrImage::tone_map` to tone map
+/// it into a regular image.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::{endec, ToneMap};
+///
+/// let file = File::open("tests/in/sample.hdr").unwrap();
+/// let hdr = endec::decode_hdr(&file).unwrap();
+/// let image = hdr.tone_map(ToneMap::Aces);
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_hdr(image_file: &File) -> RasterResult<HdrImage> {
+    decode_hdr_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a Radiance HDR from a reader with the given size limits. Otherwise works the same as
+/// `decode_hdr`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.hdr").unwrap();
+/// let hdr = endec::decode_hdr_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((hdr.width * hdr.height * 4) as usize, hdr.samples.len());
+/// ```
+pub fn decode_hdr_from_reader<R: Read>(reader: R, limits: &DecodeLimits) -> RasterResult<HdrImage> {
+    let decoder = piston_image::hdr::HDRDecoder::new(BufReader::new(reader))
+        .map_err(|e| RasterError::from_image_error(ImageFormat::Hdr, e))?;
+    let metadata = decoder.metadata();
+    let (width, height) = (metadata.width as u64, metadata.height as u64);
+    limits.check(ImageFormat::Hdr, width, height, 16)?;
+    let rgb = decoder
+        .read_image_hdr()
+        .map_err(|e| RasterError::from_image_error(ImageFormat::Hdr, e))?;
+
+    let mut pixels = Vec::with_capacity(rgb.len() * 4);
+    for p in rgb {
+        pixels.extend_from_slice(&[p.data[0], p.data[1], p.data[2], 1.0]);
+    }
+
+    HdrImage::new(metadata.width as i32, metadata.height as i32, pixels)
+}
+
+/// Decode the primary image of a HEIF or HEIC. Requires the `heif` feature.
+///
+/// Returns the image together with its EXIF orientation, if there is one. The rotation and
+/// mirroring stored in the HEIF container are already applied to the image, so the EXIF
+/// orientation is only informative and should not be applied again.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.heif").unwrap();
+/// let (image, orientation) = endec::decode_heif(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// assert_eq!(Some(6), orientation);
+/// ```
+#[cfg(feature = "heif")]
+pub fn decode_heif(image_file: &File) -> RasterResult<(Image, Option<u16>)> {
+    decode_heif_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a HEIF from a reader with the given size limits. Otherwise works the same as
+/// `decode_heif`. Requires the `heif` feature.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.heif").unwrap();
+/// let (image, _) = endec::decode_heif_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+#[cfg(feature = "heif")]
+pub fn decode_heif_from_reader<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<(Image, Option<u16>)> {
+    use libheif_rs::{ColorSpace, HeifContext, ItemId, LibHeif, RgbChroma};
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let context = HeifContext::read_from_bytes(&data)?;
+    let handle = context.primary_image_handle()?;
+    let (width, height) = (handle.width() as u64, handle.height() as u64);
+    limits.check(ImageFormat::Heif, width, height, 4)?;
+    let decoded = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)?;
+    let plane = decoded
+        .planes()
+        .interleaved
+        .ok_or_else(|| RasterError::Decode(ImageFormat::Heif, "Missing RGBA plane".to_string()))?;
+
+    let (width, height) = (plane.width as usize, plane.height as usize);
+    let mut bytes = Vec::with_capacity(width * height * 4);
+    for row in plane.data.chunks(plane.stride).take(height) {
+        bytes.extend_from_slice(&row[..width * 4]);
+    }
+
+    // The EXIF block starts with the offset to its TIFF header
+    let mut exif_ids: [ItemId; 1] = [0];
+    let orientation = if handle.metadata_block_ids(&mut exif_ids, b"Exif") > 0 {
+        handle.metadata(exif_ids[0]).ok().and_then(|exif| {
+            let offset = exif.get(0..4)?;
+            let offset = u32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]);
+            metadata::exif(exif.get(4 + offset as usize..)?)?.orientation
+        })
+    } else {
+        None
+    };
+
+    Ok((
+        Image {
+            width: width as i32,
+            height: height as i32,
+            bytes,
+        },
+        orientation,
+    ))
+}
+
+/// Decode an ICO or CUR. Icons usually hold the same picture in several sizes. Pass `None` as
+/// `size` to get the largest one, or a width in pixels to get the smallest one that is at least
+/// that wide. Falls back to the largest one if none is wide enough.
+///
+/// Both PNG compressed and BMP style entries with 1, 4, 8, 24 or 32 bits per pixel are
+/// supported.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.ico").unwrap();
+/// let largest = endec::decode_ico(&file, None).unwrap();
+///
+/// let file = File::open("tests/in/sample.ico").unwrap();
+/// let small = endec::decode_ico(&file, Some(16)).unwrap();
+///
+/// assert!(largest.width > small.width);
+/// assert_eq!(16, small.width);
+/// ```
+pub fn decode_ico(image_file: &File, size: Option<i32>) -> RasterResult<Image> {
+    decode_ico_from_reader(image_file, size, &DecodeLimits::default())
+}
+
+/// Decode an ICO from a reader with the given size limits. Otherwise works the same as
+/// `decode_ico`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.ico").unwrap();
+/// let image = endec::decode_ico_from_reader(Cursor::new(data), None, &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_ico_from_reader<R: Read>(
+    mut reader: R,
+    size: Option<i32>,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let fail = |message: &str| RasterError::Decode(ImageFormat::Ico, message.to_string());
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let read_u32 =
+        |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+
+    // Icon directory: reserved, type (1 for icons, 2 for cursors), number of entries
+    if data.len() < 6 || read_u16(0) != 0 || (read_u16(2) != 1 && read_u16(2) != 2) {
+        return Err(fail("Not an ICO file"));
+    }
+    let count = read_u16(4) as usize;
+    if count == 0 || data.len() < 6 + count * 16 {
+        return Err(fail("Invalid icon directory"));
+    }
+
+    // Entries are 16 bytes each. A width or height of 0 means 256.
+    let entries: Vec<(i32, i32, u16, usize, usize)> = (0..count)
+        .map(|i| {
+            let at = 6 + i * 16;
+            let width = if data[at] == 0 { 256 } else { data[at] as i32 };
+            let height = if data[at + 1] == 0 {
+                256
+            } else {
+                data[at + 1] as i32
+            };
+            let bits = read_u16(at + 6);
+            let length = read_u32(at + 8) as usize;
+            let offset = read_u32(at + 12) as usize;
+            (width, height, bits, length, offset)
+        })
+        .collect();
+
+    // Largest first. Prefer more colors for entries of the same size.
+    let largest = entries
+        .iter()
+        .max_by_key(|e| (e.0 * e.1, e.2))
+        .cloned()
+        .unwrap();
+    let entry = match size {
+        Some(size) => entries
+            .iter()
+            .filter(|e| e.0 >= size)
+            .min_by_key(|e| (e.0 * e.1, -(e.2 as i32)))
+            .cloned()
+            .unwrap_or(largest),
+        None => largest,
+    };
+
+    let (_, _, _, length, offset) = entry;
+    if offset.saturating_add(length) > data.len() {
+        return Err(fail("Icon entry is out of bounds"));
+    }
+    let entry_data = &data[offset..offset + length];
+
+    if entry_data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_image(png::Decoder::new(entry_data), limits, false).map_err(|err| match err {
+            RasterError::Decode(_, message) => RasterError::Decode(ImageFormat::Ico, message),
+            RasterError::LimitExceeded(_) => RasterError::LimitExceeded(ImageFormat::Ico),
+            err => err,
+        })
+    } else {
+        ico_bmp(entry_data)
+    }
+}
+
+/// Encode an ICO holding several sizes of the same picture, like 16x16, 32x32, 48x48 and
+/// 256x256 for a favicon. Images can be at most 256 pixels wide and tall. 256 pixel images are
+/// stored as PNG, smaller ones as 32-bit BMPs for compatibility with older readers.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::{editor, endec, ResizeMode};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// let sizes: Vec<_> = [16, 32, 48, 256]
+///     .iter()
+///     .map(|&size| {
+///         let mut icon = image.clone();
+///         editor::resize(&mut icon, size, size, ResizeMode::Exact).unwrap();
+///         icon
+///     })
+///     .collect();
+/// endec::encode_ico(&sizes, Path::new("tests/out/test_encode_ico.ico")).unwrap();
+/// ```
+pub fn encode_ico(images: &[Image], path: &Path) -> RasterResult<()> {
+    encode_ico_to_writer(images, BufWriter::new(File::create(path)?))
+}
+
+/// Encode an ICO to a writer. Works the same as `encode_ico`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_ico_to_writer(std::slice::from_ref(&image), &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_ico_to_writer<W: Write>(images: &[Image], mut writer: W) -> RasterResult<()> {
+    let fail = |message: &str| Err(RasterError::Encode(ImageFormat::Ico, message.to_string()));
+    if images.is_empty() {
+        return fail("No images to encode");
+    }
+    if images.len() > 65535 {
+        return fail("Too many images for an ICO");
+    }
+    if images
+        .iter()
+        .any(|i| i.width < 1 || i.height < 1 || i.width > 256 || i.height > 256)
+    {
+        return fail("Icons must be between 1 and 256 pixels wide and tall");
+    }
+
+    let mut entries = Vec::with_capacity(images.len());
+    for image in images {
+        let mut data = Vec::new();
+        if image.width == 256 || image.height == 256 {
+            let mut encoder = png::Encoder::new(&mut data, image.width as u32, image.height as u32);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&image.bytes))
+                .map_err(|err| RasterError::Encode(ImageFormat::Ico, err.to_string()))?;
+        } else {
+            // BMP without the file header. The height includes the transparency mask.
+            let mask_row_size = (image.width as usize).div_ceil(32) * 4;
+            let image_size = image.width * image.height * 4 + (mask_row_size as i32) * image.height;
+            data.extend_from_slice(&40u32.to_le_bytes());
+            data.extend_from_slice(&image.width.to_le_bytes());
+            data.extend_from_slice(&(image.height * 2).to_le_bytes());
+            data.extend_from_slice(&1u16.to_le_bytes()); // color planes
+            data.extend_from_slice(&32u16.to_le_bytes()); // bits per pixel
+            data.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+            data.extend_from_slice(&image_size.to_le_bytes());
+            data.extend_from_slice(&[0; 16]); // resolution and palette
+
+            let rows: Vec<&[u8]> = image.bytes.chunks((image.width * 4) as usize).collect();
+            for row in rows.iter().rev() {
+                for p in row.chunks(4) {
+                    data.extend_from_slice(&[p[2], p[1], p[0], p[3]]);
+                }
+            }
+            // Fully transparent pixels are masked out for readers that ignore the alpha channel
+            for row in rows.iter().rev() {
+                let mut mask = vec![0u8; mask_row_size];
+                for (x, p) in row.chunks(4).enumerate() {
+                    if p[3] == 0 {
+                        mask[x / 8] |= 0x80 >> (x % 8);
+                    }
+                }
+                data.extend_from_slice(&mask);
+            }
+        }
+        entries.push((image, data));
+    }
+
+    // Icon directory followed by one 16 byte entry per image
+    let mut header = vec![0, 0, 1, 0];
+    header.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    let mut offset = 6 + 16 * entries.len() as u32;
+    for (image, data) in &entries {
+        // A width or height of 256 is stored as 0
+        header.push(image.width as u8);
+        header.push(image.height as u8);
+        header.extend_from_slice(&[0, 0]); // palette size and reserved
+        header.extend_from_slice(&1u16.to_le_bytes()); // color planes
+        header.extend_from_slice(&32u16.to_le_bytes()); // bits per pixel
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&offset.to_le_bytes());
+        offset += data.len() as u32;
+    }
+
+    writer.write_all(&header)?;
+    for (_, data) in &entries {
+        writer.write_all(data)?;
+    }
+    Ok(())
+}
+
+/// Decode all frames of an animated PNG (APNG). PNGs that aren't animated are returned as a single
+/// frame.
+///
+/// Each frame is composited onto the canvas the way a viewer would show it, taking the previous
+/// frames and their disposal and blending into account. Delays are rounded to units of 10 ms,
+/// the same as GIF. A default image that isn't part of the animation is skipped.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/animated.png").unwrap();
+/// let animation = endec::decode_apng(&file).unwrap();
+///
+/// for (i, frame) in animation.frames.iter().enumerate() {
+///     println!("frame {} shows for {}0 ms", i, frame.delay);
+///     assert_eq!(animation.width, frame.image.width);
+/// }
+///
+/// // Re-encode with the same timing
+/// endec::ApngEncoder::new()
+///     .repeat(animation.repeat)
+///     .encode(&animation.frames, std::path::Path::new("tests/out/test_apng_timing.png"))
+///     .unwrap();
+/// ```
+pub fn decode_apng(image_file: &File) -> RasterResult<Animation> {
+    decode_apng_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode all frames of an APNG from a reader with the given size limits. Otherwise works the same
+/// as `decode_apng`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/animated.png").unwrap();
+/// let animation = endec::decode_apng_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert!(animation.frames.len() > 1);
+/// ```
+pub fn decode_apng_from_reader<R: Read>(
+    reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Animation> {
+    let mut decoder = png::Decoder::new_with_limits(
+        reader,
+        png::Limits {
+            bytes: limits.max_bytes,
+        },
+    );
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+
+    let (canvas_w, canvas_h) = reader.info().size();
+    limits.check(ImageFormat::Png, canvas_w as u64, canvas_h as u64, 4)?;
+    let (width, height) = (canvas_w as i32, canvas_h as i32);
+    let mut canvas = Image {
+        width,
+        height,
+        bytes: vec![0; (width * height) as usize * 4],
+    };
+    let mut bytes = vec![0; reader.output_buffer_size()];
+
+    // Number of plays, where 0 means forever
+    let repeat = match reader.info().animation_control {
+        Some(control) if control.num_plays > 0 => {
+            LoopCount::Finite(cmp::min(control.num_plays - 1, u16::MAX as u32) as u16)
+        }
+        Some(_) => LoopCount::Infinite,
+        None => LoopCount::Finite(0),
+    };
+    let frame_count = match reader.info().animation_control {
+        Some(control) => {
+            // Without a frame control before the image data, the default image isn't a frame.
+            if reader.info().frame_control.is_none() {
+                reader.next_frame(&mut bytes)?;
+            }
+            control.num_frames
+        }
+        None => 1,
+    };
+
+    let mut frames = Vec::new();
+    for i in 0..frame_count {
+        // Every frame is a copy of the canvas
+        let canvas_size = canvas.bytes.len() as u64;
+        limits.check_bytes(ImageFormat::Png, canvas_size * (frames.len() as u64 + 2))?;
+        let output = reader.next_frame(&mut bytes)?;
+        let control = reader.info().frame_control.unwrap_or(png::FrameControl {
+            width: canvas_w,
+            height: canvas_h,
+            delay_num: 0,
+            ..Default::default()
+        });
+        let rgba = png_rgba(output.color_type, &bytes[..output.buffer_size()]);
+
+        let (left, top) = (control.x_offset as i32, control.y_offset as i32);
+        let (frame_w, frame_h) = (output.width as i32, output.height as i32);
+        let delay = match control.delay_den {
+            0 => control.delay_num,
+            den => ((control.delay_num as u32 * 100 + den as u32 / 2) / den as u32) as u16,
+        };
+        let disposal = match control.dispose_op {
+            png::DisposeOp::None => DisposalMethod::Keep,
+            // Restoring the first frame means clearing it
+            png::DisposeOp::Previous if i == 0 => DisposalMethod::Background,
+            png::DisposeOp::Previous => DisposalMethod::Previous,
+            png::DisposeOp::Background => DisposalMethod::Background,
+        };
+
+        let previous = if disposal == DisposalMethod::Previous {
+            Some(canvas.bytes.clone())
+        } else {
+            None
+        };
+
+        // Draw the frame on the canvas, either replacing or alpha blending over what's there.
+        for y in 0..frame_h {
+            for x in 0..frame_w {
+                let (canvas_x, canvas_y) = (left + x, top + y);
+                if canvas_x >= width || canvas_y >= height {
+                    continue;
+                }
+                let src = ((y * frame_w + x) * 4) as usize;
+                let dest = ((canvas_y * width + canvas_x) * 4) as usize;
+                match control.blend_op {
+                    png::BlendOp::Source => {
+                        canvas.bytes[dest..dest + 4].copy_from_slice(&rgba[src..src + 4]);
+                    }
+                    png::BlendOp::Over => {
+                        let src_a = rgba[src + 3] as u32;
+                        let dest_a = canvas.bytes[dest + 3] as u32 * (255 - src_a) / 255;
+                        let out_a = src_a + dest_a;
+                        if out_a == 0 {
+                            continue;
+                        }
+                        for c in 0..3 {
+                            canvas.bytes[dest + c] = ((rgba[src + c] as u32 * src_a
+                                + canvas.bytes[dest + c] as u32 * dest_a)
+                                / out_a) as u8;
+                        }
+                        canvas.bytes[dest + 3] = out_a as u8;
+                    }
+                }
+            }
+        }
+
+        frames.push(Frame {
+            image: canvas.clone(),
+            delay,
+            disposal,
+            blend: FrameBlend::Source,
+        });
+
+        // Prepare the canvas for the next frame.
+        match disposal {
+            DisposalMethod::Background => {
+                for y in top..cmp::min(top + frame_h, height) {
+                    for x in left..cmp::min(left + frame_w, width) {
+                        let dest = ((y * width + x) * 4) as usize;
+                        canvas.bytes[dest..dest + 4].copy_from_slice(&[0, 0, 0, 0]);
+                    }
+                }
+            }
+            DisposalMethod::Previous => {
+                if let Some(bytes) = previous {
+                    canvas.bytes = bytes;
+                }
+            }
+            DisposalMethod::Any | DisposalMethod::Keep => {}
+        }
+    }
+
+    Ok(Animation {
+        width,
+        height,
+        frames,
+        repeat,
+    })
+}
+
+/// Encode an animated PNG (APNG) that loops forever. Use `ApngEncoder` for other settings.
+///
+/// All frames need to be the same size. Each frame's delay is given in units of 10 ms and its
+/// disposal and blending are stored as is.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure. Encoding
+/// fails if there are no frames or they aren't all the same size.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::{editor, endec, Color, DisposalMethod, Frame, FrameBlend, Image};
+///
+/// let mut red = Image::blank(16, 16);
+/// editor::fill(&mut red, Color::red()).unwrap();
+/// let mut dot = Image::blank(16, 16);
+/// editor::fill(&mut dot, Color::rgba(0, 0, 0, 0)).unwrap();
+/// dot.set_pixel(8, 8, Color::blue()).unwrap();
+///
+/// // Show red, then draw a blue dot over it.
+/// let frames = [
+///     Frame { image: red, delay: 50, disposal: DisposalMethod::Keep, blend: FrameBlend::Source },
+///     Frame { image: dot, delay: 50, disposal: DisposalMethod::Keep, blend: FrameBlend::Over },
+/// ];
+/// endec::encode_apng(&frames, Path::new("tests/out/test_encode_apng.png")).unwrap();
+/// ```
+pub fn encode_apng(frames: &[Frame], path: &Path) -> RasterResult<()> {
+    ApngEncoder::new().encode(frames, path)
+}
+
+/// Encode a sequence of frames as an animated PNG to a writer. Works the same as `encode_apng`.
+///
+/// # Errors
+///
+/// See `encode_apng`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{endec, DisposalMethod, Frame, FrameBlend, Image};
+///
+/// let frame = Frame {
+///     image: Image::blank(4, 4),
+///     delay: 10,
+///     disposal: DisposalMethod::Keep,
+///     blend: FrameBlend::Source,
+/// };
+/// let mut data = Vec::new();
+/// endec::encode_apng_to_writer(&[frame.clone(), frame], &mut data).unwrap();
+///
+/// assert!(data.starts_with(b"\x89PNG"));
+/// ```
+pub fn encode_apng_to_writer<W: Write>(frames: &[Frame], writer: W) -> RasterResult<()> {
+    ApngEncoder::new().encode_to_writer(frames, writer)
+}
+
+/// A builder for encoding animated PNGs.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::{editor, endec, Color, DisposalMethod, Frame, FrameBlend, Image, LoopCount};
+///
+/// let black = Image::blank(16, 16);
+/// let mut white = Image::blank(16, 16);
+/// editor::fill(&mut white, Color::white()).unwrap();
+///
+/// // Blink 3 times.
+/// let frames: Vec<Frame> = vec![black, white]
+///     .into_iter()
+///     .map(|image| Frame {
+///         image,
+///         delay: 20,
+///         disposal: DisposalMethod::Keep,
+///         blend: FrameBlend::Source,
+///     })
+///     .collect();
+/// endec::ApngEncoder::new()
+///     .repeat(LoopCount::Finite(2))
+///     .encode(&frames, Path::new("tests/out/test_apng_encoder.png"))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ApngEncoder {
+    repeat: LoopCount,
+}
+
+impl ApngEncoder {
+    /// Create an encoder that loops forever.
+    pub fn new() -> ApngEncoder {
+        ApngEncoder {
+            repeat: LoopCount::Infinite,
+        }
+    }
+
+    /// Set how many times the animation plays.
+    pub fn repeat(mut self, repeat: LoopCount) -> ApngEncoder {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Encode the frames to a file.
+    ///
+    /// # Errors
+    ///
+    /// See `encode_apng`.
+    pub fn encode(&self, frames: &[Frame], path: &Path) -> RasterResult<()> {
+        self.encode_to_writer(frames, BufWriter::new(File::create(path)?))
+    }
+
+    /// Encode the frames to a writer. Works the same as `encode`.
+    ///
+    /// # Errors
+    ///
+    /// See `encode_apng`.
+    pub fn encode_to_writer<W: Write>(&self, frames: &[Frame], writer: W) -> RasterResult<()> {
+        let fail = |msg: &str| Err(RasterError::Encode(ImageFormat::Png, msg.to_string()));
+
+        let (width, height) = match frames.first() {
+            Some(first) => (first.image.width, first.image.height),
+            None => return fail("No frames to encode"),
+        };
+        if frames
+            .iter()
+            .any(|f| f.image.width != width || f.image.height != height)
+        {
+            return fail("Frames are not all the same size");
+        }
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        // APNG counts the first play, 0 means forever.
+        let plays = match self.repeat {
+            LoopCount::Infinite => 0,
+            LoopCount::Finite(count) => count as u32 + 1,
+        };
+        encoder.set_animated(frames.len() as u32, plays)?;
+
+        let mut writer = encoder.write_header()?;
+        for frame in frames {
+            writer.set_frame_delay(frame.delay, 100)?;
+            writer.set_dispose_op(match frame.disposal {
+                DisposalMethod::Any | DisposalMethod::Keep => png::DisposeOp::None,
+                DisposalMethod::Background => png::DisposeOp::Background,
+                DisposalMethod::Previous => png::DisposeOp::Previous,
+            })?;
+            writer.set_blend_op(match frame.blend {
+                FrameBlend::Source => png::BlendOp::Source,
+                FrameBlend::Over => png::BlendOp::Over,
+            })?;
+            writer.write_image_data(&frame.image.bytes)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+impl Default for ApngEncoder {
+    fn default() -> ApngEncoder {
+        ApngEncoder::new()
+    }
+}
+
+/// Decode a JPEG. Both baseline and progressive JPEGs are supported.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/portrait.jpg").unwrap(); // A progressive JPEG
+/// let image = endec::decode_jpeg(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_jpeg(image_file: &File) -> RasterResult<Image> {
+    decode_jpeg_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a JPEG from a reader with the given size limits. Otherwise works the same as
+/// `decode_jpeg`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.jpg").unwrap();
+/// let image = endec::decode_jpeg_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_jpeg_from_reader<R: Read + Seek>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let start = reader.stream_position()?;
+    let (width, height) = piston_image::jpeg::JPEGDecoder::new(&mut reader)
+        .dimensions()
+        .map_err(|e| RasterError::from_image_error(ImageFormat::Jpeg, e))?;
+    limits.check(ImageFormat::Jpeg, width as u64, height as u64, 4)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    let src = piston_image::load(BufReader::new(reader), piston_image::ImageFormat::JPEG)?;
+    let src = src.to_rgba();
+    let (w, h) = src.dimensions();
+
+    Ok(Image {
+        width: w as i32,
+        height: h as i32,
+        bytes: src.into_raw(),
+    })
+}
+
+/// Encode a JPEG. JPEG has no alpha channel so transparency is discarded.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// endec::encode_jpeg(&image, Path::new("tests/out/test_encode_jpeg.jpg")).unwrap();
+/// ```
+pub fn encode_jpeg(image: &Image, path: &Path) -> RasterResult<()> {
+    encode_jpeg_to_writer(image, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a JPEG to a writer. Works the same as `encode_jpeg`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_jpeg_to_writer(&image, &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_jpeg_to_writer<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    piston_image::jpeg::JPEGEncoder::new(&mut writer)
+        .encode(
+            &image.bytes,
+            image.width as u32,
+            image.height as u32,
+            piston_image::RGBA(8),
+        )
+        .map_err(|e| RasterError::Encode(ImageFormat::Jpeg, e.to_string()))?;
+    Ok(())
+}
+
+// Decode PNG
+pub fn decode_png(image_file: &File) -> RasterResult<Image> {
+    decode_png_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a PNG from a reader with the given size limits. Otherwise works the same as `decode_png`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.png").unwrap();
+/// let image = endec::decode_png_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_png_from_reader<R: Read>(reader: R, limits: &DecodeLimits) -> RasterResult<Image> {
+    png_image(png::Decoder::new(reader), limits, false)
+}
+
+/// Decode a PNG keeping 16-bit samples intact. 8-bit and lower depths are scaled up to 16 bits,
+/// palettes are expanded and gray is spread to RGB. `decode_png` reduces samples to 8 bits.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/deep.png").unwrap();
+/// let buffer = endec::decode_png16(&file).unwrap();
+///
+/// assert_eq!(&[0x1234, 0xabcd, 0xffff, 0xffff], &buffer.samples[..4]);
+/// ```
+pub fn decode_png16(image_file: &File) -> RasterResult<ImageBuffer<Rgba16>> {
+    decode_png16_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a PNG keeping 16-bit samples intact from a reader with the given size limits. Otherwise
+/// works the same as `decode_png16`.
+///
+/// # Errors
+///
+/// See `decode_png16`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.png").unwrap();
+/// let buffer = endec::decode_png16_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((buffer.width * buffer.height * 4) as usize, buffer.samples.len());
+/// ```
+pub fn decode_png16_from_reader<R: Read>(
+    reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<ImageBuffer<Rgba16>> {
+    let mut decoder = png::Decoder::new_with_limits(
+        reader,
+        png::Limits {
+            bytes: limits.max_bytes,
+        },
+    );
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+    let (width, height) = reader.info().size();
+    limits.check(ImageFormat::Png, width as u64, height as u64, 8)?;
+    let mut bytes = vec![0; reader.output_buffer_size()];
+    let output = reader.next_frame(&mut bytes)?;
+    let bytes = &bytes[..output.buffer_size()];
+
+    // Samples are big endian, 8-bit ones are scaled so that 255 becomes 65535
+    let samples: Vec<u16> = match output.bit_depth {
+        png::BitDepth::Sixteen => bytes
+            .chunks_exact(2)
+            .map(|s| u16::from_be_bytes([s[0], s[1]]))
+            .collect(),
+        _ => bytes.iter().map(|&s| s as u16 * 257).collect(),
+    };
+    let samples = match output.color_type {
+        png::ColorType::Rgba | png::ColorType::Indexed => samples,
+        png::ColorType::Rgb => samples
+            .chunks_exact(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 65535])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => samples
+            .chunks_exact(2)
+            .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => samples.iter().flat_map(|&g| vec![g, g, g, 65535]).collect(),
+    };
+
+    ImageBuffer::new(width as i32, height as i32, samples)
+        .map_err(|_| RasterError::Decode(ImageFormat::Png, "Truncated image data".to_string()))
+}
+
+/// Decode a PNG into a single channel buffer, a quarter of the memory of an `Image`. Grayscale
+/// PNGs are read as they are, 16-bit samples are reduced to 8 bits and alpha is dropped. Color
+/// PNGs are turned gray with the weights of `filter::grayscale`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/gray.png").unwrap();
+/// let gray = endec::decode_png_gray(&file).unwrap();
+///
+/// assert_eq!(vec![0, 64, 128, 192, 255, 7], gray.samples);
+/// ```
+pub fn decode_png_gray(image_file: &File) -> RasterResult<ImageBuffer<Gray8>> {
+    decode_png_gray_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a PNG into a single channel buffer from a reader with the given size limits. Otherwise
+/// works the same as `decode_png_gray`.
+///
+/// # Errors
+///
+/// See `decode_png_gray`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.png").unwrap();
+/// let gray = endec::decode_png_gray_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((gray.width * gray.height) as usize, gray.samples.len());
+/// ```
+pub fn decode_png_gray_from_reader<R: Read>(
+    reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<ImageBuffer<Gray8>> {
+    let mut decoder = png::Decoder::new_with_limits(
+        reader,
+        png::Limits {
+            bytes: limits.max_bytes,
+        },
+    );
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let (width, height) = reader.info().size();
+    limits.check(ImageFormat::Png, width as u64, height as u64, 1)?;
+    let mut bytes = vec![0; reader.output_buffer_size()];
+    let output = reader.next_frame(&mut bytes)?;
+    bytes.truncate(output.buffer_size());
+
+    let samples = match output.color_type {
+        png::ColorType::Grayscale => bytes,
+        png::ColorType::GrayscaleAlpha => bytes.chunks_exact(2).map(|p| p[0]).collect(),
+        color_type => {
+            let image = Image {
+                width: width as i32,
+                height: height as i32,
+                bytes: png_rgba(color_type, &bytes),
+            };
+            return Ok(ImageBuffer::from_image(&image));
+        }
+    };
+    ImageBuffer::new(width as i32, height as i32, samples)
+        .map_err(|_| RasterError::Decode(ImageFormat::Png, "Truncated image data".to_string()))
+}
+
+// Encode PNG
+pub fn encode_png(image: &Image, path: &Path) -> RasterResult<()> {
+    encode_png_to_writer(image, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a PNG to a writer. Works the same as `encode_png`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_png_to_writer(&image, &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_png_to_writer<W: Write>(image: &Image, writer: W) -> RasterResult<()> {
+    encode_png_with_text_to_writer(image, writer, &[])
+}
+
+/// Encode a PNG with text chunks, like the software that made it. Keywords like `Software`,
+/// `Author` or `Comment` are standard, see the PNG specification for all of them. Text that isn't
+/// Latin-1 is stored as UTF-8.
+///
+/// The text of a PNG file is read with `MetadataBlocks::from_path`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure. Keywords
+/// must be 1 to 79 Latin-1 characters.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+/// use raster::metadata::MetadataBlocks;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let text = [("Software", "raster"), ("Comment", "A tiny chart")];
+/// let path = Path::new("tests/out/test_png_text.png");
+/// endec::encode_png_with_text(&image, path, &text).unwrap();
+///
+/// let blocks = MetadataBlocks::from_path(path).unwrap();
+/// assert_eq!("raster", blocks.text["Software"]);
+/// ```
+pub fn encode_png_with_text(image: &Image, path: &Path, text: &[(&str, &str)]) -> RasterResult<()> {
+    encode_png_with_text_to_writer(image, BufWriter::new(File::create(path)?), text)
+}
+
+/// Encode a PNG with text chunks to a writer. Works the same as `encode_png_with_text`.
+///
+/// # Errors
+///
+/// See `encode_png_with_text`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_png_with_text_to_writer(&image, &mut data, &[("Title", "Chart")]).unwrap();
+///
+/// assert!(data.windows(4).any(|w| w == b"tEXt"));
+/// ```
+pub fn encode_png_with_text_to_writer<W: Write>(
+    image: &Image,
+    writer: W,
+    text: &[(&str, &str)],
+) -> RasterResult<()> {
+    let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for &(keyword, text) in text {
+        let valid = |c: char| (c as u32) < 256;
+        if keyword.is_empty() || keyword.chars().count() > 79 || !keyword.chars().all(valid) {
+            return Err(RasterError::Encode(
+                ImageFormat::Png,
+                format!("Invalid keyword {:?}", keyword),
+            ));
+        }
+        if text.chars().all(valid) {
+            encoder.add_text_chunk(keyword.to_string(), text.to_string())?;
+        } else {
+            encoder.add_itxt_chunk(keyword.to_string(), text.to_string())?;
+        }
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.bytes)?;
+    Ok(())
+}
+
+/// Encode a 16-bit RGBA PNG. Works the same as `encode_png` otherwise.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use std::path::Path;
+/// use raster::buffer::Rgba16;
+/// use raster::{endec, ImageBuffer};
+///
+/// let buffer = ImageBuffer::<Rgba16>::new(1, 1, vec![0x1234, 0, 0xfedc, 0xffff]).unwrap();
+/// endec::encode_png16(&buffer, Path::new("tests/out/test_png16.png")).unwrap();
+///
+/// let file = File::open("tests/out/test_png16.png").unwrap();
+/// assert_eq!(buffer, endec::decode_png16(&file).unwrap());
+/// ```
+pub fn encode_png16(buffer: &ImageBuffer<Rgba16>, path: &Path) -> RasterResult<()> {
+    encode_png16_to_writer(buffer, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a 16-bit RGBA PNG to a writer. Works the same as `encode_png16`.
+///
+/// # Errors
+///
+/// See `encode_png16`.
+pub fn encode_png16_to_writer<W: Write>(
+    buffer: &ImageBuffer<Rgba16>,
+    writer: W,
+) -> RasterResult<()> {
+    let mut encoder = png::Encoder::new(writer, buffer.width as u32, buffer.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Sixteen);
+
+    let bytes: Vec<u8> = buffer
+        .samples
+        .iter()
+        .flat_map(|sample| sample.to_be_bytes())
+        .collect();
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&bytes)?;
+    Ok(())
+}
+
+/// Encode an 8-bit grayscale PNG, so that buffers from `decode_png_gray` are saved as they were
+/// read.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let gray = endec::decode_png_gray(&File::open("tests/in/gray.png").unwrap()).unwrap();
+/// endec::encode_png_gray(&gray, Path::new("tests/out/test_png_gray.png")).unwrap();
+///
+/// let file = File::open("tests/out/test_png_gray.png").unwrap();
+/// assert_eq!(gray, endec::decode_png_gray(&file).unwrap());
+/// ```
+pub fn encode_png_gray(buffer: &ImageBuffer<Gray8>, path: &Path) -> RasterResult<()> {
+    encode_png_gray_to_writer(buffer, BufWriter::new(File::create(path)?))
+}
+
+/// Encode an 8-bit grayscale PNG to a writer. Works the same as `encode_png_gray`.
+///
+/// # Errors
+///
+/// See `encode_png_gray`.
+pub fn encode_png_gray_to_writer<W: Write>(
+    buffer: &ImageBuffer<Gray8>,
+    writer: W,
+) -> RasterResult<()> {
+    let mut encoder = png::Encoder::new(writer, buffer.width as u32, buffer.height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&buffer.samples)?;
+    Ok(())
+}
+
+/// A PNG encoder that takes the image a few rows at a time, so the whole image never has to be
+/// in memory. Rows are RGBA like `Image::bytes` and are compressed as they come in.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::PngRowWriter;
+///
+/// let mut data = Vec::new();
+/// let mut writer = PngRowWriter::new(&mut data, 4, 3).unwrap();
+/// for y in 0..3 {
+///     let row: Vec<u8> = (0..4).flat_map(|x| vec![x * 60, y * 120, 0, 255]).collect();
+///     writer.write_rows(&row).unwrap();
+/// }
+/// writer.finish().unwrap();
+///
+/// let image = raster::Image::from_bytes(raster::ImageFormat::Png, &data).unwrap();
+/// assert_eq!((4, 3), (image.width, image.height));
+/// ```
+pub struct PngRowWriter<W: Write> {
+    rows_left: i32,
+    writer: png::Writer<W>,
+    deflater: ZlibEncoder<Vec<u8>>,
+    previous: Vec<u8>,
+    filtered: Vec<u8>,
+}
+
+impl<W: Write> PngRowWriter<W> {
+    /// Write the PNG header for an image of the given size and get ready for the rows.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+    pub fn new(writer: W, width: i32, height: i32) -> RasterResult<PngRowWriter<W>> {
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        Ok(PngRowWriter {
+            rows_left: height,
+            writer: encoder.write_header()?,
+            deflater: ZlibEncoder::new(Vec::new(), Compression::default()),
+            previous: vec![0; width.max(0) as usize * 4],
+            filtered: Vec::with_capacity(width.max(0) as usize * 4 + 1),
+        })
+    }
+
+    /// Append one or more whole rows of RGBA pixels.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` upon failure. Returns `RasterError::Encode` if
+    /// `rows` is not a whole number of rows or goes past the bottom of the image.
+    pub fn write_rows(&mut self, rows: &[u8]) -> RasterResult<()> {
+        let row_len = self.previous.len();
+        if row_len == 0 || !rows.chunks_exact(row_len).remainder().is_empty() {
+            return Err(RasterError::Encode(
+                ImageFormat::Png,
+                "Partial row".to_string(),
+            ));
+        }
+        let count = (rows.len() / row_len) as i32;
+        if count > self.rows_left {
+            return Err(RasterError::Encode(
+                ImageFormat::Png,
+                "Too many rows".to_string(),
+            ));
+        }
+
+        for row in rows.chunks(row_len) {
+            paeth_filter(row, &self.previous, &mut self.filtered);
+            self.deflater.write_all(&self.filtered)?;
+            self.previous.copy_from_slice(row);
+        }
+        self.rows_left -= count;
+
+        // Hand compressed data over in chunks instead of keeping it all
+        if self.deflater.get_ref().len() >= 1 << 16 {
+            self.writer
+                .write_chunk(png::chunk::IDAT, self.deflater.get_ref())?;
+            self.deflater.get_mut().clear();
+        }
+        Ok(())
+    }
+
+    /// Write the end of the PNG. All rows must have been written.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` upon failure. Returns `RasterError::Encode` if
+    /// rows are missing.
+    pub fn finish(self) -> RasterResult<()> {
+        if self.rows_left > 0 {
+            return Err(RasterError::Encode(
+                ImageFormat::Png,
+                "Missing rows".to_string(),
+            ));
+        }
+
+        let mut writer = self.writer;
+        writer.write_chunk(png::chunk::IDAT, &self.deflater.finish()?)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Enumeration of PNM formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PnmFormat {
+    /// Black and white bitmap (.pbm). Pixels darker than middle gray become black.
+    Pbm,
+    /// Grayscale graymap (.pgm).
+    Pgm,
+    /// RGB pixmap (.ppm). The alpha channel is dropped.
+    Ppm,
+    /// RGBA arbitrary map (.pam). Stores the image as is.
+    Pam,
+}
+
+/// Enumeration of PNM sample encodings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PnmEncoding {
+    /// Samples are stored as bytes.
+    Binary,
+    /// Samples are stored as ASCII decimal numbers. Not available for PAM.
+    Plain,
+}
+
+/// Decode a PNM. Supports plain and binary PBM, PGM and PPM as well as PAM with the
+/// `BLACKANDWHITE`, `GRAYSCALE` and `RGB` tuple types and their `_ALPHA` variants. Samples with
+/// a maximum value other than 255 are scaled to 8 bits.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.ppm").unwrap();
+/// let image = endec::decode_pnm(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_pnm(image_file: &File) -> RasterResult<Image> {
+    decode_pnm_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a PNM from a reader with the given size limits. Otherwise works the same as `decode_pnm`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.ppm").unwrap();
+/// let image = endec::decode_pnm_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_pnm_from_reader<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    if data.len() < 2 || data[0] != b'P' {
+        return Err(pnm_error("Not a PNM file"));
+    }
+    let magic = data[1];
+    let mut pos = 2;
+
+    let (width, height, depth, maxval) = match magic {
+        b'1'..=b'6' => {
+            let width = pnm_number(&data, &mut pos)?;
+            let height = pnm_number(&data, &mut pos)?;
+            let maxval = match magic {
+                b'1' | b'4' => 1,
+                _ => pnm_number(&data, &mut pos)?,
+            };
+            let depth = match magic {
+                b'3' | b'6' => 3,
+                _ => 1,
+            };
+            (width, height, depth, maxval)
+        }
+        b'7' => pam_header(&data, &mut pos)?,
+        _ => return Err(pnm_error("Not a PNM file")),
+    };
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(pnm_error("Invalid image dimensions"));
+    }
+    if maxval == 0 || maxval > 65535 {
+        return Err(pnm_error("Invalid maximum sample value"));
+    }
+    limits.check(ImageFormat::Pnm, width as u64, height as u64, 4)?;
+    let pixel_count = (width as usize)
+        .checked_mul(height as usize)
+        .ok_or_else(|| pnm_error("Invalid image dimensions"))?;
+    let sample_count = pixel_count * depth;
+
+    // Binary data starts after a single whitespace character.
+    if magic >= b'4' {
+        pos += 1;
+    }
+
+    let mut samples: Vec<u16> = Vec::with_capacity(sample_count);
+    match magic {
+        b'1' => {
+            // Digits don't need to be separated
+            while samples.len() < sample_count {
+                pnm_skip_whitespace(&data, &mut pos);
+                match data.get(pos) {
+                    Some(b'0') => samples.push(1),
+                    Some(b'1') => samples.push(0),
+                    _ => return Err(pnm_error("Invalid or missing bitmap data")),
+                }
+                pos += 1;
+            }
+        }
+        b'2' | b'3' => {
+            while samples.len() < sample_count {
+                samples.push(cmp::min(pnm_number(&data, &mut pos)?, maxval) as u16);
+            }
+        }
+        b'4' => {
+            // Rows are padded to whole bytes. A set bit is black.
+            let row_size = (width as usize).div_ceil(8);
+            if data.len() < pos + row_size * height as usize {
+                return Err(pnm_error("Unexpected end of file"));
+            }
+            for row in data[pos..pos + row_size * height as usize].chunks(row_size) {
+                for x in 0..width as usize {
+                    samples.push(u16::from((row[x / 8] >> (7 - x % 8)) & 1 == 0));
+                }
+            }
+        }
+        _ => {
+            let sample_size = if maxval < 256 { 1 } else { 2 };
+            if data.len() < pos + sample_count * sample_size {
+                return Err(pnm_error("Unexpected end of file"));
+            }
+            for sample in data[pos..pos + sample_count * sample_size].chunks(sample_size) {
+                let value = sample.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+                samples.push(cmp::min(value, maxval) as u16);
+            }
+        }
+    }
+
+    let scale = |value: u16| ((value as u32 * 255 + maxval / 2) / maxval) as u8;
+    let mut bytes = Vec::with_capacity(pixel_count * 4);
+    for pixel in samples.chunks(depth) {
+        match *pixel {
+            [gray] => bytes.extend_from_slice(&[scale(gray), scale(gray), scale(gray), 255]),
+            [gray, alpha] => {
+                bytes.extend_from_slice(&[scale(gray), scale(gray), scale(gray), scale(alpha)])
+            }
+            [r, g, b] => bytes.extend_from_slice(&[scale(r), scale(g), scale(b), 255]),
+            [r, g, b, a] => bytes.extend_from_slice(&[scale(r), scale(g), scale(b), scale(a)]),
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+/// Encode a PNM.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec::{self, PnmEncoding, PnmFormat};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// let path = Path::new("tests/out/test_encode_pnm.pgm");
+/// endec::encode_pnm(&image, path, PnmFormat::Pgm, PnmEncoding::Plain).unwrap();
+/// ```
+pub fn encode_pnm(
+    image: &Image,
+    path: &Path,
+    format: PnmFormat,
+    encoding: PnmEncoding,
+) -> RasterResult<()> {
+    encode_pnm_to_writer(image, BufWriter::new(File::create(path)?), format, encoding)
+}
+
+/// Encode a PNM to a writer. Works the same as `encode_pnm`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::{self, PnmEncoding, PnmFormat};
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_pnm_to_writer(&image, &mut data, PnmFormat::Ppm, PnmEncoding::Binary).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_pnm_to_writer<W: Write>(
+    image: &Image,
+    mut writer: W,
+    format: PnmFormat,
+    encoding: PnmEncoding,
+) -> RasterResult<()> {
+    let plain = match encoding {
+        PnmEncoding::Binary => false,
+        PnmEncoding::Plain => true,
+    };
+    if plain && format == PnmFormat::Pam {
+        return Err(RasterError::Encode(
+            ImageFormat::Pnm,
+            "PAM has no plain encoding".to_string(),
+        ));
+    }
+
+    // Same weights as filter::grayscale
+    let gray =
+        |p: &[u8]| ((p[0] as u32 * 30 + p[1] as u32 * 59 + p[2] as u32 * 11 + 50) / 100) as u8;
+    let samples: Vec<u8> = match format {
+        PnmFormat::Pbm => image
+            .bytes
+            .chunks(4)
+            .map(|p| u8::from(gray(p) < 128))
+            .collect(),
+        PnmFormat::Pgm => image.bytes.chunks(4).map(gray).collect(),
+        PnmFormat::Ppm => image
+            .bytes
+            .chunks(4)
+            .flat_map(|p| p[..3].iter().cloned())
+            .collect(),
+        PnmFormat::Pam => image.bytes.clone(),
+    };
+
+    let mut data = match (format, plain) {
+        (PnmFormat::Pam, _) => format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            image.width, image.height
+        ),
+        (PnmFormat::Pbm, _) => format!(
+            "P{}\n{} {}\n",
+            if plain { 1 } else { 4 },
+            image.width,
+            image.height
+        ),
+        (PnmFormat::Pgm, _) | (PnmFormat::Ppm, _) => format!(
+            "P{}\n{} {}\n255\n",
+            match (format, plain) {
+                (PnmFormat::Pgm, true) => 2,
+                (PnmFormat::Ppm, true) => 3,
+                (PnmFormat::Pgm, false) => 5,
+                _ => 6,
+            },
+            image.width,
+            image.height
+        ),
+    }
+    .into_bytes();
+
+    if plain {
+        // Lines should be no longer than 70 characters
+        let mut line_length = 0;
+        for sample in samples {
+            let text = sample.to_string();
+            if line_length + text.len() >= 70 {
+                data.push(b'\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                data.push(b' ');
+                line_length += 1;
+            }
+            data.extend_from_slice(text.as_bytes());
+            line_length += text.len();
+        }
+        data.push(b'\n');
+    } else if format == PnmFormat::Pbm {
+        // Pack 8 pixels per byte, padding each row to whole bytes
+        for row in samples.chunks(image.width as usize) {
+            for bits in row.chunks(8) {
+                data.push(
+                    bits.iter()
+                        .enumerate()
+                        .fold(0, |byte, (i, &bit)| byte | (bit << (7 - i))),
+                );
+            }
+        }
+    } else {
+        data.extend_from_slice(&samples);
+    }
 
-    // Configure the decoder such that it will expand the image to RGBA.
-    gif::SetParameter::set(&mut decoder, gif::ColorOutput::RGBA);
+    writer.write_all(&data)?;
+    Ok(())
+}
 
-    // Read the file header
-    let mut reader = decoder.read_info()?;
+/// Decode a QOI.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.qoi").unwrap();
+/// let image = endec::decode_qoi(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_qoi(image_file: &File) -> RasterResult<Image> {
+    decode_qoi_from_reader(image_file, &DecodeLimits::default())
+}
 
-    // Read frame 1.
-    // TODO: Work on all frames
-    if let Some(_) = reader.next_frame_info()? {
-        let mut bytes = vec![0; reader.buffer_size()];
-        reader.read_into_buffer(&mut bytes)?;
-        Ok(Image {
-            width: reader.width() as i32,
-            height: reader.height() as i32,
-            bytes: bytes,
-        })
+/// Decode a QOI from a reader with the given size limits. Otherwise works the same as `decode_qoi`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.qoi").unwrap();
+/// let image = endec::decode_qoi_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_qoi_from_reader<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut decoder = qoi::Decoder::new(&data)?.with_channels(qoi::Channels::Rgba);
+    let header = *decoder.header();
+    limits.check(
+        ImageFormat::Qoi,
+        header.width as u64,
+        header.height as u64,
+        4,
+    )?;
+    let bytes = decoder.decode_to_vec()?;
+
+    Ok(Image {
+        width: header.width as i32,
+        height: header.height as i32,
+        bytes,
+    })
+}
+
+/// Encode a QOI. Opaque images are saved with 3 channels, images with transparency with 4.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// endec::encode_qoi(&image, Path::new("tests/out/test_encode_qoi.qoi")).unwrap();
+/// ```
+pub fn encode_qoi(image: &Image, path: &Path) -> RasterResult<()> {
+    encode_qoi_to_writer(image, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a QOI to a writer. Works the same as `encode_qoi`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_qoi_to_writer(&image, &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_qoi_to_writer<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    let has_alpha = image.bytes.chunks(4).any(|p| p[3] != 255);
+    let rgb_bytes;
+    let bytes = if has_alpha {
+        &image.bytes
     } else {
-        Err(RasterError::Decode(
-            ImageFormat::Gif,
-            "Error getting frame info".to_string(),
-        ))
+        rgb_bytes = image
+            .bytes
+            .chunks(4)
+            .flat_map(|p| p[..3].iter().cloned())
+            .collect::<Vec<u8>>();
+        &rgb_bytes
+    };
+
+    let data = qoi::encode_to_vec(bytes, image.width as u32, image.height as u32)
+        .map_err(|err| RasterError::Encode(ImageFormat::Qoi, err.to_string()))?;
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+/// Decode an SVG by rasterizing it. Requires the `svg` feature. The drawing is stretched to fill
+/// `size` as `(width, height)`, or drawn at its own size when `size` is `None`. Text and embedded
+/// raster images are not rendered.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.svg").unwrap();
+/// let image = endec::decode_svg(&file, Some((200, 100))).unwrap();
+///
+/// assert_eq!(200, image.width);
+/// assert_eq!(100, image.height);
+/// ```
+#[cfg(feature = "svg")]
+pub fn decode_svg(image_file: &File, size: Option<(i32, i32)>) -> RasterResult<Image> {
+    decode_svg_from_reader(image_file, size, &DecodeLimits::default())
+}
+
+/// Decode an SVG from a reader with the given size limits. Otherwise works the same as
+/// `decode_svg`. Requires the `svg` feature.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.svg").unwrap();
+/// let image = endec::decode_svg_from_reader(Cursor::new(data), None, &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+#[cfg(feature = "svg")]
+pub fn decode_svg_from_reader<R: Read>(
+    mut reader: R,
+    size: Option<(i32, i32)>,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    use resvg::{tiny_skia, usvg};
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let tree = usvg::Tree::from_data(&data, &usvg::Options::default())?;
+    let (width, height) = match size {
+        Some((width, height)) => (width, height),
+        None => {
+            let size = tree.size().to_int_size();
+            (size.width() as i32, size.height() as i32)
+        }
+    };
+    if width <= 0 || height <= 0 {
+        return Err(RasterError::Decode(
+            ImageFormat::Svg,
+            format!("Invalid size {}x{}", width, height),
+        ));
     }
+    limits.check(ImageFormat::Svg, width as u64, height as u64, 4)?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width as u32, height as u32)
+        .ok_or_else(|| RasterError::Decode(ImageFormat::Svg, "Image too large".to_string()))?;
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / tree.size().width(),
+        height as f32 / tree.size().height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // tiny-skia works with premultiplied alpha
+    let mut bytes = Vec::with_capacity(pixmap.data().len());
+    for pixel in pixmap.pixels() {
+        let color = pixel.demultiply();
+        bytes.extend_from_slice(&[color.red(), color.green(), color.blue(), color.alpha()]);
+    }
+
+    Ok(Image {
+        width,
+        height,
+        bytes,
+    })
 }
 
-// Encode GIF
-pub fn encode_gif(image: &Image, path: &Path) -> RasterResult<()> {
-    // Open the file with basic error check
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
-    let frame = gif::Frame::from_rgba(
-        image.width as u16,
-        image.height as u16,
-        &mut image.bytes.clone(),
-    ); // TODO: Perf issue?
-    let mut encoder = gif::Encoder::new(writer, frame.width, frame.height, &[])?;
-    encoder.write_frame(&frame).map_err(RasterError::Io)?;
+/// Enumeration of TGA compression methods.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TgaCompression {
+    /// No compression.
+    Uncompressed,
+    /// Run-length encoding. Works well for images with large areas of flat color.
+    Rle,
+}
+
+/// Decode a TGA. Supports uncompressed and RLE compressed true color, grayscale and color
+/// mapped TGAs.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.tga").unwrap();
+/// let image = endec::decode_tga(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_tga(image_file: &File) -> RasterResult<Image> {
+    decode_tga_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a TGA from a reader with the given size limits. Otherwise works the same as `decode_tga`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.tga").unwrap();
+/// let image = endec::decode_tga_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_tga_from_reader<R: Read + Seek>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let start = reader.stream_position()?;
+    let (width, height) = piston_image::tga::TGADecoder::new(&mut reader)
+        .dimensions()
+        .map_err(|e| RasterError::from_image_error(ImageFormat::Tga, e))?;
+    limits.check(ImageFormat::Tga, width as u64, height as u64, 4)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    let src = piston_image::load(BufReader::new(reader), piston_image::ImageFormat::TGA)
+        .map_err(|e| RasterError::from_image_error(ImageFormat::Tga, e))?;
+    let src = src.to_rgba();
+    let (w, h) = src.dimensions();
+
+    Ok(Image {
+        width: w as i32,
+        height: h as i32,
+        bytes: src.into_raw(),
+    })
+}
+
+/// Encode a TGA. Opaque images are saved as 24-bit TGAs. Images with transparency are saved as
+/// 32-bit TGAs with an alpha channel.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure. TGAs can't
+/// be empty, or wider or taller than 65535 pixels.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec::{self, TgaCompression};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// endec::encode_tga(&image, Path::new("tests/out/test_encode_tga.tga"), TgaCompression::Rle).unwrap();
+/// ```
+pub fn encode_tga(image: &Image, path: &Path, compression: TgaCompression) -> RasterResult<()> {
+    encode_tga_to_writer(image, BufWriter::new(File::create(path)?), compression)
+}
+
+/// Encode a TGA to a writer. Works the same as `encode_tga`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure. TGAs can't
+/// be empty, or wider or taller than 65535 pixels.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::{self, TgaCompression};
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_tga_to_writer(&image, &mut data, TgaCompression::Rle).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_tga_to_writer<W: Write>(
+    image: &Image,
+    mut writer: W,
+    compression: TgaCompression,
+) -> RasterResult<()> {
+    if image.width > 65535 || image.height > 65535 {
+        return Err(RasterError::Encode(
+            ImageFormat::Tga,
+            "Image is too big for a TGA".to_string(),
+        ));
+    }
+    if image.width <= 0 || image.height <= 0 {
+        return Err(RasterError::Encode(
+            ImageFormat::Tga,
+            "Image is empty".to_string(),
+        ));
+    }
+
+    let has_alpha = image.bytes.chunks(4).any(|p| p[3] != 255);
+    let pixel_size = if has_alpha { 4 } else { 3 };
+
+    let mut header = Vec::with_capacity(18);
+    header.push(0); // image ID length
+    header.push(0); // no color map
+    header.push(match compression {
+        TgaCompression::Uncompressed => 2,
+        TgaCompression::Rle => 10,
+    });
+    header.extend_from_slice(&[0; 5]); // color map specification
+    header.extend_from_slice(&[0; 4]); // x and y origin
+    header.extend_from_slice(&(image.width as u16).to_le_bytes());
+    header.extend_from_slice(&(image.height as u16).to_le_bytes());
+    header.push(pixel_size * 8);
+    header.push(if has_alpha { 0x28 } else { 0x20 }); // top-left origin and alpha bits
+
+    writer.write_all(&header)?;
+
+    let row_length = (image.width * 4) as usize;
+    let mut row = Vec::with_capacity(row_length);
+    for src in image.bytes.chunks(row_length) {
+        // TGA stores pixels as BGR(A)
+        let pixels = src
+            .chunks(4)
+            .map(|p| [p[2], p[1], p[0], p[3]])
+            .collect::<Vec<[u8; 4]>>();
+        let pixel_size = pixel_size as usize;
+
+        row.clear();
+        match compression {
+            TgaCompression::Uncompressed => {
+                for p in &pixels {
+                    row.extend_from_slice(&p[..pixel_size]);
+                }
+            }
+            TgaCompression::Rle => {
+                // Packets hold up to 128 pixels and don't cross rows.
+                let mut i = 0;
+                while i < pixels.len() {
+                    let run = pixels[i..]
+                        .iter()
+                        .take(128)
+                        .take_while(|p| **p == pixels[i])
+                        .count();
+                    if run > 1 {
+                        row.push(0x80 | (run - 1) as u8);
+                        row.extend_from_slice(&pixels[i][..pixel_size]);
+                        i += run;
+                    } else {
+                        // Collect pixels until the next run starts
+                        let mut end = i + 1;
+                        while end < pixels.len()
+                            && end - i < 128
+                            && (end + 1 == pixels.len() || pixels[end] != pixels[end + 1])
+                        {
+                            end += 1;
+                        }
+                        row.push((end - i - 1) as u8);
+                        for p in &pixels[i..end] {
+                            row.extend_from_slice(&p[..pixel_size]);
+                        }
+                        i = end;
+                    }
+                }
+            }
+        }
+        writer.write_all(&row)?;
+    }
     Ok(())
 }
 
-// Decode PNG
-pub fn decode_png(image_file: &File) -> RasterResult<Image> {
-    let decoder = png::Decoder::new(image_file);
+/// Enumeration of TIFF compression methods.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TiffCompression {
+    /// No compression. Biggest files but fastest to read and write.
+    Uncompressed,
+    /// Lempel-Ziv-Welch compression.
+    Lzw,
+    /// Deflate compression, the same one used by PNG. Usually produces the smallest files.
+    Deflate,
+}
+
+/// Decode a TIFF. Only the first page is decoded, use `decode_tiff_pages` to get all of them.
+///
+/// Supports uncompressed, LZW, Deflate and PackBits compressed TIFFs with 8 or 16 bits per
+/// channel grayscale, RGB, RGBA or CMYK pixels. 16-bit channels are reduced to 8 bits.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.tif").unwrap();
+/// let image = endec::decode_tiff(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_tiff(image_file: &File) -> RasterResult<Image> {
+    decode_tiff_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a TIFF from a reader with the given size limits. Otherwise works the same as
+/// `decode_tiff`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.tif").unwrap();
+/// let image = endec::decode_tiff_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_tiff_from_reader<R: Read + Seek>(
+    reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(reader))?;
+    tiff_page(&mut decoder, limits)
+}
+
+/// Decode all pages of a multi-page TIFF.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.tif").unwrap();
+/// let pages = endec::decode_tiff_pages(&file).unwrap();
+///
+/// for (i, page) in pages.iter().enumerate() {
+///     println!("page {} is {}x{}", i + 1, page.width, page.height);
+/// }
+/// ```
+pub fn decode_tiff_pages(image_file: &File) -> RasterResult<Vec<Image>> {
+    decode_tiff_pages_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode all pages of a TIFF from a reader with the given size limits. Otherwise works the same as
+/// `decode_tiff_pages`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.tif").unwrap();
+/// let pages = endec::decode_tiff_pages_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert!(!pages.is_empty());
+/// ```
+pub fn decode_tiff_pages_from_reader<R: Read + Seek>(
+    reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Vec<Image>> {
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(reader))?;
+    let mut pages = vec![tiff_page(&mut decoder, limits)?];
+    let mut bytes = pages[0].bytes.len();
+    while decoder.more_images() {
+        decoder.next_image()?;
+        // The limit on bytes covers all pages together
+        let left = DecodeLimits {
+            max_bytes: limits.max_bytes.saturating_sub(bytes),
+            ..*limits
+        };
+        pages.push(tiff_page(&mut decoder, &left)?);
+        bytes += pages[pages.len() - 1].bytes.len();
+    }
+    Ok(pages)
+}
+
+/// Encode a TIFF as 8-bit RGBA.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec::{self, TiffCompression};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// endec::encode_tiff(&image, Path::new("tests/out/test_encode_tiff.tif"), TiffCompression::Deflate).unwrap();
+/// ```
+pub fn encode_tiff(image: &Image, path: &Path, compression: TiffCompression) -> RasterResult<()> {
+    encode_tiff_to_writer(image, BufWriter::new(File::create(path)?), compression)
+}
+
+/// Encode a TIFF to a writer. Works the same as `encode_tiff`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, TiffCompression};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// let mut data = Cursor::new(Vec::new());
+/// endec::encode_tiff_to_writer(&image, &mut data, TiffCompression::Lzw).unwrap();
+///
+/// assert!(!data.into_inner().is_empty());
+/// ```
+pub fn encode_tiff_to_writer<W: Write + Seek>(
+    image: &Image,
+    writer: W,
+    compression: TiffCompression,
+) -> RasterResult<()> {
+    let compression = match compression {
+        TiffCompression::Uncompressed => tiff::encoder::Compression::Uncompressed,
+        TiffCompression::Lzw => tiff::encoder::Compression::Lzw,
+        TiffCompression::Deflate => {
+            tiff::encoder::Compression::Deflate(tiff::encoder::DeflateLevel::Balanced)
+        }
+    };
+    tiff::encoder::TiffEncoder::new(writer)
+        .and_then(|encoder| {
+            encoder
+                .with_compression(compression)
+                .write_image::<tiff::encoder::colortype::RGBA8>(
+                    image.width as u32,
+                    image.height as u32,
+                    &image.bytes,
+                )
+        })
+        .map_err(|err| match err {
+            tiff::TiffError::IoError(io_err) => RasterError::Io(io_err),
+            err => RasterError::Encode(ImageFormat::Tiff, err.to_string()),
+        })
+}
+
+/// Decode a WebP. Both lossy and lossless WebPs are supported. Animated WebPs are not.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::endec;
+///
+/// let file = File::open("tests/in/sample.webp").unwrap();
+/// let image = endec::decode_webp(&file).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_webp(image_file: &File) -> RasterResult<Image> {
+    decode_webp_from_reader(image_file, &DecodeLimits::default())
+}
+
+/// Decode a WebP from a reader with the given size limits. Otherwise works the same as
+/// `decode_webp`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitExceeded` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use raster::endec::{self, DecodeLimits};
+///
+/// let data = std::fs::read("tests/in/sample.webp").unwrap();
+/// let image = endec::decode_webp_from_reader(Cursor::new(data), &DecodeLimits::default()).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn decode_webp_from_reader<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let features = webp::BitstreamFeatures::new(&data)
+        .ok_or_else(|| RasterError::Decode(ImageFormat::WebP, "Invalid WebP header".to_string()))?;
+    if features.has_animation() {
+        return Err(RasterError::Decode(
+            ImageFormat::WebP,
+            "Animated WebP is not supported".to_string(),
+        ));
+    }
+    limits.check(
+        ImageFormat::WebP,
+        features.width() as u64,
+        features.height() as u64,
+        4,
+    )?;
+    let decoded = webp::Decoder::new(&data)
+        .decode()
+        .ok_or_else(|| RasterError::Decode(ImageFormat::WebP, "Invalid WebP data".to_string()))?;
+
+    let bytes = if decoded.is_alpha() {
+        decoded.to_vec()
+    } else {
+        // Convert RGB to RGBA by adding alpha channel
+        let mut rgba_bytes = Vec::with_capacity(decoded.len() / 3 * 4);
+        for rgb in decoded.chunks(3) {
+            rgba_bytes.extend_from_slice(rgb);
+            rgba_bytes.push(255);
+        }
+        rgba_bytes
+    };
+
+    Ok(Image {
+        width: decoded.width() as i32,
+        height: decoded.height() as i32,
+        bytes,
+    })
+}
+
+/// Encode a lossy WebP.
+///
+/// Quality can be a value from 0.0 - 100.0. A higher value gives better quality and bigger files.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// raster::editor::resize(&mut image, 200, 200, raster::ResizeMode::Fit).unwrap();
+/// endec::encode_webp(&image, Path::new("tests/out/test_encode_webp.webp"), 80.0).unwrap();
+/// ```
+pub fn encode_webp(image: &Image, path: &Path, quality: f32) -> RasterResult<()> {
+    encode_webp_to_writer(image, BufWriter::new(File::create(path)?), quality)
+}
+
+/// Encode a lossy WebP to a writer. Works the same as `encode_webp`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_webp_to_writer(&image, &mut data, 75.0).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_webp_to_writer<W: Write>(
+    image: &Image,
+    mut writer: W,
+    quality: f32,
+) -> RasterResult<()> {
+    let quality = quality.clamp(0.0, 100.0);
+    let encoder = webp::Encoder::from_rgba(&image.bytes, image.width as u32, image.height as u32);
+    writer.write_all(&encoder.encode(quality))?;
+    Ok(())
+}
+
+/// Encode a lossless WebP.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// endec::encode_webp_lossless(&image, Path::new("tests/out/test_encode_webp_lossless.webp")).unwrap();
+/// ```
+pub fn encode_webp_lossless(image: &Image, path: &Path) -> RasterResult<()> {
+    encode_webp_lossless_to_writer(image, BufWriter::new(File::create(path)?))
+}
+
+/// Encode a lossless WebP to a writer. Works the same as `encode_webp_lossless`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` upon failure.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec;
+///
+/// let image = raster::open("tests/in/in2x2.png").unwrap();
+/// let mut data = Vec::new();
+/// endec::encode_webp_lossless_to_writer(&image, &mut data).unwrap();
+///
+/// assert!(!data.is_empty());
+/// ```
+pub fn encode_webp_lossless_to_writer<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    let encoder = webp::Encoder::from_rgba(&image.bytes, image.width as u32, image.height as u32);
+    writer.write_all(&encoder.encode_lossless())?;
+    Ok(())
+}
+
+// Private functions
+
+// Clip a rectangle to an image. Returns the left, top, right and bottom edges.
+fn clip_region(
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    image_w: u32,
+    image_h: u32,
+) -> RasterResult<(usize, usize, usize, usize)> {
+    let (left, top) = (cmp::max(0, x) as i64, cmp::max(0, y) as i64);
+    let right = cmp::min(x as i64 + width as i64, image_w as i64);
+    let bottom = cmp::min(y as i64 + height as i64, image_h as i64);
+    if left >= right || top >= bottom {
+        return Err(RasterError::PixelOutOfBounds(x, y));
+    }
+    Ok((left as usize, top as usize, right as usize, bottom as usize))
+}
+
+// Index of the palette color closest to the RGBA pixel.
+fn closest_color(palette: &[Color], pixel: &[u8]) -> u8 {
+    let mut closest = 0;
+    let mut closest_distance = i32::MAX;
+    for (index, color) in palette.iter().enumerate() {
+        let dr = color.r as i32 - pixel[0] as i32;
+        let dg = color.g as i32 - pixel[1] as i32;
+        let db = color.b as i32 - pixel[2] as i32;
+        let distance = dr * dr + dg * dg + db * db;
+        if distance < closest_distance {
+            closest = index;
+            closest_distance = distance;
+        }
+    }
+    closest as u8
+}
+
+// Pixel formats of DDS textures.
+#[derive(Clone, Copy, PartialEq)]
+enum DdsFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    // Bits per pixel and RGBA channel masks
+    Masked(u32, [u32; 4]),
+}
+
+// Decompress a 4x4 BC1, BC2 or BC3 block into RGBA pixels in row order.
+fn dds_block(format: DdsFormat, block: &[u8]) -> [[u8; 4]; 16] {
+    // BC2 and BC3 store the alpha block before the color block.
+    let color = if format == DdsFormat::Bc1 {
+        block
+    } else {
+        &block[8..]
+    };
+
+    // Two RGB565 endpoints and two interpolated colors
+    let c0 = u16::from_le_bytes([color[0], color[1]]);
+    let c1 = u16::from_le_bytes([color[2], color[3]]);
+    let rgb = |c: u16| {
+        let (r, g, b) = (
+            (c >> 11) as u32,
+            ((c >> 5) & 0x3f) as u32,
+            (c & 0x1f) as u32,
+        );
+        [
+            (r << 3) | (r >> 2),
+            (g << 2) | (g >> 4),
+            (b << 3) | (b >> 2),
+        ]
+    };
+    let (e0, e1) = (rgb(c0), rgb(c1));
+    let lerp = |w0: u32, w1: u32, d: u32| {
+        let mut out = [0u8; 4];
+        for c in 0..3 {
+            out[c] = ((e0[c] * w0 + e1[c] * w1) / d) as u8;
+        }
+        out[3] = 255;
+        out
+    };
+    let mut palette = [
+        [e0[0] as u8, e0[1] as u8, e0[2] as u8, 255],
+        [e1[0] as u8, e1[1] as u8, e1[2] as u8, 255],
+        [0; 4],
+        [0; 4],
+    ];
+    // BC1 switches to 3 colors and transparent black if the first endpoint isn't bigger.
+    if c0 > c1 || format != DdsFormat::Bc1 {
+        palette[2] = lerp(2, 1, 3);
+        palette[3] = lerp(1, 2, 3);
+    } else {
+        palette[2] = lerp(1, 1, 2);
+    }
+
+    let indices = u32::from_le_bytes([color[4], color[5], color[6], color[7]]);
+    let mut pixels = [[0u8; 4]; 16];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (i * 2)) & 0x3) as usize];
+    }
+
+    match format {
+        DdsFormat::Bc2 => {
+            // Explicit 4-bit alpha
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let alpha = (block[i / 2] >> ((i % 2) * 4)) & 0xf;
+                pixel[3] = alpha * 17;
+            }
+        }
+        DdsFormat::Bc3 => {
+            // Two alpha endpoints and 3-bit indices into 6 or 4 interpolated values
+            let (a0, a1) = (block[0] as u32, block[1] as u32);
+            let mut alphas = [a0, a1, 0, 0, 0, 0, 0, 255];
+            if a0 > a1 {
+                for (i, alpha) in alphas.iter_mut().enumerate().skip(2) {
+                    *alpha = (a0 * (8 - i as u32) + a1 * (i as u32 - 1)) / 7;
+                }
+            } else {
+                for (i, alpha) in alphas.iter_mut().enumerate().take(6).skip(2) {
+                    *alpha = (a0 * (6 - i as u32) + a1 * (i as u32 - 1)) / 5;
+                }
+            }
+            let indices = block[2..8]
+                .iter()
+                .rev()
+                .fold(0u64, |acc, &byte| (acc << 8) | byte as u64);
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                pixel[3] = alphas[((indices >> (i * 3)) & 0x7) as usize] as u8;
+            }
+        }
+        _ => {}
+    }
+    pixels
+}
+
+// Decode a JPEG, ending a truncated one early instead of failing. The decoder fills in the rest
+// of the image once it runs into the end of image marker.
+pub(crate) fn decode_jpeg_tolerant<R: Read>(
+    mut reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    match decode_jpeg_from_reader(Cursor::new(&data), limits) {
+        Err(RasterError::Decode(..)) | Err(RasterError::Io(_)) => {
+            data.extend_from_slice(&[0xff, 0xd9]);
+            decode_jpeg_from_reader(Cursor::new(&data), limits)
+        }
+        result => result,
+    }
+}
+
+// Decode a PNG, keeping the rows decoded before the data ran out or turned out to be corrupt.
+pub(crate) fn decode_png_tolerant<R: Read>(
+    reader: R,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.ignore_checksums(true);
+    png_image(decoder, limits, true)
+}
+
+// Largest size with the aspect ratio of `width` by `height` that fits within the maximum size.
+// Sizes that already fit are kept.
+fn fit_size(width: i32, height: i32, max_width: i32, max_height: i32) -> (i32, i32) {
+    if width <= max_width && height <= max_height {
+        return (width, height);
+    }
+    let ratio = f64::min(
+        max_width as f64 / width as f64,
+        max_height as f64 / height as f64,
+    );
+    (
+        cmp::max(1, (width as f64 * ratio).round() as i32),
+        cmp::max(1, (height as f64 * ratio).round() as i32),
+    )
+}
+
+// Read the loop count of the NETSCAPE2.0 application extension of a GIF, which comes before the
+// first frame. Without it the animation plays once.
+fn gif_loop_count(data: &[u8]) -> LoopCount {
+    // After the header, the screen descriptor and the global color table
+    let mut pos = 13;
+    if data.get(10).is_some_and(|flags| flags & 0x80 != 0) {
+        pos += 3 << ((data[10] & 0x07) + 1);
+    }
+    while let (Some(0x21), Some(&label)) = (data.get(pos), data.get(pos + 1)) {
+        let block = data.get(pos + 2..).unwrap_or(&[]);
+        if label == 0xff && block.starts_with(b"\x0bNETSCAPE2.0\x03\x01") {
+            return match block.get(14..16) {
+                Some(&[0, 0]) => LoopCount::Infinite,
+                Some(&[low, high]) => LoopCount::Finite(u16::from_le_bytes([low, high])),
+                _ => LoopCount::Finite(0),
+            };
+        }
+        // Skip the sub-blocks, which end with an empty one
+        pos += 2;
+        while let Some(&size) = data.get(pos) {
+            pos += 1 + size as usize;
+            if size == 0 {
+                break;
+            }
+        }
+    }
+    LoopCount::Finite(0)
+}
+
+// Decode a BMP style icon entry. It's a BMP without the file header, followed by a 1-bit
+// transparency mask. The height in the header includes the mask.
+fn ico_bmp(data: &[u8]) -> RasterResult<Image> {
+    let fail = |message: &str| RasterError::Decode(ImageFormat::Ico, message.to_string());
+    if data.len() < 40 {
+        return Err(fail("Icon entry is too short"));
+    }
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let read_u32 =
+        |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+
+    let header_size = read_u32(0) as usize;
+    let width = read_u32(4) as i32;
+    let height = read_u32(8) as i32 / 2;
+    let bits = read_u16(14) as usize;
+    let compression = read_u32(16);
+    let colors_used = read_u32(32) as usize;
+
+    if width <= 0 || height <= 0 || width > 256 || height > 256 {
+        return Err(fail("Invalid icon dimensions"));
+    }
+    match (bits, compression) {
+        (1, 0) | (4, 0) | (8, 0) | (24, 0) | (32, 0) | (32, 3) => {}
+        _ => return Err(fail("Unsupported icon bit depth or compression")),
+    }
+
+    // Palettes are stored as BGRX
+    let palette_size = if bits <= 8 {
+        if colors_used == 0 {
+            1 << bits
+        } else {
+            colors_used
+        }
+    } else {
+        0
+    };
+    let palette_start = header_size + if compression == 3 { 12 } else { 0 };
+    let pixels_start = palette_start + palette_size * 4;
+
+    // Rows are stored bottom-up and padded to a multiple of 4 bytes.
+    let row_size = (width as usize * bits).div_ceil(32) * 4;
+    let mask_row_size = (width as usize).div_ceil(32) * 4;
+    let mask_start = pixels_start + row_size * height as usize;
+    if data.len() < mask_start {
+        return Err(fail("Icon entry is too short"));
+    }
+    let palette = &data[palette_start..pixels_start];
+    let has_mask = data.len() >= mask_start + mask_row_size * height as usize;
+
+    let mut bytes = Vec::with_capacity((width * height * 4) as usize);
+    for y in (0..height as usize).rev() {
+        let row = &data[pixels_start + y * row_size..pixels_start + (y + 1) * row_size];
+        for x in 0..width as usize {
+            let mut pixel = match bits {
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                32 => [row[x * 4 + 2], row[x * 4 + 1], row[x * 4], row[x * 4 + 3]],
+                _ => {
+                    let per_byte = 8 / bits;
+                    let shift = 8 - bits * (x % per_byte + 1);
+                    let index = ((row[x / per_byte] >> shift) as usize) & ((1 << bits) - 1);
+                    match palette.get(index * 4..index * 4 + 3) {
+                        Some(color) => [color[2], color[1], color[0], 255],
+                        None => [0, 0, 0, 255],
+                    }
+                }
+            };
+            if bits != 32 && has_mask {
+                let mask = &data[mask_start + y * mask_row_size..];
+                if (mask[x / 8] >> (7 - x % 8)) & 1 == 1 {
+                    pixel[3] = 0;
+                }
+            }
+            bytes.extend_from_slice(&pixel);
+        }
+    }
+
+    Ok(Image {
+        width,
+        height,
+        bytes,
+    })
+}
+
+// Filter a row of RGBA pixels for PNG with the Paeth predictor, filter type byte included.
+fn paeth_filter(row: &[u8], previous: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    out.push(4);
+    for i in 0..row.len() {
+        let a = if i >= 4 { row[i - 4] as i16 } else { 0 };
+        let b = previous[i] as i16;
+        let c = if i >= 4 { previous[i - 4] as i16 } else { 0 };
+        let p = a + b - c;
+        let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+        let predicted = if pa <= pb && pa <= pc {
+            a
+        } else if pb <= pc {
+            b
+        } else {
+            c
+        };
+        out.push(row[i].wrapping_sub(predicted as u8));
+    }
+}
+
+// Parse the PAM header lines up to ENDHDR. Returns width, height, depth and maxval.
+fn pam_header(data: &[u8], pos: &mut usize) -> RasterResult<(u32, u32, usize, u32)> {
+    let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+    let mut tupltype = String::new();
+    loop {
+        pnm_skip_whitespace(data, pos);
+        let start = *pos;
+        while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        match &data[start..*pos] {
+            b"WIDTH" => width = Some(pnm_number(data, pos)?),
+            b"HEIGHT" => height = Some(pnm_number(data, pos)?),
+            b"DEPTH" => depth = Some(pnm_number(data, pos)?),
+            b"MAXVAL" => maxval = Some(pnm_number(data, pos)?),
+            b"TUPLTYPE" => {
+                let start = *pos;
+                while *pos < data.len() && data[*pos] != b'\n' {
+                    *pos += 1;
+                }
+                tupltype = String::from_utf8_lossy(&data[start..*pos])
+                    .trim()
+                    .to_string();
+            }
+            b"ENDHDR" => break,
+            b"" => return Err(pnm_error("Missing ENDHDR")),
+            _ => return Err(pnm_error("Invalid PAM header")),
+        }
+    }
+
+    let (width, height, depth, maxval) = match (width, height, depth, maxval) {
+        (Some(width), Some(height), Some(depth), Some(maxval)) => (width, height, depth, maxval),
+        _ => return Err(pnm_error("Incomplete PAM header")),
+    };
+    let expected_depth = match &tupltype[..] {
+        "BLACKANDWHITE" | "GRAYSCALE" => 1,
+        "BLACKANDWHITE_ALPHA" | "GRAYSCALE_ALPHA" => 2,
+        "RGB" => 3,
+        "RGB_ALPHA" => 4,
+        _ => {
+            return Err(pnm_error(&format!(
+                "Unsupported PAM tuple type {}",
+                tupltype
+            )))
+        }
+    };
+    if depth != expected_depth {
+        return Err(pnm_error("PAM depth does not match the tuple type"));
+    }
+    Ok((width, height, depth as usize, maxval))
+}
+
+fn pnm_error(message: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Pnm, message.to_string())
+}
+
+// Read the next decimal number of a PNM header or plain PNM data.
+fn pnm_number(data: &[u8], pos: &mut usize) -> RasterResult<u32> {
+    pnm_skip_whitespace(data, pos);
+    let start = *pos;
+    while *pos < data.len() && data[*pos].is_ascii_digit() {
+        *pos += 1;
+    }
+    String::from_utf8_lossy(&data[start..*pos])
+        .parse()
+        .map_err(|_| pnm_error("Invalid or missing number"))
+}
+
+// Skip whitespace and comments, which run from a # to the end of the line.
+fn pnm_skip_whitespace(data: &[u8], pos: &mut usize) {
+    while *pos < data.len() {
+        match data[*pos] {
+            b'#' => {
+                while *pos < data.len() && data[*pos] != b'\n' {
+                    *pos += 1;
+                }
+            }
+            b if b.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+}
+
+// Convert 8-bit PNG pixels as decoded with the EXPAND transformation into RGBA.
+fn png_rgba(color_type: png::ColorType, bytes: &[u8]) -> Vec<u8> {
+    match color_type {
+        // Indexed pixels have already been expanded to RGB or RGBA by the decoder
+        png::ColorType::Rgba | png::ColorType::Indexed => bytes.to_vec(),
+        png::ColorType::Rgb => bytes
+            .chunks(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => bytes
+            .chunks(2)
+            .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => bytes.iter().flat_map(|&g| vec![g, g, g, 255]).collect(),
+    }
+}
+
+// Decode a PNG into RGBA.
+fn png_image<R: Read>(
+    mut decoder: png::Decoder<R>,
+    limits: &DecodeLimits,
+    tolerant: bool,
+) -> RasterResult<Image> {
+    decoder.set_limits(png::Limits {
+        bytes: limits.max_bytes,
+    });
+    // 16-bit samples are reduced to their high byte, `decode_png16` keeps them
+    decoder.set_transformations(png::Transformations::STRIP_16);
     let mut reader = decoder.read_info()?;
+    let (width, height) = reader.info().size();
+    limits.check(ImageFormat::Png, width as u64, height as u64, 4)?;
     let mut bytes = vec![0; reader.output_buffer_size()];
 
-    reader.next_frame(&mut bytes)?;
+    match reader.next_frame(&mut bytes) {
+        Ok(_) => {}
+        // What was decoded so far is in the buffer, the rest stays zero
+        Err(png::DecodingError::IoError(_)) | Err(png::DecodingError::Format(_)) if tolerant => {}
+        Err(err) => return Err(err.into()),
+    }
     let info = reader.info();
 
     // Handle different color types
@@ -81,8 +4658,7 @@ pub fn decode_png(image_file: &File) -> RasterResult<Image> {
         png::ColorType::Grayscale => {
             // Convert grayscale to RGBA
             let mut rgba_bytes = Vec::with_capacity((info.width * info.height) as usize * 4);
-            for i in 0..(info.width * info.height) as usize {
-                let gray = bytes[i];
+            for &gray in bytes.iter().take((info.width * info.height) as usize) {
                 rgba_bytes.push(gray);
                 rgba_bytes.push(gray);
                 rgba_bytes.push(gray);
@@ -114,8 +4690,8 @@ pub fn decode_png(image_file: &File) -> RasterResult<Image> {
                 )
             })?;
 
-            for i in 0..(info.width * info.height) as usize {
-                let idx = bytes[i] as usize * 3;
+            for &index in bytes.iter().take((info.width * info.height) as usize) {
+                let idx = index as usize * 3;
                 if idx + 2 < palette.len() {
                     rgba_bytes.push(palette[idx]);
                     rgba_bytes.push(palette[idx + 1]);
@@ -136,21 +4712,137 @@ pub fn decode_png(image_file: &File) -> RasterResult<Image> {
     Ok(Image {
         width: info.width as i32,
         height: info.height as i32,
-        bytes: bytes,
+        bytes,
     })
 }
 
-// Encode PNG
-pub fn encode_png(image: &Image, path: &Path) -> RasterResult<()> {
-    // Open the file with basic error check
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
+// Decode a PNG reduced by a whole factor, so that it is still at least as large as `size`. Each
+// pixel is the average of a block of source pixels and only one source row is held at a time.
+// Interlaced PNGs are decoded at full size.
+fn png_scaled<R: Read>(reader: &mut png::Reader<R>, size: (i32, i32)) -> RasterResult<Image> {
+    let (src_w, src_h) = reader.info().size();
+    let (src_w, src_h) = (src_w as usize, src_h as usize);
+    let color_type = reader.output_color_type().0;
+    let factor = cmp::max(
+        1,
+        cmp::min(
+            src_w / cmp::max(1, size.0) as usize,
+            src_h / cmp::max(1, size.1) as usize,
+        ),
+    );
 
-    let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
-    encoder.set_color(png::ColorType::Rgba);
-    encoder.set_depth(png::BitDepth::Eight);
+    if reader.info().interlaced || factor == 1 {
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buffer)?;
+        return Ok(Image {
+            width: src_w as i32,
+            height: src_h as i32,
+            bytes: png_rgba(color_type, &buffer),
+        });
+    }
 
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&image.bytes)?;
-    Ok(())
+    let (width, height) = (src_w.div_ceil(factor), src_h.div_ceil(factor));
+    let mut bytes = Vec::with_capacity(width * height * 4);
+    let mut sums = vec![0u64; width * 4];
+    let mut counts = vec![0u64; width];
+    for y in 0..src_h {
+        let row = reader.next_row()?.ok_or_else(|| {
+            RasterError::Decode(ImageFormat::Png, "Unexpected end of image".to_string())
+        })?;
+        for (x, pixel) in png_rgba(color_type, row.data()).chunks(4).enumerate() {
+            let i = x / factor;
+            for (sum, &value) in sums[i * 4..i * 4 + 4].iter_mut().zip(pixel) {
+                *sum += value as u64;
+            }
+            counts[i] += 1;
+        }
+
+        // Emit a row once a block of rows is complete
+        if (y + 1) % factor == 0 || y + 1 == src_h {
+            for (sum, count) in sums.chunks_mut(4).zip(counts.iter_mut()) {
+                for value in sum.iter_mut() {
+                    bytes.push(((*value + *count / 2) / *count) as u8);
+                    *value = 0;
+                }
+                *count = 0;
+            }
+        }
+    }
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+// Decode the current page of a TIFF into RGBA.
+fn tiff_page<R: Read + Seek>(
+    decoder: &mut tiff::decoder::Decoder<R>,
+    limits: &DecodeLimits,
+) -> RasterResult<Image> {
+    let (width, height) = decoder.dimensions()?;
+    limits.check(ImageFormat::Tiff, width as u64, height as u64, 4)?;
+    let color_type = decoder.colortype()?;
+
+    let samples = tiff_samples(decoder.read_image()?)?;
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes: tiff_rgba(color_type, samples)?,
+    })
+}
+
+// Convert 8-bit TIFF samples to RGBA.
+fn tiff_rgba(color_type: tiff::ColorType, samples: Vec<u8>) -> RasterResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(samples.len() * 4);
+    match color_type {
+        tiff::ColorType::Gray(8) | tiff::ColorType::Gray(16) => {
+            for &gray in &samples {
+                bytes.extend_from_slice(&[gray, gray, gray, 255]);
+            }
+        }
+        tiff::ColorType::GrayA(8) | tiff::ColorType::GrayA(16) => {
+            for p in samples.chunks(2) {
+                bytes.extend_from_slice(&[p[0], p[0], p[0], p[1]]);
+            }
+        }
+        tiff::ColorType::RGB(8) | tiff::ColorType::RGB(16) => {
+            for p in samples.chunks(3) {
+                bytes.extend_from_slice(&[p[0], p[1], p[2], 255]);
+            }
+        }
+        tiff::ColorType::RGBA(8) | tiff::ColorType::RGBA(16) => bytes = samples,
+        tiff::ColorType::CMYK(8) => {
+            for p in samples.chunks(4) {
+                let k = 255 - p[3] as u32;
+                let r = (255 - p[0] as u32) * k / 255;
+                let g = (255 - p[1] as u32) * k / 255;
+                let b = (255 - p[2] as u32) * k / 255;
+                bytes.extend_from_slice(&[r as u8, g as u8, b as u8, 255]);
+            }
+        }
+        color_type => {
+            return Err(RasterError::Decode(
+                ImageFormat::Tiff,
+                format!("Unsupported color type {:?}", color_type),
+            ))
+        }
+    }
+    Ok(bytes)
+}
+
+// Reduce TIFF samples to 8 bits per channel.
+fn tiff_samples(result: tiff::decoder::DecodingResult) -> RasterResult<Vec<u8>> {
+    match result {
+        tiff::decoder::DecodingResult::U8(samples) => Ok(samples),
+        tiff::decoder::DecodingResult::U16(samples) => {
+            Ok(samples.iter().map(|s| (s >> 8) as u8).collect())
+        }
+        _ => Err(RasterError::Decode(
+            ImageFormat::Tiff,
+            "Unsupported sample format".to_string(),
+        )),
+    }
 }
@@ -6,8 +6,15 @@ use std::num::ParseIntError;
 
 // from external crates
 use gif;
+use jpeg_decoder;
+#[cfg(feature = "heif")]
+use libheif_rs;
 use piston_image;
 use png;
+use qoi;
+#[cfg(feature = "svg")]
+use resvg;
+use tiff;
 
 // from local crate
 use ImageFormat;
@@ -17,8 +24,13 @@ use ImageFormat;
 pub enum RasterError {
     /// File system read/write errors.
     Io(IoError),
+    /// Download that failed, like a response with an error status or one that is too large.
+    Http(String),
     /// Getting or setting pixels outside of image bounds.
     PixelOutOfBounds(i32, i32),
+    /// Image of another size than needed, like a mask for an image. Holds the size of the image
+    /// that doesn't fit.
+    SizeMismatch(i32, i32),
     /// Invalid start index.
     InvalidStartIndex(i32),
     /// Hex format not supported.
@@ -27,12 +39,21 @@ pub enum RasterError {
     HexParse(ParseIntError),
     /// Blending error.
     BlendingImageFallsOutsideCanvas,
+    /// Corners of a quad that can't be warped between, like 3 of them in a line.
+    InvalidQuad,
+    /// Convolution kernel with an even width or height, or without a value for each cell. Holds
+    /// the width and height.
+    InvalidKernel(usize, usize),
     /// Invalid gamma parameter.
     InvalidGamma(f32),
+    /// ICC profile that can't be read or converted from.
+    InvalidIccProfile(String),
     /// Error during decoding.
     Decode(ImageFormat, String),
     /// Error during encoding.
     Encode(ImageFormat, String),
+    /// Image is larger than the `DecodeLimits` allow.
+    LimitExceeded(ImageFormat),
     /// Unsupported image format.
     UnsupportedFormat(String),
     /// Error that does not belong in other variants.
@@ -46,6 +67,30 @@ impl From<IoError> for RasterError {
     }
 }
 
+// AVIF
+/// Convert avif_decode::Error to RasterError::Decode
+#[cfg(feature = "avif")]
+impl From<avif_decode::Error> for RasterError {
+    fn from(err: avif_decode::Error) -> RasterError {
+        match err {
+            avif_decode::Error::Io(io_err) => RasterError::Io(io_err),
+            err => RasterError::Decode(ImageFormat::Avif, err.to_string()),
+        }
+    }
+}
+
+// EXR
+/// Convert exr::error::Error to RasterError::Decode
+#[cfg(feature = "exr")]
+impl From<exr::error::Error> for RasterError {
+    fn from(err: exr::error::Error) -> RasterError {
+        match err {
+            exr::error::Error::Io(io_err) => RasterError::Io(io_err),
+            err => RasterError::Decode(ImageFormat::Exr, err.to_string()),
+        }
+    }
+}
+
 // GIF
 /// Convert gif::DecodingError to RasterError::Decode
 impl From<gif::DecodingError> for RasterError {
@@ -63,31 +108,56 @@ impl From<gif::DecodingError> for RasterError {
 }
 // NOTE: gif::EncodingError does not exist in gif crate.
 
+// HEIF
+/// Convert libheif_rs::HeifError to RasterError::Decode
+#[cfg(feature = "heif")]
+impl From<libheif_rs::HeifError> for RasterError {
+    fn from(err: libheif_rs::HeifError) -> RasterError {
+        RasterError::Decode(ImageFormat::Heif, err.message)
+    }
+}
+
 // JPEG
-/// Convert gif::DecodingError to RasterError::Decode
+/// Convert piston_image::ImageError to RasterError::Decode
 // NOTE: We assume that we are in decoding jpeg since this error's entry point is only in
-// raster::open
+// raster::open. Use RasterError::from_image_error for other formats decoded by piston_image.
 impl From<piston_image::ImageError> for RasterError {
     fn from(err: piston_image::ImageError) -> RasterError {
+        RasterError::from_image_error(ImageFormat::Jpeg, err)
+    }
+}
+
+/// Convert jpeg_decoder::Error to RasterError::Decode
+impl From<jpeg_decoder::Error> for RasterError {
+    fn from(err: jpeg_decoder::Error) -> RasterError {
         match err {
-            piston_image::ImageError::FormatError(msg) => {
-                RasterError::Decode(ImageFormat::Jpeg, msg)
-            }
+            jpeg_decoder::Error::Io(io_err) => RasterError::Io(io_err),
+            err => RasterError::Decode(ImageFormat::Jpeg, err.to_string()),
+        }
+    }
+}
+
+impl RasterError {
+    /// Convert piston_image::ImageError to RasterError::Decode for the given format.
+    pub(crate) fn from_image_error(
+        format: ImageFormat,
+        err: piston_image::ImageError,
+    ) -> RasterError {
+        match err {
+            piston_image::ImageError::FormatError(msg) => RasterError::Decode(format, msg),
             piston_image::ImageError::DimensionError => {
-                RasterError::Decode(ImageFormat::Jpeg, "DimensionError".to_string())
-            }
-            piston_image::ImageError::UnsupportedError(msg) => {
-                RasterError::Decode(ImageFormat::Jpeg, msg)
+                RasterError::Decode(format, "DimensionError".to_string())
             }
+            piston_image::ImageError::UnsupportedError(msg) => RasterError::Decode(format, msg),
             piston_image::ImageError::UnsupportedColor(_) => {
-                RasterError::Decode(ImageFormat::Jpeg, "UnsupportedColor".to_string())
+                RasterError::Decode(format, "UnsupportedColor".to_string())
             }
             piston_image::ImageError::NotEnoughData => {
-                RasterError::Decode(ImageFormat::Jpeg, "NotEnoughData".to_string())
+                RasterError::Decode(format, "NotEnoughData".to_string())
             }
             piston_image::ImageError::IoError(io_err) => RasterError::Io(io_err),
             piston_image::ImageError::ImageEnd => {
-                RasterError::Decode(ImageFormat::Jpeg, "ImageEnd".to_string())
+                RasterError::Decode(format, "ImageEnd".to_string())
             }
         }
     }
@@ -130,6 +200,39 @@ impl From<png::EncodingError> for RasterError {
     }
 }
 
+// QOI
+/// Convert qoi::Error to RasterError::Decode
+// NOTE: Encoding errors are mapped to RasterError::Encode in endec.
+impl From<qoi::Error> for RasterError {
+    fn from(err: qoi::Error) -> RasterError {
+        match err {
+            qoi::Error::IoError(io_err) => RasterError::Io(io_err),
+            err => RasterError::Decode(ImageFormat::Qoi, err.to_string()),
+        }
+    }
+}
+
+// SVG
+/// Convert usvg::Error to RasterError::Decode
+#[cfg(feature = "svg")]
+impl From<resvg::usvg::Error> for RasterError {
+    fn from(err: resvg::usvg::Error) -> RasterError {
+        RasterError::Decode(ImageFormat::Svg, err.to_string())
+    }
+}
+
+// TIFF
+/// Convert tiff::TiffError to RasterError::Decode
+// NOTE: Encoding errors are mapped to RasterError::Encode in endec.
+impl From<tiff::TiffError> for RasterError {
+    fn from(err: tiff::TiffError) -> RasterError {
+        match err {
+            tiff::TiffError::IoError(io_err) => RasterError::Io(io_err),
+            err => RasterError::Decode(ImageFormat::Tiff, err.to_string()),
+        }
+    }
+}
+
 /// [Type alias](https://doc.rust-lang.org/book/error-handling.html#the-result-type-alias-idiom)
 /// for Result.
 pub type RasterResult<T> = Result<T, RasterError>;
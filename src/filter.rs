@@ -7,6 +7,8 @@ use std::cmp;
 
 // from local crate
 use error::{RasterError, RasterResult};
+use hdr;
+use interpolate::{sample, InterpolationMode};
 use Color;
 use Image;
 
@@ -28,6 +30,345 @@ pub enum Orientation {
     Both,
 }
 
+/// An enum for how filters read pixels past the edges of an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeMode {
+    /// Repeat the pixels at the edge.
+    Clamp,
+    /// Continue from the opposite edge, as if the image were tiled.
+    Wrap,
+    /// Reflect the image at the edge.
+    Mirror,
+}
+
+/// An enum for the kernels used to find the gradient of an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeOperator {
+    /// The Sobel kernels.
+    Sobel,
+    /// The Scharr kernels, which are more accurate for the direction of edges. Scaled to the
+    /// range of `Sobel`.
+    Scharr,
+}
+
+/// The gradient of an image, from `gradient`. Values are in row order, one for each pixel.
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    /// Width of the image in pixels.
+    pub width: i32,
+
+    /// Height of the image in pixels.
+    pub height: i32,
+
+    /// How strong the edge at a pixel is. An edge between black and white is about 1020.
+    pub magnitude: Vec<f32>,
+
+    /// Direction in which the image gets brighter, in radians clockwise from the x axis.
+    pub direction: Vec<f32>,
+}
+
+impl Gradient {
+    /// Convert the magnitude into a grayscale edge map, clamping edges over 255.
+    ///
+    /// # Examples
+    /// ```
+    /// use raster::filter::{self, EdgeOperator};
+    ///
+    /// let image = raster::open("tests/in/sample.jpg").unwrap();
+    /// let edges = filter::gradient(&image, EdgeOperator::Sobel).to_image();
+    /// raster::save(&edges, "tests/out/test_filter_gradient.jpg").unwrap();
+    /// ```
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::blank(self.width, self.height);
+        for (pixel, magnitude) in image.pixels_mut().zip(self.magnitude.iter()) {
+            let value = magnitude.round().clamp(0.0, 255.0) as u8;
+            pixel.copy_from_slice(&[value, value, value, 255]);
+        }
+        image
+    }
+}
+
+/// A convolution kernel of any odd width and height, for `convolve_kernel`.
+///
+/// The weighted sum of the pixels under the kernel is divided by the divisor, then the bias is
+/// added. The divisor defaults to 1 and the bias to 0.
+///
+/// # Examples
+/// ```
+/// use raster::filter::Kernel;
+///
+/// // A 5x1 horizontal blur, divided by the sum of its values
+/// let kernel = Kernel::new(5, 1, vec![1.0, 2.0, 3.0, 2.0, 1.0]).unwrap().normalize();
+///
+/// // Sizes must be odd and match the values
+/// assert!(Kernel::new(2, 1, vec![1.0, 1.0]).is_err());
+/// assert!(Kernel::new(3, 3, vec![1.0; 8]).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    divisor: f32,
+    bias: f32,
+}
+
+impl Kernel {
+    /// Create a kernel from its values in rows from the top left.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::InvalidKernel` holding the width and height if either is even, or
+    /// there aren't `width * height` values.
+    pub fn new(width: usize, height: usize, values: Vec<f32>) -> RasterResult<Kernel> {
+        if width % 2 != 1 || height % 2 != 1 || values.len() != width * height {
+            return Err(RasterError::InvalidKernel(width, height));
+        }
+        Ok(Kernel {
+            width,
+            height,
+            values,
+            divisor: 1.0,
+            bias: 0.0,
+        })
+    }
+
+    /// Divide by the sum of the values, so the kernel keeps the brightness of the image. Kernels
+    /// summing to 0, like edge detectors, keep a divisor of 1.
+    pub fn normalize(mut self) -> Kernel {
+        let sum: f32 = self.values.iter().sum();
+        self.divisor = if sum != 0.0 { sum } else { 1.0 };
+        self
+    }
+
+    /// Set what the weighted sum is divided by.
+    pub fn divisor(mut self, divisor: f32) -> Kernel {
+        self.divisor = divisor;
+        self
+    }
+
+    /// Set the value added after dividing, like 128 to center an edge map on gray.
+    pub fn bias(mut self, bias: f32) -> Kernel {
+        self.bias = bias;
+        self
+    }
+}
+
+/// Settings of a levels adjustment, for `levels` and `levels_channels`.
+///
+/// Values from `in_black` to `in_white` are stretched to the full range, then bent by `gamma`,
+/// where more than 1.0 lightens the midtones, and finally squeezed into `out_black` to
+/// `out_white`. The default leaves values as they are.
+///
+/// # Examples
+/// ```
+/// use raster::filter::Levels;
+///
+/// // Pull in a faded blue channel
+/// let blue = Levels { in_black: 20, in_white: 230, ..Levels::default() };
+/// assert_eq!(1.0, blue.gamma);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    /// Input value that becomes `out_black`. Anything darker is clipped.
+    pub in_black: u8,
+
+    /// Input value that becomes `out_white`. Anything lighter is clipped.
+    pub in_white: u8,
+
+    /// Curve of the midtones, from 0.01 to 9.99.
+    pub gamma: f32,
+
+    /// Darkest output value.
+    pub out_black: u8,
+
+    /// Lightest output value.
+    pub out_white: u8,
+}
+
+impl Default for Levels {
+    fn default() -> Levels {
+        Levels {
+            in_black: 0,
+            in_white: 255,
+            gamma: 1.0,
+            out_black: 0,
+            out_white: 255,
+        }
+    }
+}
+
+impl Levels {
+    // The output for every input value.
+    fn table(&self) -> RasterResult<Vec<u8>> {
+        if self.gamma.is_nan() || self.gamma < 0.01 || self.gamma > 9.99 {
+            return Err(RasterError::InvalidGamma(self.gamma));
+        }
+        let black = self.in_black as f32;
+        let range = (self.in_white as f32 - black).max(1.0);
+        let (out_black, out_white) = (self.out_black as f32, self.out_white as f32);
+        Ok((0..256)
+            .map(|v| {
+                let value = ((v as f32 - black) / range)
+                    .clamp(0.0, 1.0)
+                    .powf(1.0 / self.gamma);
+                (out_black + value * (out_white - out_black)).round() as u8
+            })
+            .collect())
+    }
+}
+
+/// Turn an image black and white, comparing each pixel with the mean brightness of the
+/// `block_size` by `block_size` pixels around it minus `c`. Brighter pixels become white and the
+/// rest black, with the alpha kept. Unlike a global `threshold`, it follows uneven lighting, like
+/// shadows across a scanned page. Near the edges the block is cut to the part inside the image.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::adaptive_threshold(&mut image, 15, 5.0).unwrap();
+/// raster::save(&image, "tests/out/test_filter_adaptive_threshold.png").unwrap();
+/// ```
+pub fn adaptive_threshold(src: &mut Image, block_size: u32, c: f32) -> RasterResult<()> {
+    let (w, h) = (src.width.max(0) as usize, src.height.max(0) as usize);
+    let luma = lumas(src);
+    // Sums of the luma above and to the left of each pixel, with a row and column of zeros
+    let mut sums = vec![0u64; (w + 1) * (h + 1)];
+    for y in 0..h {
+        let mut row = 0;
+        for x in 0..w {
+            row += luma[y * w + x] as u64;
+            sums[(y + 1) * (w + 1) + x + 1] = sums[y * (w + 1) + x + 1] + row;
+        }
+    }
+
+    let radius = block_size as usize / 2;
+    for (i, pixel) in src.pixels_mut().enumerate() {
+        let (x, y) = (i % w, i / w);
+        let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let (x1, y1) = (cmp::min(x + radius + 1, w), cmp::min(y + radius + 1, h));
+        let sum = sums[y1 * (w + 1) + x1] + sums[y0 * (w + 1) + x0]
+            - sums[y0 * (w + 1) + x1]
+            - sums[y1 * (w + 1) + x0];
+        let mean = sum as f32 / ((x1 - x0) * (y1 - y0)) as f32;
+        let value = if luma[i] as f32 > mean - c { 255 } else { 0 };
+        pixel[..3].copy_from_slice(&[value, value, value]);
+    }
+    Ok(())
+}
+
+/// Correct the color cast of an image automatically with the gray world assumption: on average
+/// a scene is gray, so red and blue are scaled in linear light until their averages match green.
+/// Pixels count by their alpha. Returns the gains given to red, green and blue.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// // A gray card under yellow light
+/// let mut image = Image::filled(2, 2, Color::rgb(140, 128, 100));
+/// let gains = filter::auto_white_balance(&mut image).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((128, 128, 128), (pixel.r, pixel.g, pixel.b));
+/// assert!(gains[0] < 1.0 && gains[2] > 1.0);
+/// ```
+pub fn auto_white_balance(src: &mut Image) -> RasterResult<[f32; 3]> {
+    let mut sums = [0.0f64; 3];
+    for pixel in src.pixels() {
+        let alpha = pixel[3] as f64;
+        for (sum, &value) in sums.iter_mut().zip(pixel[..3].iter()) {
+            *sum += hdr::to_linear(value) as f64 * alpha;
+        }
+    }
+    let gain = |sum: f64| {
+        if sum > 0.0 && sums[1] > 0.0 {
+            (sums[1] / sum) as f32
+        } else {
+            1.0
+        }
+    };
+    let gains = [gain(sums[0]), 1.0, gain(sums[2])];
+    scale_linear(src, gains);
+    Ok(gains)
+}
+
+/// Apply a bilateral filter, smoothing an image while keeping its edges. Each pixel becomes an
+/// average of its neighbors weighted by both how near they are, with a standard deviation of
+/// `sigma_spatial` pixels, and how close their colors are, with a standard deviation of
+/// `sigma_color` in 0 - 255 values. Neighbors across an edge differ in color, so they count for
+/// little. A sigma of 0 or less leaves the image as it is.
+///
+/// The cost grows with the square of `sigma_spatial`, so keep it small and run it a few times
+/// for stronger smoothing.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::bilateral(&mut image, 3.0, 25.0).unwrap();
+/// raster::save(&image, "tests/out/test_filter_bilateral.jpg").unwrap();
+/// ```
+pub fn bilateral(src: &mut Image, sigma_spatial: f32, sigma_color: f32) -> RasterResult<()> {
+    if sigma_spatial.is_nan() || sigma_spatial <= 0.0 || sigma_color.is_nan() || sigma_color <= 0.0
+    {
+        return Ok(());
+    }
+    // No wider than the image, even for huge sigmas
+    let limit = cmp::max(src.width, src.height).max(0);
+    let radius = cmp::min((sigma_spatial * 2.0).ceil() as i32, limit);
+    let mut spatial = Vec::new();
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let d = (dx as f32).powi(2) + (dy as f32).powi(2);
+            spatial.push((dx, dy, (-d / (2.0 * sigma_spatial * sigma_spatial)).exp()));
+        }
+    }
+    // Weights by color distance, rounded to whole values
+    let range: Vec<f32> = (0..443)
+        .map(|d| (-(d * d) as f32 / (2.0 * sigma_color * sigma_color)).exp())
+        .collect();
+
+    let copy = src.clone();
+    let (w, h) = (src.width, src.height);
+    let width = cmp::max(w, 1) as usize;
+    let pixel = |x: i32, y: i32| {
+        let i = (y.clamp(0, h - 1) as usize * w as usize + x.clamp(0, w - 1) as usize) * 4;
+        &copy.bytes[i..i + 4]
+    };
+    for (i, out) in src.pixels_mut().enumerate() {
+        let (x, y) = ((i % width) as i32, (i / width) as i32);
+        let center = pixel(x, y);
+        let mut sum = [0.0; 4];
+        let mut total = 0.0;
+        for &(dx, dy, weight) in &spatial {
+            let other = pixel(x + dx, y + dy);
+            let d: f32 = (0..3)
+                .map(|c| (other[c] as f32 - center[c] as f32).powi(2))
+                .sum();
+            let weight = weight * range[d.sqrt() as usize];
+            let alpha = other[3] as f32 * weight;
+            for c in 0..3 {
+                sum[c] += other[c] as f32 * alpha;
+            }
+            sum[3] += alpha;
+            total += weight;
+        }
+        for c in 0..3 {
+            out[c] = if sum[3] > 0.0 {
+                (sum[c] / sum[3]).round().clamp(0.0, 255.0) as u8
+            } else {
+                0
+            };
+        }
+        out[3] = (sum[3] / total).round().clamp(0.0, 255.0) as u8;
+    }
+    Ok(())
+}
+
 /// Apply box or Gaussian blur.
 ///
 /// # Examples
@@ -70,6 +411,52 @@ pub fn blur(src: &mut Image, mode: BlurMode) -> RasterResult<()> {
     }
 }
 
+/// Apply box blur with a radius in pixels, averaging a square of `radius * 2 + 1` pixels around
+/// each one. A sliding sum over the rows and then the columns makes the cost the same for any
+/// radius, so it suits large blurs. Running more `passes` makes it smoother, and 3 passes come
+/// close to a Gaussian blur. A radius or passes of 0 leaves the image as it is, and radii past the
+/// width or height are capped.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::box_blur(&mut image, 20, 3).unwrap();
+/// raster::save(&image, "tests/out/test_filter_box_blur_radius.jpg").unwrap();
+/// ```
+pub fn box_blur(src: &mut Image, radius: u32, passes: u32) -> RasterResult<()> {
+    if radius == 0 {
+        return Ok(());
+    }
+    for _ in 0..passes {
+        separable(src, |line| {
+            // No wider than the line, even for huge radii
+            let radius = cmp::min(radius as usize, line.len()) as isize;
+            let size = (radius * 2 + 1) as f32;
+            let last = line.len() as isize - 1;
+            let at = |i: isize| line[i.clamp(0, last) as usize];
+            let mut sum = [0.0; 4];
+            for i in -radius..=radius {
+                for (total, value) in sum.iter_mut().zip(at(i).iter()) {
+                    *total += value;
+                }
+            }
+            let mut filtered = Vec::with_capacity(line.len());
+            for i in 0..line.len() as isize {
+                filtered.push([sum[0] / size, sum[1] / size, sum[2] / size, sum[3] / size]);
+                // Slide the window, adding the pixel entering it and dropping the one leaving
+                let (entering, leaving) = (at(i + radius + 1), at(i - radius));
+                for c in 0..4 {
+                    sum[c] += entering[c] - leaving[c];
+                }
+            }
+            filtered
+        });
+    }
+    Ok(())
+}
+
 /// Apply brightness.
 ///
 /// A brightness of < 0.0 will darken the image and brightness of > 1.0 will lighten it.
@@ -114,6 +501,143 @@ pub fn brightness(src: &mut Image, factor: f32) -> RasterResult<()> {
     Ok(())
 }
 
+/// Adjust brightness and contrast together, the way photo editors do. Contrast stretches the
+/// channels away from the middle gray of 128, so 1.0 leaves them as they are, 2.0 doubles the
+/// contrast and 0.0 turns everything gray. `brightness` is then added, from -255 to 255. Values are
+/// clamped to 0 - 255 and alpha is kept. Use `brightness_contrast_with` to pivot around another
+/// value.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(100, 128, 200));
+/// filter::brightness_contrast(&mut image, 10, 1.5).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((96, 138, 246), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn brightness_contrast(src: &mut Image, brightness: i32, contrast: f32) -> RasterResult<()> {
+    brightness_contrast_with(src, brightness, contrast, 128)
+}
+
+/// Adjust brightness and contrast with contrast pivoting around `midpoint`, like the average
+/// brightness of a dark photo, instead of 128. Works the same as `brightness_contrast` otherwise.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(60, 80, 40));
+/// filter::brightness_contrast_with(&mut image, 0, 2.0, 60).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((60, 100, 20), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn brightness_contrast_with(
+    src: &mut Image,
+    brightness: i32,
+    contrast: f32,
+    midpoint: u8,
+) -> RasterResult<()> {
+    let contrast = contrast.max(0.0);
+    let midpoint = midpoint as f32;
+    let table: Vec<u8> = (0..256)
+        .map(|v| {
+            let value = (v as f32 - midpoint) * contrast + midpoint + brightness as f32;
+            value.round().clamp(0.0, 255.0) as u8
+        })
+        .collect();
+    for pixel in src.pixels_mut() {
+        for value in pixel[..3].iter_mut() {
+            *value = table[*value as usize];
+        }
+    }
+    Ok(())
+}
+
+/// Apply Canny edge detection, turning the image into thin white edges on black.
+///
+/// The image is smoothed with a Gaussian blur to ignore noise, and its Sobel gradient is thinned
+/// to the pixels where the magnitude peaks across the edge. Peaks over `high_threshold` are
+/// edges, and peaks over `low_threshold` are too if they connect to one, which follows faint
+/// parts of strong edges without picking up noise. Thresholds are in magnitudes of `gradient`,
+/// where an edge between black and white is about 1020.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::canny(&mut image, 50.0, 150.0).unwrap();
+/// raster::save(&image, "tests/out/test_filter_canny.png").unwrap();
+/// ```
+pub fn canny(src: &mut Image, low_threshold: f32, high_threshold: f32) -> RasterResult<()> {
+    let mut smooth = src.clone();
+    gaussian_blur(&mut smooth, 1.4)?;
+    let gradient = gradient(&smooth, EdgeOperator::Sobel);
+    let (w, h) = (src.width.max(0) as usize, src.height.max(0) as usize);
+    let magnitude = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x >= w as isize || y >= h as isize {
+            0.0
+        } else {
+            gradient.magnitude[y as usize * w + x as usize]
+        }
+    };
+
+    // Keep the pixels that are at least as strong as both neighbors across the edge
+    let mut peaks = vec![0.0; w * h];
+    for (i, peak) in peaks.iter_mut().enumerate() {
+        let (x, y) = ((i % w) as isize, (i / w) as isize);
+        let value = gradient.magnitude[i];
+        if value < low_threshold || value == 0.0 {
+            continue;
+        }
+        let angle = gradient.direction[i].to_degrees().rem_euclid(180.0);
+        let (dx, dy) = if !(22.5..157.5).contains(&angle) {
+            (1, 0)
+        } else if angle < 67.5 {
+            (1, 1)
+        } else if angle < 112.5 {
+            (0, 1)
+        } else {
+            (-1, 1)
+        };
+        if value >= magnitude(x + dx, y + dy) && value >= magnitude(x - dx, y - dy) {
+            *peak = value;
+        }
+    }
+
+    // Grow edges from the strong peaks into the weak ones they touch
+    let mut edges = vec![false; w * h];
+    let mut stack: Vec<usize> = (0..w * h).filter(|&i| peaks[i] >= high_threshold).collect();
+    for &i in &stack {
+        edges[i] = true;
+    }
+    while let Some(i) = stack.pop() {
+        let (x, y) = ((i % w) as isize, (i / w) as isize);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= w as isize || ny >= h as isize {
+                    continue;
+                }
+                let j = ny as usize * w + nx as usize;
+                if !edges[j] && peaks[j] > 0.0 {
+                    edges[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+    }
+
+    for (pixel, &edge) in src.pixels_mut().zip(edges.iter()) {
+        let value = if edge { 255 } else { 0 };
+        pixel.copy_from_slice(&[value, value, value, 255]);
+    }
+    Ok(())
+}
+
 /// Apply a convolution matrix.
 ///
 /// The divisor is applied as the last step of convolution.
@@ -220,6 +744,132 @@ pub fn convolve(src: &mut Image, matrix: [[i32; 3]; 3], divisor: i32) -> RasterR
     Ok(())
 }
 
+/// Apply a convolution kernel of any size, with the edges handled by `edge`. Works like
+/// `convolve`, but with float weights and only on the color channels, so alpha is left as it is.
+///
+/// # Examples
+/// ```
+/// use raster::filter::{self, EdgeMode, Kernel};
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+///
+/// // Outline edges on a gray background
+/// let kernel = Kernel::new(3, 3, vec![-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0])
+///     .unwrap()
+///     .bias(128.0);
+/// filter::convolve_kernel(&mut image, &kernel, EdgeMode::Mirror).unwrap();
+/// raster::save(&image, "tests/out/test_filter_convolve_kernel.jpg").unwrap();
+/// ```
+pub fn convolve_kernel(src: &mut Image, kernel: &Kernel, edge: EdgeMode) -> RasterResult<()> {
+    if src.width <= 0 || src.height <= 0 {
+        return Ok(());
+    }
+    let copy = src.clone();
+    let (w, h) = (src.width as isize, src.height as isize);
+    let (half_w, half_h) = ((kernel.width / 2) as isize, (kernel.height / 2) as isize);
+
+    // Kernel taps with their offsets, skipping zeros
+    let taps: Vec<(isize, isize, f32)> = kernel
+        .values
+        .iter()
+        .enumerate()
+        .filter(|&(_, &value)| value != 0.0)
+        .map(|(i, &value)| {
+            let dx = (i % kernel.width) as isize - half_w;
+            let dy = (i / kernel.width) as isize - half_h;
+            (dx, dy, value)
+        })
+        .collect();
+
+    for (i, pixel) in src.pixels_mut().enumerate() {
+        let (x, y) = (i as isize % w, i as isize / w);
+        let mut sum = [0.0; 3];
+        for &(dx, dy, value) in &taps {
+            let sx = edge_index(x + dx, w, edge);
+            let sy = edge_index(y + dy, h, edge);
+            let j = (sy * w as usize + sx) * 4;
+            for (c, total) in sum.iter_mut().enumerate() {
+                *total += copy.bytes[j + c] as f32 * value;
+            }
+        }
+        for (value, total) in pixel.iter_mut().zip(sum.iter()) {
+            *value = (total / kernel.divisor + kernel.bias)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+    }
+    Ok(())
+}
+
+/// Adjust tones with a curve through `points` of input and output values, like the Curves dialog
+/// of a photo editor. The curve is a monotone cubic spline, so it is smooth and never overshoots
+/// between points: rising points give a rising curve. Before the first point and after the last
+/// the curve is flat. The same curve applies to red, green and blue, and alpha is kept. No points
+/// leave the image as it is.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// // An S curve for more contrast in the midtones
+/// let mut image = Image::from_fn(3, 1, |x, _| Color::rgb(64 + x as u8 * 64, 0, 0));
+/// filter::curves(&mut image, &[(0, 0), (64, 48), (192, 208), (255, 255)]).unwrap();
+///
+/// assert_eq!(48, image.get_pixel(0, 0).unwrap().r);
+/// assert_eq!(128, image.get_pixel(1, 0).unwrap().r);
+/// assert_eq!(208, image.get_pixel(2, 0).unwrap().r);
+/// ```
+pub fn curves(src: &mut Image, points: &[(u8, u8)]) -> RasterResult<()> {
+    curves_channels(src, points, points, points)
+}
+
+/// Adjust tones with separate curves for the red, green and blue channels. Works the same as
+/// `curves` otherwise.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// // Cool the midtones down
+/// let mut image = Image::filled(2, 2, Color::rgb(128, 128, 128));
+/// filter::curves_channels(&mut image, &[(128, 112)], &[], &[(0, 0), (128, 144), (255, 255)])
+///     .unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((112, 128, 144), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn curves_channels(
+    src: &mut Image,
+    red: &[(u8, u8)],
+    green: &[(u8, u8)],
+    blue: &[(u8, u8)],
+) -> RasterResult<()> {
+    let tables = [curve_table(red), curve_table(green), curve_table(blue)];
+    for pixel in src.pixels_mut() {
+        for (value, table) in pixel[..3].iter_mut().zip(tables.iter()) {
+            *value = table[*value as usize];
+        }
+    }
+    Ok(())
+}
+
+/// Color an image in two tones, mapping black to `shadow`, white to `highlight`, and the
+/// brightness in between to a mix of the two. Alpha is kept. See `gradient_map` for more colors.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color};
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// let navy = Color::hex("#1d1a4f").unwrap();
+/// let gold = Color::hex("#f7c948").unwrap();
+/// filter::duotone(&mut image, navy, gold).unwrap();
+/// raster::save(&image, "tests/out/test_filter_duotone.jpg").unwrap();
+/// ```
+pub fn duotone(src: &mut Image, shadow: Color, highlight: Color) -> RasterResult<()> {
+    gradient_map(src, &[shadow, highlight])
+}
+
 /// Apply emboss.
 ///
 /// # Examples
@@ -315,10 +965,35 @@ fn sobel_both(
     Ok(())
 }
 
+/// Change the exposure of an image by `stops`, like opening or closing the aperture of a camera.
+/// Each stop doubles or halves the light, so the work is done in linear light rather than on
+/// the sRGB values, which keeps the colors of the shadows and highlights natural. Alpha is kept.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(100, 50, 255));
+/// filter::exposure(&mut image, 1.0).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((138, 71, 255), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn exposure(src: &mut Image, stops: f32) -> RasterResult<()> {
+    let gain = stops.exp2();
+    scale_linear(src, [gain, gain, gain]);
+    Ok(())
+}
+
 /// Apply a gamma correction.
 ///
 /// Gamma can be a value from 0.01 - 9.99.
-/// A gamma < 1.0 will darken and a gamma > 1.0 will lighten the image.
+/// A gamma < 1.0 will darken and a gamma > 1.0 will lighten the image, like the gamma of
+/// `Levels`. Each channel value is raised to the power of `1 / gamma`.
+///
+/// # Errors
+///
+/// If `gamma` is out of range, this will fail with `RasterError::InvalidGamma`.
 ///
 /// # Examples
 /// ```
@@ -336,53 +1011,192 @@ fn sobel_both(
 /// ![](https://kosinix.github.io/raster/out/test_filter_gamma.jpg)
 ///
 // http://stackoverflow.com/questions/14088889/changing-a-color-brightness
-pub fn gamma(src: &mut Image, gamma: f32) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-
-    if gamma < 0.01 || gamma > 9.99 {
-        return Err(RasterError::InvalidGamma(gamma));
+pub fn gamma(src: &mut Image, gamma: f64) -> RasterResult<()> {
+    if gamma.is_nan() || gamma < 0.01 || gamma > 9.99 {
+        return Err(RasterError::InvalidGamma(gamma as f32));
     }
 
-    for y in 0..h {
-        for x in 0..w {
-            let p = src.get_pixel(x, y)?;
-            let r = (p.r as f32 / 255.0).powf(gamma) * 255.0;
-            let g = (p.g as f32 / 255.0).powf(gamma) * 255.0;
-            let b = (p.b as f32 / 255.0).powf(gamma) * 255.0;
-
-            src.set_pixel(x, y, Color::rgba(r as u8, g as u8, b as u8, p.a as u8))?;
+    // Every channel value maps to the same result, so work them out once
+    let table: Vec<u8> = (0..256)
+        .map(|v| ((v as f64 / 255.0).powf(1.0 / gamma) * 255.0).round() as u8)
+        .collect();
+    for pixel in src.pixels_mut() {
+        for value in pixel[..3].iter_mut() {
+            *value = table[*value as usize];
         }
     }
 
     Ok(())
 }
 
-/// Turn into grayscale image.
+/// Apply Gaussian blur with a standard deviation of `sigma` pixels. The kernel reaches 3 sigma
+/// from the center, and is run over the rows and then the columns, which is much faster than a
+/// 2D kernel of the same size. Colors are weighted by their alpha so transparent pixels don't
+/// darken the edges. A sigma of 0 or less leaves the image as it is.
+///
+/// Unlike `blur` with `BlurMode::Gaussian`, which is a fixed 3x3 kernel, this can blur by any
+/// amount.
 ///
 /// # Examples
 /// ```
 /// use raster::filter;
 ///
 /// let mut image = raster::open("tests/in/sample.jpg").unwrap();
-/// filter::grayscale(&mut image).unwrap();
-/// raster::save(&image, "tests/out/test_filter_grayscale.jpg").unwrap();
+/// filter::gaussian_blur(&mut image, 4.0).unwrap();
+/// raster::save(&image, "tests/out/test_filter_gaussian_blur_sigma.jpg").unwrap();
 /// ```
+pub fn gaussian_blur(src: &mut Image, sigma: f32) -> RasterResult<()> {
+    if sigma.is_nan() || sigma <= 0.0 {
+        return Ok(());
+    }
+    // Past the size of the image a wider kernel only adds more of the edge pixels
+    let limit = cmp::max(src.width, src.height).max(0) as usize;
+    let radius = cmp::min((sigma * 3.0).ceil() as usize, limit);
+    let mut kernel: Vec<f32> = (0..radius * 2 + 1)
+        .map(|i| {
+            let x = i as f32 - radius as f32;
+            (-x * x / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    for weight in kernel.iter_mut() {
+        *weight /= total;
+    }
+
+    separable(src, |line| {
+        let last = line.len() as isize - 1;
+        (0..line.len())
+            .map(|i| {
+                let mut sum = [0.0; 4];
+                for (k, weight) in kernel.iter().enumerate() {
+                    let j = (i as isize + k as isize - radius as isize).clamp(0, last);
+                    for (total, value) in sum.iter_mut().zip(line[j as usize].iter()) {
+                        *total += value * weight;
+                    }
+                }
+                sum
+            })
+            .collect()
+    });
+    Ok(())
+}
+
+/// Find the gradient of an image, the magnitude and direction of the change in its luminance at
+/// each pixel. Edges are where the magnitude is high. The edges of the image are extended.
 ///
-/// ### Before
-/// ![](https://kosinix.github.io/raster/in/sample.jpg)
+/// # Examples
+/// ```
+/// use raster::filter::{self, EdgeOperator};
+/// use raster::{Color, Image};
 ///
-/// ### After
-/// ![](https://kosinix.github.io/raster/out/test_filter_grayscale.jpg)
+/// // Black on the left, white on the right
+/// let image = Image::from_fn(4, 3, |x, _| if x < 2 { Color::black() } else { Color::white() });
+/// let gradient = filter::gradient(&image, EdgeOperator::Sobel);
 ///
-pub fn grayscale(src: &mut Image) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
+/// assert_eq!(0.0, gradient.magnitude[0]);
+/// assert!(gradient.magnitude[1] > 1000.0);
+/// // Gets brighter to the right
+/// assert_eq!(0.0, gradient.direction[1]);
+/// ```
+pub fn gradient(src: &Image, operator: EdgeOperator) -> Gradient {
+    let (w, h) = (src.width.max(0) as isize, src.height.max(0) as isize);
+    let luma: Vec<f32> = src
+        .pixels()
+        .map(|p| p[0] as f32 * 0.3 + p[1] as f32 * 0.59 + p[2] as f32 * 0.11)
+        .collect();
+    // Weights of the side and middle rows of the kernel for x, and the columns for y
+    let (side, middle) = match operator {
+        EdgeOperator::Sobel => (1.0, 2.0),
+        EdgeOperator::Scharr => (0.75, 2.5),
+    };
 
+    let mut gradient = Gradient {
+        width: src.width,
+        height: src.height,
+        magnitude: Vec::with_capacity(luma.len()),
+        direction: Vec::with_capacity(luma.len()),
+    };
     for y in 0..h {
         for x in 0..w {
-            let p = src.get_pixel(x, y)?;
-            let gray = (p.r as f32 * 0.3) + (p.g as f32 * 0.59) + (p.b as f32 * 0.11);
+            let at = |dx: isize, dy: isize| {
+                let sx = edge_index(x + dx, w, EdgeMode::Clamp);
+                let sy = edge_index(y + dy, h, EdgeMode::Clamp);
+                luma[sy * w as usize + sx]
+            };
+            let gx = side * (at(1, -1) - at(-1, -1))
+                + middle * (at(1, 0) - at(-1, 0))
+                + side * (at(1, 1) - at(-1, 1));
+            let gy = side * (at(-1, 1) - at(-1, -1))
+                + middle * (at(0, 1) - at(0, -1))
+                + side * (at(1, 1) - at(1, -1));
+            gradient.magnitude.push((gx * gx + gy * gy).sqrt());
+            gradient.direction.push(gy.atan2(gx));
+        }
+    }
+    gradient
+}
+
+/// Map the brightness of an image onto a gradient of colors, spread evenly from black to white,
+/// like a gradient map layer in a photo editor. Colors in between are mixed from their neighbors,
+/// and alpha is kept. No colors leave the image as it is.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(128, 128, 128));
+/// filter::gradient_map(&mut image, &[Color::black(), Color::red(), Color::white()]).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((255, 1, 1), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn gradient_map(src: &mut Image, colors: &[Color]) -> RasterResult<()> {
+    if colors.is_empty() {
+        return Ok(());
+    }
+    let last = (colors.len() - 1) as f32;
+    let table: Vec<[u8; 3]> = (0..256)
+        .map(|l| {
+            let position = l as f32 / 255.0 * last;
+            let i = cmp::min(position as usize, colors.len() - 1);
+            let (from, to) = (&colors[i], &colors[cmp::min(i + 1, colors.len() - 1)]);
+            let t = position - i as f32;
+            let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+            [mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b)]
+        })
+        .collect();
+    let luma = lumas(src);
+    for (pixel, &l) in src.pixels_mut().zip(luma.iter()) {
+        pixel[..3].copy_from_slice(&table[l as usize]);
+    }
+    Ok(())
+}
+
+/// Turn into grayscale image.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::grayscale(&mut image).unwrap();
+/// raster::save(&image, "tests/out/test_filter_grayscale.jpg").unwrap();
+/// ```
+///
+/// ### Before
+/// ![](https://kosinix.github.io/raster/in/sample.jpg)
+///
+/// ### After
+/// ![](https://kosinix.github.io/raster/out/test_filter_grayscale.jpg)
+///
+pub fn grayscale(src: &mut Image) -> RasterResult<()> {
+    let w: i32 = src.width;
+    let h: i32 = src.height;
+
+    for y in 0..h {
+        for x in 0..w {
+            let p = src.get_pixel(x, y)?;
+            let gray = (p.r as f32 * 0.3) + (p.g as f32 * 0.59) + (p.b as f32 * 0.11);
 
             src.set_pixel(
                 x,
@@ -395,6 +1209,500 @@ pub fn grayscale(src: &mut Image) -> RasterResult<()> {
     Ok(())
 }
 
+/// Apply a high pass filter, keeping only the detail smaller than about `radius` pixels on a
+/// gray background. It is the difference between the image and a Gaussian blur of it, plus 128.
+/// Blended over the image with `BlendMode::Overlay` or `SoftLight`, it sharpens or adds texture.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::high_pass(&mut image, 3.0).unwrap();
+/// raster::save(&image, "tests/out/test_filter_high_pass.jpg").unwrap();
+/// ```
+pub fn high_pass(src: &mut Image, radius: f32) -> RasterResult<()> {
+    let mut blurred = src.clone();
+    gaussian_blur(&mut blurred, radius)?;
+    for (pixel, soft) in src.pixels_mut().zip(blurred.pixels()) {
+        for c in 0..3 {
+            let detail = pixel[c] as f32 - soft[c] as f32 + 128.0;
+            pixel[c] = detail.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    Ok(())
+}
+
+/// Adjust the hue, saturation and lightness of an image, like the Hue/Saturation dialog of a
+/// photo editor. Each pixel is converted to HSL, its hue turned by `hue_shift_deg` degrees, its
+/// saturation multiplied by `saturation_mult`, and `lightness_delta` added to its lightness,
+/// which goes from 0.0 to 100.0. Alpha is kept.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::red());
+/// filter::hsl_adjust(&mut image, 120.0, 1.0, 0.0).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((0, 255, 0), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn hsl_adjust(
+    src: &mut Image,
+    hue_shift_deg: f32,
+    saturation_mult: f32,
+    lightness_delta: f32,
+) -> RasterResult<()> {
+    for pixel in src.pixels_mut() {
+        let (h, s, l) = Color::to_hsl(pixel[0], pixel[1], pixel[2]);
+        let (r, g, b) = Color::from_hsl(
+            h + hue_shift_deg,
+            s * saturation_mult.max(0.0),
+            l + lightness_delta,
+        );
+        pixel[..3].copy_from_slice(&[r, g, b]);
+    }
+    Ok(())
+}
+
+/// Apply a Kuwahara filter, which flattens an image into patches of color like brush strokes
+/// while keeping edges sharp. Each pixel takes the average color of whichever of the four
+/// squares of `radius + 1` pixels meeting at it has the least variation in brightness, so it never
+/// averages across an edge. A radius of 0 leaves the image as it is.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::kuwahara(&mut image, 3).unwrap();
+/// raster::save(&image, "tests/out/test_filter_kuwahara.jpg").unwrap();
+/// ```
+pub fn kuwahara(src: &mut Image, radius: u32) -> RasterResult<()> {
+    if radius == 0 {
+        return Ok(());
+    }
+    let (w, h) = (src.width.max(0) as isize, src.height.max(0) as isize);
+    let radius = cmp::min(radius, cmp::max(w, h) as u32) as isize;
+    let copy = src.clone();
+    let luma = lumas(&copy);
+    for (i, pixel) in src.pixels_mut().enumerate() {
+        let (x, y) = (i as isize % w, i as isize / w);
+        let mut best = (f32::MAX, [0.0; 3]);
+        for &(qx, qy) in &[(-radius, -radius), (0, -radius), (-radius, 0), (0, 0)] {
+            let mut sum = [0.0; 3];
+            let (mut total, mut squares) = (0.0, 0.0);
+            for dy in qy..=qy + radius {
+                for dx in qx..=qx + radius {
+                    let sx = edge_index(x + dx, w, EdgeMode::Clamp);
+                    let sy = edge_index(y + dy, h, EdgeMode::Clamp);
+                    let j = sy * w as usize + sx;
+                    for (total, &value) in sum.iter_mut().zip(&copy.bytes[j * 4..j * 4 + 3]) {
+                        *total += value as f32;
+                    }
+                    let l = luma[j] as f32;
+                    total += l;
+                    squares += l * l;
+                }
+            }
+            let count = ((radius + 1) * (radius + 1)) as f32;
+            let variance = squares / count - (total / count).powi(2);
+            if variance < best.0 {
+                best = (variance, [sum[0] / count, sum[1] / count, sum[2] / count]);
+            }
+        }
+        for (value, mean) in pixel[..3].iter_mut().zip(best.1.iter()) {
+            *value = mean.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    Ok(())
+}
+
+/// Apply a Laplacian filter, the second derivative of the image, which shows edges as dark and
+/// light lines on a gray background. Flat areas and even gradients become 128.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::laplacian(&mut image).unwrap();
+/// raster::save(&image, "tests/out/test_filter_laplacian.jpg").unwrap();
+/// ```
+pub fn laplacian(src: &mut Image) -> RasterResult<()> {
+    let kernel = Kernel {
+        width: 3,
+        height: 3,
+        values: vec![0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0],
+        divisor: 1.0,
+        bias: 128.0,
+    };
+    convolve_kernel(src, &kernel, EdgeMode::Clamp)
+}
+
+/// Adjust levels like the Levels dialog of Photoshop, pulling in the black and white points of
+/// a faded scan and correcting its midtones. See `Levels` for what the values do. The same
+/// levels apply to red, green and blue, and alpha is kept.
+///
+/// # Errors
+///
+/// If `gamma` is out of the 0.01 - 9.99 range, this will fail with `RasterError::InvalidGamma`.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(30, 130, 230));
+/// filter::levels(&mut image, 30, 230, 1.0, 0, 255).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((0, 128, 255), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn levels(
+    src: &mut Image,
+    in_black: u8,
+    in_white: u8,
+    gamma: f32,
+    out_black: u8,
+    out_white: u8,
+) -> RasterResult<()> {
+    let levels = Levels {
+        in_black,
+        in_white,
+        gamma,
+        out_black,
+        out_white,
+    };
+    levels_channels(src, &levels, &levels, &levels)
+}
+
+/// Adjust levels with separate settings for the red, green and blue channels, like fixing the
+/// color cast of an old print. Works the same as `levels` otherwise.
+///
+/// # Errors
+///
+/// See `levels`.
+///
+/// # Examples
+/// ```
+/// use raster::filter::{self, Levels};
+/// use raster::{Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(200, 150, 100));
+/// let warm = Levels { out_white: 200, ..Levels::default() };
+/// filter::levels_channels(&mut image, &Levels::default(), &Levels::default(), &warm).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((200, 150, 78), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn levels_channels(
+    src: &mut Image,
+    red: &Levels,
+    green: &Levels,
+    blue: &Levels,
+) -> RasterResult<()> {
+    let tables = [red.table()?, green.table()?, blue.table()?];
+    for pixel in src.pixels_mut() {
+        for (value, table) in pixel[..3].iter_mut().zip(tables.iter()) {
+            *value = table[*value as usize];
+        }
+    }
+    Ok(())
+}
+
+/// Encode a linear light value from 0.0 to 1.0 as 8-bit sRGB, the inverse of `srgb_to_linear`.
+/// Values outside of the range are clamped.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// // Mix black and white by light, not by their sRGB values
+/// let mid = (filter::srgb_to_linear(0) + filter::srgb_to_linear(255)) / 2.0;
+/// assert_eq!(188, filter::linear_to_srgb(mid));
+/// ```
+pub fn linear_to_srgb(value: f32) -> u8 {
+    hdr::to_srgb(value)
+}
+
+/// Apply a median filter, setting each channel of a pixel to its median over the square of
+/// `radius * 2 + 1` pixels around it. It removes salt and pepper noise, like dust on a scan,
+/// while keeping edges sharp. A histogram of the window slides along each row, so large radii stay
+/// fast. The edges of the image are extended, and radii past its width or height are capped.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(5, 5, Color::white());
+/// image.set_pixel(2, 2, Color::black()).unwrap();
+///
+/// filter::median(&mut image, 1).unwrap();
+/// assert_eq!(255, image.get_pixel(2, 2).unwrap().r);
+/// ```
+pub fn median(src: &mut Image, radius: u32) -> RasterResult<()> {
+    if radius == 0 || src.width <= 0 || src.height <= 0 {
+        return Ok(());
+    }
+    let copy = src.clone();
+    let (w, h) = (src.width, src.height);
+    // No wider than the image, even for huge radii
+    let radius = cmp::min(radius, cmp::max(w, h) as u32) as i32;
+    let half = (radius as u64 * 2 + 1).pow(2) / 2;
+    let pixel = |x: i32, y: i32| {
+        let i = (y.clamp(0, h - 1) as usize * w as usize + x.clamp(0, w - 1) as usize) * 4;
+        &copy.bytes[i..i + 4]
+    };
+
+    for y in 0..h {
+        let mut histograms = [[0u64; 256]; 4];
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                for (histogram, &value) in histograms.iter_mut().zip(pixel(dx, y + dy)) {
+                    histogram[value as usize] += 1;
+                }
+            }
+        }
+
+        for x in 0..w {
+            let i = (y as usize * w as usize + x as usize) * 4;
+            for (c, histogram) in histograms.iter().enumerate() {
+                let mut seen = 0;
+                for (value, &count) in histogram.iter().enumerate() {
+                    seen += count;
+                    if seen > half {
+                        src.bytes[i + c] = value as u8;
+                        break;
+                    }
+                }
+            }
+
+            // Slide the window right: drop the column leaving it and add the one entering
+            for dy in -radius..=radius {
+                let leaving = pixel(x - radius, y + dy);
+                let entering = pixel(x + radius + 1, y + dy);
+                for (c, histogram) in histograms.iter_mut().enumerate() {
+                    histogram[leaving[c] as usize] -= 1;
+                    histogram[entering[c] as usize] += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply motion blur, averaging each pixel along a line `length` pixels long through it, as if
+/// the camera moved while taking the picture. The angle is in degrees clockwise from the x axis,
+/// so 0 streaks sideways and 90 up and down. Points in between pixels are sampled bilinearly, and
+/// the edges of the image are extended. A length of 1 or less leaves the image as it is.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::motion_blur(&mut image, 15, 30.0).unwrap();
+/// raster::save(&image, "tests/out/test_filter_motion_blur.jpg").unwrap();
+/// ```
+pub fn motion_blur(src: &mut Image, length: u32, angle: f32) -> RasterResult<()> {
+    if length <= 1 || src.width <= 0 || src.height <= 0 {
+        return Ok(());
+    }
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // Evenly spaced offsets along the line, centered on the pixel
+    let offsets: Vec<(f64, f64)> = (0..length)
+        .map(|i| {
+            let t = i as f32 - (length - 1) as f32 / 2.0;
+            ((t * cos) as f64, (t * sin) as f64)
+        })
+        .collect();
+
+    let copy = src.clone();
+    let (max_x, max_y) = ((src.width - 1) as f64, (src.height - 1) as f64);
+    let width = src.width as usize;
+    for (i, pixel) in src.pixels_mut().enumerate() {
+        let (x, y) = ((i % width) as f64, (i / width) as f64);
+        let mut sum = [0.0; 4];
+        for &(dx, dy) in &offsets {
+            let (sx, sy) = ((x + dx).clamp(0.0, max_x), (y + dy).clamp(0.0, max_y));
+            let color = sample(&copy, sx, sy, InterpolationMode::Bilinear, [0; 4]);
+            let alpha = color[3] as f32;
+            for c in 0..3 {
+                sum[c] += color[c] as f32 * alpha;
+            }
+            sum[3] += alpha;
+        }
+        for c in 0..3 {
+            pixel[c] = if sum[3] > 0.0 {
+                (sum[c] / sum[3]).round().clamp(0.0, 255.0) as u8
+            } else {
+                0
+            };
+        }
+        pixel[3] = (sum[3] / length as f32).round().clamp(0.0, 255.0) as u8;
+    }
+    Ok(())
+}
+
+/// Make an image look like an oil painting. Each pixel looks at its neighbors up to `radius`
+/// pixels away, sorts them into `intensity_levels` bands of brightness, and takes the average
+/// color of the most common band. Fewer levels give bolder, flatter strokes. A radius of 0 or
+/// fewer than 2 levels leaves the image as it is.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::oil_paint(&mut image, 3, 20).unwrap();
+/// raster::save(&image, "tests/out/test_filter_oil_paint.jpg").unwrap();
+/// ```
+pub fn oil_paint(src: &mut Image, radius: u32, intensity_levels: u8) -> RasterResult<()> {
+    if radius == 0 || intensity_levels < 2 {
+        return Ok(());
+    }
+    let (w, h) = (src.width.max(0) as isize, src.height.max(0) as isize);
+    let radius = cmp::min(radius, cmp::max(w, h) as u32) as isize;
+    let levels = intensity_levels as usize;
+    let copy = src.clone();
+    let bands: Vec<usize> = lumas(&copy)
+        .into_iter()
+        .map(|l| l as usize * levels / 256)
+        .collect();
+
+    let mut counts = vec![0u32; levels];
+    let mut sums = vec![[0u32; 3]; levels];
+    for (i, pixel) in src.pixels_mut().enumerate() {
+        let (x, y) = (i as isize % w, i as isize / w);
+        for count in counts.iter_mut() {
+            *count = 0;
+        }
+        for sum in sums.iter_mut() {
+            *sum = [0; 3];
+        }
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let sx = edge_index(x + dx, w, EdgeMode::Clamp);
+                let sy = edge_index(y + dy, h, EdgeMode::Clamp);
+                let j = sy * w as usize + sx;
+                counts[bands[j]] += 1;
+                let sum = &mut sums[bands[j]];
+                for (total, &value) in sum.iter_mut().zip(&copy.bytes[j * 4..j * 4 + 3]) {
+                    *total += value as u32;
+                }
+            }
+        }
+        // The first of the most common bands, so ties go to the darker one
+        let mut band = 0;
+        for (b, &count) in counts.iter().enumerate() {
+            if count > counts[band] {
+                band = b;
+            }
+        }
+        for c in 0..3 {
+            pixel[c] = ((sums[band][c] + counts[band] / 2) / counts[band]) as u8;
+        }
+    }
+    Ok(())
+}
+
+/// Pixelate an image, filling each square of `block_size` pixels with its average color. Pass
+/// a `region` of `(x, y, width, height)` to only pixelate that part, like a face or a license
+/// plate, with the squares starting at its top left corner. The part of the region outside of the
+/// image is ignored. A block size of 0 or 1 leaves the image as it is.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::pixelate(&mut image, 12, Some((50, 20, 100, 80))).unwrap();
+/// raster::save(&image, "tests/out/test_filter_pixelate.jpg").unwrap();
+/// ```
+pub fn pixelate(
+    src: &mut Image,
+    block_size: u32,
+    region: Option<(i32, i32, i32, i32)>,
+) -> RasterResult<()> {
+    let (x, y, width, height) = region.unwrap_or((0, 0, src.width, src.height));
+    let (x, y) = (x as i64, y as i64);
+    let (left, top) = (x.max(0), y.max(0));
+    let right = cmp::min(x + width as i64, src.width as i64);
+    let bottom = cmp::min(y + height as i64, src.height as i64);
+    if block_size < 2 || left >= right || top >= bottom {
+        return Ok(());
+    }
+    let size = block_size as i64;
+    let w = src.width as usize;
+
+    // Blocks start at the corner of the region, even where it reaches past the image
+    let mut block_y = top - (top - y) % size;
+    while block_y < bottom {
+        let rows = block_y.max(top) as usize..cmp::min(block_y + size, bottom) as usize;
+        let mut block_x = left - (left - x) % size;
+        while block_x < right {
+            let columns = block_x.max(left) as usize..cmp::min(block_x + size, right) as usize;
+            let mut sum = [0u64; 4];
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    let p = &src.bytes[(row * w + column) * 4..][..4];
+                    for c in 0..3 {
+                        sum[c] += p[c] as u64 * p[3] as u64;
+                    }
+                    sum[3] += p[3] as u64;
+                }
+            }
+            let count = (rows.len() * columns.len()) as u64;
+            let average = |value: u64| (value + sum[3] / 2).checked_div(sum[3]).unwrap_or(0) as u8;
+            let color = [
+                average(sum[0]),
+                average(sum[1]),
+                average(sum[2]),
+                ((sum[3] + count / 2) / count) as u8,
+            ];
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    src.bytes[(row * w + column) * 4..][..4].copy_from_slice(&color);
+                }
+            }
+            block_x += size;
+        }
+        block_y += size;
+    }
+    Ok(())
+}
+
+/// Posterize an image, rounding each color channel to one of `levels` evenly spaced values so
+/// smooth gradients turn into flat bands of color. 2 levels gives at most 8 colors. Fewer than
+/// 2 levels leaves the image as it is.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(100, 200, 20));
+/// filter::posterize(&mut image, 3).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((128, 255, 0), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn posterize(src: &mut Image, levels: u8) -> RasterResult<()> {
+    if levels < 2 {
+        return Ok(());
+    }
+    let steps = (levels - 1) as f32;
+    let table: Vec<u8> = (0..256)
+        .map(|v| ((v as f32 * steps / 255.0).round() * 255.0 / steps).round() as u8)
+        .collect();
+    for pixel in src.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = table[pixel[c] as usize];
+        }
+    }
+    Ok(())
+}
+
 /// Change saturation.
 ///
 /// Pass a float value for sat. < 0.0 to decrease and > 0.0 to increase. Eg 0.5 for 50% increase
@@ -443,6 +1751,45 @@ pub fn saturation(src: &mut Image, sat: f32) -> RasterResult<()> {
     Ok(())
 }
 
+/// Apply Scharr edge detection, turning the image into a grayscale map of the magnitude of its
+/// edges. Like `sobel` with `Orientation::Both`, but finding edges in every direction equally
+/// well.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::scharr(&mut image).unwrap();
+/// raster::save(&image, "tests/out/test_filter_scharr.jpg").unwrap();
+/// ```
+pub fn scharr(src: &mut Image) -> RasterResult<()> {
+    let edges = gradient(src, EdgeOperator::Scharr).to_image();
+    src.bytes = edges.bytes;
+    Ok(())
+}
+
+/// Tone an image sepia, the warm brown of old photographs.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::sepia(&mut image).unwrap();
+/// raster::save(&image, "tests/out/test_filter_sepia.jpg").unwrap();
+/// ```
+pub fn sepia(src: &mut Image) -> RasterResult<()> {
+    for pixel in src.pixels_mut() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+        let tone = |kr: f32, kg: f32, kb: f32| (r * kr + g * kg + b * kb).round().min(255.0) as u8;
+        pixel[0] = tone(0.393, 0.769, 0.189);
+        pixel[1] = tone(0.349, 0.686, 0.168);
+        pixel[2] = tone(0.272, 0.534, 0.131);
+    }
+    Ok(())
+}
+
 /// Apply sharpen.
 ///
 /// # Examples
@@ -465,6 +1812,195 @@ pub fn sharpen(src: &mut Image) -> RasterResult<()> {
     convolve(src, matrix, 1)
 }
 
+/// Solarize an image, inverting the color channels brighter than `threshold` like a photo
+/// exposed to light while developing. A threshold of 0 inverts nearly everything, 255 nothing.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(2, 2, Color::rgb(50, 128, 200));
+/// filter::solarize(&mut image, 128).unwrap();
+///
+/// let pixel = image.get_pixel(0, 0).unwrap();
+/// assert_eq!((50, 128, 55), (pixel.r, pixel.g, pixel.b));
+/// ```
+pub fn solarize(src: &mut Image, threshold: u8) -> RasterResult<()> {
+    for pixel in src.pixels_mut() {
+        for value in pixel[..3].iter_mut() {
+            if *value > threshold {
+                *value = 255 - *value;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode an 8-bit sRGB value to linear light from 0.0 to 1.0. Pixels are stored in sRGB, which
+/// spends more values on dark tones, so blending, averaging or scaling light should be done on
+/// linear values and encoded back with `linear_to_srgb`.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// assert_eq!(1.0, filter::srgb_to_linear(255));
+/// assert!(filter::srgb_to_linear(128) < 0.25);
+/// ```
+pub fn srgb_to_linear(value: u8) -> f32 {
+    hdr::to_linear(value)
+}
+
+/// Turn an image black and white. Pixels brighter than `value` become white and the rest black,
+/// with the alpha kept. Brightness is the same mix of red, green and blue as `grayscale`.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::from_fn(2, 1, |x, _| Color::rgb(x as u8 * 200, 100, 100));
+/// filter::threshold(&mut image, 100).unwrap();
+///
+/// assert_eq!(0, image.get_pixel(0, 0).unwrap().r);
+/// assert_eq!(255, image.get_pixel(1, 0).unwrap().r);
+/// ```
+pub fn threshold(src: &mut Image, value: u8) -> RasterResult<()> {
+    let luma = lumas(src);
+    for (pixel, &l) in src.pixels_mut().zip(luma.iter()) {
+        let value = if l > value { 255 } else { 0 };
+        pixel[..3].copy_from_slice(&[value, value, value]);
+    }
+    Ok(())
+}
+
+/// Turn an image black and white with the threshold picked by Otsu's method, which splits the
+/// histogram of brightness into the two groups that differ from each other the most. Works best
+/// on images with a clear background and foreground, like scanned text. Returns the threshold,
+/// see `threshold`.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// let value = filter::threshold_otsu(&mut image).unwrap();
+/// raster::save(&image, "tests/out/test_filter_threshold_otsu.png").unwrap();
+/// ```
+pub fn threshold_otsu(src: &mut Image) -> RasterResult<u8> {
+    let luma = lumas(src);
+    let mut histogram = [0u64; 256];
+    for &l in &luma {
+        histogram[l as usize] += 1;
+    }
+    let total = luma.len() as f64;
+    let sum_all: f64 = (0..256).map(|i| i as f64 * histogram[i] as f64).sum();
+
+    let (mut best, mut best_variance) = (0, 0.0);
+    let (mut count, mut sum) = (0.0, 0.0);
+    for (i, &n) in histogram.iter().enumerate() {
+        count += n as f64;
+        sum += i as f64 * n as f64;
+        if count == 0.0 {
+            continue;
+        }
+        if count == total {
+            break;
+        }
+        let mean_dark = sum / count;
+        let mean_bright = (sum_all - sum) / (total - count);
+        let variance = count * (total - count) * (mean_dark - mean_bright).powi(2);
+        if variance > best_variance {
+            best = i as u8;
+            best_variance = variance;
+        }
+    }
+    threshold(src, best)?;
+    Ok(best)
+}
+
+/// Sharpen with an unsharp mask, the way photo editors do. The image is blurred with a Gaussian
+/// of `radius` sigma, and the difference to the blur is added back `amount` times, so 0.5 adds
+/// 50% more contrast to the edges. Channels that differ from the blur by less than `threshold`
+/// are left alone, which keeps smooth areas like skin or sky from getting noisy.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::unsharp_mask(&mut image, 2.0, 0.8, 4).unwrap();
+/// raster::save(&image, "tests/out/test_filter_unsharp_mask.jpg").unwrap();
+/// ```
+pub fn unsharp_mask(src: &mut Image, radius: f32, amount: f32, threshold: u8) -> RasterResult<()> {
+    let mut blurred = src.clone();
+    gaussian_blur(&mut blurred, radius)?;
+    for (pixel, soft) in src.pixels_mut().zip(blurred.pixels()) {
+        for c in 0..3 {
+            let diff = pixel[c] as f32 - soft[c] as f32;
+            if diff.abs() >= threshold as f32 {
+                pixel[c] = (pixel[c] as f32 + diff * amount).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Darken the corners of an image with a vignette. Pixels closer to the center than `radius`,
+/// as a fraction of the distance from the center to a corner, keep their color, and from there
+/// they darken smoothly until the corners are `strength` darker, where 1.0 is black.
+///
+/// # Examples
+/// ```
+/// use raster::{filter, Color, Image};
+///
+/// let mut image = Image::filled(9, 9, Color::white());
+/// filter::vignette(&mut image, 0.5, 0.5).unwrap();
+///
+/// assert_eq!(255, image.get_pixel(4, 4).unwrap().r);
+/// assert_eq!(128, image.get_pixel(0, 0).unwrap().r);
+/// ```
+pub fn vignette(src: &mut Image, strength: f32, radius: f32) -> RasterResult<()> {
+    let strength = strength.clamp(0.0, 1.0);
+    let radius = radius.clamp(0.0, 1.0);
+    let (w, h) = (src.width.max(1) as usize, src.height.max(1) as f32);
+    let (cx, cy) = ((w as f32 - 1.0) / 2.0, (h - 1.0) / 2.0);
+    let corner = (cx * cx + cy * cy).sqrt().max(f32::EPSILON);
+    for (i, pixel) in src.pixels_mut().enumerate() {
+        let (x, y) = ((i % w) as f32 - cx, (i / w) as f32 - cy);
+        let distance = (x * x + y * y).sqrt() / corner;
+        let t = if distance <= radius || radius >= 1.0 {
+            0.0
+        } else {
+            ((distance - radius) / (1.0 - radius)).min(1.0)
+        };
+        // Smoothstep, so the falloff has no visible edge
+        let factor = 1.0 - strength * t * t * (3.0 - 2.0 * t);
+        for value in pixel[..3].iter_mut() {
+            *value = (*value as f32 * factor).round() as u8;
+        }
+    }
+    Ok(())
+}
+
+/// Adjust the white balance of an image. A positive `temperature` warms it up, scaling red up
+/// and blue down in linear light, and a negative one cools it down. A positive `tint` adds
+/// magenta by scaling green down, a negative one adds green. At 1.0 the channels change by one
+/// stop, and 0.0 leaves them as they are. See `auto_white_balance` to correct a cast for you.
+///
+/// # Examples
+/// ```
+/// use raster::filter;
+///
+/// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+/// filter::white_balance(&mut image, 0.3, -0.1).unwrap();
+/// raster::save(&image, "tests/out/test_filter_white_balance.jpg").unwrap();
+/// ```
+pub fn white_balance(src: &mut Image, temperature: f32, tint: f32) -> RasterResult<()> {
+    let gains = [temperature.exp2(), (-tint).exp2(), (-temperature).exp2()];
+    scale_linear(src, gains);
+    Ok(())
+}
+
 // Private functions
 
 // Box
@@ -478,3 +2014,157 @@ fn blur_gaussian(src: &mut Image) -> RasterResult<()> {
     let matrix: [[i32; 3]; 3] = [[1, 2, 1], [2, 4, 2], [1, 2, 1]];
     convolve(src, matrix, 16)
 }
+
+// The output of a monotone cubic spline through the points for every input value, with the
+// tangents of Fritsch and Carlson. Of points with the same input, the last one counts.
+fn curve_table(points: &[(u8, u8)]) -> Vec<u8> {
+    let mut sorted = points.to_vec();
+    sorted.reverse();
+    sorted.sort_by_key(|&(x, _)| x);
+    sorted.dedup_by_key(|&mut (x, _)| x);
+    if sorted.is_empty() {
+        return (0..=255).collect();
+    }
+    let xs: Vec<f32> = sorted.iter().map(|&(x, _)| x as f32).collect();
+    let ys: Vec<f32> = sorted.iter().map(|&(_, y)| y as f32).collect();
+    let n = xs.len();
+
+    let slopes: Vec<f32> = (0..n - 1)
+        .map(|k| (ys[k + 1] - ys[k]) / (xs[k + 1] - xs[k]))
+        .collect();
+    let mut tangents = vec![0.0; n];
+    if n > 1 {
+        tangents[0] = slopes[0];
+        tangents[n - 1] = slopes[n - 2];
+    }
+    for k in 1..n - 1 {
+        if slopes[k - 1] * slopes[k] > 0.0 {
+            tangents[k] = (slopes[k - 1] + slopes[k]) / 2.0;
+        }
+    }
+    // Shorten tangents that would make a segment overshoot
+    for k in 0..n - 1 {
+        if slopes[k] == 0.0 {
+            tangents[k] = 0.0;
+            tangents[k + 1] = 0.0;
+            continue;
+        }
+        let (a, b) = (tangents[k] / slopes[k], tangents[k + 1] / slopes[k]);
+        let s = a * a + b * b;
+        if s > 9.0 {
+            let t = 3.0 / s.sqrt();
+            tangents[k] = t * a * slopes[k];
+            tangents[k + 1] = t * b * slopes[k];
+        }
+    }
+
+    (0..256)
+        .map(|v| {
+            let x = v as f32;
+            if x <= xs[0] {
+                return ys[0] as u8;
+            }
+            if x >= xs[n - 1] {
+                return ys[n - 1] as u8;
+            }
+            let k = xs.iter().rposition(|&start| start <= x).unwrap_or(0);
+            let h = xs[k + 1] - xs[k];
+            let t = (x - xs[k]) / h;
+            let (t2, t3) = (t * t, t * t * t);
+            let y = (2.0 * t3 - 3.0 * t2 + 1.0) * ys[k]
+                + (t3 - 2.0 * t2 + t) * h * tangents[k]
+                + (-2.0 * t3 + 3.0 * t2) * ys[k + 1]
+                + (t3 - t2) * h * tangents[k + 1];
+            y.round().clamp(0.0, 255.0) as u8
+        })
+        .collect()
+}
+
+// Index of a pixel in a line, with positions past the edges mapped back in.
+fn edge_index(i: isize, len: isize, edge: EdgeMode) -> usize {
+    let i = match edge {
+        EdgeMode::Clamp => i.clamp(0, len - 1),
+        EdgeMode::Wrap => i.rem_euclid(len),
+        EdgeMode::Mirror => {
+            let i = i.rem_euclid(len * 2);
+            if i < len {
+                i
+            } else {
+                len * 2 - 1 - i
+            }
+        }
+    };
+    i as usize
+}
+
+// Brightness of each pixel, as in grayscale.
+fn lumas(src: &Image) -> Vec<u8> {
+    src.pixels()
+        .map(|p| (p[0] as f32 * 0.3 + p[1] as f32 * 0.59 + p[2] as f32 * 0.11).round() as u8)
+        .collect()
+}
+
+// Scale red, green and blue in linear light.
+fn scale_linear(src: &mut Image, gains: [f32; 3]) {
+    let tables: Vec<Vec<u8>> = gains
+        .iter()
+        .map(|&gain| {
+            (0..=255)
+                .map(|v| hdr::to_srgb(hdr::to_linear(v) * gain))
+                .collect()
+        })
+        .collect();
+    for pixel in src.pixels_mut() {
+        for (value, table) in pixel[..3].iter_mut().zip(tables.iter()) {
+            *value = table[*value as usize];
+        }
+    }
+}
+
+// Filter the lines of an image with alpha weighted colors, first each row and then each column.
+fn separable<F>(src: &mut Image, filter: F)
+where
+    F: Fn(&[[f32; 4]]) -> Vec<[f32; 4]>,
+{
+    let (w, h) = (src.width.max(0) as usize, src.height.max(0) as usize);
+    if w == 0 || h == 0 {
+        return;
+    }
+    let mut pixels: Vec<[f32; 4]> = src
+        .pixels()
+        .map(|p| {
+            let a = p[3] as f32 / 255.0;
+            [
+                p[0] as f32 * a,
+                p[1] as f32 * a,
+                p[2] as f32 * a,
+                p[3] as f32,
+            ]
+        })
+        .collect();
+
+    for row in pixels.chunks_mut(w) {
+        let filtered = filter(row);
+        row.copy_from_slice(&filtered);
+    }
+    let mut column = Vec::with_capacity(h);
+    for x in 0..w {
+        column.clear();
+        column.extend((0..h).map(|y| pixels[y * w + x]));
+        for (y, pixel) in filter(&column).into_iter().enumerate() {
+            pixels[y * w + x] = pixel;
+        }
+    }
+
+    for (p, value) in src.pixels_mut().zip(pixels.iter()) {
+        let a = value[3].clamp(0.0, 255.0);
+        for c in 0..3 {
+            p[c] = if a > 0.0 {
+                (value[c] * 255.0 / a).round().clamp(0.0, 255.0) as u8
+            } else {
+                0
+            };
+        }
+        p[3] = a.round() as u8;
+    }
+}
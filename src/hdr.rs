@@ -0,0 +1,100 @@
+//!  A module for high dynamic range images.
+
+// from rust
+
+// from external crate
+
+// from local crate
+use buffer::{ImageBuffer, RgbaF32};
+use Image;
+
+/// A high dynamic range image, a buffer of `RgbaF32` pixels.
+///
+/// Pixels are stored as linear light floats in RGBA order. A value of 1.0 is the brightest a
+/// regular image can show, brighter values need to be tone mapped before converting to an
+/// `Image`. `to_image` clamps them, like `ToneMap::Clamp`.
+pub type HdrImage = ImageBuffer<RgbaF32>;
+
+impl ImageBuffer<RgbaF32> {
+    /// Convert to an 8-bit sRGB image using a tone mapping operator. Alpha is clamped to the 0.0
+    /// to 1.0 range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{HdrImage, ToneMap};
+    ///
+    /// let hdr = HdrImage::new(1, 1, vec![4.0, 1.0, 0.0, 1.0]).unwrap();
+    ///
+    /// let image = hdr.tone_map(ToneMap::Clamp);
+    /// assert_eq!(vec![255, 255, 0, 255], image.bytes);
+    ///
+    /// let image = hdr.tone_map(ToneMap::Reinhard);
+    /// assert_eq!(vec![231, 188, 0, 255], image.bytes);
+    /// ```
+    pub fn tone_map(&self, tone_map: ToneMap) -> Image {
+        let mut bytes = Vec::with_capacity(self.samples.len());
+        for p in self.samples.chunks(4) {
+            for &value in &p[..3] {
+                let mapped = match tone_map {
+                    ToneMap::Clamp => value,
+                    ToneMap::Reinhard => value / (1.0 + value),
+                    ToneMap::Aces => {
+                        // Krzysztof Narkowicz's fit of the ACES filmic curve
+                        (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+                    }
+                };
+                bytes.push(to_srgb(mapped));
+            }
+            bytes.push((p[3].clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+
+        Image {
+            width: self.width,
+            height: self.height,
+            bytes,
+        }
+    }
+}
+
+/// Enumeration of tone mapping operators for bringing high dynamic range pixels into the 0.0 to
+/// 1.0 range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    /// Clip everything brighter than 1.0.
+    Clamp,
+    /// Reinhard's `x / (1 + x)`. Keeps details in highlights but looks flat.
+    Reinhard,
+    /// Approximation of the ACES filmic curve. More contrast and saturation than Reinhard.
+    Aces,
+}
+
+// Decode 8-bit sRGB to a linear light value.
+pub(crate) fn to_linear(value: u8) -> f32 {
+    decode_srgb(value as f32 / 255.0)
+}
+
+// Decode an sRGB value from 0.0 to 1.0 to linear light. Values past 1.0 follow the same curve.
+pub(crate) fn decode_srgb(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Encode a linear light value as sRGB, the inverse of `decode_srgb`.
+pub(crate) fn encode_srgb(value: f32) -> f32 {
+    if value.is_nan() || value <= 0.0 {
+        0.0
+    } else if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Encode a linear light value as 8-bit sRGB.
+pub(crate) fn to_srgb(value: f32) -> u8 {
+    (encode_srgb(value).min(1.0) * 255.0).round() as u8
+}
@@ -0,0 +1,53 @@
+//!  A module for downloading images over HTTP.
+
+// from rust
+use std::time::Duration;
+
+// from external crate
+use ureq::{self, Agent, ResponseExt};
+
+// from local crate
+use error::{RasterError, RasterResult};
+
+// Redirects followed before giving up
+const MAX_REDIRECTS: u32 = 5;
+
+// Time for the whole download, redirects included
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Download an http or https URL, following redirects. Returns the URL that was finally fetched and
+// the body. Bodies longer than `max_bytes` fail.
+pub(crate) fn get(url: &str, max_bytes: usize) -> RasterResult<(String, Vec<u8>)> {
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .max_redirects(MAX_REDIRECTS)
+        .user_agent("raster")
+        .build()
+        .into();
+    let mut response = agent
+        .get(url)
+        .header("Accept", "image/*")
+        .call()
+        .map_err(error)?;
+    let url = response.get_uri().to_string();
+    let body = response
+        .body_mut()
+        .with_config()
+        .limit(max_bytes as u64)
+        .read_to_vec()
+        .map_err(error)?;
+    Ok((url, body))
+}
+
+// Private functions
+
+fn error(err: ureq::Error) -> RasterError {
+    match err {
+        ureq::Error::Io(io_err) => RasterError::Io(io_err),
+        ureq::Error::StatusCode(status) => RasterError::Http(format!("Response status {}", status)),
+        ureq::Error::BodyExceedsLimit(_) => {
+            RasterError::Http("Response is larger than the limits".to_string())
+        }
+        err => RasterError::Http(err.to_string()),
+    }
+}
@@ -0,0 +1,215 @@
+//!  A module for ICC color profiles.
+
+// from rust
+use std::cmp;
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use hdr::to_srgb;
+
+// XYZ relative to the D50 white of the profile connection space to linear sRGB, with Bradford
+// adaptation to D65
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133_856, -1.616_867, -0.490_615],
+    [-0.978_768, 1.916_141, 0.033_454],
+    [0.071_945, -0.228_991, 1.405_243],
+];
+
+// A conversion from the colors of a profile to sRGB.
+pub(crate) struct SrgbTransform {
+    // Linear light value of each 8-bit sample, per channel
+    curves: [[f32; 256]; 3],
+    // Linear light RGB to linear sRGB
+    matrix: [[f32; 3]; 3],
+}
+
+impl SrgbTransform {
+    // Read an RGB matrix and curves profile or a gray curve profile. Profiles that describe colors
+    // with lookup tables only are not supported.
+    pub(crate) fn new(profile: &[u8]) -> RasterResult<SrgbTransform> {
+        let invalid = |message: &str| RasterError::InvalidIccProfile(message.to_string());
+        if profile.get(36..40) != Some(b"acsp") {
+            return Err(invalid("Missing profile signature"));
+        }
+        let reader = ProfileReader { profile };
+
+        match profile.get(16..20) {
+            Some(b"RGB ") => {
+                let curve = |tag: &[u8]| -> RasterResult<[f32; 256]> {
+                    reader
+                        .curve(tag)
+                        .ok_or_else(|| invalid("Missing or invalid curve"))
+                };
+                let mut profile_to_xyz = [[0.0; 3]; 3];
+                for (column, tag) in [b"rXYZ", b"gXYZ", b"bXYZ"].iter().enumerate() {
+                    let xyz = reader
+                        .xyz(&tag[..])
+                        .ok_or_else(|| invalid("Missing or invalid colorant"))?;
+                    for (row, &value) in xyz.iter().enumerate() {
+                        profile_to_xyz[row][column] = value;
+                    }
+                }
+                Ok(SrgbTransform {
+                    curves: [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?],
+                    matrix: multiply(&XYZ_TO_SRGB, &profile_to_xyz),
+                })
+            }
+            Some(b"GRAY") => {
+                let curve = reader
+                    .curve(b"kTRC")
+                    .ok_or_else(|| invalid("Missing or invalid curve"))?;
+                Ok(SrgbTransform {
+                    curves: [curve, curve, curve],
+                    matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+                })
+            }
+            _ => Err(invalid("Unsupported color space")),
+        }
+    }
+
+    // Convert RGBA pixels to sRGB. Alpha is left as it is.
+    pub(crate) fn apply(&self, bytes: &mut [u8]) {
+        for pixel in bytes.chunks_mut(4) {
+            let linear = [
+                self.curves[0][pixel[0] as usize],
+                self.curves[1][pixel[1] as usize],
+                self.curves[2][pixel[2] as usize],
+            ];
+            for (channel, row) in self.matrix.iter().enumerate() {
+                let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+                pixel[channel] = to_srgb(value);
+            }
+        }
+    }
+}
+
+// Private functions
+
+fn multiply(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+// Reads tags from the tag table of a profile. Values are big endian.
+struct ProfileReader<'a> {
+    profile: &'a [u8],
+}
+
+impl<'a> ProfileReader<'a> {
+    // Data of a tag.
+    fn tag(&self, signature: &[u8]) -> Option<&'a [u8]> {
+        // The count is untrusted, so stop at the entries the profile has room for
+        let count = self.u32(128)? as usize;
+        let count = cmp::min(count, self.profile.len().saturating_sub(132) / 12);
+        (0..count)
+            .map(|i| 132 + i * 12)
+            .find(|&entry| self.profile.get(entry..entry + 4) == Some(signature))
+            .and_then(|entry| {
+                let offset = self.u32(entry + 4)? as usize;
+                let size = self.u32(entry + 8)? as usize;
+                self.profile.get(offset..offset.checked_add(size)?)
+            })
+    }
+
+    // Sample a tone curve at every 8-bit value. Curves are either a gamma, a table or one of the
+    // parametric functions.
+    fn curve(&self, signature: &[u8]) -> Option<[f32; 256]> {
+        let data = self.tag(signature)?;
+        let s15 = |offset: usize| -> Option<f32> {
+            let bytes = data.get(offset..offset + 4)?;
+            Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 65536.0)
+        };
+        let u16_at = |offset: usize| -> Option<u16> {
+            Some(u16::from_be_bytes([
+                *data.get(offset)?,
+                *data.get(offset + 1)?,
+            ]))
+        };
+
+        let function: Box<dyn Fn(f32) -> f32> = match data.get(0..4)? {
+            b"curv" => {
+                let count = data.get(8..12)?;
+                let count = u32::from_be_bytes([count[0], count[1], count[2], count[3]]) as usize;
+                match count {
+                    0 => Box::new(|x| x),
+                    1 => {
+                        let gamma = u16_at(12)? as f32 / 256.0;
+                        Box::new(move |x: f32| x.powf(gamma))
+                    }
+                    _ => {
+                        let table = (0..count)
+                            .map(|i| Some(u16_at(12 + i * 2)? as f32 / 65535.0))
+                            .collect::<Option<Vec<f32>>>()?;
+                        // Linear interpolation between the entries
+                        Box::new(move |x: f32| {
+                            let position = x * (table.len() - 1) as f32;
+                            let i = (position as usize).min(table.len() - 2);
+                            let t = position - i as f32;
+                            table[i] * (1.0 - t) + table[i + 1] * t
+                        })
+                    }
+                }
+            }
+            b"para" => {
+                let kind = u16_at(8)?;
+                let count = match kind {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    _ => return None,
+                };
+                let mut p = [0.0; 7];
+                for (i, value) in p.iter_mut().take(count).enumerate() {
+                    *value = s15(12 + i * 4)?;
+                }
+                let [g, a, b, c, d, e, f] = p;
+                Box::new(move |x: f32| match kind {
+                    0 => x.powf(g),
+                    1 if x >= -b / a => (a * x + b).powf(g),
+                    1 => 0.0,
+                    2 if x >= -b / a => (a * x + b).powf(g) + c,
+                    2 => c,
+                    3 if x >= d => (a * x + b).powf(g),
+                    3 => c * x,
+                    _ if x >= d => (a * x + b).powf(g) + e,
+                    _ => c * x + f,
+                })
+            }
+            _ => return None,
+        };
+
+        let mut curve = [0.0; 256];
+        for (i, value) in curve.iter_mut().enumerate() {
+            *value = function(i as f32 / 255.0);
+        }
+        Some(curve)
+    }
+
+    // Value of an XYZ tag.
+    fn xyz(&self, signature: &[u8]) -> Option<[f32; 3]> {
+        let data = self.tag(signature)?;
+        if data.get(0..4)? != b"XYZ " {
+            return None;
+        }
+        let mut xyz = [0.0; 3];
+        for (i, value) in xyz.iter_mut().enumerate() {
+            let bytes = data.get(8 + i * 4..12 + i * 4)?;
+            *value = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 65536.0;
+        }
+        Some(xyz)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.profile.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
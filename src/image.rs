@@ -1,13 +1,23 @@
 //!  A module for generic representation of image.
 
 // from rust
+use std::cmp;
 use std::collections::HashMap;
+use std::io::Cursor;
+use std::slice::{ChunksExact, ChunksExactMut};
 
 // from external crate
 
 // from local crate
 use color::Color;
+use decode;
+use encode;
+use encode_with_metadata;
+use endec::{DecodeOptions, EncodeOptions};
 use error::{RasterError, RasterResult};
+use icc::SrgbTransform;
+use metadata::MetadataBlocks;
+use view::{ImageView, ImageViewMut};
 
 /// A struct for easily representing a raster image.
 #[derive(Debug, Clone)]
@@ -51,6 +61,57 @@ impl<'a> Image {
         }
     }
 
+    /// Get the alpha channel as a grayscale image, white where the image is opaque. Change it and
+    /// put it back with `set_alpha_from`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let image = Image::filled(2, 2, Color::rgba(255, 0, 0, 64));
+    /// let mask = image.alpha_mask();
+    ///
+    /// assert_eq!(vec![64, 64, 64, 255], mask.bytes[..4].to_vec());
+    /// ```
+    pub fn alpha_mask(&self) -> Image {
+        let mut mask = Image::blank(self.width, self.height);
+        for (pixel, masked) in self.pixels().zip(mask.pixels_mut()) {
+            masked[..3].copy_from_slice(&[pixel[3]; 3]);
+            masked[3] = 255;
+        }
+        mask
+    }
+
+    /// Convert the pixels from the colors of an ICC profile to sRGB, like the
+    /// `MetadataBlocks::icc_profile` of the file the image was opened from. Photos in wide gamut
+    /// spaces like Adobe RGB otherwise look washed out once the profile is lost while processing or
+    /// saving them.
+    ///
+    /// RGB profiles made of a matrix and tone curves are supported, as well as gray profiles.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RasterError::InvalidIccProfile` if the profile can't be read or only describes its
+    /// colors with lookup tables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::metadata::MetadataBlocks;
+    ///
+    /// let mut image = raster::open("tests/in/adobe-rgb.png").unwrap();
+    /// assert_eq!(200, image.get_pixel(2, 0).unwrap().r);
+    ///
+    /// let blocks = MetadataBlocks::from_path("tests/in/adobe-rgb.png").unwrap();
+    /// image.apply_icc_to_srgb(&blocks.icc_profile.unwrap()).unwrap();
+    /// assert_eq!(227, image.get_pixel(2, 0).unwrap().r);
+    /// ```
+    pub fn apply_icc_to_srgb(&mut self, profile: &[u8]) -> RasterResult<()> {
+        SrgbTransform::new(profile)?.apply(&mut self.bytes);
+        Ok(())
+    }
+
     /// Check if there is a pixel at this location given by x and y.
     ///
     /// # Examples
@@ -72,6 +133,226 @@ impl<'a> Image {
         }
     }
 
+    /// Iterate over the pixels of an image with their x and y location, row by row. Each pixel is
+    /// a slice of its RGBA bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{editor, Color, Image};
+    ///
+    /// let mut image = Image::blank(3, 2);
+    /// editor::fill(&mut image, Color::rgba(0, 0, 0, 0)).unwrap();
+    /// image.set_pixel(2, 1, Color::red()).unwrap();
+    ///
+    /// // Find the opaque pixels
+    /// let opaque: Vec<(i32, i32)> = image
+    ///     .enumerate_pixels()
+    ///     .filter(|&(_, _, pixel)| pixel[3] == 255)
+    ///     .map(|(x, y, _)| (x, y))
+    ///     .collect();
+    /// assert_eq!(vec![(2, 1)], opaque);
+    /// ```
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (i32, i32, &[u8])> {
+        let width = cmp::max(self.width, 1);
+        self.pixels()
+            .enumerate()
+            .map(move |(i, pixel)| (i as i32 % width, i as i32 / width, pixel))
+    }
+
+    /// Create an image filled with a color.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let image = Image::filled(2, 2, Color::rgba(255, 0, 0, 128));
+    ///
+    /// assert_eq!(vec![255, 0, 0, 128], image.bytes[12..].to_vec());
+    /// ```
+    pub fn filled(width: i32, height: i32, color: Color) -> Image {
+        let pixel = [color.r, color.g, color.b, color.a];
+        Image {
+            width,
+            height,
+            bytes: pixel.repeat(width.max(0) as usize * height.max(0) as usize),
+        }
+    }
+
+    /// Composite the image onto a solid background color, making it opaque. Use it before saving
+    /// to formats without transparency like JPEG, which would otherwise drop the alpha and show
+    /// the colors of transparent pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let mut image = Image::filled(1, 1, Color::rgba(0, 0, 0, 0));
+    /// image.flatten(Color::white());
+    ///
+    /// assert_eq!(vec![255, 255, 255, 255], image.bytes);
+    /// ```
+    pub fn flatten(&mut self, background: Color) {
+        let background = [background.r, background.g, background.b];
+        for pixel in self.pixels_mut() {
+            let a = pixel[3] as f32 / 255.0;
+            for (channel, &base) in pixel[..3].iter_mut().zip(&background) {
+                *channel = (*channel as f32 * a + base as f32 * (1.0 - a)).round() as u8;
+            }
+            pixel[3] = 255;
+        }
+    }
+
+    /// Create an image from BGRA bytes, the pixel format of Windows screen captures and many
+    /// framebuffers.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the width and height if the number of bytes
+    /// isn't `width * height * 4`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let image = Image::from_bgra(1, 1, vec![255, 0, 0, 255]).unwrap();
+    /// assert_eq!(255, image.get_pixel(0, 0).unwrap().b);
+    /// ```
+    pub fn from_bgra(width: i32, height: i32, bytes: Vec<u8>) -> RasterResult<Image> {
+        let mut image = Image::from_raw(width, height, bytes)?;
+        image.swap_channels(ChannelOrder::Bgra, ChannelOrder::Rgba);
+        Ok(image)
+    }
+
+    /// Decode an image held in memory, like data received over the network or embedded with
+    /// `include_bytes!`. Use `endec::detect_format` first when the format is not known. Images
+    /// larger than `DecodeLimits::default()` are refused.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io`, `RasterError::Decode`,
+    /// `RasterError::LimitExceeded` or `RasterError::UnsupportedFormat` upon failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Image, ImageFormat};
+    ///
+    /// let data = std::fs::read("tests/in/sample.png").unwrap();
+    /// let image = Image::from_bytes(ImageFormat::Png, &data).unwrap();
+    ///
+    /// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+    /// ```
+    pub fn from_bytes(format: ImageFormat, data: &[u8]) -> RasterResult<Image> {
+        decode(format, Cursor::new(data), &DecodeOptions::default())
+    }
+
+    /// Create an image from separate red, green, blue and alpha channels, like the ones from
+    /// `split_channels`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the width and height if a channel doesn't
+    /// have `width * height` bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// // Swap the red and blue channels
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let [r, g, b, a] = image.split_channels();
+    /// let swapped = Image::from_channels(image.width, image.height, &b, &g, &r, &a).unwrap();
+    ///
+    /// assert_eq!(image.get_pixel(5, 5).unwrap().r, swapped.get_pixel(5, 5).unwrap().b);
+    /// ```
+    pub fn from_channels(
+        width: i32,
+        height: i32,
+        r: &[u8],
+        g: &[u8],
+        b: &[u8],
+        a: &[u8],
+    ) -> RasterResult<Image> {
+        let len = match pixel_count(width, height) {
+            Some(len) if [r, g, b, a].iter().all(|channel| channel.len() == len) => len,
+            _ => return Err(RasterError::SizeMismatch(width, height)),
+        };
+        let mut bytes = Vec::with_capacity(len * 4);
+        for i in 0..len {
+            bytes.extend_from_slice(&[r[i], g[i], b[i], a[i]]);
+        }
+        Ok(Image {
+            width,
+            height,
+            bytes,
+        })
+    }
+
+    /// Create an image with the color of each pixel given by a function of its x and y location.
+    /// Handy for gradients, test patterns and other generated images.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// // A horizontal gradient from black to red
+    /// let image = Image::from_fn(256, 16, |x, _| Color::rgb(x as u8, 0, 0));
+    ///
+    /// assert_eq!(128, image.get_pixel(128, 8).unwrap().r);
+    /// ```
+    pub fn from_fn<F>(width: i32, height: i32, mut f: F) -> Image
+    where
+        F: FnMut(i32, i32) -> Color,
+    {
+        let mut bytes = Vec::with_capacity(width.max(0) as usize * height.max(0) as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let color = f(x, y);
+                bytes.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+        }
+        Image {
+            width,
+            height,
+            bytes,
+        }
+    }
+
+    /// Create an image from RGBA bytes without copying them, like a frame from a capture
+    /// device.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the width and height if the number of bytes
+    /// isn't `width * height * 4`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let image = Image::from_raw(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+    /// assert_eq!(255, image.get_pixel(1, 0).unwrap().b);
+    ///
+    /// assert!(Image::from_raw(2, 2, vec![0; 8]).is_err());
+    /// ```
+    pub fn from_raw(width: i32, height: i32, bytes: Vec<u8>) -> RasterResult<Image> {
+        if pixel_count(width, height).and_then(|count| count.checked_mul(4)) != Some(bytes.len()) {
+            return Err(RasterError::SizeMismatch(width, height));
+        }
+        Ok(Image {
+            width,
+            height,
+            bytes,
+        })
+    }
+
     /// Get the histogram of the image.
     ///
     /// # Examples
@@ -202,6 +483,169 @@ impl<'a> Image {
         }
     }
 
+    /// Get pixel in a given x and y location of an image, without the result `get_pixel` wraps
+    /// it in. For hot loops that already keep their coordinates in bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside the bytes of the image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let image = Image::blank(2, 2); // Creates a 2x2 black image.
+    ///
+    /// let mut alpha = 0;
+    /// for y in 0..image.height {
+    ///     for x in 0..image.width {
+    ///         alpha += image.get_pixel_unchecked(x, y).a as u32;
+    ///     }
+    /// }
+    /// assert_eq!(4 * 255, alpha);
+    /// ```
+    pub fn get_pixel_unchecked(&self, x: i32, y: i32) -> Color {
+        let start = ((y * self.width + x) * 4) as usize;
+        let slice = &self.bytes[start..start + 4];
+        Color {
+            r: slice[0],
+            g: slice[1],
+            b: slice[2],
+            a: slice[3],
+        }
+    }
+
+    /// Take the RGBA bytes out of an image without copying them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let bytes = Image::blank(2, 2).into_raw();
+    /// assert_eq!(2 * 2 * 4, bytes.len());
+    /// ```
+    pub fn into_raw(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Iterate over the pixels of an image, row by row. Each pixel is a slice of its RGBA bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// let transparent = image.pixels().filter(|pixel| pixel[3] == 0).count();
+    /// assert!(transparent < image.pixels().len());
+    /// ```
+    pub fn pixels(&self) -> ChunksExact<'_, u8> {
+        self.bytes.chunks_exact(4)
+    }
+
+    /// Iterate over the pixels of an image for changing them, row by row. Each pixel is a slice
+    /// of its RGBA bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let mut image = Image::blank(2, 2);
+    ///
+    /// // Make every pixel half transparent
+    /// for pixel in image.pixels_mut() {
+    ///     pixel[3] = 128;
+    /// }
+    /// assert_eq!(128, image.get_pixel(1, 1).unwrap().a);
+    /// ```
+    pub fn pixels_mut(&mut self) -> ChunksExactMut<'_, u8> {
+        self.bytes.chunks_exact_mut(4)
+    }
+
+    /// Multiply the color channels of every pixel by its alpha, the format of Skia and most GPU
+    /// surfaces. Undo it with `unpremultiply_alpha`, which loses precision on pixels that are
+    /// almost transparent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let mut image = Image::filled(1, 1, Color::rgba(255, 100, 0, 128));
+    /// image.premultiply_alpha();
+    /// assert_eq!(vec![128, 50, 0, 128], image.bytes);
+    ///
+    /// image.unpremultiply_alpha();
+    /// assert_eq!(vec![255, 100, 0, 128], image.bytes);
+    /// ```
+    pub fn premultiply_alpha(&mut self) {
+        for pixel in self.pixels_mut() {
+            let a = pixel[3] as u32;
+            for channel in pixel[..3].iter_mut() {
+                *channel = ((*channel as u32 * a + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// Iterate over the rows of an image, top to bottom. Each row is a slice of the RGBA bytes of
+    /// its pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let image = Image::blank(3, 2);
+    ///
+    /// assert_eq!(2, image.rows().len());
+    /// for row in image.rows() {
+    ///     assert_eq!(3 * 4, row.len());
+    /// }
+    /// ```
+    pub fn rows(&self) -> ChunksExact<'_, u8> {
+        self.bytes
+            .chunks_exact(cmp::max(self.width, 1) as usize * 4)
+    }
+
+    /// Replace the alpha channel with a grayscale mask of the same size, like one from
+    /// `alpha_mask`. Masks in color are turned gray with the weights of `filter::grayscale`, and
+    /// their own alpha is ignored.
+    ///
+    /// # Errors
+    ///
+    /// If the mask has another size than the image, this will fail with
+    /// `RasterError::SizeMismatch`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let mut image = Image::filled(2, 1, Color::red());
+    /// let mut mask = Image::blank(2, 1);
+    /// mask.set_pixel(1, 0, Color::rgb(128, 128, 128)).unwrap();
+    ///
+    /// image.set_alpha_from(&mask).unwrap();
+    /// assert_eq!(0, image.get_pixel(0, 0).unwrap().a);
+    /// assert_eq!(128, image.get_pixel(1, 0).unwrap().a);
+    ///
+    /// assert!(image.set_alpha_from(&Image::blank(1, 1)).is_err());
+    /// ```
+    pub fn set_alpha_from(&mut self, mask: &Image) -> RasterResult<()> {
+        if (mask.width, mask.height) != (self.width, self.height) {
+            return Err(RasterError::SizeMismatch(mask.width, mask.height));
+        }
+        for (pixel, masked) in self.pixels_mut().zip(mask.pixels()) {
+            let gray = masked[0] as f32 * 0.3 + masked[1] as f32 * 0.59 + masked[2] as f32 * 0.11;
+            pixel[3] = gray.round().clamp(0.0, 255.0) as u8;
+        }
+        Ok(())
+    }
+
     /// Set pixel in a given x and y location of an image.
     ///
     /// # Errors
@@ -247,6 +691,254 @@ impl<'a> Image {
             Ok(())
         }
     }
+
+    /// Set pixel in a given x and y location of an image, without the checks of `set_pixel`. For
+    /// hot loops that already keep their coordinates in bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside the bytes of the image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    /// use raster::Color;
+    ///
+    /// let mut image = Image::blank(2, 2); // Creates a 2x2 black image.
+    ///
+    /// for y in 0..image.height {
+    ///     for x in 0..image.width {
+    ///         image.set_pixel_unchecked(x, y, Color::rgba(255, 0, 0, 255));
+    ///     }
+    /// }
+    /// assert_eq!(255, image.get_pixel(1, 1).unwrap().r);
+    /// ```
+    pub fn set_pixel_unchecked(&mut self, x: i32, y: i32, color: Color) {
+        let start = ((y * self.width + x) * 4) as usize;
+        self.bytes[start..start + 4].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+    }
+
+    /// Split an image into its red, green, blue and alpha channels, one byte per pixel each.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let image = Image::filled(2, 1, Color::rgba(10, 20, 30, 40));
+    /// let [r, g, b, a] = image.split_channels();
+    ///
+    /// assert_eq!(vec![10, 10], r);
+    /// assert_eq!(vec![40, 40], a);
+    /// # assert_eq!((vec![20, 20], vec![30, 30]), (g, b));
+    /// ```
+    pub fn split_channels(&self) -> [Vec<u8>; 4] {
+        let len = self.bytes.len() / 4;
+        let mut channels = [
+            Vec::with_capacity(len),
+            Vec::with_capacity(len),
+            Vec::with_capacity(len),
+            Vec::with_capacity(len),
+        ];
+        for pixel in self.pixels() {
+            for (channel, &value) in channels.iter_mut().zip(pixel) {
+                channel.push(value);
+            }
+        }
+        channels
+    }
+
+    /// Reorder the bytes of every pixel in place, from the channel order `from` to `to`. Use it
+    /// on `bytes` for frames in other orders than RGBA, or before handing them to an API that
+    /// expects another order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{ChannelOrder, Image};
+    ///
+    /// // A framebuffer with alpha first
+    /// let mut image = Image::from_raw(1, 1, vec![128, 10, 20, 30]).unwrap();
+    /// image.swap_channels(ChannelOrder::Argb, ChannelOrder::Rgba);
+    /// assert_eq!(vec![10, 20, 30, 128], image.bytes);
+    ///
+    /// image.swap_channels(ChannelOrder::Rgba, ChannelOrder::Abgr);
+    /// assert_eq!(vec![128, 30, 20, 10], image.bytes);
+    /// ```
+    pub fn swap_channels(&mut self, from: ChannelOrder, to: ChannelOrder) {
+        if from == to {
+            return;
+        }
+        let (from, to) = (from.indices(), to.indices());
+        for pixel in self.pixels_mut() {
+            let old = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            for channel in 0..4 {
+                pixel[to[channel]] = old[from[channel]];
+            }
+        }
+    }
+
+    /// Copy the bytes of the image in BGRA order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let image = Image::filled(1, 1, Color::rgba(10, 20, 30, 40));
+    /// assert_eq!(vec![30, 20, 10, 40], image.to_bgra());
+    /// ```
+    pub fn to_bgra(&self) -> Vec<u8> {
+        let mut bytes = self.bytes.clone();
+        for pixel in bytes.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        bytes
+    }
+
+    /// Encode the image into a new buffer, like the body of an HTTP response.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Encode` or `RasterError::UnsupportedFormat` upon
+    /// failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::endec::EncodeOptions;
+    /// use raster::ImageFormat;
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let options = EncodeOptions {
+    ///     quality: 90.0,
+    ///     ..EncodeOptions::default()
+    /// };
+    /// let data = image.to_bytes(ImageFormat::WebP, &options).unwrap();
+    ///
+    /// assert!(data.starts_with(b"RIFF"));
+    /// ```
+    pub fn to_bytes(&self, format: ImageFormat, options: &EncodeOptions) -> RasterResult<Vec<u8>> {
+        let mut data = Cursor::new(Vec::new());
+        encode(self, format, options, &mut data)?;
+        Ok(data.into_inner())
+    }
+
+    /// Encode the image into a new buffer with the metadata blocks of another file. Works the same
+    /// as `raster::save_with_metadata`.
+    ///
+    /// # Errors
+    ///
+    /// See `Image::to_bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::endec::EncodeOptions;
+    /// use raster::metadata::MetadataBlocks;
+    /// use raster::ImageFormat;
+    ///
+    /// let image = raster::open("tests/in/exif.jpg").unwrap();
+    /// let blocks = MetadataBlocks::from_path("tests/in/exif.jpg").unwrap();
+    /// let options = EncodeOptions::default();
+    /// let data = image.to_bytes_with_metadata(ImageFormat::Png, &options, &blocks).unwrap();
+    ///
+    /// assert_eq!(blocks.exif, MetadataBlocks::from_bytes(&data).unwrap().exif);
+    /// ```
+    pub fn to_bytes_with_metadata(
+        &self,
+        format: ImageFormat,
+        options: &EncodeOptions,
+        metadata: &MetadataBlocks,
+    ) -> RasterResult<Vec<u8>> {
+        let mut data = Cursor::new(Vec::new());
+        encode_with_metadata(self, metadata, format, options, &mut data)?;
+        Ok(data.into_inner())
+    }
+
+    /// Divide the color channels of every pixel by its alpha, the reverse of
+    /// `premultiply_alpha`. Fully transparent pixels stay black.
+    pub fn unpremultiply_alpha(&mut self) {
+        for pixel in self.pixels_mut() {
+            let a = pixel[3] as u32;
+            for channel in pixel[..3].iter_mut() {
+                *channel = (*channel as u32 * 255 + a / 2)
+                    .checked_div(a)
+                    .map_or(0, |v| cmp::min(255, v) as u8);
+            }
+        }
+    }
+
+    /// Borrow a rectangular window of an image without copying it. The window starts at the x and
+    /// y location and has to lie inside the image.
+    ///
+    /// # Errors
+    ///
+    /// If the window falls out of bounds, this will fail with `RasterError::PixelOutOfBounds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// let view = image.view(10, 20, 30, 40).unwrap();
+    /// assert_eq!(image.get_pixel(15, 25).unwrap().r, view.get_pixel(5, 5).unwrap().r);
+    /// assert!(image.view(10, 20, image.width, 40).is_err());
+    /// ```
+    pub fn view(&self, x: i32, y: i32, width: i32, height: i32) -> RasterResult<ImageView<'_>> {
+        ImageView::new(&self.bytes, (self.width, self.height), x, y, width, height)
+    }
+
+    /// Borrow a rectangular window of an image mutably without copying it. Works the same as
+    /// `view` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// See `view`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let mut image = Image::blank(4, 4);
+    /// let mut view = image.view_mut(2, 0, 2, 4).unwrap();
+    /// view.set_pixel(0, 0, Color::red()).unwrap();
+    ///
+    /// assert_eq!(255, image.get_pixel(2, 0).unwrap().r);
+    /// ```
+    pub fn view_mut(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> RasterResult<ImageViewMut<'_>> {
+        let size = (self.width, self.height);
+        ImageViewMut::new(&mut self.bytes, size, x, y, width, height)
+    }
+}
+
+/// Orders of the channels in the bytes of a pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelOrder {
+    Rgba,
+    Bgra,
+    Argb,
+    Abgr,
+}
+
+impl ChannelOrder {
+    // Positions of the red, green, blue and alpha bytes in a pixel
+    fn indices(self) -> [usize; 4] {
+        match self {
+            ChannelOrder::Rgba => [0, 1, 2, 3],
+            ChannelOrder::Bgra => [2, 1, 0, 3],
+            ChannelOrder::Argb => [1, 2, 3, 0],
+            ChannelOrder::Abgr => [3, 2, 1, 0],
+        }
+    }
 }
 
 /// Holds histogram information.
@@ -258,9 +950,33 @@ pub type Histogram = (
 );
 
 /// Enumeration of supported raster formats.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ImageFormat {
+    Avif,
+    Bmp,
+    Dds,
+    Exr,
+    Farbfeld,
     Gif,
+    Hdr,
+    Heif,
+    Ico,
     Jpeg,
     Png,
+    Pnm,
+    Qoi,
+    Svg,
+    Tga,
+    Tiff,
+    WebP,
+}
+
+// Private functions
+
+// Number of pixels in an image of the size, `None` for negative sizes and overflows.
+fn pixel_count(width: i32, height: i32) -> Option<usize> {
+    if width < 0 || height < 0 {
+        return None;
+    }
+    (width as usize).checked_mul(height as usize)
 }
@@ -0,0 +1,57 @@
+//!  A module for converting images to and from the `image` crate.
+
+// from rust
+use std::convert::TryFrom;
+
+// from external crate
+use image25::{DynamicImage, RgbaImage};
+
+// from local crate
+use error::{RasterError, RasterResult};
+use Image;
+
+// Other pixel formats are converted to 8-bit RGBA.
+impl From<DynamicImage> for Image {
+    fn from(image: DynamicImage) -> Image {
+        Image::from(image.into_rgba8())
+    }
+}
+
+impl<'a> From<&'a DynamicImage> for Image {
+    fn from(image: &'a DynamicImage) -> Image {
+        Image::from(image.to_rgba8())
+    }
+}
+
+impl From<RgbaImage> for Image {
+    fn from(image: RgbaImage) -> Image {
+        Image {
+            width: image.width() as i32,
+            height: image.height() as i32,
+            bytes: image.into_raw(),
+        }
+    }
+}
+
+impl TryFrom<Image> for DynamicImage {
+    type Error = RasterError;
+
+    fn try_from(image: Image) -> RasterResult<DynamicImage> {
+        RgbaImage::try_from(image).map(DynamicImage::ImageRgba8)
+    }
+}
+
+// Fails with `RasterError::SizeMismatch` holding the size of the image if the bytes don't match
+// it.
+impl TryFrom<Image> for RgbaImage {
+    type Error = RasterError;
+
+    fn try_from(image: Image) -> RasterResult<RgbaImage> {
+        let (width, height) = (image.width, image.height);
+        if width < 0 || height < 0 {
+            return Err(RasterError::SizeMismatch(width, height));
+        }
+        RgbaImage::from_raw(width as u32, height as u32, image.bytes)
+            .ok_or(RasterError::SizeMismatch(width, height))
+    }
+}
@@ -7,15 +7,23 @@ use std::cmp;
 
 // from local crate
 use error::RasterResult;
+use hdr;
 use Color;
 use Image;
 
 /// An enum for the various modes that can be used for interpolation.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InterpolationMode {
     Bilinear,
+    /// Resamples the same as `Bilinear` for now, use `CatmullRom` for a cubic filter.
     Bicubic,
     Nearest,
+    /// Lanczos filter with 3 lobes. Sharpest for downscaling photos, may ring around hard edges.
+    Lanczos3,
+    /// Mitchell-Netravali cubic filter. Softer than `CatmullRom`, with little ringing.
+    Mitchell,
+    /// Catmull-Rom cubic filter.
+    CatmullRom,
 }
 
 /// Resample an image into a new size using a given interpolation method.
@@ -24,11 +32,30 @@ pub fn resample(
     w: i32,
     h: i32,
     interpolation: InterpolationMode,
+) -> RasterResult<()> {
+    resample_with(src, w, h, interpolation, false)
+}
+
+// Resample, blending the pixels in linear light instead of sRGB if `linear` is set.
+pub(crate) fn resample_with(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    interpolation: InterpolationMode,
+    linear: bool,
 ) -> RasterResult<()> {
     match interpolation {
+        InterpolationMode::Bilinear | InterpolationMode::Bicubic if linear => {
+            convolve(src, w, h, Filter::Triangle, true)
+        }
         InterpolationMode::Bilinear => bilinear(src, w, h),
         InterpolationMode::Bicubic => bilinear(src, w, h), // TODO: bicubic
         InterpolationMode::Nearest => nearest(src, w, h),
+        InterpolationMode::Lanczos3 => convolve(src, w, h, Filter::Lanczos3, linear),
+        InterpolationMode::Mitchell => {
+            convolve(src, w, h, Filter::Cubic(1.0 / 3.0, 1.0 / 3.0), linear)
+        }
+        InterpolationMode::CatmullRom => convolve(src, w, h, Filter::Cubic(0.0, 0.5), linear),
     }
 }
 
@@ -59,8 +86,235 @@ pub fn bilinear(src: &mut Image, w2: i32, h2: i32) -> RasterResult<()> {
     bilinear_width(src, w2).and_then(|_| bilinear_height(src, h2))
 }
 
+// Sample the image at a point between pixels, with pixel centers at whole numbers. Points
+// outside the image take the background. Colors are weighted by their alpha like `convolve`.
+pub(crate) fn sample(
+    src: &Image,
+    x: f64,
+    y: f64,
+    interpolation: InterpolationMode,
+    background: [u8; 4],
+) -> [u8; 4] {
+    let pixel = |px: f64, py: f64| -> [u8; 4] {
+        if px < 0.0 || py < 0.0 || px >= src.width as f64 || py >= src.height as f64 {
+            return background;
+        }
+        let i = (py as usize * src.width as usize + px as usize) * 4;
+        [
+            src.bytes[i],
+            src.bytes[i + 1],
+            src.bytes[i + 2],
+            src.bytes[i + 3],
+        ]
+    };
+    let filter = match Filter::for_mode(interpolation) {
+        Some(filter) => filter,
+        None => return pixel(x.round(), y.round()),
+    };
+
+    let support = filter.support() as f64;
+    let mut sums = [0.0; 4];
+    let mut total = 0.0;
+    let mut py = (y - support).ceil();
+    while py <= y + support {
+        let weight_y = filter.weight((py - y) as f32);
+        let mut px = (x - support).ceil();
+        while px <= x + support {
+            let weight = filter.weight((px - x) as f32) * weight_y;
+            let color = pixel(px, py);
+            let alpha = color[3] as f32 * weight;
+            for c in 0..3 {
+                sums[c] += color[c] as f32 * alpha;
+            }
+            sums[3] += alpha;
+            total += weight;
+            px += 1.0;
+        }
+        py += 1.0;
+    }
+
+    if total == 0.0 || sums[3] <= 0.0 {
+        return [0; 4];
+    }
+    let mut result = [0; 4];
+    for c in 0..3 {
+        result[c] = (sums[c] / sums[3]).round().clamp(0.0, 255.0) as u8;
+    }
+    result[3] = (sums[3] / total).round().clamp(0.0, 255.0) as u8;
+    result
+}
+
 // Private functions
 
+// Kernels of the filters that `convolve` resamples with.
+#[derive(Debug, Clone, Copy)]
+enum Filter {
+    // Mitchell-Netravali cubic with its B and C parameters
+    Cubic(f32, f32),
+    Lanczos3,
+    Triangle,
+}
+
+impl Filter {
+    // The kernel of a mode, or none for nearest neighbor
+    fn for_mode(mode: InterpolationMode) -> Option<Filter> {
+        match mode {
+            InterpolationMode::Bilinear | InterpolationMode::Bicubic => Some(Filter::Triangle),
+            InterpolationMode::Nearest => None,
+            InterpolationMode::Lanczos3 => Some(Filter::Lanczos3),
+            InterpolationMode::Mitchell => Some(Filter::Cubic(1.0 / 3.0, 1.0 / 3.0)),
+            InterpolationMode::CatmullRom => Some(Filter::Cubic(0.0, 0.5)),
+        }
+    }
+
+    // Distance from the center that the kernel reaches
+    fn support(self) -> f32 {
+        match self {
+            Filter::Cubic(_, _) => 2.0,
+            Filter::Lanczos3 => 3.0,
+            Filter::Triangle => 1.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Cubic(b, c) => {
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else if x < 2.0 {
+                    ((-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+            Filter::Triangle => (1.0 - x).max(0.0),
+        }
+    }
+}
+
+// Resample with a filter kernel, first the width and then the height. The kernel is widened when
+// downscaling so every source pixel is taken into account. Colors are weighted by their alpha, so
+// transparent pixels don't bleed their color into the edges of opaque ones.
+fn convolve(src: &mut Image, w2: i32, h2: i32, filter: Filter, linear: bool) -> RasterResult<()> {
+    let (w1, h1) = (src.width.max(0) as usize, src.height.max(0) as usize);
+    let (w2, h2) = (w2.max(0) as usize, h2.max(0) as usize);
+
+    let mut decode = [0.0; 256];
+    for (byte, value) in decode.iter_mut().enumerate() {
+        *value = if linear {
+            hdr::to_linear(byte as u8) * 255.0
+        } else {
+            byte as f32
+        };
+    }
+
+    let mut samples: Vec<f32> = Vec::with_capacity(src.bytes.len());
+    for pixel in src.bytes.chunks_exact(4) {
+        let a = pixel[3] as f32 / 255.0;
+        samples.extend_from_slice(&[
+            decode[pixel[0] as usize] * a,
+            decode[pixel[1] as usize] * a,
+            decode[pixel[2] as usize] * a,
+            pixel[3] as f32,
+        ]);
+    }
+
+    let mut wide = vec![0.0; w2 * h1 * 4];
+    let x_weights = filter_weights(w1, w2, filter);
+    for y in 0..h1 {
+        for (x, &(start, ref weights)) in x_weights.iter().enumerate() {
+            let out = (y * w2 + x) * 4;
+            for (i, weight) in weights.iter().enumerate() {
+                let sample = (y * w1 + start + i) * 4;
+                for channel in 0..4 {
+                    wide[out + channel] += samples[sample + channel] * weight;
+                }
+            }
+        }
+    }
+
+    let mut dest = Image::blank(w2 as i32, h2 as i32);
+    let y_weights = filter_weights(h1, h2, filter);
+    for (y, &(start, ref weights)) in y_weights.iter().enumerate() {
+        for x in 0..w2 {
+            let mut pixel = [0.0; 4];
+            for (i, weight) in weights.iter().enumerate() {
+                let sample = ((start + i) * w2 + x) * 4;
+                for (channel, value) in pixel.iter_mut().enumerate() {
+                    *value += wide[sample + channel] * weight;
+                }
+            }
+            let a = pixel[3].clamp(0.0, 255.0);
+            let out = (y * w2 + x) * 4;
+            for (byte, &value) in dest.bytes[out..out + 3].iter_mut().zip(&pixel) {
+                let value = if a > 0.0 { value * 255.0 / a } else { 0.0 };
+                *byte = if linear {
+                    hdr::to_srgb(value / 255.0)
+                } else {
+                    value.round().clamp(0.0, 255.0) as u8
+                };
+            }
+            dest.bytes[out + 3] = a.round() as u8;
+        }
+    }
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
+
+// The first source sample and the normalized weights of the samples for each resampled one.
+fn filter_weights(len1: usize, len2: usize, filter: Filter) -> Vec<(usize, Vec<f32>)> {
+    if len1 == 0 {
+        return vec![(0, Vec::new()); len2];
+    }
+    let ratio = len1 as f32 / len2 as f32;
+    let scale = ratio.max(1.0);
+    let support = filter.support() * scale;
+    (0..len2)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * ratio;
+            let start = ((center - support).floor().max(0.0) as usize).min(len1 - 1);
+            let end = ((center + support).ceil() as usize).clamp(start + 1, len1);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.weight((j as f32 + 0.5 - center) / scale))
+                .collect();
+            let total: f32 = weights.iter().sum();
+            if total != 0.0 {
+                for weight in weights.iter_mut() {
+                    *weight /= total;
+                }
+            }
+            (start, weights)
+        })
+        .collect()
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * std::f32::consts::PI;
+        x.sin() / x
+    }
+}
+
 /// Interpolate the width using linear function.
 fn bilinear_width(src: &mut Image, w2: i32) -> RasterResult<()> {
     let w1 = src.width;
@@ -2,7 +2,8 @@
 //!
 //! Raster is an image processing lib for Rust.
 //!
-//! It provides a simplified API for processing raster images (JPEG, PNG and GIF).
+//! It provides a simplified API for processing raster images (JPEG, PNG, GIF, WebP, BMP, TIFF,
+//! QOI, TGA, PNM, ICO, HDR, DDS and farbfeld).
 //!
 //! ## Installation
 //! Add this to your Cargo.toml file:
@@ -21,6 +22,22 @@
 //! extern crate raster; // In your main rust file
 //! ```
 //!
+//! ### Optional features
+//!
+//! - `async`: Open and save images without blocking a tokio runtime, see `open_async` and
+//!   `save_async`.
+//! - `avif`: Decode AVIF images. Builds libaom from source, which requires CMake.
+//! - `exr`: Decode OpenEXR images.
+//! - `heif`: Decode HEIF and HEIC images. Links against libheif 1.18 or newer.
+//! - `http`: Download images from `http` and `https` URLs, see `open_url`.
+//! - `image-interop`: Convert `Image` to and from `DynamicImage` and `RgbaImage` of the `image`
+//!   crate, version 0.25. Conversions from `Image` use `TryFrom`, as its bytes might not match its
+//!   size.
+//! - `ndarray`: View an `Image` as an `ndarray` array of height by width by 4 bytes and create
+//!   one from such an array, see `Image::to_ndarray` and `Image::from_ndarray`.
+//! - `serde`: Serialize and deserialize `Image` and `Color`.
+//! - `svg`: Rasterize SVG images.
+//!
 //! ## Creating Images
 //! ### From an image file
 //!
@@ -85,53 +102,126 @@
 //!
 
 // modules
+mod animation;
+#[cfg(feature = "ndarray")]
+mod array;
 mod blend;
+pub mod buffer;
 mod color;
 pub mod compare;
 pub mod editor;
-mod endec;
+pub mod endec;
 pub mod error;
 pub mod filter;
+mod hdr;
+#[cfg(feature = "http")]
+mod http;
+mod icc;
 mod image;
+#[cfg(feature = "image-interop")]
+mod interop;
 pub mod interpolate;
+pub mod metadata;
 mod position;
+#[cfg(feature = "serde")]
+mod serialize;
 pub mod transform;
+mod view;
+pub mod yuv;
 
 // crates
+#[cfg(feature = "avif")]
+extern crate avif_decode;
+#[cfg(feature = "exr")]
+extern crate exr;
+extern crate flate2;
+#[cfg(feature = "async")]
+extern crate futures_util;
 extern crate gif;
 extern crate image as piston_image;
+#[cfg(feature = "image-interop")]
+extern crate image25;
+extern crate jpeg_decoder;
+#[cfg(feature = "heif")]
+extern crate libheif_rs;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
 extern crate png;
+extern crate qoi;
+#[cfg(feature = "svg")]
+extern crate resvg;
+#[cfg(feature = "serde")]
+extern crate serde_core as serde;
+extern crate tiff;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "http")]
+extern crate ureq;
+extern crate webp;
 
 // from rust
-use std::ascii::AsciiExt;
-use std::fs::File;
+use std::fs::{self, File};
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
 // from external crate
-use piston_image::GenericImage;
+#[cfg(feature = "async")]
+use futures_util::future::{self, FutureExt, TryFutureExt};
 
 // from local crate
+use endec::{DecodeLimits, DecodeOptions, EncodeOptions, PnmFormat};
 use error::{RasterError, RasterResult};
+use metadata::{Metadata, MetadataBlocks};
 
 // re-exports
+pub use animation::Animation;
+pub use animation::DisposalMethod;
+pub use animation::Frame;
+pub use animation::FrameBlend;
+#[allow(deprecated)]
+pub use animation::GifAnimation;
+pub use animation::LoopCount;
 pub use blend::BlendMode;
+pub use buffer::ImageBuffer;
 pub use color::Color;
 pub use editor::ResizeMode;
 pub use filter::BlurMode;
+pub use filter::EdgeMode;
+pub use filter::EdgeOperator;
 pub use filter::Orientation;
+pub use hdr::HdrImage;
+pub use hdr::ToneMap;
+pub use image::ChannelOrder;
 pub use image::Histogram;
 pub use image::Image;
 pub use image::ImageFormat;
 pub use interpolate::InterpolationMode;
 pub use position::PositionMode;
+pub use transform::RotateMode;
 pub use transform::TransformMode;
+pub use view::ImageRef;
+pub use view::ImageRefMut;
+pub use view::ImageView;
+pub use view::ImageViewMut;
 
-/// Create an image from an image file.
+/// Create an image from an image file. The image type is detected from the file extension of the
+/// file name. When the extension is unknown or the file fails to decode, the type is detected from
+/// the file contents instead, so a PNG saved as `.jpg` still opens.
+///
+/// Images larger than `DecodeLimits::default()` are refused, use `open_with_limits` to change
+/// the limits. Use `open_with` to recover what is left of a truncated or corrupt file.
+///
+/// Only the first frame of an animated GIF and the first page of a TIFF are decoded. For icons,
+/// the largest size is used. HDR and EXR images are tone mapped with `ToneMap::Reinhard`. SVG
+/// images are rasterized at their own size, use `endec::decode_svg` to pick another. AVIF, EXR,
+/// HEIF and SVG images need the `avif`, `exr`, `heif` and `svg` features.
 ///
 /// # Errors
 ///
-/// This function can return `RasterError::Io`, `RasterError::Decode`, or
-/// `RasterError::UnsupportedFormat` upon failure.
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::LimitExceeded` or `RasterError::UnsupportedFormat` upon failure.
 /// See error module for more info.
 ///
 /// # Examples
@@ -142,41 +232,164 @@ pub use transform::TransformMode;
 /// println!("{:?}", image.bytes);
 /// ```
 pub fn open(image_file: &str) -> RasterResult<Image> {
-    let path = Path::new(image_file);
-    let ext = path
-        .extension()
-        .and_then(|s| s.to_str())
-        .map_or("".to_string(), |s| s.to_ascii_lowercase());
+    open_with(image_file, &DecodeOptions::default())
+}
 
+/// Create an image from an image file with the given decoder settings. Works the same as `open`
+/// otherwise.
+///
+/// With `tolerant` set, truncated or corrupt JPEGs and PNGs return the part of the image that
+/// could be decoded, padded to their full size, instead of an error. Useful for recovering
+/// partially downloaded files.
+///
+/// # Errors
+///
+/// See `open`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::DecodeOptions;
+///
+/// let options = DecodeOptions {
+///     tolerant: true,
+///     ..DecodeOptions::default()
+/// };
+/// let image = raster::open_with("tests/in/sample.jpg", &options).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+pub fn open_with(image_file: &str, options: &DecodeOptions) -> RasterResult<Image> {
     // Open the file with basic error check
     let file = File::open(image_file)?;
 
-    match &ext[..] {
-        "gif" => Ok(endec::decode_gif(&file)?),
-        "jpg" | "jpeg" => {
-            let src = piston_image::open(image_file)?;
-            let (w, h) = src.dimensions();
-            let mut bytes = Vec::with_capacity((w * h) as usize * 4);
-            for y in 0..h {
-                for x in 0..w {
-                    let p = src.get_pixel(x, y);
-                    bytes.extend_from_slice(&p.data[0..4]);
-                }
-            }
-            Ok(Image {
-                width: w as i32,
-                height: h as i32,
-                bytes: bytes,
-            })
-        }
-        "png" => Ok(endec::decode_png(&file)?),
-        _ => Err(RasterError::UnsupportedFormat(ext)),
+    open_reader(&extension(image_file), file, options)
+}
+
+/// Create an image from an image file, refusing images larger than `limits`. Works the same as
+/// `open` otherwise. Use it with tight limits for files from untrusted sources.
+///
+/// # Errors
+///
+/// See `open`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::DecodeLimits;
+///
+/// let limits = DecodeLimits {
+///     max_width: 4096,
+///     max_height: 4096,
+///     max_bytes: 64 << 20,
+/// };
+/// let image = raster::open_with_limits("tests/in/sample.png", &limits).unwrap();
+///
+/// assert!(image.width <= 4096);
+/// ```
+pub fn open_with_limits(image_file: &str, limits: &DecodeLimits) -> RasterResult<Image> {
+    let options = DecodeOptions {
+        limits: *limits,
+        ..DecodeOptions::default()
+    };
+    open_with(image_file, &options)
+}
+
+/// Download an image and create an image from it. Requires the `http` feature. Both `http` and
+/// `https` URLs are supported, redirects are followed up to 5 times, and the download fails if it
+/// takes longer than 30 seconds. The image type is detected from the extension of the URL path, or from
+/// the data if that fails. The download and the image are held to the default `DecodeLimits`, use
+/// `open_url_with_limits` for others.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Http` for URLs that can't be fetched or responses that
+/// are too large, and the errors of `open`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let image = raster::open_url("http://example.com/sample.png").unwrap();
+/// println!("{}x{}", image.width, image.height);
+/// ```
+#[cfg(feature = "http")]
+pub fn open_url(url: &str) -> RasterResult<Image> {
+    open_url_with_limits(url, &DecodeLimits::default())
+}
+
+/// Download an image and create an image from it, refusing downloads longer than
+/// `limits.max_bytes` and images larger than `limits`. Requires the `http` feature. Works the same
+/// as `open_url` otherwise.
+///
+/// # Errors
+///
+/// See `open_url`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use raster::endec::DecodeLimits;
+///
+/// let limits = DecodeLimits {
+///     max_width: 4096,
+///     max_height: 4096,
+///     max_bytes: 16 << 20,
+/// };
+/// let image = raster::open_url_with_limits("http://example.com/sample.png", &limits).unwrap();
+///
+/// assert!(image.width <= 4096);
+/// ```
+#[cfg(feature = "http")]
+pub fn open_url_with_limits(url: &str, limits: &DecodeLimits) -> RasterResult<Image> {
+    let (url, data) = http::get(url, limits.max_bytes)?;
+    let path = url.split(['?', '#']).next().unwrap_or("");
+    let options = DecodeOptions {
+        limits: *limits,
+        ..DecodeOptions::default()
+    };
+    open_reader(&extension(path), Cursor::new(data), &options)
+}
+
+/// Create an image from an image file and turn it upright by its EXIF orientation. Works the
+/// same as `open` otherwise.
+///
+/// The orientation is read from JPEGs, PNGs and TIFFs with `metadata::Metadata`. Images without
+/// one, and HEIF images whose rotation is applied while decoding, are returned as they are. To
+/// save the image with its metadata, call `MetadataBlocks::reset_orientation` on the blocks first.
+///
+/// # Errors
+///
+/// See `open`.
+///
+/// # Examples
+///
+/// ```
+/// let image = raster::open_oriented("tests/in/exif.jpg").unwrap();
+/// let unturned = raster::open("tests/in/exif.jpg").unwrap();
+/// assert_eq!((unturned.height, unturned.width), (image.width, image.height));
+/// ```
+pub fn open_oriented(image_file: &str) -> RasterResult<Image> {
+    let mut image = open(image_file)?;
+    // Formats without EXIF data fail to read the metadata and keep their orientation
+    if let Ok(Metadata {
+        orientation: Some(orientation),
+        ..
+    }) = Metadata::from_path(image_file)
+    {
+        transform::orient(&mut image, orientation)?;
     }
+    Ok(image)
 }
 
 /// Save an image to an image file. The image type is detected from the file extension of the file
 /// name.
 ///
+/// WebP images are saved as lossy with a quality of 75. Use `endec::encode_webp` or
+/// `endec::encode_webp_lossless` for other settings. TIFF images are saved with LZW compression,
+/// TGA images with RLE compression and PNM images with binary samples. ICO images hold a single
+/// size, use `endec::encode_ico` to store several. Metadata is not saved, use `save_with_metadata`
+/// to keep it.
+///
 /// # Errors
 ///
 /// This function can return `RasterError::Io`, `RasterError::Encode`, or
@@ -191,23 +404,432 @@ pub fn open(image_file: &str) -> RasterResult<Image> {
 /// raster::save(&image, "tests/out/test.png").unwrap();
 /// ```
 pub fn save(image: &Image, out: &str) -> RasterResult<()> {
-    let path = Path::new(out);
-    let ext = path
+    let (format, options) = save_format(extension(out))?;
+    let file = File::create(out)?;
+    encode(image, format, &options, BufWriter::new(file))
+}
+
+/// Save an image to an image file with the given encoder settings. The image type is detected
+/// from the file extension, PNM files also take their format from it. Works the same as `save`
+/// otherwise.
+///
+/// # Errors
+///
+/// See `save`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::{EncodeOptions, PnmEncoding};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// let options = EncodeOptions {
+///     pnm_encoding: PnmEncoding::Plain,
+///     ..EncodeOptions::default()
+/// };
+/// raster::save_with(&image, "tests/out/test_save_with.ppm", &options).unwrap();
+///
+/// let saved = std::fs::read("tests/out/test_save_with.ppm").unwrap();
+/// assert!(saved.starts_with(b"P3"));
+/// ```
+pub fn save_with(image: &Image, out: &str, options: &EncodeOptions) -> RasterResult<()> {
+    let (format, default) = save_format(extension(out))?;
+    let options = EncodeOptions {
+        pnm_format: default.pnm_format,
+        ..*options
+    };
+    let file = File::create(out)?;
+    encode(image, format, &options, BufWriter::new(file))
+}
+
+/// Save an image to an image file with the EXIF, ICC and XMP blocks, PNG text and resolution of
+/// another file, read with `MetadataBlocks::from_path`. The blocks are written to JPEGs, PNGs and
+/// WebPs, other formats are saved without them. Works the same as `save` otherwise.
+///
+/// Images turned upright with `open_oriented` should have their blocks passed through
+/// `MetadataBlocks::reset_orientation` first, or viewers will turn them again.
+///
+/// # Errors
+///
+/// See `save`. Blocks too large for a JPEG segment fail with `RasterError::Encode`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::metadata::MetadataBlocks;
+///
+/// let image = raster::open("tests/in/exif.jpg").unwrap();
+/// let blocks = MetadataBlocks::from_path("tests/in/exif.jpg").unwrap();
+/// raster::save_with_metadata(&image, "tests/out/test_save_with_metadata.jpg", &blocks).unwrap();
+///
+/// let saved = MetadataBlocks::from_path("tests/out/test_save_with_metadata.jpg").unwrap();
+/// assert_eq!(blocks, saved);
+/// ```
+pub fn save_with_metadata(image: &Image, out: &str, metadata: &MetadataBlocks) -> RasterResult<()> {
+    let (format, options) = save_format(extension(out))?;
+    let file = File::create(out)?;
+    encode_with_metadata(image, metadata, format, &options, BufWriter::new(file))
+}
+
+/// Copy an image file without its EXIF and XMP metadata, which can hold the location a photo was
+/// taken at. The format is detected from the file's contents.
+///
+/// JPEGs, PNGs and WebPs keep their compressed data and ICC profile, only the metadata blocks are
+/// left out. JPEGs also lose their IPTC segment. TIFFs are decoded and encoded again, keeping only
+/// the first page. Formats without EXIF or XMP are copied as they are.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`, `RasterError::Encode` or
+/// `RasterError::UnsupportedFormat` upon failure. AVIF and HEIF images can't be encoded and
+/// return `RasterError::UnsupportedFormat`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::metadata::Metadata;
+///
+/// raster::strip_metadata("tests/in/exif.jpg", "tests/out/test_strip_metadata.jpg").unwrap();
+///
+/// let metadata = Metadata::from_path("tests/out/test_strip_metadata.jpg").unwrap();
+/// assert_eq!(None, metadata.gps);
+/// ```
+pub fn strip_metadata(image_in: &str, image_out: &str) -> RasterResult<()> {
+    let data = strip_metadata_from_bytes(&fs::read(image_in)?)?;
+    fs::write(image_out, data)?;
+    Ok(())
+}
+
+/// Remove the EXIF and XMP metadata of an image held in memory. Works the same as
+/// `strip_metadata`.
+///
+/// # Errors
+///
+/// See `strip_metadata`.
+///
+/// # Examples
+///
+/// ```
+/// let data = std::fs::read("tests/in/exif.jpg").unwrap();
+/// let stripped = raster::strip_metadata_from_bytes(&data).unwrap();
+///
+/// assert!(stripped.len() < data.len());
+/// ```
+pub fn strip_metadata_from_bytes(data: &[u8]) -> RasterResult<Vec<u8>> {
+    let format = endec::detect_format(&mut Cursor::new(data))?;
+    match format {
+        ImageFormat::Avif | ImageFormat::Heif => Err(RasterError::UnsupportedFormat(
+            format!("{:?}", format).to_lowercase(),
+        )),
+        ImageFormat::Tiff => {
+            let image = decode(format, Cursor::new(data), &DecodeOptions::default())?;
+            let mut out = Cursor::new(Vec::new());
+            encode(&image, format, &EncodeOptions::default(), &mut out)?;
+            Ok(out.into_inner())
+        }
+        _ => metadata::strip_blocks(format, data),
+    }
+}
+
+/// Create an image from an image file without blocking the async runtime. Requires the `async`
+/// feature. The file is read with tokio and decoded on its blocking thread pool. Works the same as
+/// `open` otherwise.
+///
+/// # Errors
+///
+/// See `open`.
+///
+/// # Examples
+///
+/// ```
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let image = runtime.block_on(raster::open_async("tests/in/sample.png")).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+#[cfg(feature = "async")]
+pub fn open_async(image_file: &str) -> impl Future<Output = RasterResult<Image>> {
+    let ext = extension(image_file);
+    let path = image_file.to_string();
+    tokio::fs::read(path)
+        .map_err(RasterError::from)
+        .and_then(move |data| {
+            blocking(move || open_reader(&ext, Cursor::new(data), &DecodeOptions::default()))
+        })
+}
+
+/// Save an image to an image file without blocking the async runtime. Requires the `async`
+/// feature. The image is copied and encoded on tokio's blocking thread pool, then written with
+/// tokio. Works the same as `save` otherwise.
+///
+/// # Errors
+///
+/// See `save`.
+///
+/// # Examples
+///
+/// ```
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// runtime.block_on(raster::save_async(&image, "tests/out/test_save_async.png")).unwrap();
+/// ```
+#[cfg(feature = "async")]
+pub fn save_async(image: &Image, out: &str) -> impl Future<Output = RasterResult<()>> {
+    let image = image.clone();
+    let path = out.to_string();
+    future::ready(save_format(extension(out)))
+        .and_then(move |(format, options)| blocking(move || image.to_bytes(format, &options)))
+        .and_then(move |data| tokio::fs::write(path, data).map_err(RasterError::from))
+}
+
+/// Decode an image held in memory on tokio's blocking thread pool. Requires the `async` feature.
+/// Works the same as `Image::from_bytes`.
+///
+/// # Errors
+///
+/// See `Image::from_bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::ImageFormat;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let data = std::fs::read("tests/in/sample.png").unwrap();
+/// let image = runtime.block_on(raster::from_bytes_async(ImageFormat::Png, data)).unwrap();
+///
+/// assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+/// ```
+#[cfg(feature = "async")]
+pub fn from_bytes_async(
+    format: ImageFormat,
+    data: Vec<u8>,
+) -> impl Future<Output = RasterResult<Image>> {
+    blocking(move || Image::from_bytes(format, &data))
+}
+
+/// Encode an image into a new buffer on tokio's blocking thread pool. Requires the `async`
+/// feature. The image is copied first. Works the same as `Image::to_bytes`.
+///
+/// # Errors
+///
+/// See `Image::to_bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::EncodeOptions;
+/// use raster::ImageFormat;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let image = raster::open("tests/in/sample.png").unwrap();
+/// let options = EncodeOptions::default();
+/// let data = runtime.block_on(raster::to_bytes_async(&image, ImageFormat::Qoi, options)).unwrap();
+///
+/// assert!(data.starts_with(b"qoif"));
+/// ```
+#[cfg(feature = "async")]
+pub fn to_bytes_async(
+    image: &Image,
+    format: ImageFormat,
+    options: EncodeOptions,
+) -> impl Future<Output = RasterResult<Vec<u8>>> {
+    let image = image.clone();
+    blocking(move || image.to_bytes(format, &options))
+}
+
+// Private functions
+
+// Run a decode or encode on tokio's blocking thread pool.
+#[cfg(feature = "async")]
+fn blocking<T, F>(f: F) -> impl Future<Output = RasterResult<T>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> RasterResult<T> + Send + 'static,
+{
+    // Spawn on first poll, when the runtime is known to be there
+    future::lazy(move |_| tokio::task::spawn_blocking(f))
+        .flatten()
+        .map(|result| match result {
+            Ok(result) => result,
+            // Pass panics on like the synchronous functions would
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(_) => Err(RasterError::Unexpected),
+        })
+}
+
+// Decode an image of a known format the way `open` does.
+fn decode<R: Read + Seek>(
+    format: ImageFormat,
+    reader: R,
+    options: &DecodeOptions,
+) -> RasterResult<Image> {
+    let limits = &options.limits;
+    match format {
+        #[cfg(feature = "avif")]
+        ImageFormat::Avif => endec::decode_avif_from_reader(reader, limits),
+        ImageFormat::Bmp => endec::decode_bmp_from_reader(reader, limits),
+        ImageFormat::Dds => endec::decode_dds_from_reader(reader, limits),
+        #[cfg(feature = "exr")]
+        ImageFormat::Exr => {
+            Ok(endec::decode_exr_from_reader(reader, limits)?.tone_map(ToneMap::Reinhard))
+        }
+        ImageFormat::Farbfeld => endec::decode_farbfeld_from_reader(reader, limits),
+        ImageFormat::Gif => endec::decode_gif_from_reader(reader, limits),
+        ImageFormat::Hdr => {
+            Ok(endec::decode_hdr_from_reader(reader, limits)?.tone_map(ToneMap::Reinhard))
+        }
+        #[cfg(feature = "heif")]
+        ImageFormat::Heif => Ok(endec::decode_heif_from_reader(reader, limits)?.0),
+        ImageFormat::Ico => endec::decode_ico_from_reader(reader, None, limits),
+        ImageFormat::Jpeg if options.tolerant => endec::decode_jpeg_tolerant(reader, limits),
+        ImageFormat::Jpeg => endec::decode_jpeg_from_reader(reader, limits),
+        ImageFormat::Pnm => endec::decode_pnm_from_reader(reader, limits),
+        ImageFormat::Png if options.tolerant => endec::decode_png_tolerant(reader, limits),
+        ImageFormat::Png => endec::decode_png_from_reader(reader, limits),
+        ImageFormat::Qoi => endec::decode_qoi_from_reader(reader, limits),
+        #[cfg(feature = "svg")]
+        ImageFormat::Svg => endec::decode_svg_from_reader(reader, None, limits),
+        ImageFormat::Tga => endec::decode_tga_from_reader(reader, limits),
+        ImageFormat::Tiff => endec::decode_tiff_from_reader(reader, limits),
+        ImageFormat::WebP => endec::decode_webp_from_reader(reader, limits),
+        // Formats behind a disabled feature
+        #[allow(unreachable_patterns)]
+        _ => Err(RasterError::UnsupportedFormat(
+            format!("{:?}", format).to_lowercase(),
+        )),
+    }
+}
+
+// Encode an image the way `save` does.
+fn encode<W: Write + Seek>(
+    image: &Image,
+    format: ImageFormat,
+    options: &EncodeOptions,
+    writer: W,
+) -> RasterResult<()> {
+    match format {
+        ImageFormat::Bmp => endec::encode_bmp_to_writer(image, writer),
+        ImageFormat::Farbfeld => endec::encode_farbfeld_to_writer(image, writer),
+        ImageFormat::Gif => endec::encode_gif_to_writer(image, writer),
+        ImageFormat::Ico => endec::encode_ico_to_writer(std::slice::from_ref(image), writer),
+        ImageFormat::Jpeg => endec::encode_jpeg_to_writer(image, writer),
+        ImageFormat::Pnm => {
+            endec::encode_pnm_to_writer(image, writer, options.pnm_format, options.pnm_encoding)
+        }
+        ImageFormat::Png => endec::encode_png_to_writer(image, writer),
+        ImageFormat::Qoi => endec::encode_qoi_to_writer(image, writer),
+        ImageFormat::Tga => endec::encode_tga_to_writer(image, writer, options.tga_compression),
+        ImageFormat::Tiff => endec::encode_tiff_to_writer(image, writer, options.tiff_compression),
+        ImageFormat::WebP if options.lossless => {
+            endec::encode_webp_lossless_to_writer(image, writer)
+        }
+        ImageFormat::WebP => endec::encode_webp_to_writer(image, writer, options.quality),
+        _ => Err(RasterError::UnsupportedFormat(
+            format!("{:?}", format).to_lowercase(),
+        )),
+    }
+}
+
+// Encode an image with metadata blocks put in the way `save_with_metadata` does.
+fn encode_with_metadata<W: Write + Seek>(
+    image: &Image,
+    metadata: &MetadataBlocks,
+    format: ImageFormat,
+    options: &EncodeOptions,
+    mut writer: W,
+) -> RasterResult<()> {
+    // Encode without the metadata, then put the blocks in
+    let mut data = Cursor::new(Vec::new());
+    encode(image, format, options, &mut data)?;
+    writer.write_all(&metadata::write_blocks(
+        image,
+        metadata,
+        format,
+        data.into_inner(),
+    )?)?;
+    Ok(())
+}
+
+// Lowercase file extension of a path, or an empty string if there is none.
+fn extension(path: &str) -> String {
+    Path::new(path)
         .extension()
         .and_then(|s| s.to_str())
-        .map_or("".to_string(), |s| s.to_ascii_lowercase());
-
-    match &ext[..] {
-        "gif" => Ok(endec::encode_gif(&image, &path)?),
-        "jpg" | "jpeg" => piston_image::save_buffer(
-            &path,
-            &image.bytes,
-            image.width as u32,
-            image.height as u32,
-            piston_image::RGBA(8),
-        )
-        .map_err(|_| RasterError::Encode(ImageFormat::Jpeg, "Format".to_string())),
-        "png" => Ok(endec::encode_png(&image, &path)?),
-        _ => Err(RasterError::UnsupportedFormat(ext)),
+        .map_or("".to_string(), |s| s.to_ascii_lowercase())
+}
+
+// Decode an image the way `open` does, picking the format by extension first.
+fn open_reader<R: Read + Seek>(
+    ext: &str,
+    mut reader: R,
+    options: &DecodeOptions,
+) -> RasterResult<Image> {
+    let format = match ext {
+        "avif" => Some(ImageFormat::Avif),
+        "bmp" => Some(ImageFormat::Bmp),
+        "dds" => Some(ImageFormat::Dds),
+        "exr" => Some(ImageFormat::Exr),
+        "ff" => Some(ImageFormat::Farbfeld),
+        "gif" => Some(ImageFormat::Gif),
+        "hdr" => Some(ImageFormat::Hdr),
+        "heic" | "heif" => Some(ImageFormat::Heif),
+        "ico" | "cur" => Some(ImageFormat::Ico),
+        "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+        "pbm" | "pgm" | "ppm" | "pnm" | "pam" => Some(ImageFormat::Pnm),
+        "png" => Some(ImageFormat::Png),
+        "qoi" => Some(ImageFormat::Qoi),
+        "svg" => Some(ImageFormat::Svg),
+        "tga" => Some(ImageFormat::Tga),
+        "tif" | "tiff" => Some(ImageFormat::Tiff),
+        "webp" => Some(ImageFormat::WebP),
+        _ => None,
+    };
+    let result = match format {
+        Some(format) => decode(format, &mut reader, options),
+        None => Err(RasterError::UnsupportedFormat(ext.to_string())),
+    };
+
+    // Fall back to the magic number for files with a wrong or missing extension
+    match result {
+        Err(RasterError::Decode(..)) | Err(RasterError::UnsupportedFormat(_)) => {
+            reader.seek(SeekFrom::Start(0))?;
+            match endec::detect_format(&mut reader) {
+                Ok(detected) if Some(detected) != format => {
+                    reader.seek(SeekFrom::Start(0))?;
+                    decode(detected, &mut reader, options)
+                }
+                _ => result,
+            }
+        }
+        result => result,
     }
 }
+
+// Format and encoder settings `save` uses for a file extension.
+fn save_format(ext: String) -> RasterResult<(ImageFormat, EncodeOptions)> {
+    let mut options = EncodeOptions::default();
+    let format = match &ext[..] {
+        "bmp" => ImageFormat::Bmp,
+        "ff" => ImageFormat::Farbfeld,
+        "gif" => ImageFormat::Gif,
+        "ico" => ImageFormat::Ico,
+        "jpg" | "jpeg" => ImageFormat::Jpeg,
+        "pam" | "pbm" | "pgm" | "ppm" | "pnm" => {
+            options.pnm_format = match &ext[..] {
+                "pam" => PnmFormat::Pam,
+                "pbm" => PnmFormat::Pbm,
+                "pgm" => PnmFormat::Pgm,
+                _ => PnmFormat::Ppm,
+            };
+            ImageFormat::Pnm
+        }
+        "png" => ImageFormat::Png,
+        "qoi" => ImageFormat::Qoi,
+        "tga" => ImageFormat::Tga,
+        "tif" | "tiff" => ImageFormat::Tiff,
+        "webp" => ImageFormat::WebP,
+        _ => return Err(RasterError::UnsupportedFormat(ext)),
+    };
+    Ok((format, options))
+}
@@ -0,0 +1,847 @@
+//!  A module for reading image metadata.
+//!
+//! EXIF data is read from JPEG APP1 segments, PNG `eXIf` chunks, WebP `EXIF` chunks and the first
+//! IFD of TIFF files.
+
+// from rust
+use std::cmp;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// from external crate
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+
+// from local crate
+use endec;
+use error::{RasterError, RasterResult};
+use Image;
+use ImageFormat;
+
+// Signatures at the start of JPEG APP1 and APP2 segments
+const JPEG_EXIF: &[u8] = b"Exif\0\0";
+const JPEG_ICC: &[u8] = b"ICC_PROFILE\0";
+const JPEG_XMP: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+// Keyword of the PNG iTXt chunk holding XMP
+const PNG_XMP: &[u8] = b"XML:com.adobe.xmp";
+
+/// A struct for the EXIF metadata of an image. Fields are `None` when the tag is missing or
+/// can't be read.
+///
+/// Timestamps are kept as written by the camera, in the `YYYY:MM:DD HH:MM:SS` format and without
+/// a time zone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// Manufacturer of the camera.
+    pub make: Option<String>,
+
+    /// Model name of the camera.
+    pub model: Option<String>,
+
+    /// How the image has to be rotated and mirrored to display upright, from 1 to 8. See the EXIF
+    /// specification for the meaning of each value.
+    pub orientation: Option<u16>,
+
+    /// When the file was last changed.
+    pub date_time: Option<String>,
+
+    /// When the photo was taken.
+    pub date_time_original: Option<String>,
+
+    /// When the photo was stored digitally. Differs from `date_time_original` for scans.
+    pub date_time_digitized: Option<String>,
+
+    /// Where the photo was taken.
+    pub gps: Option<GpsPosition>,
+}
+
+impl Metadata {
+    /// Read the metadata of an image file. The format is detected from the file's contents.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` or `RasterError::Decode` upon failure. Formats
+    /// other than JPEG, PNG, TIFF and WebP return `RasterError::UnsupportedFormat`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::metadata::Metadata;
+    ///
+    /// let metadata = Metadata::from_path("tests/in/exif.jpg").unwrap();
+    /// assert_eq!(Some("Raster".to_string()), metadata.make);
+    /// assert_eq!(Some("2016:12:16 06:49:56".to_string()), metadata.date_time_original);
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> RasterResult<Metadata> {
+        Metadata::from_bytes(&fs::read(path)?)
+    }
+
+    /// Read the metadata of an image in memory. Works the same as `Metadata::from_path`.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` or `RasterError::Decode` upon failure. Formats
+    /// other than JPEG, PNG, TIFF and WebP return `RasterError::UnsupportedFormat`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::metadata::Metadata;
+    ///
+    /// let bytes = std::fs::read("tests/in/sample.png").unwrap();
+    /// let metadata = Metadata::from_bytes(&bytes).unwrap();
+    /// assert_eq!(Metadata::default(), metadata);
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> RasterResult<Metadata> {
+        let format = endec::detect_format(&mut Cursor::new(bytes))?;
+        let tiff = match format {
+            ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP => {
+                read_blocks(format, Cursor::new(bytes)).exif
+            }
+            ImageFormat::Tiff => Some(bytes.to_vec()),
+            _ => {
+                return Err(RasterError::UnsupportedFormat(
+                    format!("{:?}", format).to_lowercase(),
+                ))
+            }
+        };
+        match tiff {
+            Some(tiff) => exif(&tiff)
+                .ok_or_else(|| RasterError::Decode(format, "Invalid EXIF data".to_string())),
+            None => Ok(Metadata::default()),
+        }
+    }
+}
+
+/// A struct for a GPS position in degrees and meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    /// Degrees north of the equator. Negative in the southern hemisphere.
+    pub latitude: f64,
+
+    /// Degrees east of the prime meridian. Negative in the western hemisphere.
+    pub longitude: f64,
+
+    /// Meters above sea level. Negative below sea level.
+    pub altitude: Option<f64>,
+}
+
+/// A struct for the raw metadata blocks of an image file. `MetadataBlocks::from_path` reads them
+/// from JPEGs, PNGs and WebPs, and `raster::save_with_metadata` writes them to a saved file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataBlocks {
+    /// EXIF data starting at its TIFF header.
+    pub exif: Option<Vec<u8>>,
+
+    /// ICC color profile.
+    pub icc_profile: Option<Vec<u8>>,
+
+    /// XMP packet.
+    pub xmp: Option<Vec<u8>>,
+
+    /// Text chunks of a PNG by keyword, like `Software` or `Comment`.
+    pub text: HashMap<String, String>,
+
+    /// Horizontal and vertical resolution in dots per inch, from the `pHYs` chunk of a PNG or the
+    /// JFIF density of a JPEG.
+    pub dpi: Option<(f32, f32)>,
+}
+
+impl MetadataBlocks {
+    /// Read the metadata blocks of an image file. The format is detected from the file's contents.
+    /// Formats other than JPEG, PNG and WebP have no blocks. Reading stops at a damaged block,
+    /// keeping the blocks before it.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` or `RasterError::UnsupportedFormat` upon
+    /// failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::metadata::MetadataBlocks;
+    ///
+    /// let blocks = MetadataBlocks::from_path("tests/in/exif.jpg").unwrap();
+    /// assert!(blocks.exif.is_some());
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> RasterResult<MetadataBlocks> {
+        MetadataBlocks::from_bytes(&fs::read(path)?)
+    }
+
+    /// Read the metadata blocks of an image in memory. Works the same as
+    /// `MetadataBlocks::from_path`.
+    ///
+    /// # Errors
+    ///
+    /// This function can return `RasterError::Io` or `RasterError::UnsupportedFormat` upon
+    /// failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::metadata::MetadataBlocks;
+    ///
+    /// let bytes = std::fs::read("tests/in/sample.tif").unwrap();
+    /// assert!(MetadataBlocks::from_bytes(&bytes).unwrap().is_empty());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> RasterResult<MetadataBlocks> {
+        let format = endec::detect_format(&mut Cursor::new(bytes))?;
+        Ok(read_blocks(format, Cursor::new(bytes)))
+    }
+
+    /// Set the EXIF orientation to 1, for images that have been turned upright with
+    /// `transform::orient`. Blocks without EXIF data are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::metadata::{Metadata, MetadataBlocks};
+    ///
+    /// let image = raster::open_oriented("tests/in/exif.jpg").unwrap();
+    /// let mut blocks = MetadataBlocks::from_path("tests/in/exif.jpg").unwrap();
+    /// blocks.reset_orientation();
+    /// raster::save_with_metadata(&image, "tests/out/test_reset_orientation.jpg", &blocks).unwrap();
+    ///
+    /// let metadata = Metadata::from_path("tests/out/test_reset_orientation.jpg").unwrap();
+    /// assert_eq!(Some(1), metadata.orientation);
+    /// ```
+    pub fn reset_orientation(&mut self) {
+        let exif = match self.exif {
+            Some(ref mut exif) => exif,
+            None => return,
+        };
+        let reader = match exif.get(0..4) {
+            Some(b"II*\0") => IfdReader {
+                tiff: exif,
+                little_endian: true,
+            },
+            Some(b"MM\0*") => IfdReader {
+                tiff: exif,
+                little_endian: false,
+            },
+            _ => return,
+        };
+        let entry = reader
+            .u32(4)
+            .and_then(|ifd| reader.entry(ifd as usize, 0x0112));
+        if let Some(entry) = entry {
+            let value = if reader.little_endian { [1, 0] } else { [0, 1] };
+            exif[entry + 8..entry + 10].copy_from_slice(&value);
+        }
+    }
+
+    /// Returns `true` if there are no blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::metadata::MetadataBlocks;
+    ///
+    /// assert!(!MetadataBlocks::from_path("tests/in/exif.jpg").unwrap().is_empty());
+    /// assert!(MetadataBlocks::from_path("tests/in/interlaced.png").unwrap().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none()
+            && self.icc_profile.is_none()
+            && self.xmp.is_none()
+            && self.text.is_empty()
+            && self.dpi.is_none()
+    }
+}
+
+// Read EXIF data starting at its TIFF header. Returns `None` if the header is invalid.
+pub(crate) fn exif(tiff: &[u8]) -> Option<Metadata> {
+    let reader = match tiff.get(0..4)? {
+        b"II*\0" => IfdReader {
+            tiff,
+            little_endian: true,
+        },
+        b"MM\0*" => IfdReader {
+            tiff,
+            little_endian: false,
+        },
+        _ => return None,
+    };
+
+    let ifd = reader.u32(4)? as usize;
+    let mut metadata = Metadata {
+        make: reader.string(ifd, 0x010f),
+        model: reader.string(ifd, 0x0110),
+        orientation: reader
+            .entry(ifd, 0x0112)
+            .and_then(|entry| reader.u16(entry + 8))
+            .filter(|orientation| (1..=8).contains(orientation)),
+        date_time: reader.string(ifd, 0x0132),
+        ..Metadata::default()
+    };
+
+    if let Some(exif_ifd) = reader.offset(ifd, 0x8769) {
+        metadata.date_time_original = reader.string(exif_ifd, 0x9003);
+        metadata.date_time_digitized = reader.string(exif_ifd, 0x9004);
+    }
+
+    if let Some(gps_ifd) = reader.offset(ifd, 0x8825) {
+        let coordinate = |ref_tag: u16, tag: u16, negative: &[u8]| -> Option<f64> {
+            let entry = reader.entry(gps_ifd, tag)?;
+            let offset = reader.u32(entry + 8)? as usize;
+            let degrees = reader.rational(offset)?
+                + reader.rational(offset + 8)? / 60.0
+                + reader.rational(offset + 16)? / 3600.0;
+            let reference = reader.entry(gps_ifd, ref_tag)?;
+            match tiff.get(reference + 8)? {
+                b if negative.contains(b) => Some(-degrees),
+                _ => Some(degrees),
+            }
+        };
+        if let (Some(latitude), Some(longitude)) = (
+            coordinate(0x0001, 0x0002, b"S"),
+            coordinate(0x0003, 0x0004, b"W"),
+        ) {
+            // An altitude reference of 1 is below sea level
+            let altitude = reader.entry(gps_ifd, 0x0006).and_then(|entry| {
+                let altitude = reader.rational(reader.u32(entry + 8)? as usize)?;
+                match reader.entry(gps_ifd, 0x0005) {
+                    Some(reference) if tiff.get(reference + 8) == Some(&1) => Some(-altitude),
+                    _ => Some(altitude),
+                }
+            });
+            metadata.gps = Some(GpsPosition {
+                latitude,
+                longitude,
+                altitude,
+            });
+        }
+    }
+
+    Some(metadata)
+}
+
+// Read the metadata blocks of a JPEG, PNG or WebP. Reading stops at the first error, keeping the
+// blocks found before it. Other formats have no blocks.
+pub(crate) fn read_blocks<R: Read + Seek>(format: ImageFormat, mut reader: R) -> MetadataBlocks {
+    let mut blocks = MetadataBlocks::default();
+    let _ = match format {
+        ImageFormat::Jpeg => jpeg_blocks(&mut reader, &mut blocks),
+        ImageFormat::Png => png_blocks(&mut reader, &mut blocks),
+        ImageFormat::WebP => webp_blocks(&mut reader, &mut blocks),
+        _ => Ok(()),
+    };
+    blocks
+}
+
+// Remove the EXIF and XMP blocks of a JPEG, PNG or WebP while copying everything else as it is.
+// JPEGs also lose their IPTC segment. ICC profiles are kept. Other formats are returned as they
+// are.
+pub(crate) fn strip_blocks(format: ImageFormat, data: &[u8]) -> RasterResult<Vec<u8>> {
+    let invalid = || RasterError::Decode(format, "Invalid metadata block".to_string());
+    let mut out = Vec::with_capacity(data.len());
+    match format {
+        ImageFormat::Jpeg => {
+            out.extend_from_slice(data.get(0..2).ok_or_else(invalid)?);
+            let mut offset = 2;
+            // The entropy coded data after the start of scan is copied whole
+            while data.get(offset) == Some(&0xff) && data.get(offset + 1) != Some(&0xda) {
+                let length = data.get(offset + 2..offset + 4).ok_or_else(invalid)?;
+                let end = offset + 2 + u16::from_be_bytes([length[0], length[1]]) as usize;
+                let segment = data.get(offset..end).ok_or_else(invalid)?;
+                // APP1 holds EXIF and XMP, APP13 holds IPTC
+                if segment[1] != 0xe1 && segment[1] != 0xed {
+                    out.extend_from_slice(segment);
+                }
+                offset = end;
+            }
+            out.extend_from_slice(&data[offset..]);
+        }
+        ImageFormat::Png => {
+            out.extend_from_slice(data.get(0..8).ok_or_else(invalid)?);
+            let mut offset = 8;
+            while let Some(header) = data.get(offset..offset + 8) {
+                let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+                let end = offset + 12 + length as usize;
+                let chunk = data.get(offset..end).ok_or_else(invalid)?;
+                let text = &chunk[8..chunk.len() - 4];
+                let strip = match &header[4..8] {
+                    b"eXIf" => true,
+                    // XMP, and the raw EXIF and XMP profiles ImageMagick writes
+                    b"iTXt" | b"tEXt" | b"zTXt" => {
+                        text.starts_with(PNG_XMP) || text.starts_with(b"Raw profile type")
+                    }
+                    _ => false,
+                };
+                if !strip {
+                    out.extend_from_slice(chunk);
+                }
+                offset = end;
+                if &header[4..8] == b"IEND" {
+                    break;
+                }
+            }
+        }
+        ImageFormat::WebP => {
+            let mut body = b"WEBP".to_vec();
+            let mut offset = 12;
+            while let Some(header) = data.get(offset..offset + 8) {
+                let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                let end = offset + 8 + size as usize + (size as usize & 1);
+                let chunk = data
+                    .get(offset..cmp::min(end, data.len()))
+                    .ok_or_else(invalid)?;
+                match &header[0..4] {
+                    b"EXIF" | b"XMP " => {}
+                    b"VP8X" if chunk.len() > 8 => {
+                        body.extend_from_slice(chunk);
+                        // Clear the EXIF and XMP flags
+                        let flags = body.len() - chunk.len() + 8;
+                        body[flags] &= !0x0c;
+                    }
+                    _ => body.extend_from_slice(chunk),
+                }
+                offset = end;
+            }
+            out.extend_from_slice(b"RIFF");
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            out.extend_from_slice(&body);
+        }
+        _ => out.extend_from_slice(data),
+    }
+    Ok(out)
+}
+
+// Add metadata blocks to the encoded JPEG, PNG or WebP data of an image. Other formats are
+// returned as they are.
+pub(crate) fn write_blocks(
+    image: &Image,
+    blocks: &MetadataBlocks,
+    format: ImageFormat,
+    data: Vec<u8>,
+) -> RasterResult<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => {
+            // After the JFIF segment, which has to come first
+            let mut offset = 2;
+            if data.get(2..4) == Some(&[0xff, 0xe0]) {
+                offset += 2 + u16::from_be_bytes([data[4], data[5]]) as usize;
+            }
+
+            let mut segments = Vec::new();
+            let too_large = || RasterError::Encode(format, "Metadata is too large".to_string());
+            let mut segment = |marker: u8, parts: &[&[u8]]| -> RasterResult<()> {
+                let length = parts.iter().map(|part| part.len()).sum::<usize>() + 2;
+                if length > 0xffff {
+                    return Err(too_large());
+                }
+                segments.extend_from_slice(&[0xff, marker]);
+                segments.extend_from_slice(&(length as u16).to_be_bytes());
+                for part in parts {
+                    segments.extend_from_slice(part);
+                }
+                Ok(())
+            };
+            if let Some(ref exif) = blocks.exif {
+                segment(0xe1, &[JPEG_EXIF, exif])?;
+            }
+            if let Some(ref xmp) = blocks.xmp {
+                segment(0xe1, &[JPEG_XMP, xmp])?;
+            }
+            if let Some(ref icc) = blocks.icc_profile {
+                // Profiles are split over numbered segments
+                let chunks: Vec<&[u8]> = icc.chunks(0xffff - 2 - JPEG_ICC.len() - 2).collect();
+                if chunks.len() > 255 {
+                    return Err(too_large());
+                }
+                for (i, chunk) in chunks.iter().enumerate() {
+                    segment(0xe2, &[JPEG_ICC, &[i as u8 + 1, chunks.len() as u8], chunk])?;
+                }
+            }
+
+            let mut out = Vec::with_capacity(data.len() + segments.len() + 18);
+            out.extend_from_slice(&data[..offset]);
+            if let Some((x, y)) = blocks.dpi {
+                if offset == 2 {
+                    // Version 1.1, with the density filled in below
+                    out.extend_from_slice(b"\xff\xe0\0\x10JFIF\0\x01\x01\0\0\0\0\0\0\0");
+                }
+                if out.get(6..11) == Some(b"JFIF\0") {
+                    let density = |dpi: f32| (dpi.round() as u16).to_be_bytes();
+                    out[13] = 1;
+                    out[14..16].copy_from_slice(&density(x));
+                    out[16..18].copy_from_slice(&density(y));
+                }
+            }
+            out.extend_from_slice(&segments);
+            out.extend_from_slice(&data[offset..]);
+            Ok(out)
+        }
+        ImageFormat::Png => {
+            // After the signature and the IHDR chunk
+            let offset = 8 + 12 + 13;
+            let mut chunks = Vec::new();
+            if let Some((x, y)) = blocks.dpi {
+                // In pixels per meter
+                let ppm = |dpi: f32| ((dpi / 0.0254).round() as u32).to_be_bytes();
+                png_chunk(&mut chunks, b"pHYs", &[&ppm(x), &ppm(y), &[1]]);
+            }
+            if let Some(ref icc) = blocks.icc_profile {
+                let mut deflater = ZlibEncoder::new(Vec::new(), Compression::default());
+                deflater.write_all(icc)?;
+                let compressed = deflater.finish()?;
+                png_chunk(&mut chunks, b"iCCP", &[b"ICC Profile\0\0", &compressed]);
+            }
+            if let Some(ref exif) = blocks.exif {
+                png_chunk(&mut chunks, b"eXIf", &[exif]);
+            }
+            if let Some(ref xmp) = blocks.xmp {
+                // Uncompressed, without a language or translated keyword
+                png_chunk(&mut chunks, b"iTXt", &[PNG_XMP, b"\0\0\0\0\0", xmp]);
+            }
+            // In keyword order, so that the same image always encodes the same
+            let mut text: Vec<_> = blocks.text.iter().collect();
+            text.sort();
+            for (keyword, text) in text {
+                png_text_chunk(&mut chunks, keyword, text);
+            }
+
+            let mut out = Vec::with_capacity(data.len() + chunks.len());
+            out.extend_from_slice(&data[..offset]);
+            out.extend_from_slice(&chunks);
+            out.extend_from_slice(&data[offset..]);
+            Ok(out)
+        }
+        ImageFormat::WebP => {
+            // Metadata needs the extended format, which starts with a VP8X chunk
+            let mut offset = 12;
+            let mut flags = 0;
+            let mut image_chunks = Vec::new();
+            while let Some(header) = data.get(offset..offset + 8) {
+                let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                let end = cmp::min(offset + 8 + size as usize + (size as usize & 1), data.len());
+                match &header[0..4] {
+                    b"VP8X" => flags = data[offset + 8],
+                    // The alpha bit of the lossless header
+                    b"VP8L" if data.get(offset + 12).is_some_and(|b| b & 0x10 != 0) => {
+                        flags |= 0x10;
+                        image_chunks.extend_from_slice(&data[offset..end]);
+                    }
+                    _ => image_chunks.extend_from_slice(&data[offset..end]),
+                }
+                offset = end;
+            }
+
+            let mut chunks = Vec::new();
+            if let Some(ref icc) = blocks.icc_profile {
+                flags |= 0x20;
+                webp_chunk(&mut chunks, b"ICCP", icc);
+            }
+            chunks.extend_from_slice(&image_chunks);
+            if let Some(ref exif) = blocks.exif {
+                flags |= 0x08;
+                webp_chunk(&mut chunks, b"EXIF", exif);
+            }
+            if let Some(ref xmp) = blocks.xmp {
+                flags |= 0x04;
+                webp_chunk(&mut chunks, b"XMP ", xmp);
+            }
+
+            let mut vp8x = vec![flags, 0, 0, 0];
+            vp8x.extend_from_slice(&(image.width as u32 - 1).to_le_bytes()[..3]);
+            vp8x.extend_from_slice(&(image.height as u32 - 1).to_le_bytes()[..3]);
+            let mut body = b"WEBP".to_vec();
+            webp_chunk(&mut body, b"VP8X", &vp8x);
+            body.extend_from_slice(&chunks);
+
+            let mut out = Vec::with_capacity(body.len() + 8);
+            out.extend_from_slice(b"RIFF");
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            out.extend_from_slice(&body);
+            Ok(out)
+        }
+        _ => Ok(data),
+    }
+}
+
+// Private functions
+
+// Decompress zlib data, or `None` if it is corrupt.
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(data).read_to_end(&mut out).ok()?;
+    Some(out)
+}
+
+// Read the JFIF density and the EXIF, XMP and ICC segments of a JPEG. The segments end where the
+// scan starts.
+fn jpeg_blocks<R: Read + Seek>(reader: &mut R, blocks: &mut MetadataBlocks) -> io::Result<()> {
+    reader.seek(SeekFrom::Current(2))?;
+    let mut header = [0; 4];
+    loop {
+        reader.read_exact(&mut header)?;
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if header[0] != 0xff || header[1] == 0xda || length < 2 {
+            return Ok(());
+        }
+        if header[1] < 0xe0 || header[1] > 0xe2 {
+            reader.seek(SeekFrom::Current(length as i64 - 2))?;
+            continue;
+        }
+
+        let mut segment = vec![0; length - 2];
+        reader.read_exact(&mut segment)?;
+        if header[1] == 0xe0 && segment.starts_with(b"JFIF\0") && segment.len() >= 12 {
+            // Units of 1 are dots per inch and units of 2 dots per centimeter. Without units the
+            // density is only an aspect ratio.
+            let density = |i: usize| u16::from_be_bytes([segment[i], segment[i + 1]]) as f32;
+            blocks.dpi = match segment[7] {
+                1 => Some((density(8), density(10))),
+                2 => Some((density(8) * 2.54, density(10) * 2.54)),
+                _ => None,
+            };
+        } else if header[1] == 0xe1 && segment.starts_with(JPEG_EXIF) {
+            blocks.exif = Some(segment[JPEG_EXIF.len()..].to_vec());
+        } else if header[1] == 0xe1 && segment.starts_with(JPEG_XMP) {
+            blocks.xmp = Some(segment[JPEG_XMP.len()..].to_vec());
+        } else if header[1] == 0xe2 && segment.starts_with(JPEG_ICC) && segment.len() >= 14 {
+            // Profiles are split over numbered segments, which come in order
+            blocks
+                .icc_profile
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(&segment[14..]);
+        }
+    }
+}
+
+// Append a PNG chunk made of the parts to the data.
+fn png_chunk(data: &mut Vec<u8>, kind: &[u8], parts: &[&[u8]]) {
+    let length: usize = parts.iter().map(|part| part.len()).sum();
+    data.extend_from_slice(&(length as u32).to_be_bytes());
+    let mut crc = Crc::new();
+    crc.update(kind);
+    data.extend_from_slice(kind);
+    for part in parts {
+        crc.update(part);
+        data.extend_from_slice(part);
+    }
+    data.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+// Read the eXIf, iCCP, pHYs and text chunks of a PNG.
+fn png_blocks<R: Read + Seek>(reader: &mut R, blocks: &mut MetadataBlocks) -> io::Result<()> {
+    reader.seek(SeekFrom::Current(8))?;
+    let mut header = [0; 8];
+    loop {
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let kind = &header[4..8];
+        if kind == b"IEND" {
+            return Ok(());
+        }
+        if ![&b"eXIf"[..], b"iCCP", b"iTXt", b"pHYs", b"tEXt", b"zTXt"].contains(&kind) {
+            reader.seek(SeekFrom::Current(length as i64 + 4))?;
+            continue;
+        }
+
+        let mut data = Vec::new();
+        reader.take(length as u64).read_to_end(&mut data)?;
+        reader.seek(SeekFrom::Current(4))?;
+        match kind {
+            b"eXIf" => blocks.exif = Some(data),
+            // Pixels per meter, unless the unit is unknown
+            b"pHYs" if data.len() == 9 && data[8] == 1 => {
+                let dpi = |i: usize| {
+                    let ppm = u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+                    // Whole resolutions are written rounded, like 300 DPI as 11811 pixels per
+                    // meter. Read them back as they were.
+                    let dpi = ppm as f32 * 0.0254;
+                    if (dpi.round() / 0.0254).round() == ppm as f32 {
+                        dpi.round()
+                    } else {
+                        dpi
+                    }
+                };
+                blocks.dpi = Some((dpi(0), dpi(4)));
+            }
+            b"pHYs" => {}
+            b"iCCP" => {
+                // A profile name, then the compression method and the compressed profile
+                if let Some(name_end) = data.iter().position(|&b| b == 0) {
+                    let compressed = data.get(name_end + 2..).unwrap_or(&[]);
+                    if let Some(profile) = inflate(compressed) {
+                        blocks.icc_profile = Some(profile);
+                    }
+                }
+            }
+            _ => {
+                if let Some((keyword, text)) = png_text(kind, &data) {
+                    if keyword.as_bytes() == PNG_XMP {
+                        blocks.xmp = Some(text.into_bytes());
+                    } else {
+                        blocks.text.insert(keyword, text);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Append a tEXt chunk to the data, or an iTXt chunk if the text isn't Latin-1.
+fn png_text_chunk(data: &mut Vec<u8>, keyword: &str, text: &str) {
+    let latin1 = |s: &str| -> Option<Vec<u8>> {
+        s.chars()
+            .map(|c| {
+                if (c as u32) < 256 {
+                    Some(c as u8)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    };
+    match (latin1(keyword), latin1(text)) {
+        (Some(keyword), Some(text)) => png_chunk(data, b"tEXt", &[&keyword, b"\0", &text]),
+        _ => png_chunk(
+            data,
+            b"iTXt",
+            &[keyword.as_bytes(), b"\0\0\0\0\0", text.as_bytes()],
+        ),
+    }
+}
+
+// Keyword and text of a tEXt, zTXt or iTXt chunk. The first two are Latin-1, iTXt is UTF-8.
+fn png_text(kind: &[u8], data: &[u8]) -> Option<(String, String)> {
+    let latin1 = |bytes: &[u8]| -> String { bytes.iter().map(|&b| b as char).collect() };
+    let keyword_end = data.iter().position(|&b| b == 0)?;
+    let keyword = latin1(&data[..keyword_end]);
+    let rest = &data[keyword_end + 1..];
+    let text = match kind {
+        b"tEXt" => latin1(rest),
+        // The compression method comes first
+        b"zTXt" => latin1(&inflate(rest.get(1..)?)?),
+        _ => {
+            // The compression flag and method, then the language and translated keyword
+            let mut text = rest.get(2..)?;
+            for _ in 0..2 {
+                let end = text.iter().position(|&b| b == 0)?;
+                text = &text[end + 1..];
+            }
+            if rest[0] == 0 {
+                String::from_utf8_lossy(text).into_owned()
+            } else {
+                String::from_utf8_lossy(&inflate(text)?).into_owned()
+            }
+        }
+    };
+    Some((keyword, text))
+}
+
+// Append a RIFF chunk, padded to an even size, to the data.
+fn webp_chunk(data: &mut Vec<u8>, kind: &[u8], chunk: &[u8]) {
+    data.extend_from_slice(kind);
+    data.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+    data.extend_from_slice(chunk);
+    if chunk.len() % 2 == 1 {
+        data.push(0);
+    }
+}
+
+// Read the EXIF, ICCP and XMP chunks of a WebP.
+fn webp_blocks<R: Read + Seek>(reader: &mut R, blocks: &mut MetadataBlocks) -> io::Result<()> {
+    reader.seek(SeekFrom::Current(12))?;
+    let mut header = [0; 8];
+    loop {
+        reader.read_exact(&mut header)?;
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        let padded = size + (size & 1);
+        let block = match &header[0..4] {
+            b"EXIF" => &mut blocks.exif,
+            b"ICCP" => &mut blocks.icc_profile,
+            b"XMP " => &mut blocks.xmp,
+            _ => {
+                reader.seek(SeekFrom::Current(padded as i64))?;
+                continue;
+            }
+        };
+        let mut data = Vec::new();
+        reader.take(size).read_to_end(&mut data)?;
+        reader.seek(SeekFrom::Current((padded - size) as i64))?;
+        // Some writers keep the JPEG signature in front of EXIF data
+        if &header[0..4] == b"EXIF" && data.starts_with(JPEG_EXIF) {
+            data.drain(..JPEG_EXIF.len());
+        }
+        *block = Some(data);
+    }
+}
+
+// Reads values from the IFDs of a TIFF header in its byte order.
+struct IfdReader<'a> {
+    tiff: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> IfdReader<'a> {
+    // Offset of the 12 byte entry for a tag.
+    fn entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| self.u16(entry) == Some(tag))
+    }
+
+    // Value of a tag pointing to another IFD.
+    fn offset(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let entry = self.entry(ifd, tag)?;
+        Some(self.u32(entry + 8)? as usize)
+    }
+
+    // Value of an unsigned rational.
+    fn rational(&self, offset: usize) -> Option<f64> {
+        let (numerator, denominator) = (self.u32(offset)?, self.u32(offset + 4)?);
+        if denominator == 0 {
+            return None;
+        }
+        Some(numerator as f64 / denominator as f64)
+    }
+
+    // Value of an ASCII tag, without the trailing NUL and padding. Values of up to four bytes are
+    // stored in the entry itself.
+    fn string(&self, ifd: usize, tag: u16) -> Option<String> {
+        let entry = self.entry(ifd, tag)?;
+        let count = self.u32(entry + 4)? as usize;
+        let offset = if count <= 4 {
+            entry + 8
+        } else {
+            self.u32(entry + 8)? as usize
+        };
+        let value = self.tiff.get(offset..offset.checked_add(count)?)?;
+        let value = String::from_utf8_lossy(value)
+            .trim_end_matches(['\0', ' '])
+            .to_string();
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = [*self.tiff.get(offset)?, *self.tiff.get(offset + 1)?];
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.tiff.get(offset..offset.checked_add(4)?)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+}
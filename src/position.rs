@@ -60,7 +60,7 @@ impl Position {
                 (x, offset_y)
             }
             PositionMode::CenterLeft => {
-                let y = ((canvas_height / 2) - (image_height / 2)) + offset_x;
+                let y = ((canvas_height / 2) - (image_height / 2)) + offset_y;
                 (offset_x, y)
             }
             PositionMode::Center => {
@@ -83,7 +83,7 @@ impl Position {
                 (x, y)
             }
             PositionMode::BottomRight => {
-                let x = (canvas_width - image_width) + offset_y;
+                let x = (canvas_width - image_width) + offset_x;
                 let y = (canvas_height - image_height) + offset_y;
                 (x, y)
             }
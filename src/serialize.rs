@@ -0,0 +1,197 @@
+//!  A module for serializing images and colors with serde.
+
+// from rust
+use std::fmt;
+
+// from external crate
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+// from local crate
+use Color;
+use Image;
+
+const COLOR_FIELDS: &[&str] = &["r", "g", "b", "a"];
+
+const IMAGE_FIELDS: &[&str] = &["width", "height", "bytes"];
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Color", 4)?;
+        state.serialize_field("r", &self.r)?;
+        state.serialize_field("g", &self.g)?;
+        state.serialize_field("b", &self.b)?;
+        state.serialize_field("a", &self.a)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        deserializer.deserialize_struct("Color", COLOR_FIELDS, ColorVisitor)
+    }
+}
+
+// The bytes are serialized as a byte string, which binary formats store as they are.
+impl Serialize for Image {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Image", 3)?;
+        state.serialize_field("width", &self.width)?;
+        state.serialize_field("height", &self.height)?;
+        state.serialize_field("bytes", &Bytes(&self.bytes))?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Image {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Image, D::Error> {
+        deserializer.deserialize_struct("Image", IMAGE_FIELDS, ImageVisitor)
+    }
+}
+
+// Private types
+
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for Bytes<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByteBuf, D::Error> {
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+// Formats without byte strings, like JSON, give a sequence of numbers instead.
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("RGBA bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteBuf(bytes))
+    }
+}
+
+struct ColorVisitor;
+
+impl<'de> Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct Color")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Color, A::Error> {
+        let mut channels = [0; 4];
+        for (i, channel) in channels.iter_mut().enumerate() {
+            *channel = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        Ok(Color::rgba(
+            channels[0],
+            channels[1],
+            channels[2],
+            channels[3],
+        ))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Color, A::Error> {
+        let mut channels = [None; 4];
+        while let Some(key) = map.next_key::<String>()? {
+            match COLOR_FIELDS.iter().position(|&field| field == key) {
+                Some(i) if channels[i].is_some() => {
+                    return Err(de::Error::duplicate_field(COLOR_FIELDS[i]));
+                }
+                Some(i) => channels[i] = Some(map.next_value()?),
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let mut rgba = [0; 4];
+        for (i, channel) in channels.iter().enumerate() {
+            rgba[i] = channel.ok_or_else(|| de::Error::missing_field(COLOR_FIELDS[i]))?;
+        }
+        Ok(Color::rgba(rgba[0], rgba[1], rgba[2], rgba[3]))
+    }
+}
+
+struct ImageVisitor;
+
+impl<'de> Visitor<'de> for ImageVisitor {
+    type Value = Image;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct Image")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Image, A::Error> {
+        let width = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let height = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let ByteBuf(bytes) = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        image(width, height, bytes)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Image, A::Error> {
+        let (mut width, mut height, mut bytes) = (None, None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match &key[..] {
+                "width" if width.is_some() => return Err(de::Error::duplicate_field("width")),
+                "width" => width = Some(map.next_value()?),
+                "height" if height.is_some() => return Err(de::Error::duplicate_field("height")),
+                "height" => height = Some(map.next_value()?),
+                "bytes" if bytes.is_some() => return Err(de::Error::duplicate_field("bytes")),
+                "bytes" => bytes = Some(map.next_value::<ByteBuf>()?.0),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        image(
+            width.ok_or_else(|| de::Error::missing_field("width"))?,
+            height.ok_or_else(|| de::Error::missing_field("height"))?,
+            bytes.ok_or_else(|| de::Error::missing_field("bytes"))?,
+        )
+    }
+}
+
+// Private functions
+
+fn image<E: de::Error>(width: i32, height: i32, bytes: Vec<u8>) -> Result<Image, E> {
+    let len = bytes.len();
+    Image::from_raw(width, height, bytes).map_err(|_| {
+        de::Error::custom(format!(
+            "{} bytes don't fit an image of {}x{} pixels",
+            len, width, height
+        ))
+    })
+}
@@ -7,8 +7,8 @@ use std::cmp;
 
 // from local crate
 use editor::crop;
-use error::RasterResult;
-use interpolate::{resample, InterpolationMode};
+use error::{RasterError, RasterResult};
+use interpolate::{resample_with, sample, InterpolationMode};
 use position::PositionMode;
 use Color;
 use Image;
@@ -22,6 +22,183 @@ pub enum TransformMode {
     Vertical,
 }
 
+/// An enum for the canvas of images rotated at arbitrary angles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RotateMode {
+    /// Grow the canvas to fit the whole rotated image, filling the corners with the background.
+    Expand,
+    /// Crop to the largest upright rectangle inside the rotated image, so no background shows.
+    Crop,
+}
+
+/// Transform an image with an affine matrix, which can scale, rotate, shear and translate in one
+/// pass. The canvas keeps its size, and areas the transformed image doesn't cover are filled with
+/// the background.
+///
+/// The matrix `[a, b, c, d, e, f]` maps a point of the image to `(a * x + b * y + c, d * x + e *
+/// y + f)` on the canvas, with the origin at the top left corner of the top left pixel. A matrix
+/// that can't be inverted flattens the image to nothing, leaving only the background.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{transform, Color, InterpolationMode};
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+///
+/// // Shear along x and move 10 pixels down
+/// let matrix = [1.0, 0.3, 0.0, 0.0, 1.0, 10.0];
+/// transform::affine(&mut image, matrix, InterpolationMode::Bicubic, Color::white()).unwrap();
+///
+/// raster::save(&image, "tests/out/test_transform_affine.png").unwrap();
+/// ```
+pub fn affine(
+    src: &mut Image,
+    matrix: [f64; 6],
+    interpolation: InterpolationMode,
+    bg: Color,
+) -> RasterResult<()> {
+    let [a, b, c, d, e, f] = matrix;
+    let background = [bg.r, bg.g, bg.b, bg.a];
+    let det = a * e - b * d;
+    if det.abs() < 1e-12 {
+        src.bytes = background.repeat(src.bytes.len() / 4);
+        return Ok(());
+    }
+
+    // The inverse maps canvas points back into the image
+    let inverse = [
+        e / det,
+        -b / det,
+        (b * f - c * e) / det,
+        -d / det,
+        a / det,
+        (c * d - a * f) / det,
+    ];
+    let image = src.clone();
+    let width = cmp::max(src.width, 1) as usize;
+    for (i, pixel) in src.bytes.chunks_exact_mut(4).enumerate() {
+        let x = (i % width) as f64 + 0.5;
+        let y = (i / width) as f64 + 0.5;
+        let src_x = inverse[0] * x + inverse[1] * y + inverse[2] - 0.5;
+        let src_y = inverse[3] * x + inverse[4] * y + inverse[5] - 0.5;
+        pixel.copy_from_slice(&sample(&image, src_x, src_y, interpolation, background));
+    }
+
+    Ok(())
+}
+
+/// Move the pixels of an image by displacement maps, with a value for each pixel in row order.
+/// Each pixel takes the color found `dx_map` pixels right and `dy_map` pixels down of it, sampled
+/// bilinearly, which is transparent outside the image.
+///
+/// # Errors
+///
+/// Fails with `RasterError::SizeMismatch` holding the size of the image if a map doesn't have a
+/// value for each of its pixels.
+///
+/// # Examples
+///
+/// ```
+/// use raster::transform;
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+/// let (w, h) = (image.width as usize, image.height as usize);
+///
+/// // Ripple the rows sideways
+/// let dx: Vec<f32> = (0..w * h).map(|i| ((i / w) as f32 / 8.0).sin() * 6.0).collect();
+/// let dy = vec![0.0; w * h];
+/// transform::displace(&mut image, &dx, &dy).unwrap();
+///
+/// raster::save(&image, "tests/out/test_transform_displace.png").unwrap();
+/// ```
+pub fn displace(src: &mut Image, dx_map: &[f32], dy_map: &[f32]) -> RasterResult<()> {
+    let len = src.bytes.len() / 4;
+    if dx_map.len() != len || dy_map.len() != len {
+        return Err(RasterError::SizeMismatch(src.width, src.height));
+    }
+    let image = src.clone();
+    let width = cmp::max(src.width, 1) as usize;
+    for (i, pixel) in src.bytes.chunks_exact_mut(4).enumerate() {
+        let x = (i % width) as f64 + dx_map[i] as f64;
+        let y = (i / width) as f64 + dy_map[i] as f64;
+        pixel.copy_from_slice(&sample(&image, x, y, InterpolationMode::Bilinear, [0; 4]));
+    }
+    Ok(())
+}
+
+/// Warp an image with a mesh, a grid of `columns` by `rows` cells laid evenly over the image.
+/// `points` holds where in the image each of the `(columns + 1) * (rows + 1)` grid corners takes
+/// its color from, in row order from the top left, and the rest is interpolated between them.
+/// Points at the grid corners themselves leave the image as it is. See `displace`.
+///
+/// # Errors
+///
+/// Fails with `RasterError::SizeMismatch` holding the size of the image if the number of points
+/// doesn't match the grid, or the grid has no cells.
+///
+/// # Examples
+///
+/// ```
+/// use raster::transform;
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+/// let (w, h) = (image.width as f64, image.height as f64);
+///
+/// // A 2x2 grid, pulling the center towards the top left to bulge it out
+/// let mut points = Vec::new();
+/// for row in 0..3 {
+///     for column in 0..3 {
+///         points.push((column as f64 * w / 2.0, row as f64 * h / 2.0));
+///     }
+/// }
+/// points[4] = (w / 2.0 - 20.0, h / 2.0 - 20.0);
+/// transform::mesh_warp(&mut image, 2, 2, &points).unwrap();
+///
+/// raster::save(&image, "tests/out/test_transform_mesh_warp.png").unwrap();
+/// ```
+pub fn mesh_warp(
+    src: &mut Image,
+    columns: usize,
+    rows: usize,
+    points: &[(f64, f64)],
+) -> RasterResult<()> {
+    if columns == 0 || rows == 0 || points.len() != (columns + 1) * (rows + 1) {
+        return Err(RasterError::SizeMismatch(src.width, src.height));
+    }
+    let (w, h) = (src.width.max(0) as usize, src.height.max(0) as usize);
+    let cell_width = w as f64 / columns as f64;
+    let cell_height = h as f64 / rows as f64;
+
+    let mut dx_map = Vec::with_capacity(w * h);
+    let mut dy_map = Vec::with_capacity(w * h);
+    for y in 0..h {
+        for x in 0..w {
+            // Blend the 4 corners of the cell by where the pixel center sits in it
+            let gx = ((x as f64 + 0.5) / cell_width).clamp(0.0, columns as f64);
+            let gy = ((y as f64 + 0.5) / cell_height).clamp(0.0, rows as f64);
+            let (column, row) = (
+                cmp::min(gx as usize, columns - 1),
+                cmp::min(gy as usize, rows - 1),
+            );
+            let (tx, ty) = (gx - column as f64, gy - row as f64);
+            let corner = |c: usize, r: usize| points[r * (columns + 1) + c];
+            let blend = |a: f64, b: f64, c: f64, d: f64| {
+                (a * (1.0 - tx) + b * tx) * (1.0 - ty) + (c * (1.0 - tx) + d * tx) * ty
+            };
+            let (p00, p10) = (corner(column, row), corner(column + 1, row));
+            let (p01, p11) = (corner(column, row + 1), corner(column + 1, row + 1));
+            let src_x = blend(p00.0, p10.0, p01.0, p11.0);
+            let src_y = blend(p00.1, p10.1, p01.1, p11.1);
+
+            // Points are in pixel edges, displacements between pixel centers
+            dx_map.push((src_x - 0.5 - x as f64) as f32);
+            dy_map.push((src_y - 0.5 - y as f64) as f32);
+        }
+    }
+    displace(src, &dx_map, &dy_map)
+}
+
 /// Flip an image on its x or y axis.
 ///
 /// # Examples
@@ -55,47 +232,173 @@ pub enum TransformMode {
 /// ![](https://kosinix.github.io/raster/out/test_transform_flip_y.png)
 ///
 pub fn flip(src: &mut Image, mode: TransformMode) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-
     match mode {
-        TransformMode::Horizontal => {
-            for x in 0..w {
-                let src_x = x;
-                let dest_x = w - x - 1;
-                if dest_x <= src_x {
-                    break;
-                }
-                for y in 0..h {
-                    let pixel_left = src.get_pixel(src_x, y)?;
-                    let pixel_right = src.get_pixel(dest_x, y)?;
+        TransformMode::Horizontal => flip_h(src),
+        TransformMode::Vertical => flip_v(src),
+    }
+}
 
-                    src.set_pixel(dest_x, y, pixel_left)?;
-                    src.set_pixel(src_x, y, pixel_right)?;
-                }
-            }
+/// Mirror an image in place, swapping its left and right.
+pub fn flip_h(src: &mut Image) -> RasterResult<()> {
+    let width = src.width.max(0) as usize;
+    for row in src.bytes.chunks_exact_mut(cmp::max(width, 1) * 4) {
+        for x in 0..width / 2 {
+            let right = width - 1 - x;
+            let (left_half, right_half) = row.split_at_mut(right * 4);
+            left_half[x * 4..x * 4 + 4].swap_with_slice(&mut right_half[..4]);
+        }
+    }
+    Ok(())
+}
 
-            Ok(())
+/// Mirror an image in place, swapping its top and bottom.
+pub fn flip_v(src: &mut Image) -> RasterResult<()> {
+    let (width, height) = (src.width.max(0) as usize * 4, src.height.max(0) as usize);
+    for y in 0..height / 2 {
+        let (top, bottom) = src.bytes.split_at_mut((height - 1 - y) * width);
+        top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+    }
+    Ok(())
+}
+
+/// Warp an image so the corners of `src_quad` land on the corners of `dst_quad`, like
+/// straightening a photographed page into a rectangle.
+///
+/// Corners are `(x, y)` points in the same order for both quads, with the origin at the top left
+/// corner of the top left pixel. The canvas reaches from the origin to the furthest corner of
+/// `dst_quad`, and areas the warped image doesn't cover are transparent. The image is sampled
+/// bilinearly.
+///
+/// # Errors
+///
+/// Fails with `RasterError::InvalidQuad` if 3 corners of a quad are in a line.
+///
+/// # Examples
+///
+/// ```
+/// use raster::transform;
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+///
+/// // Corners of a page in the photo: top left, top right, bottom right, bottom left
+/// let page = [(40.0, 20.0), (180.0, 35.0), (170.0, 140.0), (30.0, 120.0)];
+/// let a4 = [(0.0, 0.0), (210.0, 0.0), (210.0, 297.0), (0.0, 297.0)];
+/// transform::perspective(&mut image, page, a4).unwrap();
+///
+/// assert_eq!((210, 297), (image.width, image.height));
+/// raster::save(&image, "tests/out/test_transform_perspective.png").unwrap();
+/// ```
+pub fn perspective(
+    src: &mut Image,
+    src_quad: [(f64, f64); 4],
+    dst_quad: [(f64, f64); 4],
+) -> RasterResult<()> {
+    let h = homography(dst_quad, src_quad).ok_or(RasterError::InvalidQuad)?;
+    let width = dst_quad.iter().fold(0.0, |max: f64, p| max.max(p.0)).ceil();
+    let height = dst_quad.iter().fold(0.0, |max: f64, p| max.max(p.1)).ceil();
+
+    let mut dest = Image::blank(width as i32, height as i32);
+    let dest_width = cmp::max(dest.width, 1) as usize;
+    for (i, pixel) in dest.bytes.chunks_exact_mut(4).enumerate() {
+        let x = (i % dest_width) as f64 + 0.5;
+        let y = (i / dest_width) as f64 + 0.5;
+        let w = h[6] * x + h[7] * y + h[8];
+        // Points behind the horizon of the warp have nothing to show
+        if w <= 0.0 {
+            pixel.copy_from_slice(&[0; 4]);
+            continue;
         }
-        TransformMode::Vertical => {
-            for y in 0..h {
-                let src_y = y;
-                let dest_y = h - y - 1;
-                if dest_y <= src_y {
-                    break;
-                }
-                for x in 0..w {
-                    let pixel_top = src.get_pixel(x, src_y)?;
-                    let pixel_bottom = src.get_pixel(x, dest_y)?;
+        let src_x = (h[0] * x + h[1] * y + h[2]) / w - 0.5;
+        let src_y = (h[3] * x + h[4] * y + h[5]) / w - 0.5;
+        let color = sample(src, src_x, src_y, InterpolationMode::Bilinear, [0; 4]);
+        pixel.copy_from_slice(&color);
+    }
 
-                    src.set_pixel(x, dest_y, pixel_top)?;
-                    src.set_pixel(x, src_y, pixel_bottom)?;
-                }
-            }
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
 
-            Ok(())
+/// Rotate and mirror an image by an EXIF orientation, bringing a photo taken with a turned camera
+/// upright. Orientation 1 and values outside of 1 to 8 leave the image unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use raster::transform;
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+/// let corner = image.get_pixel(0, image.height - 1).unwrap();
+///
+/// // Orientation 6 means the image has to be turned 90 degrees clockwise
+/// transform::orient(&mut image, 6).unwrap();
+/// assert_eq!(corner.r, image.get_pixel(0, 0).unwrap().r);
+/// ```
+pub fn orient(src: &mut Image, orientation: u16) -> RasterResult<()> {
+    let (w, h) = (src.width as usize, src.height as usize);
+    if !(2..=8).contains(&orientation) {
+        return Ok(());
+    }
+
+    // Orientations from 5 up swap the width and height
+    let (dest_w, dest_h) = if orientation >= 5 { (h, w) } else { (w, h) };
+    let mut bytes = vec![0; src.bytes.len()];
+    for y in 0..dest_h {
+        for x in 0..dest_w {
+            let (src_x, src_y) = match orientation {
+                2 => (w - 1 - x, y),
+                3 => (w - 1 - x, h - 1 - y),
+                4 => (x, h - 1 - y),
+                5 => (y, x),
+                6 => (y, h - 1 - x),
+                7 => (w - 1 - y, h - 1 - x),
+                _ => (w - 1 - y, x),
+            };
+            let (dest, src_i) = ((y * dest_w + x) * 4, (src_y * w + src_x) * 4);
+            bytes[dest..dest + 4].copy_from_slice(&src.bytes[src_i..src_i + 4]);
         }
     }
+
+    src.width = dest_w as i32;
+    src.height = dest_h as i32;
+    src.bytes = bytes;
+
+    Ok(())
+}
+
+/// Rotate an image 90 degrees clockwise by moving its pixels, without sampling them.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{transform, Color, Image};
+///
+/// let mut image = Image::blank(3, 2);
+/// image.set_pixel(0, 0, Color::red()).unwrap();
+///
+/// transform::rotate90(&mut image).unwrap();
+/// assert_eq!((2, 3), (image.width, image.height));
+/// assert_eq!(255, image.get_pixel(1, 0).unwrap().r);
+/// ```
+pub fn rotate90(src: &mut Image) -> RasterResult<()> {
+    orient(src, 6)
+}
+
+/// Rotate an image 180 degrees in place.
+pub fn rotate180(src: &mut Image) -> RasterResult<()> {
+    let len = src.bytes.len() / 4;
+    for i in 0..len / 2 {
+        let (first, last) = src.bytes.split_at_mut((len - 1 - i) * 4);
+        first[i * 4..i * 4 + 4].swap_with_slice(&mut last[..4]);
+    }
+    Ok(())
+}
+
+/// Rotate an image 270 degrees clockwise, or 90 degrees counter-clockwise.
+pub fn rotate270(src: &mut Image) -> RasterResult<()> {
+    orient(src, 8)
 }
 
 /// Rotate an image clockwise. Negate the degrees to do a counter-clockwise rotation. Background
@@ -193,15 +496,124 @@ pub fn rotate(src: &mut Image, degree: i32, bg: Color) -> RasterResult<()> {
     Ok(())
 }
 
+/// Rotate an image clockwise by any angle, sampling it bilinearly. Negate the degrees to do a
+/// counter-clockwise rotation. Pixels outside the image are sampled as the background color,
+/// which shows in the corners with `RotateMode::Expand`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{transform, Color, RotateMode};
+///
+/// // Straighten a slightly tilted photo
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+/// let (width, height) = (image.width, image.height);
+///
+/// let mut expanded = image.clone();
+/// transform::rotate_smooth(&mut expanded, -3.5, Color::white(), RotateMode::Expand).unwrap();
+/// assert!(expanded.width > width && expanded.height > height);
+///
+/// transform::rotate_smooth(&mut image, -3.5, Color::white(), RotateMode::Crop).unwrap();
+/// assert!(image.width < width && image.height < height);
+/// ```
+pub fn rotate_smooth(
+    src: &mut Image,
+    degrees: f64,
+    bg: Color,
+    mode: RotateMode,
+) -> RasterResult<()> {
+    let (w1, h1) = (src.width.max(0) as f64, src.height.max(0) as f64);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+
+    // Sizes are rounded down a little first, so float noise doesn't add a row
+    let (w2, h2) = match mode {
+        RotateMode::Expand => (
+            (w1 * cos.abs() + h1 * sin.abs() - 1e-6).ceil(),
+            (w1 * sin.abs() + h1 * cos.abs() - 1e-6).ceil(),
+        ),
+        RotateMode::Crop => {
+            let (w, h) = inscribed_size(w1, h1, sin.abs(), cos.abs());
+            ((w + 1e-6).floor(), (h + 1e-6).floor())
+        }
+    };
+
+    let background = [bg.r as f64, bg.g as f64, bg.b as f64, bg.a as f64];
+    let sample = |x: i32, y: i32| -> [f64; 4] {
+        if x < 0 || y < 0 || x >= src.width || y >= src.height {
+            return background;
+        }
+        let i = ((y * src.width + x) * 4) as usize;
+        let pixel = &src.bytes[i..i + 4];
+        [
+            pixel[0] as f64,
+            pixel[1] as f64,
+            pixel[2] as f64,
+            pixel[3] as f64,
+        ]
+    };
+
+    let mut dest = Image::blank(w2.max(0.0) as i32, h2.max(0.0) as i32);
+    let dest_width = dest.width.max(1) as usize;
+    for (i, pixel) in dest.bytes.chunks_exact_mut(4).enumerate() {
+        // Pixel centers relative to the center of the canvas, rotated back into the source
+        let x = (i % dest_width) as f64 + 0.5 - w2 / 2.0;
+        let y = (i / dest_width) as f64 + 0.5 - h2 / 2.0;
+        let src_x = x * cos + y * sin + w1 / 2.0 - 0.5;
+        let src_y = y * cos - x * sin + h1 / 2.0 - 0.5;
+
+        let (x0, y0) = (src_x.floor(), src_y.floor());
+        let (tx, ty) = (src_x - x0, src_y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let corners = [
+            (sample(x0, y0), (1.0 - tx) * (1.0 - ty)),
+            (sample(x0 + 1, y0), tx * (1.0 - ty)),
+            (sample(x0, y0 + 1), (1.0 - tx) * ty),
+            (sample(x0 + 1, y0 + 1), tx * ty),
+        ];
+        for (channel, value) in pixel.iter_mut().enumerate() {
+            let blended: f64 = corners
+                .iter()
+                .map(|(color, weight)| color[channel] * weight)
+                .sum();
+            *value = blended.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
+
 /// Resize image to exact dimensions ignoring aspect ratio.
 /// Useful if you want to force exact width and height.
 pub fn resize_exact(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
-    resample(src, w, h, InterpolationMode::Bicubic)
+    resize_exact_with(src, w, h, InterpolationMode::Bicubic, false)
+}
+
+pub(crate) fn resize_exact_with(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    interpolation: InterpolationMode,
+    linear: bool,
+) -> RasterResult<()> {
+    resample_with(src, w, h, interpolation, linear)
 }
 
 /// Resize image to exact height. Width is auto calculated.
 /// Useful for creating row of images with the same height.
 pub fn resize_exact_height(src: &mut Image, h: i32) -> RasterResult<()> {
+    resize_exact_height_with(src, h, InterpolationMode::Bicubic, false)
+}
+
+pub(crate) fn resize_exact_height_with(
+    src: &mut Image,
+    h: i32,
+    interpolation: InterpolationMode,
+    linear: bool,
+) -> RasterResult<()> {
     let width = src.width;
     let height = src.height;
     let ratio = width as f32 / height as f32;
@@ -209,12 +621,21 @@ pub fn resize_exact_height(src: &mut Image, h: i32) -> RasterResult<()> {
     let resize_height = h;
     let resize_width = (h as f32 * ratio) as i32;
 
-    resample(src, resize_width, resize_height, InterpolationMode::Bicubic)
+    resample_with(src, resize_width, resize_height, interpolation, linear)
 }
 
 /// Resize image to exact width. Height is auto calculated.
 /// Useful for creating column of images with the same width.
 pub fn resize_exact_width(src: &mut Image, w: i32) -> RasterResult<()> {
+    resize_exact_width_with(src, w, InterpolationMode::Bicubic, false)
+}
+
+pub(crate) fn resize_exact_width_with(
+    src: &mut Image,
+    w: i32,
+    interpolation: InterpolationMode,
+    linear: bool,
+) -> RasterResult<()> {
     let width = src.width;
     let height = src.height;
     let ratio = width as f32 / height as f32;
@@ -222,11 +643,21 @@ pub fn resize_exact_width(src: &mut Image, w: i32) -> RasterResult<()> {
     let resize_width = w;
     let resize_height = (w as f32 / ratio).round() as i32;
 
-    resample(src, resize_width, resize_height, InterpolationMode::Bicubic)
+    resample_with(src, resize_width, resize_height, interpolation, linear)
 }
 
 /// Resize image to fill all the space in the given dimension. Excess parts are removed.
 pub fn resize_fill(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
+    resize_fill_with(src, w, h, InterpolationMode::Bicubic, false)
+}
+
+pub(crate) fn resize_fill_with(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    interpolation: InterpolationMode,
+    linear: bool,
+) -> RasterResult<()> {
     let width = src.width;
     let height = src.height;
     let ratio = width as f32 / height as f32;
@@ -242,19 +673,24 @@ pub fn resize_fill(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
         optimum_height = h;
     }
 
-    resample(
-        src,
-        optimum_width,
-        optimum_height,
-        InterpolationMode::Bicubic,
-    )
-    .and_then(|_| crop(src, w, h, PositionMode::Center, 0, 0)) // Trim excess parts
+    resample_with(src, optimum_width, optimum_height, interpolation, linear)
+        .and_then(|_| crop(src, w, h, PositionMode::Center, 0, 0)) // Trim excess parts
 }
 
 /// Resize an image to fit within the given width and height.
 /// The re-sized image will not exceed the given dimension.
 /// Preserves the aspect ratio.
 pub fn resize_fit(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
+    resize_fit_with(src, w, h, InterpolationMode::Bicubic, false)
+}
+
+pub(crate) fn resize_fit_with(
+    src: &mut Image,
+    w: i32,
+    h: i32,
+    interpolation: InterpolationMode,
+    linear: bool,
+) -> RasterResult<()> {
     let ratio: f64 = src.width as f64 / src.height as f64;
 
     // Try basing it on width first
@@ -268,11 +704,72 @@ pub fn resize_fit(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
         resize_width = (h as f64 * ratio).round() as i32;
     }
 
-    resample(src, resize_width, resize_height, InterpolationMode::Bicubic)
+    resample_with(src, resize_width, resize_height, interpolation, linear)
 }
 
 // Private functions
 
+// The projective matrix mapping the corners of one quad onto another, in rows, solving its 8
+// unknowns with Gaussian elimination. None if either quad is flat.
+fn homography(from: [(f64, f64); 4], to: [(f64, f64); 4]) -> Option<[f64; 9]> {
+    let mut rows = [[0.0; 9]; 8];
+    for (i, (&(u, v), &(x, y))) in from.iter().zip(to.iter()).enumerate() {
+        rows[i * 2] = [u, v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x, x];
+        rows[i * 2 + 1] = [0.0, 0.0, 0.0, u, v, 1.0, -u * y, -v * y, y];
+    }
+
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&a, &b| rows[a][col].abs().total_cmp(&rows[b][col].abs()))?;
+        if rows[pivot][col].abs() < 1e-10 {
+            return None;
+        }
+        rows.swap(col, pivot);
+        for row in 0..8 {
+            if row != col {
+                let factor = rows[row][col] / rows[col][col];
+                let pivot_row = rows[col];
+                for (value, pivot_value) in rows[row].iter_mut().zip(pivot_row.iter()) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    let mut h = [1.0; 9];
+    for (i, row) in rows.iter().enumerate() {
+        h[i] = row[8] / row[i];
+    }
+
+    // A solution can still collapse the quad onto a line if only the other quad is flat
+    let det = h[0] * (h[4] * h[8] - h[5] * h[7]) - h[1] * (h[3] * h[8] - h[5] * h[6])
+        + h[2] * (h[3] * h[7] - h[4] * h[6]);
+    if det.abs() < 1e-10 {
+        return None;
+    }
+    Some(h)
+}
+
+// Size of the largest upright rectangle inside a w by h rectangle rotated by an angle with the
+// given absolute sine and cosine.
+fn inscribed_size(w: f64, h: f64, sin: f64, cos: f64) -> (f64, f64) {
+    if w <= 0.0 || h <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let (long, short) = if w >= h { (w, h) } else { (h, w) };
+    if short <= 2.0 * sin * cos * long || (sin - cos).abs() < 1e-10 {
+        // Two corners of the rectangle touch the long sides
+        let half = short / 2.0;
+        if w >= h {
+            (half / sin, half / cos)
+        } else {
+            (half / cos, half / sin)
+        }
+    } else {
+        let cos_2a = cos * cos - sin * sin;
+        ((w * cos - h * sin) / cos_2a, (h * cos - w * sin) / cos_2a)
+    }
+}
+
 // Rotate a point clockwise to a given degree.
 fn _rotate(p: (i32, i32), deg: f32) -> (i32, i32) {
     let radians: f32 = deg.to_radians();
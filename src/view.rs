@@ -0,0 +1,587 @@
+//!  A module for borrowed images and windows of them.
+
+// from rust
+use std::cmp;
+
+// from external crate
+
+// from local crate
+use endec::{EncodeOptions, PngRowWriter};
+use error::{RasterError, RasterResult};
+use Color;
+use Image;
+use ImageFormat;
+
+/// A rectangular window of an image, borrowed without copying. Create one with `Image::view` or
+/// `ImageRef::view`.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView<'a> {
+    bytes: &'a [u8],
+    // Width of the whole image in pixels
+    stride: i32,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl<'a> ImageView<'a> {
+    pub(crate) fn new(
+        bytes: &'a [u8],
+        image_size: (i32, i32),
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> RasterResult<ImageView<'a>> {
+        check_window(image_size, x, y, width, height)?;
+        Ok(ImageView {
+            bytes,
+            stride: image_size.0,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Width of the view in pixels.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Height of the view in pixels.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Get pixel in a given x and y location of the view.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls outside the view, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let mut image = Image::blank(4, 4);
+    /// image.set_pixel(3, 2, Color::red()).unwrap();
+    ///
+    /// let view = image.view(2, 2, 2, 2).unwrap();
+    /// assert_eq!(255, view.get_pixel(1, 0).unwrap().r);
+    /// assert!(view.get_pixel(2, 0).is_err());
+    /// ```
+    pub fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Color> {
+        check_pixel(self.width, self.height, x, y)?;
+        let start = (((self.y + y) * self.stride + self.x + x) * 4) as usize;
+        let pixel = &self.bytes[start..start + 4];
+        Ok(Color::rgba(pixel[0], pixel[1], pixel[2], pixel[3]))
+    }
+
+    /// Iterate over the pixels of the view, row by row. Each pixel is a slice of its RGBA bytes.
+    pub fn pixels(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.rows().flat_map(|row| row.chunks_exact(4))
+    }
+
+    /// Iterate over the rows of the view, top to bottom. Each row is a slice of the RGBA bytes of
+    /// its pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let view = image.view(10, 10, 16, 8).unwrap();
+    ///
+    /// assert_eq!(8, view.rows().count());
+    /// for row in view.rows() {
+    ///     assert_eq!(16 * 4, row.len());
+    /// }
+    /// ```
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> {
+        let (start, end) = ((self.x * 4) as usize, ((self.x + self.width) * 4) as usize);
+        self.bytes
+            .chunks_exact(cmp::max(self.stride, 1) as usize * 4)
+            .skip(self.y as usize)
+            .take(self.height as usize)
+            .map(move |row| &row[start..end])
+    }
+
+    /// Encode the view into a new buffer. Works the same as `Image::to_bytes`. PNGs are encoded
+    /// straight from the borrowed rows, other formats copy the view first.
+    ///
+    /// # Errors
+    ///
+    /// See `Image::to_bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::endec::EncodeOptions;
+    /// use raster::ImageFormat;
+    ///
+    /// // Encode the top left corner of a large image
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let view = image.view(0, 0, 32, 32).unwrap();
+    /// let data = view.to_bytes(ImageFormat::Png, &EncodeOptions::default()).unwrap();
+    ///
+    /// assert_eq!(32, raster::Image::from_bytes(ImageFormat::Png, &data).unwrap().width);
+    /// ```
+    pub fn to_bytes(&self, format: ImageFormat, options: &EncodeOptions) -> RasterResult<Vec<u8>> {
+        if format != ImageFormat::Png {
+            return self.to_image().to_bytes(format, options);
+        }
+        let mut data = Vec::new();
+        let mut writer = PngRowWriter::new(&mut data, self.width, self.height)?;
+        for row in self.rows() {
+            writer.write_rows(row)?;
+        }
+        writer.finish()?;
+        Ok(data)
+    }
+
+    /// Copy the view into a new image.
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::blank(self.width, self.height);
+        image.bytes.clear();
+        for row in self.rows() {
+            image.bytes.extend_from_slice(row);
+        }
+        image
+    }
+}
+
+/// A rectangular window of an image, borrowed mutably without copying. Create one with
+/// `Image::view_mut` or `ImageRefMut::view_mut`.
+#[derive(Debug)]
+pub struct ImageViewMut<'a> {
+    bytes: &'a mut [u8],
+    // Width of the whole image in pixels
+    stride: i32,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl<'a> ImageViewMut<'a> {
+    pub(crate) fn new(
+        bytes: &'a mut [u8],
+        image_size: (i32, i32),
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> RasterResult<ImageViewMut<'a>> {
+        check_window(image_size, x, y, width, height)?;
+        Ok(ImageViewMut {
+            bytes,
+            stride: image_size.0,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Width of the view in pixels.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Height of the view in pixels.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Run a filter or another function that works on whole images on the view. The function gets
+    /// a copy of the view, which is written back after it returns. Only the part that still fits
+    /// in the view is written back when the function changes the size of the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the function, the view is left as it is then.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::filter;
+    ///
+    /// // Turn a large image gray tile by tile
+    /// let mut image = raster::open("tests/in/sample.png").unwrap();
+    /// for y in (0..image.height).step_by(64) {
+    ///     for x in (0..image.width).step_by(64) {
+    ///         let width = std::cmp::min(64, image.width - x);
+    ///         let height = std::cmp::min(64, image.height - y);
+    ///         let mut tile = image.view_mut(x, y, width, height).unwrap();
+    ///         tile.apply(|tile| filter::grayscale(tile)).unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn apply<F>(&mut self, f: F) -> RasterResult<()>
+    where
+        F: FnOnce(&mut Image) -> RasterResult<()>,
+    {
+        let mut image = self.view().to_image();
+        f(&mut image)?;
+
+        let width = cmp::min(image.width, self.width) as usize * 4;
+        for (row, changed) in self.rows_mut().zip(image.rows()) {
+            row[..width].copy_from_slice(&changed[..width]);
+        }
+        Ok(())
+    }
+
+    /// Get pixel in a given x and y location of the view.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls outside the view, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    pub fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Color> {
+        self.view().get_pixel(x, y)
+    }
+
+    /// Iterate over the rows of the view for changing them, top to bottom. Each row is a slice of
+    /// the RGBA bytes of its pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let mut image = Image::blank(4, 4);
+    ///
+    /// // Clear the bottom right quarter
+    /// for row in image.view_mut(2, 2, 2, 2).unwrap().rows_mut() {
+    ///     for byte in row.iter_mut() {
+    ///         *byte = 0;
+    ///     }
+    /// }
+    /// assert_eq!(0, image.get_pixel(3, 3).unwrap().a);
+    /// assert_eq!(255, image.get_pixel(1, 3).unwrap().a);
+    /// ```
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let (start, end) = ((self.x * 4) as usize, ((self.x + self.width) * 4) as usize);
+        self.bytes
+            .chunks_exact_mut(cmp::max(self.stride, 1) as usize * 4)
+            .skip(self.y as usize)
+            .take(self.height as usize)
+            .map(move |row| &mut row[start..end])
+    }
+
+    /// Set pixel in a given x and y location of the view.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls outside the view, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Color, Image};
+    ///
+    /// let mut image = Image::blank(4, 4);
+    /// image.view_mut(1, 1, 2, 2).unwrap().set_pixel(1, 1, Color::red()).unwrap();
+    ///
+    /// assert_eq!(255, image.get_pixel(2, 2).unwrap().r);
+    /// ```
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: Color) -> RasterResult<()> {
+        check_pixel(self.width, self.height, x, y)?;
+        let start = (((self.y + y) * self.stride + self.x + x) * 4) as usize;
+        self.bytes[start..start + 4].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        Ok(())
+    }
+
+    /// Borrow the view as an `ImageView`, for reading it or encoding it.
+    pub fn view(&self) -> ImageView<'_> {
+        ImageView {
+            bytes: self.bytes,
+            stride: self.stride,
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+/// An image over borrowed RGBA bytes, like a frame from a video decoder or shared memory. It can
+/// be read and encoded without copying the bytes into an `Image`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::endec::EncodeOptions;
+/// use raster::{ImageFormat, ImageRef};
+///
+/// let frame = vec![255; 64 * 48 * 4];
+/// let image = ImageRef::new(64, 48, &frame).unwrap();
+///
+/// let data = image.to_bytes(ImageFormat::Png, &EncodeOptions::default()).unwrap();
+/// assert_eq!(48, raster::Image::from_bytes(ImageFormat::Png, &data).unwrap().height);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRef<'a> {
+    bytes: &'a [u8],
+    width: i32,
+    height: i32,
+}
+
+impl<'a> ImageRef<'a> {
+    /// Wrap RGBA bytes.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the width and height if the number of bytes
+    /// isn't `width * height * 4`.
+    pub fn new(width: i32, height: i32, bytes: &'a [u8]) -> RasterResult<ImageRef<'a>> {
+        if !valid_size(width, height, bytes.len()) {
+            return Err(RasterError::SizeMismatch(width, height));
+        }
+        Ok(ImageRef {
+            bytes,
+            width,
+            height,
+        })
+    }
+
+    /// Width of the image in pixels.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// The borrowed RGBA bytes.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Get pixel in a given x and y location of the image.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls out of bounds, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    pub fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Color> {
+        self.as_view().get_pixel(x, y)
+    }
+
+    /// Iterate over the pixels of the image, row by row. Each pixel is a slice of its RGBA bytes.
+    pub fn pixels(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.bytes.chunks_exact(4)
+    }
+
+    /// Iterate over the rows of the image, top to bottom. Each row is a slice of the RGBA bytes of
+    /// its pixels.
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.as_view().rows()
+    }
+
+    /// Encode the image into a new buffer. Works the same as `ImageView::to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// See `Image::to_bytes`.
+    pub fn to_bytes(&self, format: ImageFormat, options: &EncodeOptions) -> RasterResult<Vec<u8>> {
+        self.as_view().to_bytes(format, options)
+    }
+
+    /// Copy the bytes into a new image.
+    pub fn to_image(&self) -> Image {
+        self.as_view().to_image()
+    }
+
+    /// Borrow a rectangular window of the image. Works the same as `Image::view`.
+    ///
+    /// # Errors
+    ///
+    /// See `Image::view`.
+    pub fn view(&self, x: i32, y: i32, width: i32, height: i32) -> RasterResult<ImageView<'a>> {
+        ImageView::new(self.bytes, (self.width, self.height), x, y, width, height)
+    }
+
+    fn as_view(&self) -> ImageView<'a> {
+        ImageView {
+            bytes: self.bytes,
+            stride: self.width,
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+/// An image over mutably borrowed RGBA bytes. Pixels are set in place, while `apply` runs on a
+/// copy of the bytes that is written back.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{filter, Color, ImageRefMut};
+///
+/// let mut frame = vec![0; 8 * 8 * 4];
+/// let mut image = ImageRefMut::new(8, 8, &mut frame).unwrap();
+/// image.set_pixel(0, 0, Color::white()).unwrap();
+/// image.apply(|image| filter::brightness(image, 0.5)).unwrap();
+///
+/// assert_eq!(127, frame[0]);
+/// ```
+#[derive(Debug)]
+pub struct ImageRefMut<'a> {
+    bytes: &'a mut [u8],
+    width: i32,
+    height: i32,
+}
+
+impl<'a> ImageRefMut<'a> {
+    /// Wrap RGBA bytes.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::SizeMismatch` holding the width and height if the number of bytes
+    /// isn't `width * height * 4`.
+    pub fn new(width: i32, height: i32, bytes: &'a mut [u8]) -> RasterResult<ImageRefMut<'a>> {
+        if !valid_size(width, height, bytes.len()) {
+            return Err(RasterError::SizeMismatch(width, height));
+        }
+        Ok(ImageRefMut {
+            bytes,
+            width,
+            height,
+        })
+    }
+
+    /// Width of the image in pixels.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Run a filter or another function that works on whole images on the bytes. The function gets
+    /// a copy of them, which is written back after it returns. Works the same as
+    /// `ImageViewMut::apply`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the function, the bytes are left as they are then.
+    pub fn apply<F>(&mut self, f: F) -> RasterResult<()>
+    where
+        F: FnOnce(&mut Image) -> RasterResult<()>,
+    {
+        self.as_view_mut().apply(f)
+    }
+
+    /// Get pixel in a given x and y location of the image.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls out of bounds, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    pub fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Color> {
+        self.view(0, 0, self.width, self.height)?.get_pixel(x, y)
+    }
+
+    /// Iterate over the pixels of the image for changing them, row by row. Each pixel is a slice
+    /// of its RGBA bytes.
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        self.bytes.chunks_exact_mut(4)
+    }
+
+    /// Set pixel in a given x and y location of the image.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls out of bounds, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: Color) -> RasterResult<()> {
+        self.as_view_mut().set_pixel(x, y, color)
+    }
+
+    /// Borrow a rectangular window of the image. Works the same as `Image::view`.
+    ///
+    /// # Errors
+    ///
+    /// See `Image::view`.
+    pub fn view(&self, x: i32, y: i32, width: i32, height: i32) -> RasterResult<ImageView<'_>> {
+        ImageView::new(self.bytes, (self.width, self.height), x, y, width, height)
+    }
+
+    /// Borrow a rectangular window of the image mutably. Works the same as `Image::view_mut`.
+    ///
+    /// # Errors
+    ///
+    /// See `Image::view`.
+    pub fn view_mut(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> RasterResult<ImageViewMut<'_>> {
+        ImageViewMut::new(self.bytes, (self.width, self.height), x, y, width, height)
+    }
+
+    fn as_view_mut(&mut self) -> ImageViewMut<'_> {
+        ImageViewMut {
+            bytes: self.bytes,
+            stride: self.width,
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+// Private functions
+
+fn check_pixel(width: i32, height: i32, x: i32, y: i32) -> RasterResult<()> {
+    if x < 0 || y < 0 || x >= width || y >= height {
+        return Err(RasterError::PixelOutOfBounds(x, y));
+    }
+    Ok(())
+}
+
+// A window has to lie inside the image.
+fn check_window(
+    (image_width, image_height): (i32, i32),
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+) -> RasterResult<()> {
+    if x < 0 || y < 0 {
+        return Err(RasterError::PixelOutOfBounds(x, y));
+    }
+    let right = x.checked_add(width).filter(|_| width >= 0);
+    let bottom = y.checked_add(height).filter(|_| height >= 0);
+    match (right, bottom) {
+        (Some(right), Some(bottom)) if right <= image_width && bottom <= image_height => Ok(()),
+        _ => Err(RasterError::PixelOutOfBounds(
+            x.saturating_add(width),
+            y.saturating_add(height),
+        )),
+    }
+}
+
+fn valid_size(width: i32, height: i32, len: usize) -> bool {
+    width >= 0
+        && height >= 0
+        && (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            == Some(len)
+}
@@ -0,0 +1,217 @@
+//!  A module for converting images to and from YUV video frames.
+//!
+//! Frames are 4:2:0, with one pair of chroma samples for every 2x2 block of pixels, in limited
+//! range: luma from 16 to 235 and chroma from 16 to 240. Planar YUV420 (I420) keeps U and V in
+//! separate planes, NV12 interleaves them in one plane. Chroma planes of odd sizes are rounded
+//! up. Alpha is dropped when converting to YUV and set to 255 when converting back.
+//!
+//! # Examples
+//!
+//! ```
+//! use raster::yuv::{self, YuvMatrix};
+//!
+//! let image = raster::open("tests/in/sample.png").unwrap();
+//!
+//! let (y, uv) = yuv::to_nv12(&image, YuvMatrix::Bt709);
+//! assert_eq!(y.len() / 2, uv.len());
+//!
+//! let frame = yuv::from_nv12(image.width, image.height, &y, &uv, YuvMatrix::Bt709).unwrap();
+//! assert_eq!(image.width, frame.width);
+//! ```
+
+// from rust
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use Color;
+use Image;
+
+/// The matrix for converting between RGB and YUV.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YuvMatrix {
+    /// Standard definition video.
+    Bt601,
+
+    /// High definition video.
+    Bt709,
+}
+
+impl YuvMatrix {
+    // Weights of red and blue in luma
+    fn weights(self) -> (f32, f32) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+/// Create an image from a planar YUV420 frame.
+///
+/// # Errors
+///
+/// Fails with `RasterError::SizeMismatch` holding the width and height if they are negative or a
+/// plane is shorter than they need.
+///
+/// # Examples
+///
+/// ```
+/// use raster::yuv::{self, YuvMatrix};
+///
+/// // A gray 2x2 frame
+/// let image = yuv::from_yuv420(2, 2, &[126; 4], &[128], &[128], YuvMatrix::Bt601).unwrap();
+/// assert_eq!(128, image.get_pixel(1, 1).unwrap().g);
+/// ```
+pub fn from_yuv420(
+    width: i32,
+    height: i32,
+    y: &[u8],
+    u: &[u8],
+    v: &[u8],
+    matrix: YuvMatrix,
+) -> RasterResult<Image> {
+    let (chroma_width, chroma_len) =
+        chroma_size(width, height).ok_or(RasterError::SizeMismatch(width, height))?;
+    if y.len() < width as usize * height as usize || u.len() < chroma_len || v.len() < chroma_len {
+        return Err(RasterError::SizeMismatch(width, height));
+    }
+    Ok(from_planes(
+        width,
+        height,
+        y,
+        matrix,
+        |i| (u[i], v[i]),
+        chroma_width,
+    ))
+}
+
+/// Create an image from an NV12 frame, with interleaved U and V samples in the second plane.
+///
+/// # Errors
+///
+/// See `from_yuv420`.
+pub fn from_nv12(
+    width: i32,
+    height: i32,
+    y: &[u8],
+    uv: &[u8],
+    matrix: YuvMatrix,
+) -> RasterResult<Image> {
+    let (chroma_width, chroma_len) =
+        chroma_size(width, height).ok_or(RasterError::SizeMismatch(width, height))?;
+    if y.len() < width as usize * height as usize || uv.len() < chroma_len * 2 {
+        return Err(RasterError::SizeMismatch(width, height));
+    }
+    Ok(from_planes(
+        width,
+        height,
+        y,
+        matrix,
+        |i| (uv[i * 2], uv[i * 2 + 1]),
+        chroma_width,
+    ))
+}
+
+/// Convert an image to a planar YUV420 frame. Returns the Y, U and V planes.
+///
+/// # Examples
+///
+/// ```
+/// use raster::yuv::{self, YuvMatrix};
+/// use raster::{Color, Image};
+///
+/// let image = Image::filled(3, 3, Color::white());
+/// let (y, u, v) = yuv::to_yuv420(&image, YuvMatrix::Bt601);
+///
+/// assert_eq!(vec![235; 9], y);
+/// assert_eq!((vec![128; 4], vec![128; 4]), (u, v));
+/// ```
+pub fn to_yuv420(image: &Image, matrix: YuvMatrix) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let (y, chroma) = to_planes(image, matrix);
+    let (u, v) = chroma.into_iter().unzip();
+    (y, u, v)
+}
+
+/// Convert an image to an NV12 frame. Returns the Y plane and the plane of interleaved U and V
+/// samples.
+pub fn to_nv12(image: &Image, matrix: YuvMatrix) -> (Vec<u8>, Vec<u8>) {
+    let (y, chroma) = to_planes(image, matrix);
+    let uv = chroma.into_iter().flat_map(|(u, v)| vec![u, v]).collect();
+    (y, uv)
+}
+
+// Private functions
+
+// Width and number of samples of a chroma plane.
+fn chroma_size(width: i32, height: i32) -> Option<(usize, usize)> {
+    if width < 0 || height < 0 {
+        return None;
+    }
+    let chroma_width = (width as usize).div_ceil(2);
+    Some((chroma_width, chroma_width * (height as usize).div_ceil(2)))
+}
+
+fn from_planes<F>(
+    width: i32,
+    height: i32,
+    y: &[u8],
+    matrix: YuvMatrix,
+    chroma: F,
+    chroma_width: usize,
+) -> Image
+where
+    F: Fn(usize) -> (u8, u8),
+{
+    let (kr, kb) = matrix.weights();
+    let kg = 1.0 - kr - kb;
+    Image::from_fn(width, height, |x, row| {
+        let luma = (y[row as usize * width as usize + x as usize] as f32 - 16.0) / 219.0;
+        let (u, v) = chroma(row as usize / 2 * chroma_width + x as usize / 2);
+        let pb = (u as f32 - 128.0) / 224.0;
+        let pr = (v as f32 - 128.0) / 224.0;
+
+        let r = luma + 2.0 * (1.0 - kr) * pr;
+        let b = luma + 2.0 * (1.0 - kb) * pb;
+        let g = (luma - kr * r - kb * b) / kg;
+        Color::rgb(to_u8(r), to_u8(g), to_u8(b))
+    })
+}
+
+// The luma plane and the U and V samples of each 2x2 block, averaged over its pixels.
+fn to_planes(image: &Image, matrix: YuvMatrix) -> (Vec<u8>, Vec<(u8, u8)>) {
+    let (kr, kb) = matrix.weights();
+    let kg = 1.0 - kr - kb;
+    let (chroma_width, chroma_len) = chroma_size(image.width, image.height).unwrap_or((0, 0));
+
+    let mut y = Vec::with_capacity(image.bytes.len() / 4);
+    let mut sums = vec![(0.0, 0.0, 0); chroma_len];
+    for (x, row, pixel) in image.enumerate_pixels() {
+        let r = pixel[0] as f32 / 255.0;
+        let g = pixel[1] as f32 / 255.0;
+        let b = pixel[2] as f32 / 255.0;
+        let luma = kr * r + kg * g + kb * b;
+        y.push((16.0 + 219.0 * luma).round() as u8);
+
+        let sum = &mut sums[row as usize / 2 * chroma_width + x as usize / 2];
+        sum.0 += (b - luma) / (2.0 * (1.0 - kb));
+        sum.1 += (r - luma) / (2.0 * (1.0 - kr));
+        sum.2 += 1;
+    }
+
+    let chroma = sums
+        .into_iter()
+        .map(|(pb, pr, count)| {
+            let count = count.max(1) as f32;
+            let u = (128.0 + 224.0 * pb / count).round().clamp(16.0, 240.0) as u8;
+            let v = (128.0 + 224.0 * pr / count).round().clamp(16.0, 240.0) as u8;
+            (u, v)
+        })
+        .collect();
+    (y, chroma)
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
@@ -22,6 +22,20 @@ fn conversion_accuracy_test() {
     assert_eq!(rgb1.2, rgb2.2);
 }
 
+#[test]
+fn hsl_round_trip_test() {
+    for &(r, g, b) in &[
+        (0, 0, 0),
+        (255, 255, 255),
+        (12, 200, 97),
+        (250, 3, 140),
+        (90, 90, 91),
+    ] {
+        let (h, s, l) = Color::to_hsl(r, g, b);
+        assert_eq!((r, g, b), Color::from_hsl(h, s, l));
+    }
+}
+
 #[test]
 fn hex_test() {
     // Ok tests
@@ -0,0 +1,555 @@
+extern crate raster;
+
+use raster::editor::{self, Montage};
+use raster::{BlendMode, Color, Image, InterpolationMode, PositionMode, ResizeMode};
+
+#[test]
+fn crop_position_offsets() {
+    // Each offset moves the crop along its own axis
+    let source = Image::from_fn(10, 10, |x, y| Color::rgba(x as u8, y as u8, 0, 255));
+    let cases = [
+        (PositionMode::CenterLeft, 2, 1, 2, 5),
+        (PositionMode::BottomRight, -3, -1, 5, 7),
+    ];
+    for (position, offset_x, offset_y, x, y) in cases {
+        let mut image = source.clone();
+        editor::crop(&mut image, 2, 2, position, offset_x, offset_y).unwrap();
+        let pixel = image.get_pixel(0, 0).unwrap();
+        assert_eq!((x, y), (pixel.r, pixel.g));
+    }
+}
+
+#[test]
+fn resize_interpolation() {
+    let modes = [
+        InterpolationMode::Nearest,
+        InterpolationMode::Bilinear,
+        InterpolationMode::Bicubic,
+        InterpolationMode::Lanczos3,
+        InterpolationMode::Mitchell,
+        InterpolationMode::CatmullRom,
+    ];
+    for &mode in &modes {
+        // Flat colors stay flat, even transparent ones
+        let mut image = Image::filled(30, 20, Color::rgba(200, 100, 50, 128));
+        editor::resize_with(&mut image, 12, 9, ResizeMode::Exact, mode).unwrap();
+        assert_eq!((12, 9), (image.width, image.height));
+        for pixel in image.pixels() {
+            assert_eq!(&[200, 100, 50, 128], pixel);
+        }
+
+        let mut image = raster::open("tests/in/sample.png").unwrap();
+        editor::resize_with(&mut image, 64, 64, ResizeMode::Fit, mode).unwrap();
+        assert_eq!(64, image.width);
+    }
+}
+
+#[test]
+fn resize_liquid() {
+    // A black line on white, which the seams go around
+    let line = |x: i32, _: i32| {
+        if x == 6 {
+            Color::black()
+        } else {
+            Color::white()
+        }
+    };
+
+    let mut image = Image::from_fn(10, 6, line);
+    editor::resize_liquid(&mut image, 5, 4).unwrap();
+    assert_eq!((5, 4), (image.width, image.height));
+    assert_eq!(
+        1,
+        (0..5)
+            .filter(|&x| image.get_pixel(x, 0).unwrap().r == 0)
+            .count()
+    );
+
+    // Widening by more than half the width takes several steps
+    let mut image = Image::from_fn(10, 6, line);
+    editor::resize_liquid(&mut image, 25, 8).unwrap();
+    assert_eq!((25, 8), (image.width, image.height));
+    assert!((0..25).any(|x| image.get_pixel(x, 7).unwrap().r == 0));
+}
+
+#[test]
+fn blend_modes() {
+    let blend = |base: Color, top: Color, mode| {
+        let base = Image::filled(2, 2, base);
+        let top = Image::filled(2, 2, top);
+        let image = editor::blend(&base, &top, mode, 1.0, PositionMode::TopLeft, 0, 0).unwrap();
+        let pixel = image.get_pixel(1, 1).unwrap();
+        (pixel.r, pixel.g, pixel.b, pixel.a)
+    };
+    let gray = Color::rgb(128, 128, 128);
+    let red = Color::rgb(200, 40, 40);
+
+    let gray_rgba = (128, 128, 128, 255);
+
+    assert_eq!(
+        (127, 127, 127, 255),
+        blend(gray.clone(), Color::white(), BlendMode::Exclusion)
+    );
+    assert_eq!(
+        255,
+        blend(gray.clone(), Color::white(), BlendMode::ColorDodge).0
+    );
+    assert_eq!(
+        0,
+        blend(gray.clone(), Color::black(), BlendMode::ColorBurn).0
+    );
+    assert_eq!(
+        255,
+        blend(gray.clone(), Color::white(), BlendMode::HardLight).1
+    );
+    assert_eq!(
+        gray_rgba,
+        blend(gray.clone(), gray.clone(), BlendMode::SoftLight)
+    );
+
+    // Gray has no hue or saturation to give
+    assert_eq!(
+        (88, 88, 88, 255),
+        blend(red.clone(), gray.clone(), BlendMode::Color)
+    );
+    let colored = blend(gray.clone(), red.clone(), BlendMode::Color);
+    assert!(colored.0 > colored.1);
+    assert_eq!(colored.1, colored.2);
+
+    // The original modes blend opaque images as they always did
+    assert_eq!(
+        (0, 0, 0, 255),
+        blend(gray.clone(), gray.clone(), BlendMode::Difference)
+    );
+    assert_eq!(
+        gray_rgba,
+        blend(gray.clone(), Color::white(), BlendMode::Multiply)
+    );
+    assert_eq!(
+        gray_rgba,
+        blend(gray.clone(), Color::black(), BlendMode::Screen)
+    );
+
+    // Alpha composites instead of turning opaque, in every mode
+    let clear = Color::rgba(0, 0, 0, 0);
+    let half = Color::rgba(255, 0, 0, 128);
+    for mode in [
+        BlendMode::Normal,
+        BlendMode::Difference,
+        BlendMode::Multiply,
+        BlendMode::Overlay,
+        BlendMode::Screen,
+        BlendMode::Hue,
+        BlendMode::SoftLight,
+    ] {
+        assert_eq!((255, 0, 0, 128), blend(clear.clone(), half.clone(), mode));
+    }
+    assert_eq!(
+        (255, 127, 127, 255),
+        blend(Color::white(), half, BlendMode::Normal)
+    );
+}
+
+#[test]
+fn paste_clipped() {
+    let overlay = Image::from_fn(6, 6, |x, y| Color::rgb(x as u8, y as u8, 0));
+
+    // Larger than the base and hanging off the top left
+    let mut image = Image::filled(3, 3, Color::white());
+    editor::paste(&mut image, &overlay, -2, -1, 1.0).unwrap();
+    let pixel = image.get_pixel(0, 0).unwrap();
+    assert_eq!((2, 1, 0), (pixel.r, pixel.g, pixel.b));
+    let pixel = image.get_pixel(2, 2).unwrap();
+    assert_eq!((4, 3, 0), (pixel.r, pixel.g, pixel.b));
+
+    // Fully outside leaves the base alone
+    let mut image = Image::filled(3, 3, Color::white());
+    editor::paste(&mut image, &overlay, 3, -10, 1.0).unwrap();
+    assert!(image.bytes.iter().all(|&byte| byte == 255));
+
+    // Onto a transparent base the overlay keeps its opacity
+    let mut image = Image::blank(2, 2);
+    editor::fill(&mut image, Color::rgba(0, 0, 0, 0)).unwrap();
+    editor::paste(&mut image, &overlay, 0, 0, 0.5).unwrap();
+    assert_eq!(128, image.get_pixel(1, 1).unwrap().a);
+}
+
+#[test]
+fn watermark_margins() {
+    let mark = Image::filled(2, 2, Color::black());
+    let corners = [
+        (PositionMode::TopLeft, 1, 1),
+        (PositionMode::CenterLeft, 1, 4),
+        (PositionMode::Center, 4, 4),
+        (PositionMode::BottomRight, 7, 7),
+    ];
+    for (position, x, y) in corners {
+        let mut image = Image::filled(10, 10, Color::white());
+        editor::watermark(&mut image, &mark, position, 1, 1.0).unwrap();
+        assert_eq!(0, image.get_pixel(x, y).unwrap().r);
+        assert_eq!(0, image.get_pixel(x + 1, y + 1).unwrap().r);
+        let dark = image.bytes.chunks(4).filter(|pixel| pixel[0] == 0).count();
+        assert_eq!(4, dark);
+    }
+}
+
+#[test]
+fn extend_pad() {
+    let source = Image::from_fn(3, 2, |x, y| Color::rgba(x as u8, y as u8, 0, 100));
+
+    let mut image = source.clone();
+    editor::extend(&mut image, 1, 2, 3, 4, Color::blue()).unwrap();
+    assert_eq!((9, 6), (image.width, image.height));
+    let pixel = image.get_pixel(6, 2).unwrap();
+    assert_eq!((2, 1, 0, 100), (pixel.r, pixel.g, pixel.b, pixel.a));
+    assert_eq!(255, image.get_pixel(8, 5).unwrap().b);
+
+    // Pixels are copied, not blended onto the background
+    let mut image = source.clone();
+    editor::pad_to(&mut image, 5, 1, PositionMode::BottomRight, Color::white()).unwrap();
+    assert_eq!((5, 2), (image.width, image.height));
+    let pixel = image.get_pixel(4, 1).unwrap();
+    assert_eq!((2, 1, 0, 100), (pixel.r, pixel.g, pixel.b, pixel.a));
+    assert_eq!(255, image.get_pixel(1, 0).unwrap().a);
+}
+
+#[test]
+fn trim_borders() {
+    // A noisy gray border around a 4x2 red block
+    let mut image = Image::from_fn(10, 8, |x, y| {
+        if (3..7).contains(&x) && (2..4).contains(&y) {
+            Color::red()
+        } else {
+            let noise = ((x + y) % 3) as u8;
+            Color::rgb(200 + noise, 200, 200 - noise)
+        }
+    });
+    let mut exact = image.clone();
+    editor::trim(&mut exact, 0).unwrap();
+    assert!(exact.width > 4);
+
+    editor::trim(&mut image, 2).unwrap();
+    assert_eq!((4, 2), (image.width, image.height));
+    assert!(image.bytes.chunks(4).all(|pixel| pixel == [255, 0, 0, 255]));
+
+    // Solid images and empty ones are left alone
+    let mut solid = Image::filled(3, 3, Color::blue());
+    editor::trim(&mut solid, 0).unwrap();
+    editor::trim_transparent(&mut solid, 255).unwrap();
+    assert_eq!((3, 3), (solid.width, solid.height));
+    let mut empty = Image::blank(0, 0);
+    editor::trim(&mut empty, 0).unwrap();
+}
+
+#[test]
+fn concat_montage() {
+    let images = vec![
+        Image::from_fn(3, 2, |x, y| Color::rgba(x as u8, y as u8, 0, 100)),
+        Image::filled(1, 4, Color::blue()),
+    ];
+
+    let strip = editor::hconcat(&images);
+    assert_eq!((4, 4), (strip.width, strip.height));
+    let pixel = strip.get_pixel(2, 1).unwrap();
+    assert_eq!((2, 1, 0, 100), (pixel.r, pixel.g, pixel.b, pixel.a));
+    assert_eq!(255, strip.get_pixel(3, 3).unwrap().b);
+
+    let column = editor::vconcat(&images);
+    assert_eq!((3, 6), (column.width, column.height));
+    assert_eq!(255, column.get_pixel(0, 5).unwrap().b);
+    assert_eq!(0, column.get_pixel(1, 5).unwrap().a);
+
+    let empty = editor::hconcat(&[]);
+    assert_eq!((0, 0), (empty.width, empty.height));
+
+    // Three images in two columns leave the last cell empty
+    let sheet = Montage::new(2)
+        .gap(1)
+        .background(Color::white())
+        .render(&[images[1].clone(), images[1].clone(), images[1].clone()])
+        .unwrap();
+    assert_eq!((5, 11), (sheet.width, sheet.height));
+    assert_eq!(255, sheet.get_pixel(3, 6).unwrap().b);
+    assert_eq!(255, sheet.get_pixel(3, 6).unwrap().r);
+}
+
+#[test]
+fn nine_slice_corners() {
+    let skin = Image::from_fn(7, 5, |x, y| Color::rgb(x as u8 * 30, y as u8 * 50, 0));
+
+    let mut wide = skin.clone();
+    editor::nine_slice(&mut wide, (2, 2, 1, 3), 30, 12).unwrap();
+    assert_eq!((30, 12), (wide.width, wide.height));
+    // Corners are copied as they are
+    for &(x, y, sx, sy) in &[(0, 0, 0, 0), (2, 1, 2, 1), (29, 0, 6, 0), (28, 11, 5, 4)] {
+        let (a, b) = (
+            wide.get_pixel(x, y).unwrap(),
+            skin.get_pixel(sx, sy).unwrap(),
+        );
+        assert_eq!((a.r, a.g), (b.r, b.g), "{} {}", x, y);
+    }
+
+    // Corners shrink to fit a size smaller than them
+    let mut small = skin.clone();
+    editor::nine_slice(&mut small, (3, 3, 3, 3), 4, 2).unwrap();
+    assert_eq!((4, 2), (small.width, small.height));
+}
+
+#[test]
+fn tile_canvas() {
+    let image = Image::from_fn(3, 2, |x, y| Color::rgb(x as u8, y as u8, 0));
+    let red = |image: &Image, x, y| image.get_pixel(x, y).unwrap().r;
+    let green = |image: &Image, x, y| image.get_pixel(x, y).unwrap().g;
+
+    let mut tiled = image.clone();
+    editor::tile(&mut tiled, 8, 5).unwrap();
+    assert_eq!((8, 5), (tiled.width, tiled.height));
+    let row: Vec<u8> = (0..8).map(|x| red(&tiled, x, 0)).collect();
+    assert_eq!(vec![0, 1, 2, 0, 1, 2, 0, 1], row);
+    assert_eq!(0, green(&tiled, 0, 4));
+
+    let mut mirrored = image.clone();
+    editor::tile_mirrored(&mut mirrored, 8, 5).unwrap();
+    let row: Vec<u8> = (0..8).map(|x| red(&mirrored, x, 0)).collect();
+    assert_eq!(vec![0, 1, 2, 2, 1, 0, 0, 1], row);
+    let column: Vec<u8> = (0..5).map(|y| green(&mirrored, 0, y)).collect();
+    assert_eq!(vec![0, 1, 1, 0, 0], column);
+
+    let mut empty = Image::blank(0, 0);
+    editor::tile(&mut empty, 2, 2).unwrap();
+    assert_eq!(0, empty.get_pixel(1, 1).unwrap().a);
+}
+
+#[test]
+fn round_corners_mask() {
+    let mut card = Image::filled(20, 10, Color::blue());
+    editor::round_corners(&mut card, 4).unwrap();
+    let alpha = |image: &Image, x, y| image.get_pixel(x, y).unwrap().a;
+    for &(x, y) in &[(0, 0), (19, 0), (0, 9), (19, 9)] {
+        assert_eq!(0, alpha(&card, x, y));
+    }
+    // The curve is antialiased and the sides are untouched
+    assert!((1..255).contains(&alpha(&card, 1, 1)) || (1..255).contains(&alpha(&card, 2, 0)));
+    assert_eq!(255, alpha(&card, 10, 0));
+    assert_eq!(255, alpha(&card, 0, 5));
+
+    // A huge radius makes a pill
+    let mut pill = Image::filled(20, 10, Color::blue());
+    editor::round_corners(&mut pill, 100).unwrap();
+    assert_eq!(0, alpha(&pill, 0, 1));
+    assert_eq!(255, alpha(&pill, 5, 5));
+
+    // Masks scale the alpha already there
+    let mut image = Image::filled(2, 1, Color::rgba(255, 0, 0, 128));
+    let mask = Image::from_fn(2, 1, |x, _| {
+        if x == 0 {
+            Color::white()
+        } else {
+            Color::rgb(128, 128, 128)
+        }
+    });
+    editor::apply_mask(&mut image, &mask).unwrap();
+    assert_eq!((128, 64), (alpha(&image, 0, 0), alpha(&image, 1, 0)));
+    assert!(editor::apply_mask(&mut image, &card).is_err());
+}
+
+#[test]
+fn blend_premultiplied() {
+    let straight_base = Image::filled(3, 2, Color::rgba(0, 0, 200, 128));
+    let straight_top = Image::filled(2, 1, Color::rgba(255, 100, 0, 64));
+    let mut base = straight_base.clone();
+    base.premultiply_alpha();
+    let mut top = straight_top.clone();
+    top.premultiply_alpha();
+
+    // The same as blending straight alpha, premultiplied afterwards
+    let mut expected = editor::blend(
+        &straight_base,
+        &straight_top,
+        BlendMode::Normal,
+        1.0,
+        PositionMode::BottomRight,
+        0,
+        0,
+    )
+    .unwrap();
+    expected.premultiply_alpha();
+    let image = editor::blend_premultiplied(
+        &base,
+        &top,
+        BlendMode::Normal,
+        1.0,
+        PositionMode::BottomRight,
+        0,
+        0,
+    )
+    .unwrap();
+    assert_eq!((3, 2), (image.width, image.height));
+    for (pixel, expected) in image.pixels().zip(expected.pixels()) {
+        for (&a, &b) in pixel.iter().zip(expected) {
+            assert!(
+                (a as i32 - b as i32).abs() <= 1,
+                "{:?} {:?}",
+                pixel,
+                expected
+            );
+        }
+    }
+    // Pixels outside the top image keep the premultiplied base
+    assert_eq!(base.bytes[..4], image.bytes[..4]);
+}
+
+#[test]
+fn resize_linear_light() {
+    let stripes = Image::from_fn(8, 8, |x, _| {
+        if x % 2 == 0 {
+            Color::black()
+        } else {
+            Color::white()
+        }
+    });
+
+    // Blending in linear light keeps the stripes as bright as they look
+    for &mode in &[
+        InterpolationMode::Bilinear,
+        InterpolationMode::Bicubic,
+        InterpolationMode::Lanczos3,
+        InterpolationMode::Mitchell,
+        InterpolationMode::CatmullRom,
+    ] {
+        let mut linear = stripes.clone();
+        editor::resize_linear(&mut linear, 4, 4, ResizeMode::Exact, mode).unwrap();
+        let mut plain = stripes.clone();
+        editor::resize_with(&mut plain, 4, 4, ResizeMode::Exact, mode).unwrap();
+        assert_eq!((4, 4), (linear.width, linear.height));
+        let center = linear.get_pixel(1, 1).unwrap().r;
+        assert!((180..=196).contains(&center), "{:?}: {}", mode, center);
+        assert!(center > plain.get_pixel(1, 1).unwrap().r + 40, "{:?}", mode);
+        assert!(linear.pixels().all(|pixel| pixel[3] == 255), "{:?}", mode);
+    }
+
+    // Nearest picks pixels without blending them
+    let mut linear = stripes.clone();
+    editor::resize_linear(
+        &mut linear,
+        4,
+        4,
+        ResizeMode::Exact,
+        InterpolationMode::Nearest,
+    )
+    .unwrap();
+    let mut plain = stripes.clone();
+    editor::resize_with(
+        &mut plain,
+        4,
+        4,
+        ResizeMode::Exact,
+        InterpolationMode::Nearest,
+    )
+    .unwrap();
+    assert_eq!(plain.bytes, linear.bytes);
+
+    // Solid colors come back as they were, in every shape
+    let solid = Image::filled(6, 3, Color::rgba(30, 140, 220, 200));
+    for &(w, h) in &[(3, 2), (12, 5), (1, 4)] {
+        let mut image = solid.clone();
+        editor::resize_linear(
+            &mut image,
+            w,
+            h,
+            ResizeMode::Exact,
+            InterpolationMode::Bilinear,
+        )
+        .unwrap();
+        assert_eq!((w, h), (image.width, image.height));
+        assert!(
+            image.pixels().all(|pixel| pixel == [30, 140, 220, 200]),
+            "{}x{}",
+            w,
+            h
+        );
+    }
+    let mut column = Image::filled(1, 5, Color::red());
+    editor::resize_linear(
+        &mut column,
+        1,
+        2,
+        ResizeMode::Exact,
+        InterpolationMode::Lanczos3,
+    )
+    .unwrap();
+    assert_eq!([255, 0, 0, 255].repeat(2), column.bytes);
+    let mut empty = solid.clone();
+    editor::resize_linear(
+        &mut empty,
+        0,
+        0,
+        ResizeMode::Exact,
+        InterpolationMode::Bilinear,
+    )
+    .unwrap();
+    assert!(empty.bytes.is_empty());
+}
+
+#[test]
+fn smart_crop_subject() {
+    // A red square marked with green at its corner, on a gray image
+    let scene = |left: i32, top: i32| {
+        Image::from_fn(60, 40, move |x, y| {
+            if (x, y) == (left, top) {
+                Color::green()
+            } else if x >= left && x < left + 8 && y >= top && y < top + 8 {
+                Color::red()
+            } else {
+                Color::rgb(128, 128, 128)
+            }
+        })
+    };
+    for &(left, top) in &[(2, 2), (50, 30), (4, 28), (26, 16)] {
+        let mut image = scene(left, top);
+        editor::smart_crop(&mut image, 20, 20).unwrap();
+        assert_eq!((20, 20), (image.width, image.height));
+        let corner = image
+            .enumerate_pixels()
+            .find(|&(_, _, pixel)| pixel == [0, 255, 0, 255])
+            .map(|(x, y, _)| (x, y));
+        let (x, y) = corner.unwrap_or_else(|| panic!("lost the subject at {} {}", left, top));
+        assert!(x + 8 <= 20 && y + 8 <= 20, "{} {}: {} {}", left, top, x, y);
+    }
+
+    // Flat and transparent images crop around the center
+    let mut image = Image::filled(60, 40, Color::rgb(128, 128, 128));
+    editor::smart_crop(&mut image, 20, 20).unwrap();
+    assert_eq!((20, 20), (image.width, image.height));
+    let hidden = Image::from_fn(60, 40, |x, y| Color::rgba(x as u8, y as u8, 0, 0));
+    let mut image = hidden.clone();
+    let mut centered = hidden.clone();
+    editor::smart_crop(&mut image, 20, 20).unwrap();
+    editor::crop(&mut centered, 20, 20, PositionMode::Center, 0, 0).unwrap();
+    assert_eq!(vec![20, 10], image.bytes[..2].to_vec());
+    assert_eq!(centered.bytes, image.bytes);
+
+    // Sizes are limited to the image
+    let mut image = scene(2, 2);
+    editor::smart_crop(&mut image, 100, 30).unwrap();
+    assert_eq!((60, 30), (image.width, image.height));
+    assert!(image.pixels().any(|pixel| pixel == [0, 255, 0, 255]));
+
+    // Single columns and empty crops
+    let mut column = Image::from_fn(1, 30, |_, y| {
+        if y == 25 {
+            Color::red()
+        } else {
+            Color::black()
+        }
+    });
+    editor::smart_crop(&mut column, 1, 6).unwrap();
+    assert_eq!((1, 6), (column.width, column.height));
+    assert!(column.pixels().any(|pixel| pixel[0] == 255));
+    let mut image = scene(2, 2);
+    editor::smart_crop(&mut image, 0, 0).unwrap();
+    assert!(image.bytes.is_empty());
+    let mut empty = Image::blank(0, 0);
+    editor::smart_crop(&mut empty, 5, 5).unwrap();
+    assert!(empty.bytes.is_empty());
+}
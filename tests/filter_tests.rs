@@ -1,6 +1,8 @@
 extern crate raster;
 
-use raster::{filter, Orientation};
+use raster::error::RasterError;
+use raster::filter::{self, EdgeOperator, Kernel, Levels};
+use raster::{editor, Color, EdgeMode, Image, Orientation};
 
 #[test]
 fn brightness_test() {
@@ -43,3 +45,686 @@ fn sobel_d2_test() {
     filter::sobel(&mut image, Orientation::DiagonalDown).unwrap();
     raster::save(&image, "tests/out/test_filter_sobel_d2.jpg").unwrap();
 }
+
+#[test]
+fn gaussian_blur_sigma() {
+    // A solid image stays solid, however large the kernel
+    let mut solid = Image::filled(5, 4, Color::rgb(10, 200, 30));
+    filter::gaussian_blur(&mut solid, 6.0).unwrap();
+    assert!(solid
+        .bytes
+        .chunks(4)
+        .all(|pixel| pixel == [10, 200, 30, 255]));
+
+    // A dot spreads out symmetrically, more with a larger sigma
+    let dot = |sigma| {
+        let mut image = Image::filled(21, 21, Color::black());
+        image.set_pixel(10, 10, Color::white()).unwrap();
+        filter::gaussian_blur(&mut image, sigma).unwrap();
+        image
+    };
+    let (small, large) = (dot(1.0), dot(3.0));
+    let red = |image: &Image, x, y| image.get_pixel(x, y).unwrap().r;
+    assert_eq!(red(&small, 8, 10), red(&small, 12, 10));
+    assert_eq!(red(&small, 10, 8), red(&small, 8, 10));
+    assert!(red(&small, 10, 10) > red(&large, 10, 10));
+    assert!(red(&small, 15, 10) < red(&large, 15, 10));
+
+    // Transparent neighbors don't bleed their color
+    let mut image = Image::filled(4, 1, Color::rgba(0, 0, 0, 0));
+    image.set_pixel(1, 0, Color::red()).unwrap();
+    filter::gaussian_blur(&mut image, 1.0).unwrap();
+    assert_eq!(255, image.get_pixel(2, 0).unwrap().r);
+
+    let mut same = dot(1.0);
+    filter::gaussian_blur(&mut same, 0.0).unwrap();
+    assert_eq!(small.bytes, same.bytes);
+
+    // Huge sigmas average the whole image
+    let mut flat = dot(1.0);
+    filter::gaussian_blur(&mut flat, f32::INFINITY).unwrap();
+    assert_eq!(flat.get_pixel(0, 0).unwrap().r, red(&flat, 20, 20));
+}
+
+#[test]
+fn box_blur_radius() {
+    let mut solid = Image::filled(6, 3, Color::rgb(10, 200, 30));
+    filter::box_blur(&mut solid, 50, 3).unwrap();
+    filter::box_blur(&mut solid, u32::MAX, 1).unwrap();
+    assert!(solid
+        .bytes
+        .chunks(4)
+        .all(|pixel| pixel == [10, 200, 30, 255]));
+
+    // One pass of radius 1 averages 3 pixels along each axis
+    let mut image = Image::filled(9, 9, Color::black());
+    image.set_pixel(4, 4, Color::rgb(225, 0, 0)).unwrap();
+    filter::box_blur(&mut image, 1, 1).unwrap();
+    assert_eq!(25, image.get_pixel(3, 5).unwrap().r);
+    assert_eq!(0, image.get_pixel(2, 4).unwrap().r);
+
+    // More passes spread further
+    let mut smooth = Image::filled(9, 9, Color::black());
+    smooth.set_pixel(4, 4, Color::rgb(225, 0, 0)).unwrap();
+    filter::box_blur(&mut smooth, 1, 3).unwrap();
+    assert!(smooth.get_pixel(2, 4).unwrap().r > 0);
+}
+
+#[test]
+fn motion_blur_line() {
+    let dot = |angle| {
+        let mut image = Image::filled(11, 11, Color::black());
+        image.set_pixel(5, 5, Color::rgb(250, 0, 0)).unwrap();
+        filter::motion_blur(&mut image, 5, angle).unwrap();
+        image
+    };
+    let red = |image: &Image, x, y| image.get_pixel(x, y).unwrap().r;
+
+    // Sideways streaks stay on their row
+    let sideways = dot(0.0);
+    assert_eq!(50, red(&sideways, 3, 5));
+    assert_eq!(50, red(&sideways, 7, 5));
+    assert_eq!(0, red(&sideways, 8, 5));
+    assert_eq!(0, red(&sideways, 5, 4));
+
+    let upright = dot(90.0);
+    assert_eq!(50, red(&upright, 5, 7));
+    assert_eq!(0, red(&upright, 7, 5));
+
+    let mut same = Image::filled(3, 3, Color::blue());
+    filter::motion_blur(&mut same, 9, 45.0).unwrap();
+    assert!(same.bytes.chunks(4).all(|pixel| pixel == [0, 0, 255, 255]));
+}
+
+#[test]
+fn median_noise() {
+    // Salt and pepper on gray, with a hard edge down the middle
+    let mut image = Image::from_fn(12, 12, |x, _| {
+        if x < 6 {
+            Color::rgb(60, 60, 60)
+        } else {
+            Color::rgb(200, 200, 200)
+        }
+    });
+    for &(x, y, salt) in &[(2, 2, true), (3, 8, false), (9, 4, false), (10, 10, true)] {
+        let color = if salt { Color::white() } else { Color::black() };
+        image.set_pixel(x, y, color).unwrap();
+    }
+
+    filter::median(&mut image, 2).unwrap();
+    for y in 0..12 {
+        for x in 0..12 {
+            let expected = if x < 6 { 60 } else { 200 };
+            assert_eq!(expected, image.get_pixel(x, y).unwrap().r, "{} {}", x, y);
+        }
+    }
+
+    // Huge radii are capped to the image
+    let small = Image::from_fn(3, 2, |x, y| Color::rgb(x as u8 * 40, y as u8 * 90, 7));
+    let mut capped = small.clone();
+    filter::median(&mut capped, 3).unwrap();
+    let mut huge = small.clone();
+    filter::median(&mut huge, u32::MAX).unwrap();
+    assert_eq!(capped.bytes, huge.bytes);
+}
+
+#[test]
+fn bilateral_edges() {
+    // A noisy step edge: the noise smooths out but the step stays
+    let image = Image::from_fn(16, 8, |x, y| {
+        let noise = ((x * 7 + y * 3) % 5) as u8 * 2;
+        if x < 8 {
+            Color::rgb(40 + noise, 40 + noise, 40 + noise)
+        } else {
+            Color::rgb(210 + noise, 210 + noise, 210 + noise)
+        }
+    });
+    let mut smooth = image.clone();
+    filter::bilateral(&mut smooth, 2.0, 20.0).unwrap();
+    let mut blurred = image.clone();
+    filter::gaussian_blur(&mut blurred, 2.0).unwrap();
+
+    let red = |image: &Image, x, y| image.get_pixel(x, y).unwrap().r as i32;
+    assert!(red(&smooth, 7, 4) < 55);
+    assert!(red(&smooth, 8, 4) > 200);
+    assert!(red(&blurred, 7, 4) > 80);
+
+    let spread = |image: &Image| {
+        let row: Vec<i32> = (0..5).map(|x| red(image, x, 3)).collect();
+        row.iter().max().unwrap() - row.iter().min().unwrap()
+    };
+    assert!(spread(&smooth) < spread(&image));
+
+    // Huge sigmas are limited to the size of the image
+    let mut flat = image.clone();
+    filter::bilateral(&mut flat, f32::INFINITY, f32::INFINITY).unwrap();
+    assert!(red(&flat, 0, 0) > 55 && red(&flat, 15, 7) < 200);
+}
+
+#[test]
+fn unsharp_mask_edges() {
+    let image = Image::from_fn(10, 4, |x, y| {
+        let noise = (y % 2) as u8;
+        if x < 5 {
+            Color::rgb(100 + noise, 100, 100)
+        } else {
+            Color::rgb(150, 150, 150)
+        }
+    });
+    let red = |image: &Image, x, y| image.get_pixel(x, y).unwrap().r;
+
+    let mut sharp = image.clone();
+    filter::unsharp_mask(&mut sharp, 1.0, 1.0, 3).unwrap();
+    // The edge gains contrast on both sides
+    assert!(red(&sharp, 4, 1) < 100);
+    assert!(red(&sharp, 5, 1) > 150);
+    // The one value noise is under the threshold
+    assert_eq!(red(&image, 0, 1), red(&sharp, 0, 1));
+    assert_eq!(red(&image, 1, 0), red(&sharp, 1, 0));
+
+    let mut same = image.clone();
+    filter::unsharp_mask(&mut same, 1.0, 0.0, 0).unwrap();
+    assert_eq!(image.bytes, same.bytes);
+}
+
+#[test]
+fn convolve_any_kernel() {
+    let image = Image::from_fn(4, 1, |x, _| Color::rgba(x as u8 * 10, 0, 0, 200));
+    let row =
+        |image: &Image| -> Vec<u8> { (0..4).map(|x| image.get_pixel(x, 0).unwrap().r).collect() };
+
+    // Shift right by one pixel, with each edge mode filling the gap differently
+    let shift = Kernel::new(3, 1, vec![1.0, 0.0, 0.0]).unwrap();
+    let expected = [
+        (EdgeMode::Clamp, vec![0, 0, 10, 20]),
+        (EdgeMode::Wrap, vec![30, 0, 10, 20]),
+        (EdgeMode::Mirror, vec![0, 0, 10, 20]),
+    ];
+    for (edge, values) in expected {
+        let mut shifted = image.clone();
+        filter::convolve_kernel(&mut shifted, &shift, edge).unwrap();
+        assert_eq!(values, row(&shifted), "{:?}", edge);
+        assert_eq!(200, shifted.get_pixel(0, 0).unwrap().a);
+    }
+
+    // Mirroring differs from clamping further out
+    let far = Kernel::new(5, 1, vec![1.0, 0.0, 0.0, 0.0, 0.0]).unwrap();
+    let mut mirrored = image.clone();
+    filter::convolve_kernel(&mut mirrored, &far, EdgeMode::Mirror).unwrap();
+    assert_eq!(vec![10, 0, 0, 10], row(&mirrored));
+
+    // Normalized blur with a bias
+    let blur = Kernel::new(3, 3, vec![1.0; 9])
+        .unwrap()
+        .normalize()
+        .bias(5.0);
+    let mut flat = Image::filled(3, 3, Color::rgb(100, 50, 0));
+    filter::convolve_kernel(&mut flat, &blur, EdgeMode::Clamp).unwrap();
+    let pixel = flat.get_pixel(1, 1).unwrap();
+    assert_eq!((105, 55, 5), (pixel.r, pixel.g, pixel.b));
+
+    // Even sizes and missing values are rejected
+    match Kernel::new(2, 1, vec![1.0, 1.0]) {
+        Err(RasterError::InvalidKernel(2, 1)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    match Kernel::new(3, 3, vec![1.0; 8]) {
+        Err(RasterError::InvalidKernel(3, 3)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn gradient_operators() {
+    // A white square on black
+    let image = Image::from_fn(9, 9, |x, y| {
+        if (3..6).contains(&x) && (3..6).contains(&y) {
+            Color::white()
+        } else {
+            Color::black()
+        }
+    });
+    for &operator in &[EdgeOperator::Sobel, EdgeOperator::Scharr] {
+        let gradient = filter::gradient(&image, operator);
+        let at = |x: usize, y: usize| y * 9 + x;
+        assert_eq!(81, gradient.magnitude.len());
+        assert_eq!(0.0, gradient.magnitude[at(0, 0)]);
+        assert_eq!(0.0, gradient.magnitude[at(4, 4)]);
+        assert!(gradient.magnitude[at(2, 4)] > 500.0);
+        // Brighter towards the middle of the square
+        let left = gradient.direction[at(2, 4)];
+        let top = gradient.direction[at(4, 2)];
+        assert!(left.abs() < 1e-6, "{:?}", operator);
+        assert!((top - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+    }
+
+    let mut edges = image.clone();
+    filter::scharr(&mut edges).unwrap();
+    assert_eq!(255, edges.get_pixel(2, 4).unwrap().r);
+    assert_eq!(0, edges.get_pixel(4, 4).unwrap().r);
+}
+
+#[test]
+fn canny_edges() {
+    // A gray square on black, with faint noise
+    let image = Image::from_fn(24, 24, |x, y| {
+        let noise = ((x * 13 + y * 7) % 5) as u8;
+        if (6..18).contains(&x) && (6..18).contains(&y) {
+            Color::rgb(180 + noise, 180 + noise, 180 + noise)
+        } else {
+            Color::rgb(noise, noise, noise)
+        }
+    });
+    let mut edges = image.clone();
+    filter::canny(&mut edges, 100.0, 300.0).unwrap();
+
+    let on = |x, y| edges.get_pixel(x, y).unwrap().r == 255;
+    // The outline is found, thin and closed, and the noise isn't
+    for i in 8..16 {
+        assert!(on(5, i) || on(6, i), "left {}", i);
+        assert!(on(i, 5) || on(i, 6), "top {}", i);
+        assert!(!(on(4, i) && on(5, i) && on(6, i)));
+    }
+    assert!(!on(12, 12));
+    assert!(!on(1, 1));
+}
+
+#[test]
+fn high_pass_laplacian() {
+    // An even ramp has no detail or curvature
+    let ramp = Image::from_fn(12, 12, |x, _| Color::rgb(x as u8 * 10 + 50, 90, 90));
+    let mut flat = ramp.clone();
+    filter::laplacian(&mut flat).unwrap();
+    let pixel = flat.get_pixel(5, 5).unwrap();
+    assert_eq!((128, 128), (pixel.r, pixel.g));
+
+    // A dot stands out of the gray in both
+    let mut dot = Image::filled(9, 9, Color::rgb(100, 100, 100));
+    dot.set_pixel(4, 4, Color::rgb(200, 100, 100)).unwrap();
+    let mut lap = dot.clone();
+    filter::laplacian(&mut lap).unwrap();
+    assert_eq!(0, lap.get_pixel(4, 4).unwrap().r);
+    assert_eq!(228, lap.get_pixel(4, 3).unwrap().r);
+    assert_eq!(128, lap.get_pixel(3, 3).unwrap().r);
+
+    let mut high = dot.clone();
+    filter::high_pass(&mut high, 1.0).unwrap();
+    assert!(high.get_pixel(4, 4).unwrap().r > 180);
+    assert_eq!(128, high.get_pixel(0, 0).unwrap().r);
+    assert_eq!(128, high.get_pixel(4, 4).unwrap().g);
+}
+
+#[test]
+fn threshold_binary() {
+    // Dark text on the left half, a bright page on the right
+    let page = Image::from_fn(10, 4, |x, _| {
+        if x < 5 {
+            Color::rgb(40, 40, 40)
+        } else {
+            Color::rgba(210, 210, 210, 128)
+        }
+    });
+    let mut otsu = page.clone();
+    let value = filter::threshold_otsu(&mut otsu).unwrap();
+    assert!((40..210).contains(&value));
+    let dark = otsu.get_pixel(0, 0).unwrap();
+    let bright = otsu.get_pixel(9, 3).unwrap();
+    assert_eq!((0, 0, 0, 255), (dark.r, dark.g, dark.b, dark.a));
+    assert_eq!(
+        (255, 255, 255, 128),
+        (bright.r, bright.g, bright.b, bright.a)
+    );
+
+    // A dark line on a page that gets darker to the right, past any single threshold
+    let shaded = Image::from_fn(30, 9, |x, y| {
+        let light = 250 - x as u8 * 5;
+        if y == 4 {
+            Color::rgb(light - 60, light - 60, light - 60)
+        } else {
+            Color::rgb(light, light, light)
+        }
+    });
+    let mut adaptive = shaded.clone();
+    filter::adaptive_threshold(&mut adaptive, 7, 10.0).unwrap();
+    for x in 0..30 {
+        assert_eq!(0, adaptive.get_pixel(x, 4).unwrap().r);
+        assert_eq!(255, adaptive.get_pixel(x, 0).unwrap().r);
+    }
+    let mut global = shaded.clone();
+    filter::threshold(&mut global, 150).unwrap();
+    assert_eq!(0, global.get_pixel(29, 0).unwrap().r);
+}
+
+#[test]
+fn posterize_solarize() {
+    let ramp = Image::from_fn(256, 1, |x, _| Color::rgba(x as u8, 255 - x as u8, 0, 77));
+    let mut poster = ramp.clone();
+    filter::posterize(&mut poster, 4).unwrap();
+    let mut values: Vec<u8> = (0..256)
+        .map(|x| poster.get_pixel(x, 0).unwrap().r)
+        .collect();
+    values.dedup();
+    assert_eq!(vec![0, 85, 170, 255], values);
+    assert_eq!(77, poster.get_pixel(10, 0).unwrap().a);
+
+    let mut same = ramp.clone();
+    filter::posterize(&mut same, 1).unwrap();
+    assert_eq!(ramp.bytes, same.bytes);
+
+    let mut solar = ramp.clone();
+    filter::solarize(&mut solar, 127).unwrap();
+    let pixel = solar.get_pixel(200, 0).unwrap();
+    assert_eq!((55, 55, 0, 77), (pixel.r, pixel.g, pixel.b, pixel.a));
+    let mut none = ramp.clone();
+    filter::solarize(&mut none, 255).unwrap();
+    assert_eq!(ramp.bytes, none.bytes);
+}
+
+#[test]
+fn pixelate_region() {
+    let stripes = Image::from_fn(8, 8, |x, _| {
+        if x % 2 == 0 {
+            Color::rgb(200, 0, 0)
+        } else {
+            Color::rgb(0, 0, 100)
+        }
+    });
+    let mut whole = stripes.clone();
+    filter::pixelate(&mut whole, 4, None).unwrap();
+    for &(x, y) in &[(0, 0), (3, 1), (7, 7)] {
+        let pixel = whole.get_pixel(x, y).unwrap();
+        assert_eq!((100, 0, 50, 255), (pixel.r, pixel.g, pixel.b, pixel.a));
+    }
+
+    // Only the region changes, with blocks from its corner and cut at the image edge
+    let mut part = stripes.clone();
+    filter::pixelate(&mut part, 2, Some((5, 1, 10, 2))).unwrap();
+    assert_eq!(200, part.get_pixel(4, 1).unwrap().r);
+    assert_eq!(100, part.get_pixel(5, 1).unwrap().r);
+    assert_eq!(200, part.get_pixel(6, 0).unwrap().r);
+    assert_eq!(0, part.get_pixel(7, 2).unwrap().r);
+    assert_eq!(100, part.get_pixel(7, 2).unwrap().b);
+
+    let mut same = stripes.clone();
+    filter::pixelate(&mut same, 1, None).unwrap();
+    filter::pixelate(&mut same, 4, Some((20, 20, 4, 4))).unwrap();
+    assert_eq!(stripes.bytes, same.bytes);
+}
+
+#[test]
+fn oil_paint_kuwahara() {
+    // A step stays sharp
+    let step = Image::from_fn(10, 10, |x, _| {
+        if x < 5 {
+            Color::black()
+        } else {
+            Color::white()
+        }
+    });
+    let mut kuwahara = step.clone();
+    filter::kuwahara(&mut kuwahara, 2).unwrap();
+    assert_eq!(step.bytes, kuwahara.bytes);
+    let mut oil = step.clone();
+    filter::oil_paint(&mut oil, 2, 8).unwrap();
+    assert_eq!(step.bytes, oil.bytes);
+
+    // A speck on a flat color is painted over
+    let mut speck = Image::filled(9, 9, Color::rgba(180, 40, 40, 200));
+    speck.set_pixel(4, 4, Color::rgb(20, 20, 240)).unwrap();
+    let mut oil = speck.clone();
+    filter::oil_paint(&mut oil, 1, 10).unwrap();
+    let pixel = oil.get_pixel(4, 4).unwrap();
+    assert_eq!((180, 40, 40, 255), (pixel.r, pixel.g, pixel.b, pixel.a));
+    let mut kuwahara = speck.clone();
+    filter::kuwahara(&mut kuwahara, 2).unwrap();
+    let pixel = kuwahara.get_pixel(3, 3).unwrap();
+    assert_eq!((180, 40, 40, 200), (pixel.r, pixel.g, pixel.b, pixel.a));
+
+    let mut same = speck.clone();
+    filter::oil_paint(&mut same, 3, 1).unwrap();
+    filter::kuwahara(&mut same, 0).unwrap();
+    assert_eq!(speck.bytes, same.bytes);
+}
+
+#[test]
+fn vignette_drop_shadow() {
+    let mut image = Image::filled(21, 11, Color::rgba(200, 200, 200, 90));
+    filter::vignette(&mut image, 1.0, 0.4).unwrap();
+    assert_eq!(200, image.get_pixel(10, 5).unwrap().r);
+    assert_eq!(0, image.get_pixel(20, 10).unwrap().r);
+    let between = image.get_pixel(17, 8).unwrap();
+    assert!(between.r > 0 && between.r < 200);
+    assert_eq!(90, between.a);
+
+    // A shadow up and to the left, softened past the edges of the shape
+    let mut card = Image::filled(8, 6, Color::white());
+    editor::drop_shadow(&mut card, -3, -2, 1.0, Color::black()).unwrap();
+    assert_eq!((14, 12), (card.width, card.height));
+    let pixel = card.get_pixel(6, 5).unwrap();
+    assert_eq!((255, 255), (pixel.r, pixel.a));
+    let shadow = card.get_pixel(4, 4).unwrap();
+    assert!(shadow.r == 0 && shadow.a > 128);
+    let soft = card.get_pixel(1, 2).unwrap().a;
+    assert!(soft > 0 && soft < shadow.a);
+    assert_eq!(0, card.get_pixel(13, 0).unwrap().a);
+}
+
+#[test]
+fn sepia_duotone() {
+    let ramp = Image::from_fn(256, 1, |x, _| Color::rgba(x as u8, x as u8, x as u8, 60));
+    let mut duo = ramp.clone();
+    filter::duotone(&mut duo, Color::rgb(0, 0, 100), Color::rgb(250, 200, 100)).unwrap();
+    let dark = duo.get_pixel(0, 0).unwrap();
+    let middle = duo.get_pixel(51, 0).unwrap();
+    let light = duo.get_pixel(255, 0).unwrap();
+    assert_eq!((0, 0, 100, 60), (dark.r, dark.g, dark.b, dark.a));
+    assert_eq!((50, 40, 100), (middle.r, middle.g, middle.b));
+    assert_eq!((250, 200, 100), (light.r, light.g, light.b));
+
+    let mut one = ramp.clone();
+    filter::gradient_map(&mut one, &[Color::green()]).unwrap();
+    assert_eq!(255, one.get_pixel(9, 0).unwrap().g);
+    let mut same = ramp.clone();
+    filter::gradient_map(&mut same, &[]).unwrap();
+    assert_eq!(ramp.bytes, same.bytes);
+
+    let mut sepia = Image::filled(1, 1, Color::rgb(100, 100, 100));
+    filter::sepia(&mut sepia).unwrap();
+    let pixel = sepia.get_pixel(0, 0).unwrap();
+    assert_eq!((135, 120, 94), (pixel.r, pixel.g, pixel.b));
+    assert!(pixel.r > pixel.g && pixel.g > pixel.b);
+}
+
+#[test]
+fn hsl_adjust_channels() {
+    let image = Image::from_fn(3, 1, |x, _| {
+        [
+            Color::rgba(200, 50, 50, 70),
+            Color::rgb(128, 128, 128),
+            Color::blue(),
+        ][x as usize]
+            .clone()
+    });
+    let mut same = image.clone();
+    filter::hsl_adjust(&mut same, 360.0, 1.0, 0.0).unwrap();
+    assert_eq!(image.bytes, same.bytes);
+
+    let mut gray = image.clone();
+    filter::hsl_adjust(&mut gray, 0.0, 0.0, 0.0).unwrap();
+    let pixel = gray.get_pixel(0, 0).unwrap();
+    assert_eq!((125, 125, 125, 70), (pixel.r, pixel.g, pixel.b, pixel.a));
+
+    let mut lighter = image.clone();
+    filter::hsl_adjust(&mut lighter, -240.0, 1.0, 25.0).unwrap();
+    let pixel = lighter.get_pixel(2, 0).unwrap();
+    assert_eq!((255, 128, 128), (pixel.r, pixel.g, pixel.b));
+    assert_eq!(192, lighter.get_pixel(1, 0).unwrap().r);
+}
+
+#[test]
+fn brightness_contrast_pivot() {
+    let ramp = Image::from_fn(256, 1, |x, _| Color::rgba(x as u8, x as u8, x as u8, 33));
+    let mut same = ramp.clone();
+    filter::brightness_contrast(&mut same, 0, 1.0).unwrap();
+    assert_eq!(ramp.bytes, same.bytes);
+
+    let mut flat = ramp.clone();
+    filter::brightness_contrast(&mut flat, -28, 0.0).unwrap();
+    for x in 0..256 {
+        let pixel = flat.get_pixel(x, 0).unwrap();
+        assert_eq!((100, 33), (pixel.r, pixel.a));
+    }
+
+    // The pivot stays put and the rest is clamped
+    let mut strong = ramp.clone();
+    filter::brightness_contrast_with(&mut strong, 0, 3.0, 200).unwrap();
+    assert_eq!(200, strong.get_pixel(200, 0).unwrap().r);
+    assert_eq!(230, strong.get_pixel(210, 0).unwrap().r);
+    assert_eq!(255, strong.get_pixel(250, 0).unwrap().r);
+    assert_eq!(0, strong.get_pixel(100, 0).unwrap().r);
+}
+
+#[test]
+fn gamma_table_linear() {
+    let ramp = Image::from_fn(256, 1, |x, _| Color::rgba(x as u8, 0, 255, x as u8));
+    let mut image = ramp.clone();
+    filter::gamma(&mut image, 2.0).unwrap();
+    for x in 0..256 {
+        let pixel = image.get_pixel(x, 0).unwrap();
+        let expected = ((x as f64 / 255.0).powf(0.5) * 255.0).round() as u8;
+        assert_eq!(
+            (expected, 0, 255, x as u8),
+            (pixel.r, pixel.g, pixel.b, pixel.a)
+        );
+    }
+    assert!(filter::gamma(&mut image, 10.0).is_err());
+    assert!(filter::gamma(&mut image, f64::NAN).is_err());
+
+    // Above 1 lightens and below 1 darkens, the same way as levels
+    let gray = Image::filled(1, 1, Color::rgb(64, 64, 64));
+    let mut light = gray.clone();
+    filter::gamma(&mut light, 2.2).unwrap();
+    assert!(light.get_pixel(0, 0).unwrap().r > 64);
+    let mut leveled = gray.clone();
+    filter::levels(&mut leveled, 0, 255, 2.2, 0, 255).unwrap();
+    assert_eq!(leveled.bytes, light.bytes);
+    let mut dark = gray.clone();
+    filter::gamma(&mut dark, 0.5).unwrap();
+    assert_eq!(16, dark.get_pixel(0, 0).unwrap().r);
+
+    // Every 8-bit value survives the trip through linear light
+    for value in 0..=255u8 {
+        assert_eq!(value, filter::linear_to_srgb(filter::srgb_to_linear(value)));
+    }
+    assert_eq!(0, filter::linear_to_srgb(-1.0));
+    assert_eq!(255, filter::linear_to_srgb(2.0));
+}
+
+#[test]
+fn levels_points() {
+    let ramp = Image::from_fn(256, 1, |x, _| Color::rgba(x as u8, x as u8, x as u8, 40));
+    let mut same = ramp.clone();
+    filter::levels(&mut same, 0, 255, 1.0, 0, 255).unwrap();
+    assert_eq!(ramp.bytes, same.bytes);
+
+    let mut image = ramp.clone();
+    filter::levels(&mut image, 50, 150, 2.0, 20, 220).unwrap();
+    let value = |image: &Image, x: i32| image.get_pixel(x, 0).unwrap().r;
+    assert_eq!(20, value(&image, 0));
+    assert_eq!(20, value(&image, 50));
+    assert_eq!(220, value(&image, 150));
+    assert_eq!(220, value(&image, 255));
+    // Gamma lifts the middle of the input range above the middle of the output
+    assert_eq!(161, value(&image, 100));
+    assert_eq!(40, image.get_pixel(100, 0).unwrap().a);
+
+    let red = Levels {
+        out_black: 255,
+        out_white: 0,
+        ..Levels::default()
+    };
+    let mut inverted = ramp.clone();
+    filter::levels_channels(&mut inverted, &red, &Levels::default(), &Levels::default()).unwrap();
+    let pixel = inverted.get_pixel(55, 0).unwrap();
+    assert_eq!((200, 55, 55), (pixel.r, pixel.g, pixel.b));
+
+    let bad = Levels {
+        gamma: 0.0,
+        ..Levels::default()
+    };
+    let mut untouched = ramp.clone();
+    assert!(filter::levels_channels(&mut untouched, &Levels::default(), &bad, &bad).is_err());
+    assert_eq!(ramp.bytes, untouched.bytes);
+}
+
+#[test]
+fn curves_monotone() {
+    let ramp = Image::from_fn(256, 1, |x, _| Color::rgba(x as u8, x as u8, x as u8, 90));
+    let mut same = ramp.clone();
+    filter::curves(&mut same, &[(255, 255), (0, 0)]).unwrap();
+    filter::curves(&mut same, &[]).unwrap();
+    assert_eq!(ramp.bytes, same.bytes);
+
+    // A steep step between flat parts stays rising and inside the points
+    let mut image = ramp.clone();
+    filter::curves(&mut image, &[(40, 30), (100, 40), (110, 200), (200, 210)]).unwrap();
+    let values: Vec<u8> = (0..256).map(|x| image.get_pixel(x, 0).unwrap().r).collect();
+    assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(
+        (30, 30, 40, 200, 210, 210),
+        (
+            values[0],
+            values[40],
+            values[100],
+            values[110],
+            values[200],
+            values[255]
+        )
+    );
+    assert!(values[100..=110].iter().all(|&v| (40..=200).contains(&v)));
+    assert_eq!(90, image.get_pixel(5, 0).unwrap().a);
+
+    // The last of points with the same input wins, and falling curves work too
+    let mut invert = ramp.clone();
+    filter::curves_channels(&mut invert, &[(0, 9), (0, 255), (255, 0)], &[], &[]).unwrap();
+    let pixel = invert.get_pixel(60, 0).unwrap();
+    assert_eq!((195, 60, 60), (pixel.r, pixel.g, pixel.b));
+}
+
+#[test]
+fn exposure_white_balance() {
+    let ramp = Image::from_fn(256, 1, |x, _| Color::rgba(x as u8, 255 - x as u8, 30, 120));
+    let mut same = ramp.clone();
+    filter::exposure(&mut same, 0.0).unwrap();
+    filter::white_balance(&mut same, 0.0, 0.0).unwrap();
+    assert_eq!(ramp.bytes, same.bytes);
+
+    // A stop up and back down again, in linear light
+    let mut image = Image::filled(1, 1, Color::rgba(60, 120, 180, 200));
+    filter::exposure(&mut image, 1.0).unwrap();
+    let brighter = image.get_pixel(0, 0).unwrap();
+    assert!((brighter.r as f32 / 60.0) < 2.0 && brighter.r > 60);
+    assert_eq!(200, brighter.a);
+    filter::exposure(&mut image, -1.0).unwrap();
+    let pixel = image.get_pixel(0, 0).unwrap();
+    assert_eq!((60, 120, 180), (pixel.r, pixel.g, pixel.b));
+
+    let mut warm = Image::filled(1, 1, Color::rgb(128, 128, 128));
+    filter::white_balance(&mut warm, 0.5, 0.5).unwrap();
+    let pixel = warm.get_pixel(0, 0).unwrap();
+    assert!(pixel.r > 128 && pixel.g < 128 && pixel.b < 128);
+    assert_eq!(pixel.g, pixel.b);
+
+    // A transparent pixel of another color doesn't count
+    let mut cast = Image::filled(2, 1, Color::rgb(120, 110, 150));
+    cast.set_pixel(1, 0, Color::rgba(255, 0, 0, 0)).unwrap();
+    filter::auto_white_balance(&mut cast).unwrap();
+    let pixel = cast.get_pixel(0, 0).unwrap();
+    assert_eq!((110, 110, 110), (pixel.r, pixel.g, pixel.b));
+
+    let mut black = Image::filled(2, 2, Color::black());
+    assert_eq!(
+        [1.0, 1.0, 1.0],
+        filter::auto_white_balance(&mut black).unwrap()
+    );
+}
@@ -0,0 +1,637 @@
+extern crate raster;
+
+use raster::buffer::{Gray8, GrayA8, Rgb8, Rgba16, Rgba8, RgbaF32};
+use raster::endec::EncodeOptions;
+use raster::error::RasterError;
+use raster::{
+    filter, ChannelOrder, Color, HdrImage, Image, ImageBuffer, ImageFormat, ImageRef, ImageRefMut,
+    ToneMap,
+};
+
+#[test]
+fn view_bounds() {
+    let mut image = Image::blank(4, 3);
+
+    // Windows reaching the edges fit, and so do empty ones
+    assert_eq!(4, image.view(0, 0, 4, 3).unwrap().width());
+    assert_eq!(0, image.view(4, 3, 0, 0).unwrap().height());
+
+    for &(x, y, width, height) in &[
+        (-1, 0, 2, 2),
+        (0, 0, 5, 3),
+        (2, 2, 2, 2),
+        (0, 0, -1, 1),
+        (1, 1, i32::MAX, 1),
+        (1, 1, 1, i32::MAX),
+    ] {
+        for result in &[
+            image.view(x, y, width, height).map(|_| ()),
+            image.view_mut(x, y, width, height).map(|_| ()),
+        ] {
+            match *result {
+                Err(RasterError::PixelOutOfBounds(_, _)) => {}
+                ref other => panic!("{} {} {} {}: {:?}", x, y, width, height, other),
+            }
+        }
+    }
+}
+
+#[test]
+fn hdr_buffer() {
+    // Float buffers hold linear light, so 8-bit sRGB is decoded going in and encoded coming out
+    let image = Image::from_fn(3, 1, |x, _| Color::rgba(x as u8 * 100, 0, 255, 128));
+    let hdr = HdrImage::from_image(&image);
+    let linear = filter::srgb_to_linear(200);
+    assert!((hdr.samples[8] - linear).abs() < 1e-6);
+    assert!((hdr.samples[11] - 128.0 / 255.0).abs() < 1e-6);
+    assert_eq!(image.bytes, hdr.to_image().bytes);
+    assert_eq!(image.bytes, hdr.tone_map(ToneMap::Clamp).bytes);
+
+    // Values past 1.0 survive converting between float buffers
+    let bright = HdrImage::new(1, 1, vec![4.0, 0.5, 0.0, 1.0]).unwrap();
+    let copy = bright.convert::<RgbaF32>();
+    for (a, b) in bright.samples.iter().zip(&copy.samples) {
+        assert!((a - b).abs() < 1e-4);
+    }
+    assert_eq!(vec![255, 188, 0, 255], bright.to_image().bytes);
+}
+
+#[test]
+fn raw_round_trip() {
+    let bytes: Vec<u8> = (0..24).collect();
+    let image = Image::from_raw(3, 2, bytes.clone()).unwrap();
+    assert_eq!(12, image.get_pixel(0, 1).unwrap().r);
+    assert_eq!(bytes, image.into_raw());
+
+    // A single column
+    let column = Image::from_raw(1, 3, (0..12).collect()).unwrap();
+    assert_eq!(8, column.get_pixel(0, 2).unwrap().r);
+    assert_eq!(3, column.rows().len());
+
+    // Empty images have no bytes
+    assert!(Image::from_raw(0, 0, Vec::new())
+        .unwrap()
+        .into_raw()
+        .is_empty());
+    assert!(Image::from_raw(0, 5, Vec::new()).is_ok());
+
+    for &(width, height, len) in &[(3, 2, 20), (-1, -4, 16), (i32::MAX, i32::MAX, 0)] {
+        match Image::from_raw(width, height, vec![0; len]) {
+            Err(RasterError::SizeMismatch(w, h)) => assert_eq!((width, height), (w, h)),
+            other => panic!("{} {}: {:?}", width, height, other.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn channel_orders() {
+    let image = Image::from_fn(3, 1, |x, _| Color::rgba(x as u8, 10, 20 + x as u8, 30));
+    let bgra = image.to_bgra();
+    assert_eq!(vec![22, 10, 2, 30], bgra[8..].to_vec());
+    assert_eq!(image.bytes, Image::from_bgra(3, 1, bgra).unwrap().bytes);
+    assert!(Image::from_bgra(0, 0, Vec::new()).unwrap().bytes.is_empty());
+    match Image::from_bgra(2, 1, vec![0; 4]) {
+        Err(RasterError::SizeMismatch(2, 1)) => {}
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+
+    // Every order swaps back to where it started
+    let orders = [
+        ChannelOrder::Rgba,
+        ChannelOrder::Bgra,
+        ChannelOrder::Argb,
+        ChannelOrder::Abgr,
+    ];
+    for &from in &orders {
+        for &to in &orders {
+            let mut swapped = image.clone();
+            swapped.swap_channels(from, to);
+            swapped.swap_channels(to, from);
+            assert_eq!(image.bytes, swapped.bytes, "{:?} {:?}", from, to);
+        }
+    }
+    let mut argb = image.clone();
+    argb.swap_channels(ChannelOrder::Rgba, ChannelOrder::Argb);
+    assert_eq!(vec![30, 2, 10, 22], argb.bytes[8..].to_vec());
+}
+
+#[test]
+fn channels_round_trip() {
+    let image = Image::from_fn(1, 3, |_, y| Color::rgba(y as u8, 50, 100, 150 + y as u8));
+    let [r, g, b, a] = image.split_channels();
+    assert_eq!(vec![0, 1, 2], r);
+    assert_eq!(vec![150, 151, 152], a);
+    let joined = Image::from_channels(1, 3, &r, &g, &b, &a).unwrap();
+    assert_eq!(image.bytes, joined.bytes);
+
+    let [r, g, b, a] = Image::blank(0, 0).split_channels();
+    assert!(r.is_empty() && g.is_empty() && b.is_empty() && a.is_empty());
+    assert!(Image::from_channels(0, 0, &[], &[], &[], &[]).is_ok());
+
+    // Every channel has to fit
+    match Image::from_channels(1, 3, &r, &g, &b, &a) {
+        Err(RasterError::SizeMismatch(1, 3)) => {}
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    let short = [0, 0];
+    match Image::from_channels(1, 3, &[0; 3], &[0; 3], &[0; 3], &short) {
+        Err(RasterError::SizeMismatch(1, 3)) => {}
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn pixel_access() {
+    let rgba = |color: Color| (color.r, color.g, color.b, color.a);
+    let mut image = Image::from_fn(3, 2, |x, y| Color::rgba(x as u8, y as u8, 7, 255));
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let color = image.get_pixel(x, y).unwrap();
+        assert_eq!((x as u8, y as u8, 7, 255), rgba(color.clone()));
+        assert_eq!(rgba(color), rgba(image.get_pixel_unchecked(x, y)));
+        assert_eq!(&[x as u8, y as u8, 7, 255], pixel);
+    }
+
+    image.set_pixel(0, 1, Color::rgba(1, 2, 3, 4)).unwrap();
+    image.set_pixel_unchecked(2, 1, Color::rgba(5, 6, 7, 8));
+    assert_eq!(vec![1, 2, 3, 4], image.bytes[12..16].to_vec());
+    assert_eq!(vec![5, 6, 7, 8], image.bytes[20..24].to_vec());
+    assert_eq!((5, 6, 7, 8), rgba(image.get_pixel(2, 1).unwrap()));
+
+    for &(x, y) in &[(0, 2), (-1, 0), (0, -1)] {
+        match image.get_pixel(x, y) {
+            Err(RasterError::PixelOutOfBounds(a, b)) => assert_eq!((x, y), (a, b)),
+            other => panic!("{} {}: {:?}", x, y, other),
+        }
+    }
+    for &(x, y) in &[(3, 0), (0, 2)] {
+        match image.set_pixel(x, y, Color::red()) {
+            Err(RasterError::PixelOutOfBounds(a, b)) => assert_eq!((x, y), (a, b)),
+            other => panic!("{} {}: {:?}", x, y, other),
+        }
+    }
+    assert!(image.set_pixel(-1, 0, Color::red()).is_err());
+
+    // A single column, and an image without pixels
+    let mut column = Image::blank(1, 4);
+    column.set_pixel(0, 3, Color::blue()).unwrap();
+    assert_eq!(255, column.get_pixel_unchecked(0, 3).b);
+    assert!(column.get_pixel(0, 4).is_err());
+    assert!(Image::blank(0, 0).get_pixel(0, 0).is_err());
+    assert!(Image::blank(0, 0).set_pixel(0, 0, Color::red()).is_err());
+}
+
+#[test]
+#[should_panic]
+fn get_pixel_unchecked_out_of_bounds() {
+    Image::blank(2, 2).get_pixel_unchecked(0, 2);
+}
+
+#[test]
+#[should_panic]
+fn set_pixel_unchecked_out_of_bounds() {
+    Image::blank(2, 2).set_pixel_unchecked(1, 2, Color::red());
+}
+
+#[test]
+fn pixel_iterators() {
+    let mut image = Image::from_fn(3, 2, |x, y| Color::rgba(x as u8, y as u8, 0, 255));
+    assert_eq!(6, image.pixels().len());
+    assert_eq!(vec![2, 1, 0, 255], image.pixels().last().unwrap().to_vec());
+
+    // Rows run top to bottom, and pixels left to right within them
+    let rows: Vec<&[u8]> = image.rows().collect();
+    assert_eq!(2, rows.len());
+    assert_eq!(image.bytes[12..], *rows[1]);
+    let coordinates: Vec<(i32, i32)> = image.enumerate_pixels().map(|(x, y, _)| (x, y)).collect();
+    assert_eq!(
+        vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)],
+        coordinates
+    );
+    for (x, y, pixel) in image.enumerate_pixels() {
+        assert_eq!(&[x as u8, y as u8], &pixel[..2]);
+    }
+
+    for pixel in image.pixels_mut() {
+        pixel[2] = pixel[0] + pixel[1];
+    }
+    assert_eq!(3, image.get_pixel(2, 1).unwrap().b);
+
+    // A single column has one pixel per row
+    let column = Image::from_fn(1, 4, |_, y| Color::rgb(y as u8, 0, 0));
+    assert_eq!(4, column.rows().len());
+    assert!(column.rows().all(|row| row.len() == 4));
+    assert_eq!(
+        vec![(0, 3)],
+        column
+            .enumerate_pixels()
+            .filter(|&(_, _, pixel)| pixel[0] == 3)
+            .map(|(x, y, _)| (x, y))
+            .collect::<Vec<_>>()
+    );
+
+    let mut empty = Image::blank(0, 0);
+    assert_eq!(0, empty.pixels().len());
+    assert_eq!(0, empty.pixels_mut().len());
+    assert_eq!(0, empty.rows().len());
+    assert_eq!(0, empty.enumerate_pixels().count());
+}
+
+#[test]
+fn view_pixels() {
+    let image = Image::from_fn(5, 4, |x, y| Color::rgba(x as u8, y as u8, 0, 255));
+    let view = image.view(1, 2, 3, 2).unwrap();
+    assert_eq!((3, 2), (view.width(), view.height()));
+    assert_eq!((2, 3), {
+        let color = view.get_pixel(1, 1).unwrap();
+        (color.r, color.g)
+    });
+    match view.get_pixel(3, 0) {
+        Err(RasterError::PixelOutOfBounds(3, 0)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(view.get_pixel(0, -1).is_err());
+
+    // Only the window is read, row by row
+    let copy = view.to_image();
+    assert_eq!((3, 2), (copy.width, copy.height));
+    assert_eq!(6, view.pixels().count());
+    for (pixel, copied) in view.pixels().zip(copy.pixels()) {
+        assert_eq!(pixel, copied);
+    }
+    assert_eq!(
+        vec![1, 2, 0, 255],
+        view.rows().next().unwrap()[..4].to_vec()
+    );
+    let png = view
+        .to_bytes(ImageFormat::Png, &EncodeOptions::default())
+        .unwrap();
+    assert_eq!(
+        copy.bytes,
+        Image::from_bytes(ImageFormat::Png, &png).unwrap().bytes
+    );
+
+    // Views of single columns and empty views
+    let column = Image::from_fn(1, 5, |_, y| Color::rgb(y as u8, 0, 0));
+    let view = column.view(0, 1, 1, 3).unwrap();
+    let reds: Vec<u8> = view.pixels().map(|pixel| pixel[0]).collect();
+    assert_eq!(vec![1, 2, 3], reds);
+    let empty = image.view(5, 4, 0, 0).unwrap();
+    assert_eq!(0, empty.pixels().count());
+    assert!(empty.to_image().bytes.is_empty());
+}
+
+#[test]
+fn view_writeback() {
+    let source = Image::from_fn(4, 3, |x, y| Color::rgba(x as u8, y as u8, 0, 255));
+
+    // Pixels and rows are changed in place
+    let mut image = source.clone();
+    {
+        let mut view = image.view_mut(1, 1, 2, 2).unwrap();
+        view.set_pixel(1, 1, Color::red()).unwrap();
+        assert!(view.set_pixel(2, 0, Color::red()).is_err());
+        for row in view.rows_mut() {
+            row[3] = 9;
+        }
+        assert_eq!(9, view.get_pixel(0, 1).unwrap().a);
+    }
+    assert_eq!(255, image.get_pixel(2, 2).unwrap().r);
+    assert_eq!(9, image.get_pixel(1, 1).unwrap().a);
+    assert_eq!(9, image.get_pixel(1, 2).unwrap().a);
+    let changed: Vec<(i32, i32)> = image
+        .enumerate_pixels()
+        .zip(source.pixels())
+        .filter(|&((_, _, pixel), original)| pixel != original)
+        .map(|((x, y, _), _)| (x, y))
+        .collect();
+    assert_eq!(vec![(1, 1), (1, 2), (2, 2)], changed);
+
+    // `apply` writes the copy back over the window only
+    let mut image = source.clone();
+    image
+        .view_mut(2, 0, 2, 3)
+        .unwrap()
+        .apply(|tile| {
+            assert_eq!((2, 3), (tile.width, tile.height));
+            for pixel in tile.pixels_mut() {
+                pixel[2] = 200;
+            }
+            Ok(())
+        })
+        .unwrap();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        assert_eq!(if x >= 2 { 200 } else { 0 }, pixel[2], "{} {}", x, y);
+    }
+
+    // Nothing is written back when the function fails
+    let mut image = source.clone();
+    let result = image.view_mut(0, 0, 2, 2).unwrap().apply(|tile| {
+        tile.bytes[0] = 100;
+        Err(RasterError::Unexpected)
+    });
+    assert!(result.is_err());
+    assert_eq!(source.bytes, image.bytes);
+
+    // A smaller result only covers part of the window
+    let mut image = source.clone();
+    image
+        .view_mut(0, 0, 4, 3)
+        .unwrap()
+        .apply(|tile| {
+            *tile = Image::filled(1, 1, Color::white());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(255, image.get_pixel(0, 0).unwrap().b);
+    assert_eq!(source.bytes[4..], image.bytes[4..]);
+
+    // Single columns
+    let mut column = Image::filled(1, 4, Color::white());
+    column
+        .view_mut(0, 1, 1, 2)
+        .unwrap()
+        .apply(|tile| filter::brightness(tile, 0.5))
+        .unwrap();
+    let dimmed: Vec<bool> = column.pixels().map(|pixel| pixel[0] < 255).collect();
+    assert_eq!(vec![false, true, true, false], dimmed);
+}
+
+#[test]
+fn buffer_conversions() {
+    let image = Image::from_fn(4, 3, |x, y| {
+        Color::rgba(x as u8 * 60, y as u8 * 100, 30, 255 - x as u8)
+    });
+
+    // Formats with every channel at 8 bits or more convert back to the same bytes
+    assert_eq!(
+        image.bytes,
+        ImageBuffer::<Rgba8>::from_image(&image).to_image().bytes
+    );
+    let deep = ImageBuffer::<Rgba16>::from_image(&image);
+    assert_eq!(4 * 3 * 4, deep.samples.len());
+    assert_eq!(image.bytes[5] as u16 * 257, deep.samples[5]);
+    assert_eq!(image.bytes, deep.to_image().bytes);
+    assert_eq!(
+        image.bytes,
+        deep.convert::<RgbaF32>().convert::<Rgba8>().samples
+    );
+
+    // Rgb8 drops alpha, and gray formats keep the luma
+    let rgb = ImageBuffer::<Rgb8>::from_image(&image);
+    assert_eq!(image.bytes[4..7].to_vec(), rgb.samples[3..6].to_vec());
+    assert!(rgb.to_image().pixels().all(|pixel| pixel[3] == 255));
+    let gray = ImageBuffer::<GrayA8>::from_image(&image);
+    assert_eq!(2 * 4 * 3, gray.samples.len());
+    assert_eq!(
+        image.pixels().map(|pixel| pixel[3]).collect::<Vec<_>>(),
+        gray.samples
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .cloned()
+            .collect::<Vec<_>>()
+    );
+    let mut grayed = image.clone();
+    filter::grayscale(&mut grayed).unwrap();
+    let gray = ImageBuffer::<Gray8>::from_image(&grayed);
+    assert_eq!(gray, ImageBuffer::<Gray8>::from_image(&gray.to_image()));
+
+    // Single columns and empty buffers
+    let column = ImageBuffer::<Gray8>::new(1, 3, vec![0, 128, 255]).unwrap();
+    assert_eq!(
+        vec![128, 128, 128, 255],
+        column.to_image().bytes[4..8].to_vec()
+    );
+    assert_eq!(3, column.convert::<Rgb8>().samples.len() / 3);
+    let empty = ImageBuffer::<Rgba16>::new(0, 0, Vec::new()).unwrap();
+    assert!(empty.convert::<Gray8>().samples.is_empty());
+    assert!(empty.to_image().bytes.is_empty());
+    assert!(ImageBuffer::<Rgb8>::blank(0, 0).samples.is_empty());
+
+    for &(width, height, len) in &[(2, 2, 3), (2, 2, 5), (-1, -3, 3), (i32::MAX, i32::MAX, 0)] {
+        match ImageBuffer::<Gray8>::new(width, height, vec![0; len]) {
+            Err(RasterError::SizeMismatch(w, h)) => assert_eq!((width, height), (w, h)),
+            other => panic!("{} {}: {:?}", width, height, other),
+        }
+    }
+}
+
+#[test]
+fn borrowed_images() {
+    let image = Image::from_fn(3, 2, |x, y| Color::rgba(x as u8, y as u8, 5, 255));
+    let frame = image.bytes.clone();
+    let borrowed = ImageRef::new(3, 2, &frame).unwrap();
+    assert_eq!((3, 2), (borrowed.width(), borrowed.height()));
+    assert_eq!(2, borrowed.get_pixel(2, 1).unwrap().r);
+    assert!(borrowed.get_pixel(3, 0).is_err());
+    assert_eq!(image.bytes, borrowed.to_image().bytes);
+    assert_eq!(6, borrowed.pixels().count());
+    assert_eq!(image.bytes[12..], *borrowed.rows().nth(1).unwrap());
+    assert_eq!(
+        1,
+        borrowed
+            .view(1, 1, 2, 1)
+            .unwrap()
+            .get_pixel(0, 0)
+            .unwrap()
+            .r
+    );
+    let png = borrowed
+        .to_bytes(ImageFormat::Png, &EncodeOptions::default())
+        .unwrap();
+    assert_eq!(
+        image.bytes,
+        Image::from_bytes(ImageFormat::Png, &png).unwrap().bytes
+    );
+
+    for &(width, height, len) in &[(3, 2, 20), (3, 2, 28), (-1, -2, 8), (i32::MAX, 2, 0)] {
+        let mut bytes = vec![0; len];
+        match ImageRef::new(width, height, &bytes) {
+            Err(RasterError::SizeMismatch(w, h)) => assert_eq!((width, height), (w, h)),
+            other => panic!("{} {}: {:?}", width, height, other),
+        }
+        match ImageRefMut::new(width, height, &mut bytes) {
+            Err(RasterError::SizeMismatch(w, h)) => assert_eq!((width, height), (w, h)),
+            other => panic!("{} {}: {:?}", width, height, other),
+        }
+    }
+    assert_eq!(0, ImageRef::new(0, 0, &[]).unwrap().pixels().count());
+    assert!(ImageRefMut::new(0, 0, &mut []).is_ok());
+
+    // Changes land in the borrowed bytes
+    let mut frame = vec![0; 4 * 4];
+    {
+        let mut column = ImageRefMut::new(1, 4, &mut frame).unwrap();
+        column.set_pixel(0, 3, Color::red()).unwrap();
+        assert!(column.set_pixel(1, 0, Color::red()).is_err());
+        for pixel in column.pixels_mut() {
+            pixel[3] = 255;
+        }
+        column
+            .view_mut(0, 1, 1, 2)
+            .unwrap()
+            .set_pixel(0, 0, Color::blue())
+            .unwrap();
+        assert_eq!(255, column.get_pixel(0, 1).unwrap().b);
+    }
+    assert_eq!(vec![0, 0, 0, 255], frame[..4].to_vec());
+    assert_eq!(vec![0, 0, 255, 255], frame[4..8].to_vec());
+    assert_eq!(vec![255, 0, 0, 255], frame[12..].to_vec());
+
+    // `apply` writes its copy back, unless it fails
+    let mut frame = image.bytes.clone();
+    let mut borrowed = ImageRefMut::new(3, 2, &mut frame).unwrap();
+    let result = borrowed.apply(|copy| {
+        copy.bytes[0] = 99;
+        Err(RasterError::Unexpected)
+    });
+    assert!(result.is_err());
+    borrowed
+        .apply(|copy| {
+            for pixel in copy.pixels_mut() {
+                pixel[2] = 200;
+            }
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(0, frame[0]);
+    assert_eq!(vec![1, 0, 200, 255], frame[4..8].to_vec());
+    assert!(frame.chunks_exact(4).all(|pixel| pixel[2] == 200));
+}
+
+#[test]
+fn constructors() {
+    let blank = Image::blank(3, 2);
+    assert_eq!((3, 2, 24), (blank.width, blank.height, blank.bytes.len()));
+    assert!(blank.pixels().all(|pixel| pixel == [0, 0, 0, 255]));
+
+    let filled = Image::filled(2, 3, Color::rgba(1, 2, 3, 4));
+    assert_eq!(
+        (2, 3, 24),
+        (filled.width, filled.height, filled.bytes.len())
+    );
+    assert!(filled.pixels().all(|pixel| pixel == [1, 2, 3, 4]));
+
+    // The function is called row by row
+    let mut calls = Vec::new();
+    let image = Image::from_fn(2, 2, |x, y| {
+        calls.push((x, y));
+        Color::rgba(x as u8, y as u8, 9, 255)
+    });
+    assert_eq!(vec![(0, 0), (1, 0), (0, 1), (1, 1)], calls);
+    assert_eq!(vec![0, 1, 9, 255], image.bytes[8..12].to_vec());
+
+    // Single columns
+    assert_eq!([0, 0, 0, 255].repeat(4), Image::blank(1, 4).bytes);
+    let column = Image::from_fn(1, 4, |_, y| Color::rgb(y as u8, 0, 0));
+    assert_eq!(3, column.get_pixel(0, 3).unwrap().r);
+    assert_eq!(16, Image::filled(1, 4, Color::red()).bytes.len());
+
+    // Images without pixels, and sizes below zero, have no bytes
+    assert!(Image::blank(0, 0).bytes.is_empty());
+    assert!(Image::filled(0, 0, Color::red()).bytes.is_empty());
+    assert!(Image::filled(-2, 3, Color::red()).bytes.is_empty());
+    let empty = Image::from_fn(0, 5, |_, _| panic!("no pixels to fill"));
+    assert!(empty.bytes.is_empty());
+    assert!(Image::from_fn(-1, -1, |_, _| Color::red()).bytes.is_empty());
+}
+
+#[test]
+fn premultiply_round_trip() {
+    // Precision is only lost where the alpha leaves fewer steps than the channel had
+    for a in 0..=255u32 {
+        let mut image = Image::from_fn(256, 1, |x, _| {
+            Color::rgba(x as u8, 255 - x as u8, 0, a as u8)
+        });
+        let original = image.clone();
+        image.premultiply_alpha();
+        for (pixel, straight) in image.pixels().zip(original.pixels()) {
+            assert!(pixel[0] as u32 <= straight[0] as u32 * a / 255 + 1);
+            assert_eq!(straight[3], pixel[3]);
+        }
+        image.unpremultiply_alpha();
+        for (pixel, straight) in image.pixels().zip(original.pixels()) {
+            if a == 0 {
+                assert_eq!(&[0, 0, 0, 0], pixel);
+                continue;
+            }
+            for channel in 0..3 {
+                let error = (pixel[channel] as f32 - straight[channel] as f32).abs();
+                assert!(
+                    error <= 127.5 / a as f32 + 0.5,
+                    "{:?} {:?}",
+                    straight,
+                    pixel
+                );
+            }
+        }
+    }
+
+    // Opaque images are left as they are
+    let mut image = Image::from_fn(3, 1, |x, _| Color::rgb(x as u8 * 100, 7, 255));
+    let original = image.clone();
+    image.premultiply_alpha();
+    assert_eq!(original.bytes, image.bytes);
+    image.unpremultiply_alpha();
+    assert_eq!(original.bytes, image.bytes);
+
+    let mut empty = Image::blank(0, 0);
+    empty.premultiply_alpha();
+    empty.unpremultiply_alpha();
+    assert!(empty.bytes.is_empty());
+}
+
+#[test]
+fn alpha_channel() {
+    let image = Image::from_fn(4, 1, |x, _| Color::rgba(200, 100, 0, x as u8 * 85));
+
+    // The mask is opaque gray, and putting it back restores the alpha
+    let mask = image.alpha_mask();
+    assert_eq!((4, 1), (mask.width, mask.height));
+    assert_eq!(vec![85, 85, 85, 255], mask.bytes[4..8].to_vec());
+    let mut copy = Image::filled(4, 1, Color::rgb(200, 100, 0));
+    copy.set_alpha_from(&mask).unwrap();
+    assert_eq!(image.bytes, copy.bytes);
+
+    // Colored masks count by their luma
+    let mut tinted = image.clone();
+    tinted
+        .set_alpha_from(&Image::filled(4, 1, Color::rgb(255, 0, 0)))
+        .unwrap();
+    assert!(tinted.pixels().all(|pixel| pixel[3] == 77));
+
+    for &(width, height) in &[(3, 1), (4, 2), (1, 4), (0, 0)] {
+        match copy.set_alpha_from(&Image::blank(width, height)) {
+            Err(RasterError::SizeMismatch(w, h)) => assert_eq!((width, height), (w, h)),
+            other => panic!("{} {}: {:?}", width, height, other),
+        }
+    }
+    assert_eq!(image.bytes, copy.bytes);
+
+    // Flattening mixes each pixel with the background by its alpha
+    let mut flat = image.clone();
+    flat.flatten(Color::white());
+    assert_eq!(vec![255, 255, 255, 255], flat.bytes[..4].to_vec());
+    assert_eq!(vec![200, 100, 0, 255], flat.bytes[12..].to_vec());
+    assert_eq!(vec![218, 152, 85, 255], flat.bytes[8..12].to_vec());
+
+    // Single columns and empty images
+    let mut column = Image::filled(1, 3, Color::rgba(0, 0, 0, 0));
+    let mask = Image::from_fn(1, 3, |_, y| {
+        Color::rgb(y as u8 * 100, y as u8 * 100, y as u8 * 100)
+    });
+    column.set_alpha_from(&mask).unwrap();
+    assert_eq!(
+        vec![0, 100, 200],
+        column
+            .alpha_mask()
+            .pixels()
+            .map(|pixel| pixel[0])
+            .collect::<Vec<_>>()
+    );
+    let mut empty = Image::blank(0, 0);
+    assert!(empty.alpha_mask().bytes.is_empty());
+    empty.set_alpha_from(&Image::blank(0, 0)).unwrap();
+    empty.flatten(Color::white());
+    assert!(empty.bytes.is_empty());
+}
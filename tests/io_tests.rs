@@ -1,4 +1,13 @@
+#[cfg(feature = "image-interop")]
+extern crate image25;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
 extern crate raster;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_core;
+#[cfg(feature = "async")]
+extern crate tokio;
 
 #[test]
 fn open_fail() {
@@ -123,3 +132,1883 @@ fn read_png_format_fail() {
         }
     });
 }
+
+#[test]
+fn read_gif_animation() {
+    let file = std::fs::File::open("tests/in/animated.gif").unwrap();
+    let animation = raster::endec::decode_gif_animation(&file).unwrap();
+
+    assert!(animation.frames.len() > 1);
+    for frame in &animation.frames {
+        assert_eq!(animation.width, frame.image.width);
+        assert_eq!(animation.height, frame.image.height);
+        assert_eq!(
+            (animation.width * animation.height * 4) as usize,
+            frame.image.bytes.len()
+        );
+    }
+}
+
+#[test]
+fn read_gif_animation_fail() {
+    let file = std::fs::File::open("tests/in/not-a-gif.gif").unwrap();
+    assert!(raster::endec::decode_gif_animation(&file).is_err());
+}
+
+#[test]
+fn write_gif_animation() {
+    let file = std::fs::File::open("tests/in/animated.gif").unwrap();
+    let animation = raster::endec::decode_gif_animation(&file).unwrap();
+    let images: Vec<raster::Image> = animation.frames.iter().map(|f| f.image.clone()).collect();
+    let delays: Vec<u16> = animation.frames.iter().map(|f| f.delay).collect();
+
+    let path = std::path::Path::new("tests/out/test_write_gif_animation.gif");
+    raster::endec::encode_gif_animation(&images, &delays, path).unwrap();
+
+    let file = std::fs::File::open(path).unwrap();
+    let reencoded = raster::endec::decode_gif_animation(&file).unwrap();
+    assert_eq!(animation.frames.len(), reencoded.frames.len());
+    assert_eq!(animation.width, reencoded.width);
+    assert_eq!(animation.height, reencoded.height);
+    for (before, after) in animation.frames.iter().zip(&reencoded.frames) {
+        assert_eq!(before.delay, after.delay);
+    }
+}
+
+#[test]
+fn write_gif_animation_fail() {
+    let path = std::path::Path::new("tests/out/test_write_gif_animation_fail.gif");
+    let frames = [raster::Image::blank(2, 2), raster::Image::blank(3, 3)];
+
+    assert!(raster::endec::encode_gif_animation(&[], &[], path).is_err());
+    assert!(raster::endec::encode_gif_animation(&frames[..1], &[10, 10], path).is_err());
+    assert!(raster::endec::encode_gif_animation(&frames, &[10, 10], path).is_err());
+
+    let empty_palette = raster::endec::GifEncoder::new().palette(Vec::new());
+    let mut data = Vec::new();
+    assert!(empty_palette
+        .encode_to_writer(&frames[..1], &[10], &mut data)
+        .is_err());
+    let wide = [raster::Image::blank(65536, 1)];
+    assert!(raster::endec::encode_gif_animation_to_writer(&wide, &[10], &mut data).is_err());
+}
+
+#[test]
+fn read_animation_loop_count() {
+    use raster::endec::{self, ApngEncoder, GifEncoder};
+    use raster::LoopCount;
+
+    let file = std::fs::File::open("tests/in/animated.gif").unwrap();
+    let animation = endec::decode_gif_animation(&file).unwrap();
+    assert_eq!(LoopCount::Infinite, animation.repeat);
+    let file = std::fs::File::open("tests/in/animated.png").unwrap();
+    let apng = endec::decode_apng(&file).unwrap();
+    assert_eq!(LoopCount::Infinite, apng.repeat);
+
+    let images: Vec<raster::Image> = animation.frames.iter().map(|f| f.image.clone()).collect();
+    let delays: Vec<u16> = animation.frames.iter().map(|f| f.delay).collect();
+    let gif = std::path::Path::new("tests/out/test_loop_count.gif");
+    let png = std::path::Path::new("tests/out/test_loop_count.png");
+    for &repeat in &[LoopCount::Finite(0), LoopCount::Finite(3)] {
+        GifEncoder::new()
+            .repeat(repeat)
+            .encode(&images, &delays, gif)
+            .unwrap();
+        let file = std::fs::File::open(gif).unwrap();
+        assert_eq!(repeat, endec::decode_gif_animation(&file).unwrap().repeat);
+
+        ApngEncoder::new()
+            .repeat(repeat)
+            .encode(&apng.frames, png)
+            .unwrap();
+        let file = std::fs::File::open(png).unwrap();
+        assert_eq!(repeat, endec::decode_apng(&file).unwrap().repeat);
+    }
+
+    // Without the extension a GIF plays once
+    let path = std::path::Path::new("tests/out/test_loop_count_still.gif");
+    endec::encode_gif(&images[0], path).unwrap();
+    let file = std::fs::File::open(path).unwrap();
+    assert_eq!(
+        LoopCount::Finite(0),
+        endec::decode_gif_animation(&file).unwrap().repeat
+    );
+}
+
+#[test]
+fn read_progressive_jpg_format() {
+    let image = raster::open("tests/in/portrait.jpg").unwrap();
+    assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+}
+
+#[test]
+fn write_jpg_format() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    raster::save(&image, "tests/out/test_write_jpg_format.jpg").unwrap();
+
+    let reopened = raster::open("tests/out/test_write_jpg_format.jpg").unwrap();
+    assert_eq!(image.width, reopened.width);
+    assert_eq!(image.height, reopened.height);
+}
+
+#[test]
+fn read_webp_format() {
+    assert!(raster::open("tests/in/sample.webp").is_ok());
+}
+
+#[test]
+fn read_webp_format_fail() {
+    match raster::open("tests/in/not-a-webp.webp") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::WebP, _)) => {}
+        _ => panic!("expected a WebP decode error"),
+    }
+}
+
+#[test]
+fn write_webp_lossless() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let path = std::path::Path::new("tests/out/test_write_webp_lossless.webp");
+    raster::endec::encode_webp_lossless(&image, path).unwrap();
+
+    let reopened = raster::open("tests/out/test_write_webp_lossless.webp").unwrap();
+    assert!(raster::compare::equal(&image, &reopened).unwrap());
+}
+
+#[test]
+fn read_bmp_format() {
+    assert!(raster::open("tests/in/sample.bmp").is_ok());
+}
+
+#[test]
+fn read_bmp_rle8_format() {
+    let image = raster::open("tests/in/rle8.bmp").unwrap();
+    assert_eq!(4, image.width);
+    assert_eq!(2, image.height);
+
+    let top = image.get_pixel(0, 0).unwrap();
+    assert_eq!((255, 0, 0), (top.r, top.g, top.b));
+    let top = image.get_pixel(1, 0).unwrap();
+    assert_eq!((0, 0, 255), (top.r, top.g, top.b));
+    let bottom = image.get_pixel(0, 1).unwrap();
+    assert_eq!((0, 0, 255), (bottom.r, bottom.g, bottom.b));
+}
+
+#[test]
+fn read_bmp_format_fail() {
+    match raster::open("tests/in/not-a-bmp.bmp") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Bmp, _)) => {}
+        _ => panic!("expected a BMP decode error"),
+    }
+}
+
+#[test]
+fn write_bmp_format() {
+    // 24-bit
+    let image = raster::open("tests/in/sample.png").unwrap();
+    raster::save(&image, "tests/out/test_write_bmp_24.bmp").unwrap();
+    let reopened = raster::open("tests/out/test_write_bmp_24.bmp").unwrap();
+    assert!(raster::compare::equal(&image, &reopened).unwrap());
+
+    // 32-bit
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    raster::save(&image, "tests/out/test_write_bmp_32.bmp").unwrap();
+    let reopened = raster::open("tests/out/test_write_bmp_32.bmp").unwrap();
+    assert_eq!(image.bytes, reopened.bytes);
+}
+
+#[test]
+fn read_tiff_format() {
+    assert!(raster::open("tests/in/sample.tif").is_ok());
+}
+
+#[test]
+fn read_tiff_pages() {
+    // Page 1 is LZW RGB, page 2 is Deflate grayscale, page 3 is uncompressed RGBA.
+    let file = std::fs::File::open("tests/in/sample.tif").unwrap();
+    let pages = raster::endec::decode_tiff_pages(&file).unwrap();
+    assert_eq!(3, pages.len());
+    for page in &pages {
+        assert_eq!(pages[0].width, page.width);
+        assert_eq!(pages[0].height, page.height);
+    }
+    assert!(raster::compare::equal(&pages[0], &pages[2]).unwrap());
+}
+
+#[test]
+fn read_tiff_format_fail() {
+    match raster::open("tests/in/not-a-tiff.tif") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Tiff, _)) => {}
+        _ => panic!("expected a TIFF decode error"),
+    }
+}
+
+#[test]
+fn write_tiff_format() {
+    use raster::endec::TiffCompression;
+
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    for compression in &[
+        TiffCompression::Uncompressed,
+        TiffCompression::Lzw,
+        TiffCompression::Deflate,
+    ] {
+        let path = std::path::Path::new("tests/out/test_write_tiff_format.tif");
+        raster::endec::encode_tiff(&image, path, *compression).unwrap();
+        let reopened = raster::open("tests/out/test_write_tiff_format.tif").unwrap();
+        assert_eq!(image.bytes, reopened.bytes);
+    }
+}
+
+#[test]
+fn read_qoi_format() {
+    let image = raster::open("tests/in/sample.qoi").unwrap();
+    assert_eq!(120, image.width);
+    assert_eq!(60, image.height);
+}
+
+#[test]
+fn read_qoi_format_fail() {
+    match raster::open("tests/in/not-a-qoi.qoi") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Qoi, _)) => {}
+        _ => panic!("expected a QOI decode error"),
+    }
+}
+
+#[test]
+fn write_qoi_format() {
+    // Opaque images are written with 3 channels and transparent ones with 4.
+    for input in &["tests/in/sample.png", "tests/in/in2x2trans.png"] {
+        let image = raster::open(input).unwrap();
+        raster::save(&image, "tests/out/test_write_qoi_format.qoi").unwrap();
+        let reopened = raster::open("tests/out/test_write_qoi_format.qoi").unwrap();
+        assert!(raster::compare::equal(&image, &reopened).unwrap());
+    }
+}
+
+#[test]
+fn read_tga_format() {
+    let image = raster::open("tests/in/sample.tga").unwrap();
+    assert_eq!(120, image.width);
+    assert_eq!(60, image.height);
+}
+
+#[test]
+fn read_tga_rle32_format() {
+    // Bottom-up 4x2: top row is 3 red pixels and a half transparent blue one, bottom row is green.
+    let image = raster::open("tests/in/rle32.tga").unwrap();
+    assert_eq!(4, image.width);
+    assert_eq!(2, image.height);
+
+    let top = image.get_pixel(0, 0).unwrap();
+    assert_eq!((255, 0, 0, 255), (top.r, top.g, top.b, top.a));
+    let top = image.get_pixel(3, 0).unwrap();
+    assert_eq!((0, 0, 255, 128), (top.r, top.g, top.b, top.a));
+    let bottom = image.get_pixel(3, 1).unwrap();
+    assert_eq!((0, 255, 0, 255), (bottom.r, bottom.g, bottom.b, bottom.a));
+}
+
+#[test]
+fn read_tga_format_fail() {
+    match raster::open("tests/in/not-a-tga.tga") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Tga, _)) => {}
+        _ => panic!("expected a TGA decode error"),
+    }
+}
+
+#[test]
+fn write_tga_format() {
+    use raster::endec::TgaCompression;
+
+    // Opaque images are written as 24-bit TGAs and transparent ones as 32-bit.
+    for input in &["tests/in/sample.tga", "tests/in/in2x2trans.png"] {
+        let image = raster::open(input).unwrap();
+        for compression in &[TgaCompression::Uncompressed, TgaCompression::Rle] {
+            let path = std::path::Path::new("tests/out/test_write_tga_format.tga");
+            raster::endec::encode_tga(&image, path, *compression).unwrap();
+            let reopened = raster::open("tests/out/test_write_tga_format.tga").unwrap();
+            assert!(raster::compare::equal(&image, &reopened).unwrap());
+        }
+    }
+
+    let empty = raster::Image::blank(0, 3);
+    let mut data = Vec::new();
+    let result = raster::endec::encode_tga_to_writer(&empty, &mut data, TgaCompression::Rle);
+    assert!(result.is_err());
+}
+
+#[test]
+fn read_pnm_format() {
+    let image = raster::open("tests/in/sample.ppm").unwrap();
+    assert_eq!(120, image.width);
+    assert_eq!(60, image.height);
+}
+
+#[test]
+fn read_plain_pbm_format() {
+    // 4x2: top row is black, white, black, white, bottom row is white.
+    let image = raster::open("tests/in/plain.pbm").unwrap();
+    assert_eq!(4, image.width);
+    assert_eq!(2, image.height);
+
+    let top = image.get_pixel(0, 0).unwrap();
+    assert_eq!((0, 0, 0), (top.r, top.g, top.b));
+    let top = image.get_pixel(1, 0).unwrap();
+    assert_eq!((255, 255, 255), (top.r, top.g, top.b));
+    let bottom = image.get_pixel(0, 1).unwrap();
+    assert_eq!((255, 255, 255), (bottom.r, bottom.g, bottom.b));
+}
+
+#[test]
+fn read_16bit_pgm_format() {
+    let image = raster::open("tests/in/gray16.pgm").unwrap();
+    assert_eq!(vec![255, 255, 255, 255, 128, 128, 128, 255], image.bytes);
+}
+
+#[test]
+fn read_pam_format() {
+    let image = raster::open("tests/in/graya.pam").unwrap();
+    assert_eq!(vec![255, 255, 255, 128, 0, 0, 0, 255], image.bytes);
+}
+
+#[test]
+fn read_pnm_format_fail() {
+    match raster::open("tests/in/not-a-pnm.pnm") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Pnm, _)) => {}
+        _ => panic!("expected a PNM decode error"),
+    }
+}
+
+#[test]
+fn write_pnm_format() {
+    use raster::endec::{PnmEncoding, PnmFormat};
+
+    let path = std::path::Path::new("tests/out/test_write_pnm_format.pnm");
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    raster::endec::encode_pnm(&image, path, PnmFormat::Pam, PnmEncoding::Binary).unwrap();
+    assert_eq!(
+        image.bytes,
+        raster::open(path.to_str().unwrap()).unwrap().bytes
+    );
+    assert!(raster::endec::encode_pnm(&image, path, PnmFormat::Pam, PnmEncoding::Plain).is_err());
+
+    let image = raster::open("tests/in/sample.ppm").unwrap();
+    for encoding in &[PnmEncoding::Binary, PnmEncoding::Plain] {
+        raster::endec::encode_pnm(&image, path, PnmFormat::Ppm, *encoding).unwrap();
+        let reopened = raster::open(path.to_str().unwrap()).unwrap();
+        assert!(raster::compare::equal(&image, &reopened).unwrap());
+
+        // Grayscale and black and white survive a round trip unchanged.
+        for format in &[PnmFormat::Pgm, PnmFormat::Pbm] {
+            raster::endec::encode_pnm(&image, path, *format, *encoding).unwrap();
+            let once = raster::open(path.to_str().unwrap()).unwrap();
+            raster::endec::encode_pnm(&once, path, *format, *encoding).unwrap();
+            let twice = raster::open(path.to_str().unwrap()).unwrap();
+            assert_eq!(once.bytes, twice.bytes);
+        }
+    }
+}
+
+#[test]
+fn read_ico_format() {
+    // The largest entry is a blue 48x48 PNG.
+    let image = raster::open("tests/in/sample.ico").unwrap();
+    assert_eq!(48, image.width);
+    assert_eq!(48, image.height);
+    let pixel = image.get_pixel(0, 0).unwrap();
+    assert_eq!((0, 0, 255, 255), (pixel.r, pixel.g, pixel.b, pixel.a));
+}
+
+#[test]
+fn read_ico_sizes() {
+    let decode = |size| {
+        let file = std::fs::File::open("tests/in/sample.ico").unwrap();
+        raster::endec::decode_ico(&file, size).unwrap()
+    };
+
+    // 32-bit BMP entry with its own alpha channel
+    let image = decode(Some(16));
+    assert_eq!(16, image.width);
+    let pixel = image.get_pixel(0, 0).unwrap();
+    assert_eq!((255, 0, 0, 200), (pixel.r, pixel.g, pixel.b, pixel.a));
+
+    // 8-bit BMP entry whose mask hides the right half
+    let image = decode(Some(20));
+    assert_eq!(32, image.width);
+    let left = image.get_pixel(0, 0).unwrap();
+    assert_eq!((0, 255, 0, 255), (left.r, left.g, left.b, left.a));
+    let right = image.get_pixel(31, 31).unwrap();
+    assert_eq!(0, right.a);
+
+    // Falls back to the largest one
+    assert_eq!(48, decode(Some(300)).width);
+}
+
+#[test]
+fn read_ico_format_fail() {
+    match raster::open("tests/in/not-an-ico.ico") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Ico, _)) => {}
+        _ => panic!("expected an ICO decode error"),
+    }
+}
+
+#[test]
+fn write_ico_format() {
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    let mut large = raster::open("tests/in/sample.png").unwrap();
+    raster::editor::resize(&mut large, 256, 256, raster::ResizeMode::Exact).unwrap();
+
+    let path = std::path::Path::new("tests/out/test_write_ico_format.ico");
+    raster::endec::encode_ico(&[image.clone(), large.clone()], path).unwrap();
+
+    let file = std::fs::File::open(path).unwrap();
+    let reopened = raster::endec::decode_ico(&file, Some(2)).unwrap();
+    assert_eq!(image.bytes, reopened.bytes);
+    let file = std::fs::File::open(path).unwrap();
+    let reopened = raster::endec::decode_ico(&file, None).unwrap();
+    assert!(raster::compare::equal(&large, &reopened).unwrap());
+}
+
+#[test]
+fn write_ico_format_fail() {
+    let path = std::path::Path::new("tests/out/test_write_ico_format_fail.ico");
+    assert!(raster::endec::encode_ico(&[], path).is_err());
+    assert!(raster::endec::encode_ico(&[raster::Image::blank(257, 16)], path).is_err());
+}
+
+#[cfg(feature = "avif")]
+#[test]
+fn read_avif_format() {
+    let image = raster::open("tests/in/sample.avif").unwrap();
+    assert_eq!(120, image.width);
+    assert_eq!(60, image.height);
+}
+
+#[cfg(not(feature = "avif"))]
+#[test]
+fn read_avif_format_disabled() {
+    match raster::open("tests/in/sample.avif") {
+        Err(raster::error::RasterError::UnsupportedFormat(_)) => {}
+        _ => panic!("expected AVIF to be unsupported without the avif feature"),
+    }
+}
+
+#[test]
+fn read_hdr_format() {
+    // 64x32 gradient, red goes from 0.0 on the left to 8.0 on the right.
+    let file = std::fs::File::open("tests/in/sample.hdr").unwrap();
+    let hdr = raster::endec::decode_hdr(&file).unwrap();
+    assert_eq!(64, hdr.width);
+    assert_eq!(32, hdr.height);
+    let right = &hdr.samples[(63 * 4)..(64 * 4)];
+    assert!((right[0] - 8.0).abs() < 0.1);
+    assert_eq!(1.0, right[3]);
+
+    // Clamping blows out the highlights, the other operators keep them apart.
+    let clamped = hdr.tone_map(raster::ToneMap::Clamp);
+    assert_eq!(clamped.bytes[40 * 4], clamped.bytes[63 * 4]);
+    for tone_map in &[raster::ToneMap::Reinhard, raster::ToneMap::Aces] {
+        let image = hdr.tone_map(*tone_map);
+        assert!(image.bytes[40 * 4] < image.bytes[63 * 4]);
+    }
+
+    assert!(raster::open("tests/in/sample.hdr").is_ok());
+}
+
+#[test]
+fn read_hdr_format_fail() {
+    match raster::open("tests/in/not-a-hdr.hdr") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Hdr, _)) => {}
+        _ => panic!("expected an HDR decode error"),
+    }
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn read_exr_format() {
+    // 64x32 half floats, red goes from 0.0 to 4.0 left to right, alpha from 1.0 to 0.0 top to
+    // bottom.
+    let file = std::fs::File::open("tests/in/sample.exr").unwrap();
+    let hdr = raster::endec::decode_exr(&file).unwrap();
+    assert_eq!(64, hdr.width);
+    assert_eq!(32, hdr.height);
+    assert_eq!(&[4.0, 0.5, 0.25, 1.0], &hdr.samples[(63 * 4)..(64 * 4)]);
+    assert_eq!(0.0, hdr.samples[hdr.samples.len() - 1]);
+
+    let image = raster::open("tests/in/sample.exr").unwrap();
+    assert_eq!(0, image.bytes[image.bytes.len() - 1]);
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn read_exr_format_fail() {
+    match raster::open("tests/in/not-an-exr.exr") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Exr, _)) => {}
+        _ => panic!("expected an EXR decode error"),
+    }
+}
+
+#[test]
+fn read_farbfeld_format() {
+    let image = raster::open("tests/in/sample.ff").unwrap();
+    assert_eq!(4, image.width);
+    assert_eq!(2, image.height);
+    assert_eq!(
+        vec![255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0, 18, 86, 154, 222],
+        image.bytes[..16].to_vec()
+    );
+}
+
+#[test]
+fn read_farbfeld_format_fail() {
+    match raster::open("tests/in/not-a-farbfeld.ff") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Farbfeld, _)) => {}
+        _ => panic!("expected a farbfeld decode error"),
+    }
+}
+
+#[test]
+fn write_farbfeld_format() {
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    raster::save(&image, "tests/out/test_write_farbfeld_format.ff").unwrap();
+    let reopened = raster::open("tests/out/test_write_farbfeld_format.ff").unwrap();
+    assert_eq!(image.bytes, reopened.bytes);
+}
+
+#[test]
+fn read_apng_animation() {
+    // 8x8 with a white default image that isn't part of the animation, then a red frame, a half
+    // transparent blue 4x4 frame blended over it and a green 2x2 frame replacing the top left.
+    let file = std::fs::File::open("tests/in/animated.png").unwrap();
+    let animation = raster::endec::decode_apng(&file).unwrap();
+    assert_eq!(8, animation.width);
+    assert_eq!(3, animation.frames.len());
+
+    let delays: Vec<u16> = animation.frames.iter().map(|f| f.delay).collect();
+    assert_eq!(vec![10, 20, 5], delays);
+
+    let pixel = |frame: usize, x, y| {
+        let p = animation.frames[frame].image.get_pixel(x, y).unwrap();
+        (p.r, p.g, p.b, p.a)
+    };
+    assert_eq!((255, 0, 0, 255), pixel(0, 0, 0));
+    assert_eq!((127, 0, 128, 255), pixel(1, 3, 3));
+    assert_eq!((255, 0, 0, 255), pixel(1, 0, 0));
+    // The blue frame was disposed to the previous canvas.
+    assert_eq!((0, 255, 0, 255), pixel(2, 0, 0));
+    assert_eq!((255, 0, 0, 255), pixel(2, 3, 3));
+}
+
+#[test]
+fn read_apng_still() {
+    let file = std::fs::File::open("tests/in/sample.png").unwrap();
+    let animation = raster::endec::decode_apng(&file).unwrap();
+    assert_eq!(1, animation.frames.len());
+
+    let image = raster::open("tests/in/sample.png").unwrap();
+    assert!(raster::compare::equal(&image, &animation.frames[0].image).unwrap());
+}
+
+#[test]
+fn write_apng_animation() {
+    let file = std::fs::File::open("tests/in/animated.png").unwrap();
+    let animation = raster::endec::decode_apng(&file).unwrap();
+
+    let path = std::path::Path::new("tests/out/test_write_apng_animation.png");
+    raster::endec::encode_apng(&animation.frames, path).unwrap();
+
+    let file = std::fs::File::open(path).unwrap();
+    let reencoded = raster::endec::decode_apng(&file).unwrap();
+    assert_eq!(animation.frames.len(), reencoded.frames.len());
+    for (before, after) in animation.frames.iter().zip(&reencoded.frames) {
+        assert_eq!(before.delay, after.delay);
+        assert_eq!(before.image.bytes, after.image.bytes);
+    }
+}
+
+#[test]
+fn write_apng_blending() {
+    use raster::{Color, DisposalMethod, Frame, FrameBlend, Image};
+
+    let mut red = Image::blank(2, 1);
+    raster::editor::fill(&mut red, Color::red()).unwrap();
+    let mut dot = Image::blank(2, 1);
+    raster::editor::fill(&mut dot, Color::rgba(0, 0, 0, 0)).unwrap();
+    dot.set_pixel(1, 0, Color::blue()).unwrap();
+
+    let frame = |image: &Image, disposal, blend| Frame {
+        image: image.clone(),
+        delay: 10,
+        disposal,
+        blend,
+    };
+    let frames = [
+        frame(&red, DisposalMethod::Keep, FrameBlend::Source),
+        frame(&dot, DisposalMethod::Background, FrameBlend::Over),
+        frame(&dot, DisposalMethod::Keep, FrameBlend::Source),
+    ];
+    let path = std::path::Path::new("tests/out/test_write_apng_blending.png");
+    raster::endec::encode_apng(&frames, path).unwrap();
+
+    let file = std::fs::File::open(path).unwrap();
+    let animation = raster::endec::decode_apng(&file).unwrap();
+    // Blending over keeps the red, replacing after clearing doesn't.
+    assert_eq!(
+        vec![255, 0, 0, 255, 0, 0, 255, 255],
+        animation.frames[1].image.bytes
+    );
+    assert_eq!(dot.bytes, animation.frames[2].image.bytes);
+}
+
+#[test]
+fn write_apng_animation_fail() {
+    let path = std::path::Path::new("tests/out/test_write_apng_animation_fail.png");
+    let frame = |size| raster::Frame {
+        image: raster::Image::blank(size, size),
+        delay: 10,
+        disposal: raster::DisposalMethod::Keep,
+        blend: raster::FrameBlend::Source,
+    };
+
+    assert!(raster::endec::encode_apng(&[], path).is_err());
+    assert!(raster::endec::encode_apng(&[frame(2), frame(3)], path).is_err());
+}
+
+#[test]
+fn read_dds_bc1_format() {
+    // Left block goes from red to blue row by row, right block is transparent except for a
+    // purple pixel in the top left.
+    let image = raster::open("tests/in/bc1.dds").unwrap();
+    assert_eq!(8, image.width);
+    assert_eq!(4, image.height);
+
+    let pixel = |x, y| {
+        let p = image.get_pixel(x, y).unwrap();
+        (p.r, p.g, p.b, p.a)
+    };
+    assert_eq!((255, 0, 0, 255), pixel(0, 0));
+    assert_eq!((0, 0, 255, 255), pixel(3, 1));
+    assert_eq!((170, 0, 85, 255), pixel(0, 2));
+    assert_eq!((85, 0, 170, 255), pixel(0, 3));
+    assert_eq!((127, 0, 127, 255), pixel(4, 0));
+    assert_eq!((0, 0, 0, 0), pixel(7, 3));
+}
+
+#[test]
+fn read_dds_bc3_format() {
+    // Green with the alpha index of each pixel going 0 to 7, twice.
+    let image = raster::open("tests/in/bc3.dds").unwrap();
+    let alphas: Vec<u8> = image.bytes.chunks(4).map(|p| p[3]).take(8).collect();
+    assert_eq!(vec![255, 0, 218, 182, 145, 109, 72, 36], alphas);
+    assert_eq!(&[0, 255, 0], &image.bytes[..3]);
+}
+
+#[test]
+fn read_dds_uncompressed_format() {
+    let image = raster::open("tests/in/rgba.dds").unwrap();
+    assert_eq!(vec![10, 20, 30, 40, 1, 2, 3, 4], image.bytes);
+}
+
+#[test]
+fn read_dds_wide_masks() {
+    // Red takes all 32 bits of a pixel and alpha overlaps it
+    let mut data = std::fs::read("tests/in/rgba.dds").unwrap();
+    data[92..96].copy_from_slice(&0xffff_ffffu32.to_le_bytes());
+    data[96..104].copy_from_slice(&[0; 8]);
+    let limits = raster::endec::DecodeLimits::default();
+    let image = raster::endec::decode_dds_from_reader(std::io::Cursor::new(data), &limits).unwrap();
+    assert_eq!(vec![39, 255, 255, 40, 3, 255, 255, 4], image.bytes);
+}
+
+#[test]
+fn read_dds_format_fail() {
+    match raster::open("tests/in/not-a-dds.dds") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Dds, _)) => {}
+        _ => panic!("expected a DDS decode error"),
+    }
+
+    // A header claiming far more pixels than the file holds
+    let mut data = std::fs::read("tests/in/rgba.dds").unwrap();
+    data[12..16].copy_from_slice(&8000u32.to_le_bytes());
+    data[16..20].copy_from_slice(&8000u32.to_le_bytes());
+    let limits = raster::endec::DecodeLimits::default();
+    match raster::endec::decode_dds_from_reader(std::io::Cursor::new(data), &limits) {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Dds, _)) => {}
+        _ => panic!("expected a DDS decode error"),
+    }
+}
+
+#[cfg(feature = "svg")]
+#[test]
+fn read_svg_format() {
+    // Red square on the left, half transparent blue circle on the right.
+    let image = raster::open("tests/in/sample.svg").unwrap();
+    assert_eq!(120, image.width);
+    assert_eq!(60, image.height);
+
+    let pixel = |image: &raster::Image, x, y| {
+        let p = image.get_pixel(x, y).unwrap();
+        (p.r, p.g, p.b, p.a)
+    };
+    assert_eq!((255, 0, 0, 255), pixel(&image, 30, 30));
+    assert_eq!((0, 0, 255, 128), pixel(&image, 90, 30));
+    assert_eq!((0, 0, 0, 0), pixel(&image, 119, 0));
+
+    let file = std::fs::File::open("tests/in/sample.svg").unwrap();
+    let image = raster::endec::decode_svg(&file, Some((240, 30))).unwrap();
+    assert_eq!(240, image.width);
+    assert_eq!(30, image.height);
+    assert_eq!((255, 0, 0, 255), pixel(&image, 60, 15));
+    assert_eq!((0, 0, 255, 128), pixel(&image, 180, 15));
+}
+
+#[cfg(feature = "svg")]
+#[test]
+fn read_svg_format_fail() {
+    match raster::open("tests/in/not-an-svg.svg") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Svg, _)) => {}
+        _ => panic!("expected an SVG decode error"),
+    }
+
+    let file = std::fs::File::open("tests/in/sample.svg").unwrap();
+    match raster::endec::decode_svg(&file, Some((0, 10))) {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Svg, _)) => {}
+        _ => panic!("expected an SVG decode error"),
+    }
+}
+
+#[cfg(feature = "heif")]
+#[test]
+fn read_heif_format() {
+    // Generic AV1 coded HEIF with an EXIF block saying the camera was rotated 90 degrees.
+    let file = std::fs::File::open("tests/in/sample.heif").unwrap();
+    let (image, orientation) = raster::endec::decode_heif(&file).unwrap();
+    assert_eq!(120, image.width);
+    assert_eq!(60, image.height);
+    assert_eq!(Some(6), orientation);
+
+    let image = raster::open("tests/in/sample.heif").unwrap();
+    assert_eq!(120, image.width);
+}
+
+#[cfg(feature = "heif")]
+#[test]
+fn read_heif_format_fail() {
+    match raster::open("tests/in/not-a-heif.heif") {
+        Err(raster::error::RasterError::Decode(raster::ImageFormat::Heif, _)) => {}
+        _ => panic!("expected a HEIF decode error"),
+    }
+}
+
+#[cfg(not(feature = "heif"))]
+#[test]
+fn read_heif_format_disabled() {
+    match raster::open("tests/in/sample.heif") {
+        Err(raster::error::RasterError::UnsupportedFormat(_)) => {}
+        _ => panic!("expected HEIF to be unsupported without the heif feature"),
+    }
+}
+
+#[test]
+fn detect_format() {
+    use raster::ImageFormat;
+
+    let detect = |path| {
+        let mut file = std::fs::File::open(path).unwrap();
+        raster::endec::detect_format(&mut file).ok()
+    };
+    assert_eq!(Some(ImageFormat::Avif), detect("tests/in/sample.avif"));
+    assert_eq!(Some(ImageFormat::Bmp), detect("tests/in/sample.bmp"));
+    assert_eq!(Some(ImageFormat::Dds), detect("tests/in/bc1.dds"));
+    assert_eq!(Some(ImageFormat::Exr), detect("tests/in/sample.exr"));
+    assert_eq!(Some(ImageFormat::Farbfeld), detect("tests/in/sample.ff"));
+    assert_eq!(Some(ImageFormat::Gif), detect("tests/in/sample.gif"));
+    assert_eq!(Some(ImageFormat::Hdr), detect("tests/in/sample.hdr"));
+    assert_eq!(Some(ImageFormat::Heif), detect("tests/in/sample.heif"));
+    assert_eq!(Some(ImageFormat::Ico), detect("tests/in/sample.ico"));
+    assert_eq!(Some(ImageFormat::Jpeg), detect("tests/in/sample.jpg"));
+    assert_eq!(Some(ImageFormat::Png), detect("tests/in/sample.png"));
+    assert_eq!(Some(ImageFormat::Pnm), detect("tests/in/plain.pbm"));
+    assert_eq!(Some(ImageFormat::Pnm), detect("tests/in/graya.pam"));
+    assert_eq!(Some(ImageFormat::Qoi), detect("tests/in/sample.qoi"));
+    assert_eq!(Some(ImageFormat::Svg), detect("tests/in/sample.svg"));
+    assert_eq!(Some(ImageFormat::Tiff), detect("tests/in/sample.tif"));
+    assert_eq!(Some(ImageFormat::WebP), detect("tests/in/sample.webp"));
+    assert_eq!(None, detect("tests/in/sample.tga"));
+    assert_eq!(None, detect("tests/in/unsupported.txt"));
+}
+
+#[test]
+fn read_misnamed_format() {
+    // A PNG with a .jpg extension.
+    let image = raster::open("tests/in/png-as.jpg").unwrap();
+    assert_eq!(
+        raster::open("tests/in/in2x2.png").unwrap().bytes,
+        image.bytes
+    );
+}
+
+#[test]
+fn read_from_bytes() {
+    use raster::{Image, ImageFormat};
+
+    let image = Image::from_bytes(ImageFormat::Png, include_bytes!("in/sample.png")).unwrap();
+    assert_eq!(
+        raster::open("tests/in/sample.png").unwrap().bytes,
+        image.bytes
+    );
+
+    let image = Image::from_bytes(ImageFormat::Tiff, include_bytes!("in/sample.tif")).unwrap();
+    assert_eq!(
+        raster::open("tests/in/sample.tif").unwrap().bytes,
+        image.bytes
+    );
+
+    assert!(Image::from_bytes(ImageFormat::Jpeg, include_bytes!("in/not-a-jpeg.jpg")).is_err());
+}
+
+#[test]
+fn read_from_reader() {
+    use raster::endec::DecodeLimits;
+
+    let data = std::fs::read("tests/in/animated.gif").unwrap();
+    let animation = raster::endec::decode_gif_animation_from_reader(
+        std::io::Cursor::new(&data),
+        &DecodeLimits::default(),
+    )
+    .unwrap();
+    let file = std::fs::File::open("tests/in/animated.gif").unwrap();
+    let expected = raster::endec::decode_gif_animation(&file).unwrap();
+    assert_eq!(expected.frames.len(), animation.frames.len());
+
+    let data: &[u8] = include_bytes!("in/sample.ico");
+    let image =
+        raster::endec::decode_ico_from_reader(data, Some(16), &DecodeLimits::default()).unwrap();
+    assert_eq!(16, image.width);
+}
+
+#[test]
+fn read_icc_profile() {
+    use raster::error::RasterError;
+    use raster::metadata::MetadataBlocks;
+
+    let profile = |path| {
+        MetadataBlocks::from_path(path)
+            .unwrap()
+            .icc_profile
+            .unwrap()
+    };
+
+    // Adobe RGB colors come out more saturated in sRGB
+    let mut image = raster::open("tests/in/adobe-rgb.png").unwrap();
+    image
+        .apply_icc_to_srgb(&profile("tests/in/adobe-rgb.png"))
+        .unwrap();
+    let expected = [
+        0, 255, 0, 255, 129, 129, 129, 255, 227, 100, 100, 255, 255, 255, 255, 255,
+    ];
+    assert_eq!(&expected[..], &image.bytes[..]);
+
+    // An sRGB profile barely changes anything
+    let original = raster::open("tests/in/sample.png").unwrap();
+    let mut image = original.clone();
+    image
+        .apply_icc_to_srgb(&profile("tests/in/sample.png"))
+        .unwrap();
+    assert!(original
+        .bytes
+        .iter()
+        .zip(image.bytes.iter())
+        .all(|(&a, &b)| (a as i32 - b as i32).abs() <= 1));
+
+    match image.apply_icc_to_srgb(b"not a profile") {
+        Err(RasterError::InvalidIccProfile(_)) => {}
+        other => panic!("{:?}", other),
+    }
+
+    // A tag count far past the end of the profile
+    let mut profile = vec![0; 132];
+    profile[16..20].copy_from_slice(b"RGB ");
+    profile[36..40].copy_from_slice(b"acsp");
+    profile[128..132].copy_from_slice(&[0xff; 4]);
+    match image.apply_icc_to_srgb(&profile) {
+        Err(RasterError::InvalidIccProfile(_)) => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn read_limits() {
+    use raster::endec::{self, DecodeLimits};
+    use raster::error::RasterError;
+    use raster::{Image, ImageFormat};
+    use std::io::Cursor;
+
+    // A header claiming 100000x100000 pixels without any pixel data
+    let mut bomb = b"farbfeld".to_vec();
+    bomb.extend_from_slice(&100_000u32.to_be_bytes());
+    bomb.extend_from_slice(&100_000u32.to_be_bytes());
+    match Image::from_bytes(ImageFormat::Farbfeld, &bomb) {
+        Err(RasterError::LimitExceeded(ImageFormat::Farbfeld)) => {}
+        _ => panic!("expected the farbfeld limits to be exceeded"),
+    }
+
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let limits = DecodeLimits {
+        max_width: image.width as u32 - 1,
+        ..DecodeLimits::default()
+    };
+    match raster::open_with_limits("tests/in/sample.png", &limits) {
+        Err(RasterError::LimitExceeded(ImageFormat::Png)) => {}
+        _ => panic!("expected the png width limit to be exceeded"),
+    }
+    let limits = DecodeLimits {
+        max_bytes: image.bytes.len(),
+        ..DecodeLimits::default()
+    };
+    assert!(raster::open_with_limits("tests/in/sample.png", &limits).is_ok());
+    for path in &[
+        "tests/in/sample.jpg",
+        "tests/in/sample.bmp",
+        "tests/in/sample.tga",
+    ] {
+        let image = raster::open(path).unwrap();
+        let limits = DecodeLimits {
+            max_bytes: image.bytes.len() - 1,
+            ..DecodeLimits::default()
+        };
+        match raster::open_with_limits(path, &limits) {
+            Err(RasterError::LimitExceeded(_)) => {}
+            _ => panic!("expected the limits of {} to be exceeded", path),
+        }
+    }
+
+    // The byte limit covers all frames together
+    let data = std::fs::read("tests/in/animated.gif").unwrap();
+    let animation =
+        endec::decode_gif_animation_from_reader(Cursor::new(&data), &DecodeLimits::default())
+            .unwrap();
+    let limits = DecodeLimits {
+        max_bytes: animation.frames[0].image.bytes.len() * 2,
+        ..DecodeLimits::default()
+    };
+    match endec::decode_gif_animation_from_reader(Cursor::new(&data), &limits) {
+        Err(RasterError::LimitExceeded(ImageFormat::Gif)) => {}
+        _ => panic!("expected the gif limits to be exceeded"),
+    }
+}
+
+#[test]
+fn read_metadata() {
+    use raster::error::RasterError;
+    use raster::metadata::Metadata;
+
+    // Big endian EXIF in a JPEG
+    let metadata = Metadata::from_path("tests/in/exif.jpg").unwrap();
+    assert_eq!(Some("Raster".to_string()), metadata.make);
+    assert_eq!(Some("Test Camera".to_string()), metadata.model);
+    assert_eq!(Some(6), metadata.orientation);
+    assert_eq!(Some("2017:01:02 03:04:05".to_string()), metadata.date_time);
+    assert_eq!(
+        Some("2016:12:16 06:49:56".to_string()),
+        metadata.date_time_original
+    );
+    assert_eq!(
+        Some("2016:12:17 10:00:00".to_string()),
+        metadata.date_time_digitized
+    );
+    let gps = metadata.gps.unwrap();
+    assert!((gps.latitude - 52.375).abs() < 1e-9);
+    assert!((gps.longitude + 4.9).abs() < 1e-9);
+    assert_eq!(Some(-12.5), gps.altitude);
+    raster::open("tests/in/exif.jpg").unwrap();
+
+    // Little endian EXIF in a PNG, with the make stored inside its entry
+    let metadata = Metadata::from_path("tests/in/exif.png").unwrap();
+    assert_eq!(Some("Mk".to_string()), metadata.make);
+    assert_eq!(Some(3), metadata.orientation);
+    assert_eq!(None, metadata.gps);
+
+    let metadata = Metadata::from_path("tests/in/sample.jpg").unwrap();
+    assert_eq!(Some(1), metadata.orientation);
+    assert_eq!(Some("2016:12:16 06:49:56".to_string()), metadata.date_time);
+
+    let metadata = Metadata::from_path("tests/in/sample.tif").unwrap();
+    assert_eq!(None, metadata.make);
+
+    match Metadata::from_path("tests/in/sample.gif") {
+        Err(RasterError::UnsupportedFormat(_)) => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn read_oriented() {
+    use raster::transform;
+    use raster::Image;
+
+    // Orientation 6 is turned clockwise
+    let full = raster::open("tests/in/exif.jpg").unwrap();
+    let image = raster::open_oriented("tests/in/exif.jpg").unwrap();
+    assert_eq!((full.height, full.width), (image.width, image.height));
+    for &(x, y) in &[(0, 0), (image.width - 1, 0), (3, image.height - 1)] {
+        let (turned, pixel) = (
+            full.get_pixel(y, full.height - 1 - x).unwrap(),
+            image.get_pixel(x, y).unwrap(),
+        );
+        assert_eq!((turned.r, turned.g, turned.b), (pixel.r, pixel.g, pixel.b));
+    }
+
+    // Images without an orientation are unchanged
+    let image = raster::open_oriented("tests/in/sample.png").unwrap();
+    assert_eq!(
+        raster::open("tests/in/sample.png").unwrap().bytes,
+        image.bytes
+    );
+
+    // A 3x2 image numbered 0 to 5, and where each pixel ends up for every orientation
+    let mut image = Image::blank(3, 2);
+    for i in 0..6 {
+        image.bytes[i * 4] = i as u8;
+    }
+    let expected: [&[u8]; 8] = [
+        &[0, 1, 2, 3, 4, 5],
+        &[2, 1, 0, 5, 4, 3],
+        &[5, 4, 3, 2, 1, 0],
+        &[3, 4, 5, 0, 1, 2],
+        &[0, 3, 1, 4, 2, 5],
+        &[3, 0, 4, 1, 5, 2],
+        &[5, 2, 4, 1, 3, 0],
+        &[2, 5, 1, 4, 0, 3],
+    ];
+    for (orientation, expected) in (1..).zip(expected.iter()) {
+        let mut oriented = image.clone();
+        transform::orient(&mut oriented, orientation).unwrap();
+        let width = if orientation >= 5 { 2 } else { 3 };
+        assert_eq!((width, 6 / width), (oriented.width, oriented.height));
+        let red: Vec<u8> = oriented.bytes.chunks(4).map(|p| p[0]).collect();
+        assert_eq!(expected, &red.as_slice(), "orientation {}", orientation);
+    }
+}
+
+#[test]
+fn read_region() {
+    use raster::editor;
+    use raster::endec;
+    use raster::error::RasterError;
+    use raster::ImageFormat;
+    use std::cmp;
+    use std::fs::File;
+
+    for &(path, format) in &[
+        ("tests/in/sample.png", ImageFormat::Png),
+        ("tests/in/interlaced.png", ImageFormat::Png),
+        ("tests/in/sample.tif", ImageFormat::Tiff),
+        ("tests/in/tiled.tif", ImageFormat::Tiff),
+    ] {
+        let full = raster::open(path).unwrap();
+
+        // Inside, and crossing the bottom right corner
+        for &(x, y, w, h) in &[(5, 3, 20, 17), (full.width - 7, full.height - 9, 30, 30)] {
+            let file = File::open(path).unwrap();
+            let region = endec::decode_region(format, file, x, y, w, h).unwrap();
+
+            let mut expected = full.clone();
+            editor::crop(
+                &mut expected,
+                cmp::min(w, full.width - x),
+                cmp::min(h, full.height - y),
+                raster::PositionMode::TopLeft,
+                x,
+                y,
+            )
+            .unwrap();
+            assert_eq!(
+                (expected.width, expected.height),
+                (region.width, region.height)
+            );
+            assert_eq!(expected.bytes, region.bytes);
+        }
+
+        let file = File::open(path).unwrap();
+        match endec::decode_region(format, file, full.width, 0, 10, 10) {
+            Err(RasterError::PixelOutOfBounds(_, _)) => {}
+            other => panic!("{:?}", other.map(|image| image.width)),
+        }
+    }
+
+    // Tiles hold the expected pixels
+    let file = File::open("tests/in/tiled.tif").unwrap();
+    let region = endec::decode_region(ImageFormat::Tiff, file, 14, 14, 4, 4).unwrap();
+    let pixel = region.get_pixel(3, 2).unwrap();
+    assert_eq!((17 * 6, 16 * 10, 33 * 3), (pixel.r, pixel.g, pixel.b));
+}
+
+#[test]
+fn read_scaled() {
+    use raster::endec;
+    use std::path::Path;
+
+    for path in &[
+        "tests/in/sample.jpg",
+        "tests/in/sample.png",
+        "tests/in/sample.gif",
+    ] {
+        let full = raster::open(path).unwrap();
+        let image = endec::decode_scaled(Path::new(path), 100, 100).unwrap();
+        assert_eq!(100, image.width);
+        let height = (full.height as f64 * 100.0 / full.width as f64).round() as i32;
+        assert_eq!(height, image.height);
+        assert_eq!((image.width * image.height * 4) as usize, image.bytes.len());
+    }
+
+    // Blocks of a PNG are averaged
+    let full = raster::open("tests/in/sample.png").unwrap();
+    let scaled = endec::decode_scaled(Path::new("tests/in/sample.png"), 250, 125).unwrap();
+    assert_eq!((250, 125), (scaled.width, scaled.height));
+    let mut sum = 0;
+    for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+        sum += full.get_pixel(x, y).unwrap().r as i32;
+    }
+    assert_eq!((sum + 2) / 4, scaled.get_pixel(0, 0).unwrap().r as i32);
+
+    // Small images are not enlarged
+    let image = endec::decode_scaled(Path::new("tests/in/in2x2.png"), 100, 100).unwrap();
+    assert_eq!((2, 2), (image.width, image.height));
+}
+
+#[test]
+fn read_tolerant() {
+    use raster::endec::DecodeOptions;
+
+    let options = DecodeOptions {
+        tolerant: true,
+        ..DecodeOptions::default()
+    };
+    for path in &["tests/in/sample.jpg", "tests/in/sample.png"] {
+        let image = raster::open(path).unwrap();
+        let data = std::fs::read(path).unwrap();
+        let truncated = path.replace("tests/in/sample", "tests/out/test_read_tolerant");
+        std::fs::write(&truncated, &data[..data.len() / 2]).unwrap();
+
+        assert!(raster::open(&truncated).is_err());
+        let partial = raster::open_with(&truncated, &options).unwrap();
+        assert_eq!((image.width, image.height), (partial.width, partial.height));
+    }
+
+    // Rows above the cut are intact
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let partial = raster::open_with("tests/out/test_read_tolerant.png", &options).unwrap();
+    let row = image.width as usize * 4;
+    assert_eq!(image.bytes[..row], partial.bytes[..row]);
+}
+
+#[test]
+fn strip_metadata() {
+    use raster::endec::EncodeOptions;
+    use raster::metadata::{Metadata, MetadataBlocks};
+    use raster::{Image, ImageFormat};
+
+    // Only the EXIF segment is left out of a JPEG
+    raster::strip_metadata("tests/in/exif.jpg", "tests/out/test_strip.jpg").unwrap();
+    let original = std::fs::read("tests/in/exif.jpg").unwrap();
+    let stripped = std::fs::read("tests/out/test_strip.jpg").unwrap();
+    let exif = MetadataBlocks::from_path("tests/in/exif.jpg")
+        .unwrap()
+        .exif
+        .unwrap();
+    assert_eq!(original.len() - exif.len() - 10, stripped.len());
+    assert_eq!(
+        original[original.len() - 1000..],
+        stripped[stripped.len() - 1000..]
+    );
+    assert_eq!(
+        Metadata::default(),
+        Metadata::from_path("tests/out/test_strip.jpg").unwrap()
+    );
+
+    // PNGs and WebPs keep their ICC profile
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    let blocks = MetadataBlocks {
+        exif: MetadataBlocks::from_path("tests/in/exif.png").unwrap().exif,
+        icc_profile: Some(vec![1, 2, 3]),
+        xmp: Some(b"<x:xmpmeta xmlns:x='adobe:ns:meta/'/>".to_vec()),
+        ..MetadataBlocks::default()
+    };
+    let options = EncodeOptions {
+        lossless: true,
+        ..EncodeOptions::default()
+    };
+    for &format in &[ImageFormat::Png, ImageFormat::WebP] {
+        let data = image
+            .to_bytes_with_metadata(format, &options, &blocks)
+            .unwrap();
+        let stripped = raster::strip_metadata_from_bytes(&data).unwrap();
+        let decoded = Image::from_bytes(format, &stripped).unwrap();
+        assert_eq!(
+            Image::from_bytes(format, &data).unwrap().bytes,
+            decoded.bytes
+        );
+        let metadata = MetadataBlocks {
+            icc_profile: Some(vec![1, 2, 3]),
+            ..MetadataBlocks::default()
+        };
+        assert_eq!(
+            metadata,
+            MetadataBlocks::from_bytes(&stripped).unwrap(),
+            "{:?}",
+            format
+        );
+    }
+
+    // TIFFs are encoded again
+    let data = std::fs::read("tests/in/sample.tif").unwrap();
+    let stripped = raster::strip_metadata_from_bytes(&data).unwrap();
+    let decoded = Image::from_bytes(ImageFormat::Tiff, &stripped).unwrap();
+    assert_eq!(
+        raster::open("tests/in/sample.tif").unwrap().bytes,
+        decoded.bytes
+    );
+
+    // Formats without metadata are copied
+    let data = std::fs::read("tests/in/sample.gif").unwrap();
+    assert_eq!(data, raster::strip_metadata_from_bytes(&data).unwrap());
+}
+
+#[test]
+fn write_to_bytes() {
+    use raster::endec::{EncodeOptions, PnmFormat};
+    use raster::{Image, ImageFormat};
+
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    let options = EncodeOptions::default();
+    for &format in &[
+        ImageFormat::Png,
+        ImageFormat::Qoi,
+        ImageFormat::Tga,
+        ImageFormat::Tiff,
+    ] {
+        let data = image.to_bytes(format, &options).unwrap();
+        assert_eq!(image.bytes, Image::from_bytes(format, &data).unwrap().bytes);
+    }
+
+    let options = EncodeOptions {
+        pnm_format: PnmFormat::Pgm,
+        ..EncodeOptions::default()
+    };
+    let data = image.to_bytes(ImageFormat::Pnm, &options).unwrap();
+    assert!(data.starts_with(b"P5\n2 2\n255\n"));
+
+    match image.to_bytes(ImageFormat::Hdr, &options) {
+        Err(raster::error::RasterError::UnsupportedFormat(_)) => {}
+        _ => panic!("expected HDR encoding to be unsupported"),
+    }
+}
+
+#[test]
+fn write_animation_to_writer() {
+    use raster::endec::DecodeLimits;
+    use raster::{endec, Color, DisposalMethod, Frame, FrameBlend, Image};
+    use std::io::Cursor;
+
+    let black = Image::blank(4, 4);
+    let mut white = Image::blank(4, 4);
+    raster::editor::fill(&mut white, Color::white()).unwrap();
+
+    let mut data = Vec::new();
+    endec::encode_gif_animation_to_writer(&[black.clone(), white.clone()], &[10, 20], &mut data)
+        .unwrap();
+    let animation =
+        endec::decode_gif_animation_from_reader(Cursor::new(&data), &DecodeLimits::default())
+            .unwrap();
+    assert_eq!(2, animation.frames.len());
+    assert_eq!(white.bytes, animation.frames[1].image.bytes);
+
+    let frames: Vec<Frame> = [black, white]
+        .iter()
+        .map(|image| Frame {
+            image: image.clone(),
+            delay: 10,
+            disposal: DisposalMethod::Keep,
+            blend: FrameBlend::Source,
+        })
+        .collect();
+    let mut data = Vec::new();
+    endec::ApngEncoder::new()
+        .encode_to_writer(&frames, &mut data)
+        .unwrap();
+    let animation =
+        endec::decode_apng_from_reader(Cursor::new(&data), &DecodeLimits::default()).unwrap();
+    assert_eq!(2, animation.frames.len());
+    assert_eq!(frames[1].image.bytes, animation.frames[1].image.bytes);
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn read_url() {
+    use raster::endec::DecodeLimits;
+    use raster::error::RasterError;
+    use std::io::{BufRead, BufReader, Write};
+
+    // A server with an image behind a redirect, without an extension
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let data = std::fs::read("tests/in/sample.png").unwrap();
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            // Read the whole request, the first line has the path
+            let lines: Vec<String> = BufReader::new(&stream)
+                .lines()
+                .map(|line| line.unwrap())
+                .take_while(|line| !line.is_empty())
+                .collect();
+            let request = &lines[0];
+            let scheme_relative = format!(
+                "HTTP/1.0 301 Moved Permanently\r\nLocation: //{}/images/sample\r\n\r\n",
+                address
+            );
+            let response: &[&[u8]] = match request.split_whitespace().nth(1).unwrap() {
+                "/redirect" => &[b"HTTP/1.0 302 Found\r\nLocation: /images/sample\r\n\r\n"],
+                "/scheme-relative" => &[scheme_relative.as_bytes()],
+                "/images/sample" => &[b"HTTP/1.0 200 OK\r\n\r\n", &data],
+                _ => &[b"HTTP/1.0 404 Not Found\r\n\r\n"],
+            };
+            for part in response {
+                stream.write_all(part).unwrap();
+            }
+        }
+    });
+
+    let url = |path: &str| format!("http://{}{}", address, path);
+    let image = raster::open_url(&url("/redirect")).unwrap();
+    let expected = raster::open("tests/in/sample.png").unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+    let image = raster::open_url(&url("/scheme-relative")).unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+
+    let limits = DecodeLimits {
+        max_bytes: 1024,
+        ..DecodeLimits::default()
+    };
+    let too_large = raster::open_url_with_limits(&url("/images/sample"), &limits);
+    assert!(matches!(too_large, Err(RasterError::Http(_))));
+    assert!(matches!(
+        raster::open_url(&url("/missing")),
+        Err(RasterError::Http(_))
+    ));
+    assert!(matches!(
+        raster::open_url("ftp://example.com/sample.png"),
+        Err(RasterError::Http(_))
+    ));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn read_write_async() {
+    use raster::endec::EncodeOptions;
+    use raster::ImageFormat;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let expected = raster::open("tests/in/sample.png").unwrap();
+
+    let image = runtime
+        .block_on(raster::open_async("tests/in/sample.png"))
+        .unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+
+    let path = "tests/out/test_read_write_async.qoi";
+    runtime.block_on(raster::save_async(&image, path)).unwrap();
+    assert_eq!(expected.bytes, raster::open(path).unwrap().bytes);
+
+    let data = runtime
+        .block_on(raster::to_bytes_async(
+            &image,
+            ImageFormat::Png,
+            EncodeOptions::default(),
+        ))
+        .unwrap();
+    let image = runtime
+        .block_on(raster::from_bytes_async(ImageFormat::Png, data))
+        .unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+
+    assert!(runtime
+        .block_on(raster::open_async("tests/in/not-a-png.png"))
+        .is_err());
+    assert!(runtime
+        .block_on(raster::save_async(&image, "tests/out/test.hdr"))
+        .is_err());
+}
+
+#[test]
+fn read_streaming() {
+    use raster::endec::{self, StreamingDecoder, TiffCompression};
+    use raster::ImageFormat;
+    use std::io::Cursor;
+
+    fn collect<R: std::io::Read + std::io::Seek>(decoder: StreamingDecoder<R>) -> (i32, Vec<u8>) {
+        let mut bands = 0;
+        let mut bytes = Vec::new();
+        for band in decoder {
+            bands += 1;
+            bytes.extend_from_slice(&band.unwrap().bytes);
+        }
+        (bands, bytes)
+    }
+
+    // One band per row for PNGs, including interlaced ones
+    for path in &["tests/in/sample.png", "tests/in/interlaced.png"] {
+        let file = std::fs::File::open(path).unwrap();
+        let decoder = StreamingDecoder::new(ImageFormat::Png, file).unwrap();
+        let height = decoder.height();
+        let (bands, bytes) = collect(decoder);
+        assert_eq!(height, bands);
+        assert_eq!(raster::open(path).unwrap().bytes, bytes);
+    }
+
+    // One band per strip for TIFFs. Strips are about 1 MB.
+    let mut image = raster::Image::blank(1000, 600);
+    for (i, byte) in image.bytes.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    let mut data = Cursor::new(Vec::new());
+    endec::encode_tiff_to_writer(&image, &mut data, TiffCompression::Lzw).unwrap();
+    data.set_position(0);
+    let decoder = StreamingDecoder::new(ImageFormat::Tiff, data).unwrap();
+    assert_eq!(image.width, decoder.width());
+    let (bands, bytes) = collect(decoder);
+    assert!(bands > 1);
+    assert_eq!(image.bytes, bytes);
+
+    let file = std::fs::File::open("tests/in/sample.jpg").unwrap();
+    assert!(StreamingDecoder::new(ImageFormat::Jpeg, file).is_err());
+}
+
+#[test]
+fn write_metadata() {
+    use raster::endec::EncodeOptions;
+    use raster::metadata::{Metadata, MetadataBlocks};
+    use raster::ImageFormat;
+
+    // Blocks are carried from an opened file to the saved one
+    let image = raster::open("tests/in/exif.jpg").unwrap();
+    let blocks = MetadataBlocks::from_path("tests/in/exif.jpg").unwrap();
+    assert!(blocks.exif.is_some());
+    for out in &["tests/out/test_metadata.jpg", "tests/out/test_metadata.png"] {
+        raster::save_with_metadata(&image, out, &blocks).unwrap();
+        assert_eq!(blocks, MetadataBlocks::from_path(out).unwrap());
+        assert_eq!(
+            Some("Raster".to_string()),
+            Metadata::from_path(out).unwrap().make
+        );
+    }
+
+    // Profiles are decompressed
+    let blocks = MetadataBlocks::from_path("tests/in/sample.png").unwrap();
+    assert_eq!(
+        Some(&b"acsp"[..]),
+        blocks.icc_profile.as_ref().map(|icc| &icc[36..40])
+    );
+
+    // Saving strips them by default
+    let image = raster::open("tests/in/sample.png").unwrap();
+    raster::save(&image, "tests/out/test_metadata_stripped.jpg").unwrap();
+    let saved = MetadataBlocks::from_path("tests/out/test_metadata_stripped.jpg").unwrap();
+    assert!(saved.is_empty());
+
+    // A profile too large for a single JPEG segment, and every format that keeps them
+    let image = raster::open("tests/in/in2x2trans.png").unwrap();
+    let blocks = MetadataBlocks {
+        icc_profile: Some((0..70000).map(|i| i as u8).collect()),
+        xmp: Some(b"<x:xmpmeta xmlns:x='adobe:ns:meta/'/>".to_vec()),
+        ..MetadataBlocks::default()
+    };
+    for &format in &[ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP] {
+        for &lossless in &[false, true] {
+            let options = EncodeOptions {
+                lossless,
+                ..EncodeOptions::default()
+            };
+            let data = image
+                .to_bytes_with_metadata(format, &options, &blocks)
+                .unwrap();
+            let decoded = raster::Image::from_bytes(format, &data).unwrap();
+            assert_eq!(
+                blocks,
+                MetadataBlocks::from_bytes(&data).unwrap(),
+                "{:?}",
+                format
+            );
+            assert_eq!((2, 2), (decoded.width, decoded.height));
+        }
+    }
+
+    // Formats without blocks are saved without them
+    let data = image
+        .to_bytes_with_metadata(ImageFormat::Qoi, &EncodeOptions::default(), &blocks)
+        .unwrap();
+    assert_eq!(
+        image
+            .to_bytes(ImageFormat::Qoi, &EncodeOptions::default())
+            .unwrap(),
+        data
+    );
+
+    // An image turned upright has its orientation reset
+    let image = raster::open_oriented("tests/in/exif.jpg").unwrap();
+    let mut blocks = MetadataBlocks::from_path("tests/in/exif.jpg").unwrap();
+    blocks.reset_orientation();
+    raster::save_with_metadata(&image, "tests/out/test_metadata_oriented.jpg", &blocks).unwrap();
+    let metadata = Metadata::from_path("tests/out/test_metadata_oriented.jpg").unwrap();
+    assert_eq!(Some(1), metadata.orientation);
+    assert_eq!(Some("Raster".to_string()), metadata.make);
+}
+
+#[test]
+fn write_dpi() {
+    use raster::metadata::MetadataBlocks;
+
+    // Pixels per meter for PNGs and dots per inch for JPEGs
+    let dpi = |path| MetadataBlocks::from_path(path).unwrap().dpi;
+    assert_eq!(Some((72.0, 72.0)), dpi("tests/in/sample.png"));
+    assert_eq!(Some((72.0, 72.0)), dpi("tests/in/gamma_dalai_lama.jpg"));
+    assert_eq!(None, dpi("tests/in/gamma_saturn.jpg"));
+
+    let image = raster::open("tests/in/exif.jpg").unwrap();
+    let mut blocks = MetadataBlocks::from_path("tests/in/exif.jpg").unwrap();
+    blocks.dpi = Some((300.0, 150.0));
+    for out in &["tests/out/test_dpi.jpg", "tests/out/test_dpi.png"] {
+        raster::save_with_metadata(&image, out, &blocks).unwrap();
+        let saved = MetadataBlocks::from_path(out).unwrap();
+        assert_eq!(Some((300.0, 150.0)), saved.dpi, "{}", out);
+        assert_eq!(blocks.exif, saved.exif);
+    }
+}
+
+#[test]
+fn write_png_text() {
+    use raster::endec::{self, EncodeOptions};
+    use raster::metadata::MetadataBlocks;
+    use raster::ImageFormat;
+
+    // Latin-1 text goes in tEXt chunks, anything else in iTXt chunks
+    let image = raster::open("tests/in/in2x2.png").unwrap();
+    let text = [("Software", "raster 0.2"), ("Title", "Größe ≈ 2×2")];
+    let mut data = Vec::new();
+    endec::encode_png_with_text_to_writer(&image, &mut data, &text).unwrap();
+    let blocks = MetadataBlocks::from_bytes(&data).unwrap();
+    assert_eq!(2, blocks.text.len());
+    for &(keyword, text) in &text {
+        assert_eq!(text, blocks.text[keyword]);
+    }
+
+    // Text of files
+    let opened = MetadataBlocks::from_path("tests/in/in2x2trans.png").unwrap();
+    assert_eq!("Adobe ImageReady", opened.text["Software"]);
+
+    // Text is kept with the other metadata
+    let options = EncodeOptions::default();
+    let data = image
+        .to_bytes_with_metadata(ImageFormat::Png, &options, &blocks)
+        .unwrap();
+    assert_eq!(blocks, MetadataBlocks::from_bytes(&data).unwrap());
+
+    let mut data = Vec::new();
+    assert!(endec::encode_png_with_text_to_writer(&image, &mut data, &[("", "empty")]).is_err());
+}
+
+#[test]
+fn read_write_png16() {
+    use raster::buffer::{Rgba16, Rgba8};
+    use raster::{endec, ImageBuffer};
+
+    // A 16-bit RGB PNG keeps its low bytes
+    let file = std::fs::File::open("tests/in/deep.png").unwrap();
+    let buffer = endec::decode_png16(&file).unwrap();
+    assert_eq!(
+        vec![0x1234, 0xabcd, 0xffff, 0xffff, 0, 0x0101, 0x8000, 0xffff],
+        buffer.samples
+    );
+    let image = raster::open("tests/in/deep.png").unwrap();
+    assert_eq!(vec![0x12, 0xab, 0xff, 0xff, 0, 1, 0x80, 0xff], image.bytes);
+
+    let path = std::path::Path::new("tests/out/test_read_write_png16.png");
+    endec::encode_png16(&buffer, path).unwrap();
+    let file = std::fs::File::open(path).unwrap();
+    assert_eq!(buffer, endec::decode_png16(&file).unwrap());
+
+    // 8-bit PNGs are scaled up
+    let file = std::fs::File::open("tests/in/sample.png").unwrap();
+    let buffer = endec::decode_png16(&file).unwrap();
+    let image = raster::open("tests/in/sample.png").unwrap();
+    assert_eq!(image.bytes, buffer.convert::<Rgba8>().samples);
+    assert_eq!(ImageBuffer::<Rgba16>::from_image(&image), buffer);
+}
+
+#[test]
+fn read_write_png_gray() {
+    use raster::buffer::Gray8;
+    use raster::{endec, ImageBuffer};
+
+    let file = std::fs::File::open("tests/in/gray.png").unwrap();
+    let gray = endec::decode_png_gray(&file).unwrap();
+    assert_eq!((3, 2), (gray.width, gray.height));
+    assert_eq!(vec![0, 64, 128, 192, 255, 7], gray.samples);
+    let image = raster::open("tests/in/gray.png").unwrap();
+    assert_eq!(image.bytes, gray.to_image().bytes);
+
+    // Saved as one byte per pixel
+    let path = std::path::Path::new("tests/out/test_read_write_png_gray.png");
+    endec::encode_png_gray(&gray, path).unwrap();
+    let data = std::fs::read(path).unwrap();
+    assert_eq!(0, data[25]);
+    let limits = endec::DecodeLimits::default();
+    assert_eq!(
+        gray,
+        endec::decode_png_gray_from_reader(&data[..], &limits).unwrap()
+    );
+
+    // Color PNGs are turned gray
+    let file = std::fs::File::open("tests/in/sample.png").unwrap();
+    let gray = endec::decode_png_gray(&file).unwrap();
+    let image = raster::open("tests/in/sample.png").unwrap();
+    assert_eq!(ImageBuffer::<Gray8>::from_image(&image), gray);
+}
+
+#[test]
+fn write_png_rows() {
+    use raster::endec::PngRowWriter;
+    use raster::error::RasterError;
+    use raster::{Image, ImageFormat};
+
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let row_len = image.width as usize * 4;
+
+    // Uneven bands of rows
+    let mut data = Vec::new();
+    let mut writer = PngRowWriter::new(&mut data, image.width, image.height).unwrap();
+    for band in image.bytes.chunks(row_len * 7) {
+        writer.write_rows(band).unwrap();
+    }
+    writer.finish().unwrap();
+    assert_eq!(
+        image.bytes,
+        Image::from_bytes(ImageFormat::Png, &data).unwrap().bytes
+    );
+
+    let mut writer = PngRowWriter::new(Vec::new(), image.width, 1).unwrap();
+    match writer.write_rows(&image.bytes[..row_len - 4]) {
+        Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+        _ => panic!("expected a partial row to fail"),
+    }
+    match writer.write_rows(&image.bytes[..row_len * 2]) {
+        Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+        _ => panic!("expected too many rows to fail"),
+    }
+    match writer.finish() {
+        Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+        _ => panic!("expected missing rows to fail"),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_image() {
+    use serde_core::de::value::{Error, MapDeserializer, SeqDeserializer};
+    use serde_core::de::{Deserialize, Deserializer, IntoDeserializer, Visitor};
+
+    // A field value of JSON-like formats, which have no byte strings
+    enum Value {
+        Int(i32),
+        Bytes(Vec<u8>),
+    }
+
+    impl<'de> Deserializer<'de> for Value {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self {
+                Value::Int(value) => visitor.visit_i32(value),
+                Value::Bytes(bytes) => visitor.visit_seq(SeqDeserializer::new(bytes.into_iter())),
+            }
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map
+            struct enum identifier ignored_any
+        }
+    }
+
+    impl<'de> IntoDeserializer<'de, Error> for Value {
+        type Deserializer = Value;
+
+        fn into_deserializer(self) -> Value {
+            self
+        }
+    }
+
+    let fields = |bytes: Vec<u8>| {
+        MapDeserializer::<_, Error>::new(
+            vec![
+                ("width", Value::Int(2)),
+                ("height", Value::Int(1)),
+                ("bytes", Value::Bytes(bytes)),
+            ]
+            .into_iter(),
+        )
+    };
+
+    let image = raster::Image::deserialize(fields(vec![255, 0, 0, 255, 0, 0, 255, 128])).unwrap();
+    assert_eq!((2, 1), (image.width, image.height));
+    assert_eq!(128, image.get_pixel(1, 0).unwrap().a);
+
+    // The bytes have to fit the size
+    assert!(raster::Image::deserialize(fields(vec![255, 0, 0, 255])).is_err());
+
+    let color = raster::Color::deserialize(SeqDeserializer::<_, Error>::new(
+        vec![1u8, 2, 3, 4].into_iter(),
+    ));
+    assert_eq!(4, color.unwrap().a);
+}
+
+#[cfg(feature = "image-interop")]
+#[test]
+fn convert_dynamic_image() {
+    use image25::{DynamicImage, GrayImage, Luma, RgbaImage};
+    use raster::error::RasterError;
+    use std::convert::TryFrom;
+
+    let gray = DynamicImage::ImageLuma8(GrayImage::from_pixel(3, 2, Luma([128])));
+    let image = raster::Image::from(&gray);
+    assert_eq!((3, 2), (image.width, image.height));
+    let pixel = image.get_pixel(2, 1).unwrap();
+    assert_eq!((128, 128, 128, 255), (pixel.r, pixel.g, pixel.b, pixel.a));
+
+    let original = raster::open("tests/in/sample.png").unwrap();
+    let converted = DynamicImage::try_from(original.clone()).unwrap();
+    assert_eq!(original.width as u32, converted.width());
+    assert_eq!(original.bytes, raster::Image::from(converted).bytes);
+
+    let mut broken = original.clone();
+    broken.bytes.pop();
+    match RgbaImage::try_from(broken) {
+        Err(RasterError::SizeMismatch(width, height)) => {
+            assert_eq!((original.width, original.height), (width, height))
+        }
+        other => panic!("{:?}", other.map(|image| image.dimensions())),
+    }
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn convert_ndarray() {
+    use ndarray::{Array3, Axis};
+    use raster::Image;
+
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let array = image.to_ndarray().unwrap();
+    assert_eq!(
+        (image.height as usize, image.width as usize, 4),
+        array.dim()
+    );
+    assert_eq!(image.bytes, Image::from_ndarray(array).unwrap().bytes);
+
+    // Arrays in another layout keep their logical order
+    let mut transposed = array.to_owned();
+    transposed.swap_axes(0, 1);
+    let flipped = Image::from_ndarray(transposed.view()).unwrap();
+    assert_eq!((image.height, image.width), (flipped.width, flipped.height));
+    let pixel = image.get_pixel(7, 3).unwrap();
+    assert_eq!(pixel.g, flipped.get_pixel(3, 7).unwrap().g);
+
+    let rgb = array.select(Axis(2), &[0, 1, 2]);
+    match Image::from_ndarray(rgb.view()) {
+        Err(raster::error::RasterError::SizeMismatch(_, _)) => {}
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    assert!(Image::from_ndarray(Array3::zeros((2, 2, 4)).view()).is_ok());
+
+    let mut broken = image.clone();
+    broken.bytes.pop();
+    assert!(broken.to_ndarray().is_err());
+}
+
+#[test]
+fn convert_yuv() {
+    use raster::error::RasterError;
+    use raster::yuv::{self, YuvMatrix};
+
+    let image = raster::open("tests/in/sample.png").unwrap();
+    for &matrix in &[YuvMatrix::Bt601, YuvMatrix::Bt709] {
+        let (y, u, v) = yuv::to_yuv420(&image, matrix);
+        let (_, uv) = yuv::to_nv12(&image, matrix);
+        assert_eq!(u.len() * 2, uv.len());
+        assert_eq!((u[7], v[7]), (uv[14], uv[15]));
+
+        // Chroma is shared by 2x2 blocks, so colors only come back close
+        let planar = yuv::from_yuv420(image.width, image.height, &y, &u, &v, matrix).unwrap();
+        let interleaved = yuv::from_nv12(image.width, image.height, &y, &uv, matrix).unwrap();
+        assert_eq!(planar.bytes, interleaved.bytes);
+        let difference: u64 = image
+            .bytes
+            .iter()
+            .zip(&planar.bytes)
+            .map(|(&a, &b)| (a as i32 - b as i32).unsigned_abs() as u64)
+            .sum();
+        assert!(difference / (image.bytes.len() as u64) < 4);
+    }
+
+    match yuv::from_nv12(4, 4, &[0; 16], &[0; 4], YuvMatrix::Bt601) {
+        Err(RasterError::SizeMismatch(4, 4)) => {}
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn convert_yuv_odd_sizes() {
+    use raster::error::RasterError;
+    use raster::yuv::{self, YuvMatrix};
+    use raster::{Color, Image};
+
+    // Chroma planes are rounded up, so the last column and row get samples of their own
+    for &(width, height, chroma) in &[(3, 3, 4), (5, 1, 3), (1, 5, 3), (1, 1, 1), (0, 0, 0)] {
+        let image = Image::from_fn(width, height, |x, y| {
+            if x == width - 1 && y == height - 1 {
+                Color::red()
+            } else {
+                Color::blue()
+            }
+        });
+        let (y, u, v) = yuv::to_yuv420(&image, YuvMatrix::Bt601);
+        let (_, uv) = yuv::to_nv12(&image, YuvMatrix::Bt601);
+        assert_eq!((width * height) as usize, y.len());
+        assert_eq!((chroma, chroma, chroma * 2), (u.len(), v.len(), uv.len()));
+
+        let planar = yuv::from_yuv420(width, height, &y, &u, &v, YuvMatrix::Bt601).unwrap();
+        let interleaved = yuv::from_nv12(width, height, &y, &uv, YuvMatrix::Bt601).unwrap();
+        assert_eq!(planar.bytes, interleaved.bytes);
+        if width > 0 {
+            let corner = planar.get_pixel(width - 1, height - 1).unwrap();
+            assert!(corner.r > 200 && corner.b < 50, "{}x{}", width, height);
+        }
+
+        // One sample short in any plane
+        if chroma > 0 {
+            let shortened = &u[..chroma - 1];
+            for result in &[
+                yuv::from_yuv420(width, height, &y[1..], &u, &v, YuvMatrix::Bt601),
+                yuv::from_yuv420(width, height, &y, shortened, &v, YuvMatrix::Bt601),
+                yuv::from_nv12(width, height, &y, &uv[1..], YuvMatrix::Bt601),
+            ] {
+                match *result {
+                    Err(RasterError::SizeMismatch(w, h)) => assert_eq!((width, height), (w, h)),
+                    ref other => panic!("{}x{}: {:?}", width, height, other.as_ref().map(|_| ())),
+                }
+            }
+        }
+    }
+    assert!(yuv::from_yuv420(-1, 2, &[], &[], &[], YuvMatrix::Bt709).is_err());
+}
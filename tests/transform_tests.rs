@@ -0,0 +1,155 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{transform, Color, Image, InterpolationMode, RotateMode};
+
+#[test]
+fn rotate_smooth() {
+    let image = Image::from_fn(5, 3, |x, y| Color::rgb(x as u8 * 50, y as u8 * 100, 0));
+
+    // Right angles move pixels without blending them
+    let mut rotated = image.clone();
+    transform::rotate_smooth(&mut rotated, 90.0, Color::black(), RotateMode::Expand).unwrap();
+    let mut oriented = image.clone();
+    transform::orient(&mut oriented, 6).unwrap();
+    assert_eq!((3, 5), (rotated.width, rotated.height));
+    assert_eq!(oriented.bytes, rotated.bytes);
+
+    let mut same = image.clone();
+    transform::rotate_smooth(&mut same, 0.0, Color::black(), RotateMode::Crop).unwrap();
+    assert_eq!(image.bytes, same.bytes);
+
+    let mut square = Image::filled(100, 100, Color::red());
+    transform::rotate_smooth(&mut square, 45.0, Color::blue(), RotateMode::Crop).unwrap();
+    assert_eq!((70, 70), (square.width, square.height));
+    assert_eq!(255, square.get_pixel(35, 35).unwrap().r);
+}
+
+#[test]
+fn rotate_flip_lossless() {
+    let image = Image::from_fn(5, 3, |x, y| Color::rgb(x as u8 * 50, y as u8 * 100, 0));
+    let check = |f: fn(&mut Image) -> raster::error::RasterResult<()>, orientation: u16| {
+        let (mut fast, mut oriented) = (image.clone(), image.clone());
+        f(&mut fast).unwrap();
+        transform::orient(&mut oriented, orientation).unwrap();
+        assert_eq!((oriented.width, oriented.height), (fast.width, fast.height));
+        assert_eq!(oriented.bytes, fast.bytes);
+    };
+    check(transform::flip_h, 2);
+    check(transform::rotate180, 3);
+    check(transform::flip_v, 4);
+    check(transform::rotate90, 6);
+    check(transform::rotate270, 8);
+}
+
+#[test]
+fn affine_matrix() {
+    let image = Image::from_fn(6, 4, |x, y| Color::rgb(x as u8 * 40, y as u8 * 60, 90));
+
+    // The identity leaves interpolating filters that pass through their samples alone
+    let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+    for &mode in &[
+        InterpolationMode::Nearest,
+        InterpolationMode::Bilinear,
+        InterpolationMode::Bicubic,
+        InterpolationMode::CatmullRom,
+        InterpolationMode::Lanczos3,
+    ] {
+        let mut same = image.clone();
+        transform::affine(&mut same, identity, mode, Color::black()).unwrap();
+        assert_eq!(image.bytes, same.bytes, "{:?}", mode);
+    }
+
+    // Translation uncovers the background
+    let mut moved = image.clone();
+    let matrix = [1.0, 0.0, 2.0, 0.0, 1.0, -1.0];
+    transform::affine(
+        &mut moved,
+        matrix,
+        InterpolationMode::Nearest,
+        Color::blue(),
+    )
+    .unwrap();
+    assert_eq!(
+        image.get_pixel(0, 1).unwrap().r,
+        moved.get_pixel(2, 0).unwrap().r
+    );
+    assert_eq!(255, moved.get_pixel(1, 1).unwrap().b);
+    assert_eq!(255, moved.get_pixel(3, 3).unwrap().b);
+
+    // Scaling up twice
+    let mut scaled = image.clone();
+    let matrix = [2.0, 0.0, 0.0, 0.0, 2.0, 0.0];
+    transform::affine(
+        &mut scaled,
+        matrix,
+        InterpolationMode::Nearest,
+        Color::blue(),
+    )
+    .unwrap();
+    assert_eq!(
+        image.get_pixel(1, 1).unwrap().r,
+        scaled.get_pixel(3, 2).unwrap().r
+    );
+
+    let mut flat = image.clone();
+    transform::affine(
+        &mut flat,
+        [0.0; 6],
+        InterpolationMode::Bilinear,
+        Color::red(),
+    )
+    .unwrap();
+    assert!(flat.bytes.chunks(4).all(|pixel| pixel == [255, 0, 0, 255]));
+}
+
+#[test]
+fn perspective_warp() {
+    let image = Image::from_fn(8, 6, |x, y| Color::rgb(x as u8 * 30, y as u8 * 40, 90));
+    let corners = [(0.0, 0.0), (8.0, 0.0), (8.0, 6.0), (0.0, 6.0)];
+
+    // Mapping a rectangle onto itself changes nothing
+    let mut same = image.clone();
+    transform::perspective(&mut same, corners, corners).unwrap();
+    assert_eq!(image.bytes, same.bytes);
+
+    // A keystoned quad into a rectangle: the corners follow
+    let mut warped = Image::filled(20, 20, Color::black());
+    warped.set_pixel(12, 3, Color::red()).unwrap();
+    let quad = [(2.0, 2.0), (13.0, 3.0), (15.0, 15.0), (1.0, 12.0)];
+    let rect = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+    transform::perspective(&mut warped, quad, rect).unwrap();
+    assert_eq!((10, 10), (warped.width, warped.height));
+    assert!(warped.get_pixel(9, 0).unwrap().r > 0);
+    assert_eq!(0, warped.get_pixel(0, 9).unwrap().r);
+
+    let line = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (0.0, 5.0)];
+    match transform::perspective(&mut same, line, corners) {
+        Err(RasterError::InvalidQuad) => {}
+        _ => panic!("expected an invalid quad"),
+    }
+}
+
+#[test]
+fn displace_mesh() {
+    let image = Image::from_fn(8, 6, |x, y| Color::rgb(x as u8 * 30, y as u8 * 40, 90));
+
+    // Whole pixel shifts copy neighbors and leave transparency at the edge
+    let mut shifted = image.clone();
+    transform::displace(&mut shifted, &[1.0; 48], &[0.0; 48]).unwrap();
+    assert_eq!(
+        image.get_pixel(3, 2).unwrap().r,
+        shifted.get_pixel(2, 2).unwrap().r
+    );
+    assert_eq!(0, shifted.get_pixel(7, 2).unwrap().a);
+    assert!(transform::displace(&mut shifted, &[0.0; 47], &[0.0; 48]).is_err());
+
+    // An even mesh changes nothing
+    let mut meshed = image.clone();
+    let points: Vec<(f64, f64)> = (0..12)
+        .map(|i| ((i % 4) as f64 * 8.0 / 3.0, (i / 4) as f64 * 3.0))
+        .collect();
+    transform::mesh_warp(&mut meshed, 3, 2, &points).unwrap();
+    assert_eq!(image.bytes, meshed.bytes);
+    assert!(transform::mesh_warp(&mut meshed, 3, 3, &points).is_err());
+}